@@ -1,6 +1,8 @@
 use std::net::SocketAddr;
 
-use data_server::api::subscriber::{ReconnectingClient, SubMessage};
+use data_server::api::subscriber::{
+    Filter, Kind, ReconnectingClient, SubMessage, SubscribeOptions,
+};
 use tokio::sync::broadcast;
 
 use crate::controller::Event;
@@ -9,15 +11,26 @@ pub async fn subscribe(
     event_tx: broadcast::Sender<Event>,
     data_server: SocketAddr,
 ) {
-    let mut sub =
-        ReconnectingClient::new(data_server, "ha-brain".to_owned()).subscribe();
+    let options = SubscribeOptions {
+        filter: Filter::default().of_kinds([Kind::Reading]),
+        ..SubscribeOptions::default()
+    };
+    let mut sub = ReconnectingClient::new(data_server, "ha-brain".to_owned())
+        .subscribe_with(options);
     loop {
         match sub.next().await {
             SubMessage::Reading(reading) => {
                 event_tx.send(Event::Sensor(reading)).unwrap();
             }
+            SubMessage::Lagged { dropped } => {
+                tracing::warn!(
+                    "Could not keep up with data-server, missed {dropped} readings"
+                );
+            }
             SubMessage::ErrorReport(_)
-            | SubMessage::AffectorControlled { .. } => continue,
+            | SubMessage::DecodeFailure(_)
+            | SubMessage::AffectorControlled { .. }
+            | SubMessage::NodeReset(_) => continue,
         }
     }
 }
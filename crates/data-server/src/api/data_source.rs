@@ -6,6 +6,12 @@ use tokio::net::{TcpStream, ToSocketAddrs};
 
 pub mod reconnecting;
 
+/// Data sources not on the data-servers node allow list send this followed
+/// by their token and a zero byte before the handshake.
+pub(crate) const TOKEN_PREFIX: &[u8] = b"token:";
+/// Longest token the data-server reads, in bytes
+pub const MAX_TOKEN_LEN: usize = 128;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Could not connect to data-server")]
@@ -14,6 +20,11 @@ pub enum Error {
     Handshake(#[source] std::io::Error),
     #[error("Too many affectors, max: {max}, requires: {requires}")]
     TooManyAffectors { max: usize, requires: usize },
+    #[error(
+        "Token may not contain zero bytes or be longer then {MAX_TOKEN_LEN} \
+        bytes"
+    )]
+    InvalidToken,
 }
 
 pub struct Client {
@@ -28,9 +39,49 @@ impl Client {
     pub async fn connect(
         addr: impl ToSocketAddrs,
         affectors: Vec<protocol::Affector>,
+    ) -> Result<Self, Error> {
+        Self::connect_inner(addr, affectors, None, false).await
+    }
+
+    /// Use when this data source is not on the data-servers node allow list.
+    /// The token needs the `DataSource` role.
+    pub async fn connect_with_token(
+        addr: impl ToSocketAddrs,
+        affectors: Vec<protocol::Affector>,
+        token: &str,
+    ) -> Result<Self, Error> {
+        Self::connect_inner(addr, affectors, Some(token), false).await
+    }
+
+    /// Use when this data source calls [`Sender::send_order_ack`] for every
+    /// order it receives. Clients controlling the affectors then learn
+    /// whether their order was executed.
+    pub async fn connect_acking_orders(
+        addr: impl ToSocketAddrs,
+        affectors: Vec<protocol::Affector>,
+        token: Option<&str>,
+    ) -> Result<Self, Error> {
+        Self::connect_inner(addr, affectors, token, true).await
+    }
+
+    async fn connect_inner(
+        addr: impl ToSocketAddrs,
+        affectors: Vec<protocol::Affector>,
+        token: Option<&str>,
+        acks_orders: bool,
     ) -> Result<Self, Error> {
         let mut stream = TcpStream::connect(addr).await.map_err(Error::Connecting)?;
+        if let Some(token) = token {
+            if token.as_bytes().contains(&0) || token.len() > MAX_TOKEN_LEN {
+                return Err(Error::InvalidToken);
+            }
+            let mut frame = TOKEN_PREFIX.to_vec();
+            frame.extend_from_slice(token.as_bytes());
+            frame.push(0);
+            stream.write_all(&frame).await.map_err(Error::Handshake)?;
+        }
         let mut list = protocol::affector::ListMessage::<50>::empty();
+        list.acks_orders = acks_orders;
 
         if affectors.len() > list.values.capacity() {
             return Err(Error::TooManyAffectors {
@@ -82,6 +133,19 @@ impl Sender {
         self.0.write_all(&bytes).await
     }
 
+    /// Let the data-server know if the order with id `order` was executed.
+    /// Only has effect when connected using [`Client::connect_acking_orders`].
+    pub async fn send_order_ack(
+        &mut self,
+        order: u32,
+        outcome: protocol::OrderOutcome,
+    ) -> Result<(), std::io::Error> {
+        let msg = protocol::Msg::<1>::OrderAck(protocol::OrderAck::new(order, outcome));
+        let bytes = msg.encode();
+
+        self.0.write_all(&bytes).await
+    }
+
     pub async fn send_error(&mut self, report: protocol::Error) -> Result<(), std::io::Error> {
         let msg = protocol::Msg::<1>::ErrorReport(protocol::ErrorReport::new(report));
         let bytes = msg.encode();
@@ -116,7 +180,7 @@ impl Receiver {
         }
     }
 
-    pub async fn receive(&mut self) -> Result<protocol::Affector, ReceiveError> {
+    pub async fn receive(&mut self) -> Result<affector::Order, ReceiveError> {
         loop {
             if !self.buffer.is_empty() {
                 if let Some((item, remaining)) = self
@@ -53,6 +53,7 @@ impl SendQueue {
 async fn handle_conn(
     addr: SocketAddr,
     affectors: Vec<protocol::Affector>,
+    token: Option<String>,
     msgs_to_send: mpsc::Receiver<SendItem>,
     msgs_recieved: Option<mpsc::Sender<Affector>>,
 ) {
@@ -62,7 +63,9 @@ async fn handle_conn(
     };
     loop {
         let mut retry_period = Duration::from_millis(200);
-        let conn = reconnect(addr, &affectors, &mut retry_period).await;
+        let conn =
+            reconnect(addr, &affectors, token.as_deref(), &mut retry_period)
+                .await;
 
         if let Some(ref msgs_recieved) = msgs_recieved {
             (
@@ -142,7 +145,7 @@ async fn handle_recieving(
 
         tracing::debug!("recv item: {msg:?}");
         msgs_recieved
-            .send(msg)
+            .send(msg.affector)
             .await
             .expect("Reciever in the 'Client' is never dropped")
     }
@@ -155,7 +158,8 @@ pub struct InvalidAddress(String);
 impl Client {
     /// Needs a list of the affectors that can be controlled through this
     /// node as an argument. If your node provides not controllable affectors
-    /// pass in an empty Vec.
+    /// pass in an empty Vec. Pass a `token` with the `DataSource` role if
+    /// this node is not on the data-servers node allow list.
     ///
     /// # Errors
     /// returns an error if the address could not looked up
@@ -163,6 +167,7 @@ impl Client {
         addr: A,
         affectors: Vec<protocol::Affector>,
         affector_tx: Option<mpsc::Sender<Affector>>,
+        token: Option<String>,
     ) -> Result<Self, InvalidAddress> {
         let addr: SocketAddr = tokio::net::lookup_host(addr)
             .await
@@ -174,7 +179,7 @@ impl Client {
             })?;
 
         let (to_send_tx, to_send_rx) = mpsc::channel(100);
-        let task = handle_conn(addr, affectors, to_send_rx, affector_tx);
+        let task = handle_conn(addr, affectors, token, to_send_rx, affector_tx);
         let handle = tokio::spawn(task);
         Ok(Self {
             _conn_handler_task: AbortOnDrop(handle),
@@ -231,6 +236,8 @@ impl Client {
     }
 
     const ERROR_REPORT_SEND_DEADLINE: Duration = Duration::from_secs(60 * 15);
+    /// After this the data-server no longer waits for the ack
+    const ORDER_ACK_SEND_DEADLINE: Duration = Duration::from_secs(2);
     /// # Cancel safety
     /// This is cancel safe however the connection will need to be re-established
     /// the next time its called. This will retry forever, you should call this
@@ -263,6 +270,7 @@ impl Client {
         let deadline = match decoded {
             protocol::Msg::Readings(sensor_message) => sensor_message.values.iter().map(|v| v.device().info().temporal_resolution).min().expect("empty sensormessages are forbidden"),
             protocol::Msg::ErrorReport(_) => Self::ERROR_REPORT_SEND_DEADLINE,
+            protocol::Msg::OrderAck(_) => Self::ORDER_ACK_SEND_DEADLINE,
             protocol::Msg::AffectorList(_) => unreachable!("send by client on reconnect only, never send by user of client"),
         };
 
@@ -275,12 +283,18 @@ impl Client {
 async fn reconnect(
     addr: SocketAddr,
     affectors: &[protocol::Affector],
+    token: Option<&str>,
     retry_period: &mut Duration,
 ) -> super::Client {
     loop {
         match timeout(
             Duration::from_millis(500),
-            super::Client::connect(addr, affectors.to_vec()),
+            super::Client::connect_inner(
+                addr,
+                affectors.to_vec(),
+                token,
+                false,
+            ),
         )
         .await
         {
@@ -3,23 +3,62 @@ pub use client::reconnecting::SubscribedClient as ReconnectingSubscribedClient;
 pub use client::Client;
 pub use client::Subscribed as SubscribedClient;
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
 
-pub use crate::server::affector::AffectorError;
+pub use crate::server::affector::{AffectorError, Delivered};
+pub use crate::server::watch::{ResetAction, ResetDecision};
+pub use filter::{Filter, Kind, Selector};
 pub mod client;
+pub mod filter;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Request {
     Handshake { name: String },
     Actuate(protocol::Affector),
     ListAffectors,
+    /// The last known value of every reading, the current error of every
+    /// device and the state of all affectors.
+    Snapshot,
+    /// How many messages had to be dropped for each subscriber
+    SubscriberStats,
+    /// The nodes that are connected and those refused because they speak
+    /// another protocol version
+    ListNodes,
+}
+
+/// Send along when subscribing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubscribeOptions {
+    /// The server only forwards messages that pass this filter
+    pub filter: Filter,
+    /// Limit how many readings the server forwards
+    pub decimation: Decimation,
+}
+
+/// Downsample the stream of readings. Both limits can be combined. Readings
+/// without a unit, like button presses and pir status, are never decimated.
+///
+/// Note that the last value of a reading might not be forwarded if it is
+/// dropped and the reading never changes again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Decimation {
+    /// Forward at most one reading per `ReadingId` per interval
+    pub min_interval: Option<Duration>,
+    /// Only forward a reading if its value differs more then the readings
+    /// `resolution()` from the last one forwarded
+    pub on_change: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Response {
     Handshake,
     Error(ServerError),
-    Actuate(Result<(), AffectorError>),
+    Actuate(Result<Delivered, AffectorError>),
     ListAffectors(Vec<protocol::Affector>),
+    Snapshot(Snapshot),
+    SubscriberStats(Vec<SubscriberStats>),
+    ListNodes(Vec<NodeInfo>),
     SubUpdate(SubMessage),
     Subscribe,
 }
@@ -28,11 +67,117 @@ pub enum Response {
 pub enum SubMessage {
     Reading(protocol::Reading),
     // An affector was moved/updated
+    /// Send for every attempt to control an affector, including failed ones
     AffectorControlled {
         affector: protocol::Affector,
         controlled_by: String,
+        result: Result<Delivered, AffectorError>,
     },
     ErrorReport(Box<protocol::Error>),
+    /// A node send a message that could not be decoded. Has kind
+    /// [`Kind::ErrorReport`].
+    DecodeFailure(Box<DecodeFailure>),
+    /// The node watchdog reset a node or decided not to
+    NodeReset(ResetDecision),
+    /// The subscriber did not keep up and this many messages were dropped
+    /// since the last one it received. Send as soon as there is room in the
+    /// queue again, regardless of the subscription's filter.
+    Lagged { dropped: usize },
+}
+
+/// A message from a node that could not be decoded. The node probably runs
+/// firmware build against a different or broken version of the protocol.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecodeFailure {
+    pub peer: SocketAddr,
+    /// The affectors the node registered with during its handshake. Empty
+    /// if the handshake itself was refused.
+    pub affectors: Vec<protocol::Affector>,
+    /// The first byte of the message, tells what type of message it is
+    pub header: u8,
+    /// Up to the first [`DecodeFailure::HEX_PREFIX_LEN`] bytes of the
+    /// message as hex
+    pub hex_prefix: String,
+    pub error: String,
+}
+
+impl DecodeFailure {
+    pub const HEX_PREFIX_LEN: usize = 32;
+
+    pub(crate) fn new(
+        peer: SocketAddr,
+        affectors: Vec<protocol::Affector>,
+        bytes: &[u8],
+        error: impl std::fmt::Display,
+    ) -> Self {
+        let hex_prefix = bytes
+            .iter()
+            .take(Self::HEX_PREFIX_LEN)
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Self {
+            peer,
+            affectors,
+            header: bytes[0],
+            hex_prefix,
+            error: error.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for DecodeFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "node {} speaks a broken protocol, could not decode message \
+            with header {}: {}",
+            self.peer, self.header, self.error
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriberStats {
+    pub name: String,
+    /// Messages dropped since the subscriber connected
+    pub dropped: u64,
+    /// Messages waiting to be send to the subscriber
+    pub queued: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub addr: SocketAddr,
+    /// The protocol version the node send during its handshake
+    pub protocol_version: u8,
+    pub status: NodeStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NodeStatus {
+    Connected { affectors: Vec<protocol::Affector> },
+    /// The node speaks another protocol version then this server
+    /// ([`protocol::PROTOCOL_VERSION`]), its firmware needs to be reflashed.
+    Refused {
+        /// Time since the node last tried to connect
+        last_attempt: Duration,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub readings: Vec<Retained<protocol::Reading>>,
+    /// Errors that have not been followed by a reading from the same device
+    pub errors: Vec<Retained<protocol::Error>>,
+    pub affectors: Vec<protocol::Affector>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Retained<T> {
+    pub value: T,
+    /// Time since the server received the value
+    pub age: Duration,
 }
 
 #[derive(Clone, Debug, thiserror::Error, Serialize, Deserialize)]
@@ -43,4 +188,8 @@ pub struct SubscribeError;
 pub enum ServerError {
     #[error("Could not activate affector")]
     FailedToSpread,
+    #[error("Could not collect the last known values")]
+    SnapshotUnavailable,
+    #[error("Could not collect the subscriber statistics")]
+    StatsUnavailable,
 }
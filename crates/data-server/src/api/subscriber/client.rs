@@ -3,14 +3,18 @@ use rpc::client::RpcError;
 use tokio::net::ToSocketAddrs;
 
 use super::AffectorError;
+use super::Delivered;
 use super::Request;
 use super::Response;
+use super::SubscribeOptions;
 
 use crate::api::subscriber;
 pub(crate) mod reconnecting;
 
 #[derive(Debug)]
-pub struct Client(rpc::client::RpcClient<super::Request, super::Response>);
+pub struct Client(
+    rpc::client::RpcClient<super::Request, super::Response, SubscribeOptions>,
+);
 
 impl Client {
     pub async fn connect(
@@ -21,13 +25,28 @@ impl Client {
         Ok(Self(rpc_client))
     }
 
+    /// Needed when the data-server requires a token, its role decides what
+    /// this client may do.
+    pub async fn connect_with_token(
+        addr: impl ToSocketAddrs,
+        name: String,
+        token: String,
+    ) -> Result<Self, rpc::client::ConnectError> {
+        let rpc_client =
+            RpcClient::connect_with_token(addr, name, Some(token)).await?;
+        Ok(Self(rpc_client))
+    }
+
+    /// Returns once the node confirmed the order or, for nodes that do not
+    /// acknowledge orders, once the order is send to the node.
     pub async fn actuate_affector(
         &mut self,
         affector: protocol::Affector,
-    ) -> Result<(), Error<AffectorError>> {
+    ) -> Result<Delivered, Error<AffectorError>> {
         let request = Request::Actuate(affector);
         match self.0.send_receive(request.clone()).await? {
             Response::Actuate(res) => res.map_err(Error::Request),
+            Response::Error(err) => Err(Error::Server(err)),
             response => Err(Error::Comms(RpcError::IncorrectResponse {
                 request: format!("{request:?}"),
                 response: format!("{response:?}"),
@@ -48,10 +67,67 @@ impl Client {
         }
     }
 
+    /// The last known value of every reading, outstanding errors and the
+    /// affectors. Useful to show something before the first update arrives.
+    pub async fn snapshot(
+        &mut self,
+    ) -> Result<subscriber::Snapshot, Error<subscriber::ServerError>> {
+        let request = Request::Snapshot;
+        match self.0.send_receive(request.clone()).await? {
+            Response::Snapshot(snapshot) => Ok(snapshot),
+            Response::Error(err) => Err(Error::Server(err)),
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
+
+    /// Per subscriber how many messages were dropped because it could not
+    /// keep up.
+    pub async fn subscriber_stats(
+        &mut self,
+    ) -> Result<Vec<subscriber::SubscriberStats>, Error<subscriber::ServerError>>
+    {
+        let request = Request::SubscriberStats;
+        match self.0.send_receive(request.clone()).await? {
+            Response::SubscriberStats(stats) => Ok(stats),
+            Response::Error(err) => Err(Error::Server(err)),
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
+
+    /// Connected nodes and the nodes refused because of their protocol
+    /// version. Use it to find firmware that needs to be reflashed.
+    pub async fn list_nodes(
+        &mut self,
+    ) -> Result<Vec<subscriber::NodeInfo>, Error<subscriber::ServerError>> {
+        let request = Request::ListNodes;
+        match self.0.send_receive(request.clone()).await? {
+            Response::ListNodes(nodes) => Ok(nodes),
+            Response::Error(err) => Err(Error::Server(err)),
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
+
     pub async fn subscribe(
+        self,
+    ) -> Result<Subscribed, Error<subscriber::SubscribeError>> {
+        self.subscribe_with(SubscribeOptions::default()).await
+    }
+
+    /// Subscribe to only what passes the filter in the `options`
+    pub async fn subscribe_with(
         mut self,
+        options: SubscribeOptions,
     ) -> Result<Subscribed, Error<subscriber::SubscribeError>> {
-        self.0.subscribe().await?;
+        self.0.subscribe(options).await?;
         Ok(Subscribed(self))
     }
 }
@@ -1,4 +1,6 @@
-use crate::api::subscriber;
+use crate::api::subscriber::{
+    self, AffectorError, Delivered, SubscribeOptions,
+};
 
 use std::net::SocketAddr;
 use std::time::Duration;
@@ -26,11 +28,19 @@ impl Client {
 
     #[must_use]
     pub fn subscribe(self) -> SubscribedClient {
+        self.subscribe_with(SubscribeOptions::default())
+    }
+
+    /// The options are send again every time we resubscribe after a
+    /// reconnect.
+    #[must_use]
+    pub fn subscribe_with(self, options: SubscribeOptions) -> SubscribedClient {
         SubscribedClient {
             retry_period: self.retry_period,
             connection: self.connection.map(ConnState::Connected),
             addr: self.addr,
             name: self.name,
+            options,
         }
     }
 
@@ -38,7 +48,10 @@ impl Client {
     /// This is cancel safe however the connection will need to be re-established
     /// the next time its called. This will retry forever, you should call this
     /// in a timeout future.
-    pub async fn actuate_affector(&mut self, affector: protocol::Affector) {
+    pub async fn actuate_affector(
+        &mut self,
+        affector: protocol::Affector,
+    ) -> Result<Delivered, AffectorError> {
         loop {
             let mut conn = if let Some(conn) = self.connection.take() {
                 conn
@@ -52,12 +65,16 @@ impl Client {
             };
 
             match conn.actuate_affector(affector).await {
-                Ok(msg) => {
+                Ok(delivered) => {
                     self.retry_period /= 2;
                     self.retry_period =
                         self.retry_period.max(Duration::from_millis(200));
                     self.connection = Some(conn);
-                    return msg;
+                    return Ok(delivered);
+                }
+                Err(super::Error::Request(err)) => {
+                    self.connection = Some(conn);
+                    return Err(err);
                 }
                 Err(issue) => {
                     warn!("Conn issue while getting next_msg: {issue}, reconnecting");
@@ -97,6 +114,38 @@ impl Client {
             };
         }
     }
+
+    /// # Cancel safety
+    /// This is cancel safe however the connection will need to be re-established
+    /// the next time its called. This will retry forever, you should call this
+    /// in a timeout future.
+    pub async fn snapshot(&mut self) -> subscriber::Snapshot {
+        loop {
+            let mut conn = if let Some(conn) = self.connection.take() {
+                conn
+            } else {
+                get_connection_or_reconnect(
+                    self.addr,
+                    &self.name,
+                    &mut self.retry_period,
+                )
+                .await
+            };
+
+            match conn.snapshot().await {
+                Ok(snapshot) => {
+                    self.retry_period /= 2;
+                    self.retry_period =
+                        self.retry_period.max(Duration::from_millis(200));
+                    self.connection = Some(conn);
+                    return snapshot;
+                }
+                Err(issue) => {
+                    warn!("Conn issue while getting snapshot: {issue}, reconnecting");
+                }
+            };
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -120,6 +169,7 @@ pub struct SubscribedClient {
     connection: Option<ConnState>,
     addr: SocketAddr,
     name: String,
+    options: SubscribeOptions,
 }
 
 impl SubscribedClient {
@@ -142,7 +192,10 @@ impl SubscribedClient {
             };
 
             let subbed = match conn {
-                ConnState::Connected(conn) => match conn.subscribe().await {
+                ConnState::Connected(conn) => match conn
+                    .subscribe_with(self.options.clone())
+                    .await
+                {
                     Ok(subbed) => subbed,
                     Err(e) => {
                         tracing::warn!("Error subscribing to data-server: {e}");
@@ -0,0 +1,145 @@
+use protocol::reading::tree::Id;
+use protocol::reading::ReadingId;
+use protocol::{Affector, Device, IsSameAs, Reading};
+use serde::{Deserialize, Serialize};
+
+use super::SubMessage;
+
+/// Selects which messages the server forwards to a subscriber. The default
+/// filter forwards everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Filter {
+    /// Only forward messages concerning at least one of these. If empty
+    /// messages concerning anything are forwarded.
+    pub selectors: Vec<Selector>,
+    /// Only forward these kinds of messages. If empty all kinds are forwarded.
+    pub kinds: Vec<Kind>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Selector {
+    /// Readings and errors from this device as well as the affectors it
+    /// provides.
+    Device(Device),
+    /// Readings whose path through the reading tree starts with this prefix.
+    /// Errors are selected if their device provides such a reading.
+    Subtree(Vec<Id>),
+    /// A single reading, for example `Temperature` on the large bedroom desk.
+    Reading(ReadingId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
+    Reading,
+    ErrorReport,
+    AffectorControlled,
+    NodeReset,
+    /// Always forwarded, see [`SubMessage::Lagged`]
+    Lagged,
+}
+
+impl Filter {
+    /// Only forward readings, errors and affector updates concerning these
+    #[must_use]
+    pub fn selecting(selectors: impl IntoIterator<Item = Selector>) -> Self {
+        Self {
+            selectors: selectors.into_iter().collect(),
+            kinds: Vec::new(),
+        }
+    }
+
+    /// Additionally only forward messages of these kinds
+    #[must_use]
+    pub fn of_kinds(mut self, kinds: impl IntoIterator<Item = Kind>) -> Self {
+        self.kinds = kinds.into_iter().collect();
+        self
+    }
+
+    #[must_use]
+    pub fn matches(&self, msg: &SubMessage) -> bool {
+        if let SubMessage::Lagged { .. } = msg {
+            return true;
+        }
+        let kind_ok = self.kinds.is_empty() || self.kinds.contains(&msg.kind());
+        let selected = self.selectors.is_empty()
+            || self.selectors.iter().any(|s| s.matches(msg));
+        kind_ok && selected
+    }
+}
+
+impl Selector {
+    /// Selects all readings in the subtree `depth` levels below the root
+    /// containing `reading`. For example a depth of 2 for a reading from the
+    /// large bedroom desk selects all readings from that desk.
+    #[must_use]
+    pub fn subtree_of(reading: &Reading, depth: usize) -> Self {
+        let id = reading.id();
+        let depth = depth.min(id.path().len());
+        Self::Subtree(id.path()[..depth].to_vec())
+    }
+
+    fn matches(&self, msg: &SubMessage) -> bool {
+        match msg {
+            SubMessage::Reading(reading) => self.matches_reading(reading),
+            SubMessage::ErrorReport(error) => {
+                self.matches_device(&error.device())
+            }
+            SubMessage::AffectorControlled { affector, .. } => {
+                self.matches_affector(affector)
+            }
+            SubMessage::DecodeFailure(failure) => failure
+                .affectors
+                .iter()
+                .any(|affector| self.matches_affector(affector)),
+            SubMessage::NodeReset(decision) => {
+                self.matches_reading(&decision.stale_reading)
+            }
+            SubMessage::Lagged { .. } => true,
+        }
+    }
+
+    pub(crate) fn matches_reading(&self, reading: &Reading) -> bool {
+        match self {
+            Selector::Device(device) => reading.device() == *device,
+            Selector::Subtree(prefix) => reading.id().starts_with(prefix),
+            Selector::Reading(id) => reading.id() == *id,
+        }
+    }
+
+    fn matches_affector(&self, affector: &Affector) -> bool {
+        match self {
+            Selector::Device(device) => device
+                .info()
+                .affectors
+                .iter()
+                .any(|provided| provided.is_same_as(affector)),
+            Selector::Subtree(_) | Selector::Reading(_) => false,
+        }
+    }
+
+    fn matches_device(&self, device: &Device) -> bool {
+        match self {
+            Selector::Device(selected) => selected == device,
+            Selector::Subtree(_) | Selector::Reading(_) => device
+                .info()
+                .affects_readings
+                .iter()
+                .any(|reading| self.matches_reading(reading)),
+        }
+    }
+}
+
+impl SubMessage {
+    #[must_use]
+    pub fn kind(&self) -> Kind {
+        match self {
+            SubMessage::Reading(_) => Kind::Reading,
+            SubMessage::ErrorReport(_) | SubMessage::DecodeFailure(_) => {
+                Kind::ErrorReport
+            }
+            SubMessage::AffectorControlled { .. } => Kind::AffectorControlled,
+            SubMessage::NodeReset(_) => Kind::NodeReset,
+            SubMessage::Lagged { .. } => Kind::Lagged,
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::net::SocketAddr;
+use std::path::PathBuf;
 
 use tokio::select;
 use tokio::sync::mpsc;
@@ -25,6 +26,18 @@ struct Cli {
     /// Reset data-sources that have missing or slow sensors
     #[arg(short, long)]
     enable_reset: bool,
+
+    /// RON file with the watchdog policy per node, see
+    /// `data_server::server::WatchdogConfig`. Uses a built in policy if not
+    /// set.
+    #[arg(short, long, requires = "enable_reset")]
+    watchdog_config: Option<PathBuf>,
+
+    /// RON file with the tokens clients and data sources need and the nodes
+    /// that may connect without one, see `data_server::server::Access`.
+    /// Without it anyone may connect.
+    #[arg(short, long)]
+    access: Option<PathBuf>,
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -35,26 +48,38 @@ async fn main() -> Result<()> {
         subscribe_addr,
         update_addr,
         enable_reset,
+        watchdog_config,
+        access,
     } = Cli::parse();
     assert_ne!(subscribe_addr, update_addr);
 
     info!("listening for updates on: {update_addr}");
     info!("serving subscribers on: {subscribe_addr}");
 
+    let watchdog_config = match watchdog_config {
+        Some(path) => server::WatchdogConfig::load(&path)?,
+        None => server::WatchdogConfig::default(),
+    };
+
+    let access = match access {
+        Some(path) => server::Access::load(&path)?,
+        None => server::Access::default(),
+    };
+
     let affectors = server::AffectorRegistar::default();
     let (tx, rx) = mpsc::channel(2000);
 
     if enable_reset {
         select! {
-            e = server::client::handle(subscribe_addr, tx.clone(), affectors.clone()) => e,
-            e = server::handle_nodes(update_addr, &tx, affectors.clone()) => e,
+            e = server::client::handle(subscribe_addr, tx.clone(), affectors.clone(), access.clone()) => e,
+            e = server::handle_nodes(update_addr, &tx, affectors.clone(), access) => e,
             e = server::handle_updates(rx) => e,
-            _ = server::node_watchdog(affectors, &tx) => Ok(()),
+            _ = server::node_watchdog(affectors, &tx, watchdog_config) => Ok(()),
         }
     } else {
         select! {
-            e = server::client::handle(subscribe_addr, tx.clone(), affectors.clone()) => e,
-            e = server::handle_nodes(update_addr, &tx, affectors.clone()) => e,
+            e = server::client::handle(subscribe_addr, tx.clone(), affectors.clone(), access.clone()) => e,
+            e = server::handle_nodes(update_addr, &tx, affectors.clone(), access) => e,
             e = server::handle_updates(rx) => e,
         }
     }
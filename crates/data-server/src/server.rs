@@ -1,22 +1,24 @@
 use color_eyre::Result;
 use protocol::Reading;
 use tokio::net::TcpStream;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio_serde::formats::Bincode;
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
+mod access;
 pub mod affector;
 pub mod client;
 mod data_source;
 mod subscribe;
-mod watch;
+pub mod watch;
 
+pub use access::Access;
 pub use affector::Registar as AffectorRegistar;
 pub use data_source::handle_nodes;
 pub use subscribe::handle_updates;
-pub use watch::node_watchdog;
+pub use watch::{node_watchdog, Config as WatchdogConfig};
 
-use crate::api::subscriber::{self, SubMessage};
+use crate::api::subscriber::{self, SubMessage, SubscribeOptions};
 
 pub type Conn = tokio_serde::Framed<
     Framed<TcpStream, LengthDelimitedCodec>,
@@ -29,10 +31,24 @@ pub type Conn = tokio_serde::Framed<
 pub enum Event {
     NewSub {
         tx: mpsc::Sender<SubMessage>,
+        /// Name the subscriber gave during the handshake
+        name: String,
+        options: SubscribeOptions,
     },
     NewReading(Result<Reading, Box<protocol::Error>>),
+    DecodeFailure(Box<subscriber::DecodeFailure>),
     AffectorControlled {
         affector: protocol::Affector,
         controlled_by: String,
+        result: Result<subscriber::Delivered, subscriber::AffectorError>,
+    },
+    NodeReset(subscriber::ResetDecision),
+    /// Does not include the affectors, those are tracked by the
+    /// [`AffectorRegistar`].
+    Snapshot {
+        tx: oneshot::Sender<subscriber::Snapshot>,
+    },
+    SubscriberStats {
+        tx: oneshot::Sender<Vec<subscriber::SubscriberStats>>,
     },
 }
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use color_eyre::Result;
+use rpc::Role;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::time::timeout;
+
+use crate::api::data_source::{MAX_TOKEN_LEN, TOKEN_PREFIX};
+
+/// Nodes that send a token must do so within this time after connecting
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Prefix, token and the zero byte ending it
+const MAX_TOKEN_FRAME: usize = TOKEN_PREFIX.len() + MAX_TOKEN_LEN + 1;
+
+/// Who may connect to the data-server. Can be loaded from a RON file, for
+/// example:
+/// `(tokens: { "secret": Actuate, "bridge": DataSource }, node_allow_list: ["192.168.1.20"])`
+///
+/// The default lets anyone connect and do anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Access {
+    /// Tokens clients and data sources present during their handshake and
+    /// the role they get. If there are no tokens clients need none.
+    pub tokens: HashMap<String, Role>,
+    /// Nodes connecting from these addresses do not need a token. Meant for
+    /// embedded nodes that can not send one.
+    pub node_allow_list: Vec<IpAddr>,
+}
+
+impl Access {
+    pub fn load(path: &Path) -> Result<Self> {
+        rpc::auth::load(path)
+    }
+
+    pub(crate) fn for_clients(&self) -> rpc::Access {
+        rpc::Access {
+            tokens: self.tokens.clone(),
+        }
+    }
+
+    /// Nodes not on the allow list must send a token before their handshake
+    pub(crate) async fn check_node(
+        &self,
+        source: IpAddr,
+        reader: &mut BufReader<OwnedReadHalf>,
+    ) -> Result<(), String> {
+        if self.tokens.is_empty() && self.node_allow_list.is_empty() {
+            return Ok(());
+        }
+        if self.node_allow_list.contains(&source) {
+            return Ok(());
+        }
+        if self.tokens.is_empty() {
+            return Err(format!("{source} is not on the node allow list"));
+        }
+
+        let mut buf = Vec::new();
+        let mut limited = (&mut *reader).take(MAX_TOKEN_FRAME as u64);
+        timeout(TOKEN_TIMEOUT, limited.read_until(0, &mut buf))
+            .await
+            .map_err(|_| format!("{source} did not send a token in time"))?
+            .map_err(|e| format!("Could not read token: {e}"))?;
+        let token = buf
+            .strip_prefix(TOKEN_PREFIX)
+            .and_then(|token| token.strip_suffix(&[0]))
+            .ok_or_else(|| {
+                format!(
+                    "{source} is not on the node allow list and send no token"
+                )
+            })?;
+        let token = std::str::from_utf8(token)
+            .map_err(|_| "Token is not valid utf8".to_owned())?;
+
+        match self.tokens.get(token) {
+            Some(role) if role.includes(Role::DataSource) => Ok(()),
+            Some(role) => {
+                Err(format!("Token has role {role:?}, it may not supply data"))
+            }
+            None => Err("Unknown token".to_owned()),
+        }
+    }
+}
@@ -1,32 +1,80 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
 use std::num::NonZeroU32;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use color_eyre::Result;
 use governor::Quota;
-use protocol::{Affector, IsSameAs};
+use protocol::affector::Order;
+use protocol::{Affector, IsSameAs, OrderAck, OrderOutcome};
 use serde::{Deserialize, Serialize};
 use slotmap::{DefaultKey, SlotMap};
 use tokio::io::AsyncWriteExt;
 use tokio::net::tcp::OwnedWriteHalf;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::oneshot;
 
 use tracing::{instrument, warn};
 
+use crate::api::subscriber::{NodeInfo, NodeStatus};
+
 pub(crate) struct Registration {
-    tx: tokio::sync::mpsc::Sender<protocol::Affector>,
+    tx: tokio::sync::mpsc::Sender<Order>,
+    addr: SocketAddr,
+    protocol_version: u8,
     controls: Vec<protocol::Affector>,
     rate_limiter: governor::DefaultDirectRateLimiter,
+    /// Id we give the next order we send the node
+    next_order: u32,
+    /// The node said during the handshake that it acknowledges orders
+    acks_orders: bool,
+    awaiting_ack: HashMap<u32, oneshot::Sender<OrderOutcome>>,
 }
 
 impl std::fmt::Debug for Registration {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Registration")
+            .field("addr", &self.addr)
+            .field("protocol_version", &self.protocol_version)
             .field("controls", &self.controls)
             .finish()
     }
 }
 
+/// How long a client waits for a node to acknowledge an order
+pub(crate) const ACK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub(crate) enum Delivery {
+    /// The node does not acknowledge orders
+    Send,
+    AwaitingAck(oneshot::Receiver<OrderOutcome>),
+}
+
 impl Registration {
+    /// Sends the order to the node. The ack is awaited before sending so
+    /// it can not arrive before we know to expect it.
+    fn send(&mut self, affector: Affector) -> Option<Delivery> {
+        let id = self.next_order;
+        // acks that never arrived
+        self.awaiting_ack.retain(|_, tx| !tx.is_closed());
+
+        let delivery = if self.acks_orders {
+            let (tx, rx) = oneshot::channel();
+            self.awaiting_ack.insert(id, tx);
+            Delivery::AwaitingAck(rx)
+        } else {
+            Delivery::Send
+        };
+
+        if self.tx.try_send(Order { id, affector }).is_err() {
+            self.awaiting_ack.remove(&id);
+            return None;
+        }
+        self.next_order = self.next_order.wrapping_add(1);
+        Some(delivery)
+    }
+
     fn update(&mut self, new: Affector) {
         let curr = self
             .controls
@@ -37,24 +85,47 @@ impl Registration {
     }
 }
 
+/// A node that was refused because it speaks another protocol version
+#[derive(Debug)]
+struct Refused {
+    addr: SocketAddr,
+    protocol_version: u8,
+    at: Instant,
+}
+
 #[derive(Clone, Default)]
-pub struct Registar(Arc<Mutex<SlotMap<DefaultKey, Registration>>>);
+pub struct Registar {
+    nodes: Arc<Mutex<SlotMap<DefaultKey, Registration>>>,
+    /// The last refused connection from each address
+    refused: Arc<Mutex<HashMap<IpAddr, Refused>>>,
+}
 
 impl std::fmt::Debug for Registar {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let this = self.0.lock().unwrap();
+        let this = self.nodes.lock().unwrap();
         let list: Vec<_> = this.values().map(|r| format!("{r:?}")).collect();
-        f.debug_struct("Registar").field("0", &list).finish()
+        let refused = self.refused.lock().unwrap();
+        f.debug_struct("Registar")
+            .field("nodes", &list)
+            .field("refused", &refused.values().collect::<Vec<_>>())
+            .finish()
     }
 }
 
 impl Registar {
     pub(crate) fn register(
         &self,
-        tx: Sender<Affector>,
+        tx: Sender<Order>,
+        addr: SocketAddr,
+        protocol_version: u8,
         affectors: Vec<Affector>,
+        acks_orders: bool,
     ) -> DefaultKey {
-        let mut this = self.0.lock().expect("nothing should panic");
+        self.refused
+            .lock()
+            .expect("nothing should panic")
+            .remove(&addr.ip());
+        let mut this = self.nodes.lock().expect("nothing should panic");
 
         let to_remove: Vec<_> = this
             .iter_mut()
@@ -75,25 +146,42 @@ impl Registar {
 
         this.insert(Registration {
             tx,
+            addr,
+            protocol_version,
             controls: affectors,
             rate_limiter: governor::DefaultDirectRateLimiter::direct(
                 Quota::per_second(NonZeroU32::new(1).expect("not zero"))
                     .allow_burst(NonZeroU32::new(5).expect("not zero")),
             ),
+            next_order: 0,
+            acks_orders,
+            awaiting_ack: HashMap::new(),
         })
     }
 
+    /// Remember a node that was refused so it shows up in [`Self::nodes`]
+    pub(crate) fn refused(&self, addr: SocketAddr, protocol_version: u8) {
+        self.refused.lock().expect("nothing should panic").insert(
+            addr.ip(),
+            Refused {
+                addr,
+                protocol_version,
+                at: Instant::now(),
+            },
+        );
+    }
+
     pub(crate) fn remove(&self, key: DefaultKey) {
-        let mut this = self.0.lock().expect("nothing should panic");
+        let mut this = self.nodes.lock().expect("nothing should panic");
         let _ = this.remove(key); // Could have been removed by register
     }
 
     pub(crate) fn activate(
         &self,
         order: Affector,
-    ) -> Result<(), AffectorError> {
+    ) -> Result<Delivery, AffectorError> {
         tracing::debug!("client is trying to activate: {order:?}");
-        let mut this = self.0.lock().expect("nothing should panic");
+        let mut this = self.nodes.lock().expect("nothing should panic");
         for possible_controller in
             this.iter_mut().map(|(_, reg)| reg).filter(|reg| {
                 reg.controls
@@ -104,23 +192,54 @@ impl Registar {
             if possible_controller.rate_limiter.check().is_err() {
                 return Err(AffectorError::RateLimited);
             }
-            if possible_controller.tx.try_send(order).is_ok() {
+            if let Some(delivery) = possible_controller.send(order) {
                 possible_controller.update(order);
                 tracing::info!("client activated: {order:?}");
-                return Ok(());
+                return Ok(delivery);
             };
         }
 
         Err(AffectorError::Offline)
     }
 
+    pub(crate) fn order_acked(&self, key: DefaultKey, ack: OrderAck) {
+        let mut this = self.nodes.lock().expect("nothing should panic");
+        let Some(reg) = this.get_mut(key) else {
+            return; // node was replaced by a new connection
+        };
+        if let Some(tx) = reg.awaiting_ack.remove(&ack.order) {
+            let _ = tx.send(ack.outcome);
+        }
+    }
+
     pub(crate) fn list(&self) -> Vec<Affector> {
-        let this = self.0.lock().expect("nothing should panic");
+        let this = self.nodes.lock().expect("nothing should panic");
         this.iter()
             .flat_map(|(_, reg)| reg.controls.iter())
             .cloned()
             .collect()
     }
+
+    /// All connected nodes and those recently refused
+    pub(crate) fn nodes(&self) -> Vec<NodeInfo> {
+        let this = self.nodes.lock().expect("nothing should panic");
+        let connected = this.values().map(|reg| NodeInfo {
+            addr: reg.addr,
+            protocol_version: reg.protocol_version,
+            status: NodeStatus::Connected {
+                affectors: reg.controls.clone(),
+            },
+        });
+        let refused = self.refused.lock().expect("nothing should panic");
+        let refused = refused.values().map(|refused| NodeInfo {
+            addr: refused.addr,
+            protocol_version: refused.protocol_version,
+            status: NodeStatus::Refused {
+                last_attempt: refused.at.elapsed(),
+            },
+        });
+        connected.chain(refused).collect()
+    }
 }
 
 #[derive(Debug, thiserror::Error, Clone, Serialize, Deserialize)]
@@ -129,12 +248,27 @@ pub enum AffectorError {
     Offline,
     #[error("Too many requests, ratelimited")]
     RateLimited,
+    #[error("Sensor node refused to execute the order")]
+    Rejected,
+    #[error("Sensor node did not acknowledge the order in time")]
+    TimedOut,
+    #[error("The role of this client does not allow controlling affectors")]
+    NotAllowed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Delivered {
+    /// Send to the node, the node does not acknowledge orders so we do not
+    /// know if it executed it.
+    Send,
+    /// The node executed the order
+    Confirmed,
 }
 
 #[instrument(skip_all)]
 pub(super) async fn control_affectors(
     mut writer: OwnedWriteHalf,
-    mut rx: Receiver<Affector>,
+    mut rx: Receiver<Order>,
 ) {
     while let Some(new_order) = rx.recv().await {
         let buf = new_order.encode();
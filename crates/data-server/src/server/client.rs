@@ -1,12 +1,13 @@
 use futures::{stream, Stream};
 use std::net::SocketAddr;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 
 use color_eyre::Result;
 
-use crate::api::subscriber;
+use crate::api::subscriber::{self, SubscribeOptions};
 
-use super::{affector::Registar, Event};
+use super::affector::{self, Delivery, Registar};
+use super::{Access, Event};
 
 #[derive(Debug, Clone)]
 struct SubHandler {
@@ -15,10 +16,12 @@ struct SubHandler {
 
 async fn do_setup(
     new_events: mpsc::Sender<Event>,
+    name: String,
+    options: SubscribeOptions,
 ) -> impl Stream<Item = subscriber::Response> + Send + 'static {
     let (tx, rx) = tokio::sync::mpsc::channel(100);
     new_events
-        .send(Event::NewSub { tx })
+        .send(Event::NewSub { tx, name, options })
         .await
         .expect("Events processor (rx) should never stop");
     stream::unfold(rx, |mut rx| async move {
@@ -30,15 +33,18 @@ async fn do_setup(
 }
 
 impl rpc::SubscriberHandler for SubHandler {
+    type Request = SubscribeOptions;
     type Update = subscriber::Response;
 
     fn setup(
         &mut self,
+        client_name: &str,
+        options: Self::Request,
     ) -> impl std::future::Future<
         Output = impl futures::prelude::Stream<Item = Self::Update> + Send + 'static,
     > + Send
            + 'static {
-        do_setup(self.new_events.clone())
+        do_setup(self.new_events.clone(), client_name.to_owned(), options)
     }
 }
 
@@ -46,6 +52,7 @@ pub async fn handle(
     addr: SocketAddr,
     tx: mpsc::Sender<Event>,
     affectors: Registar,
+    access: Access,
 ) -> color_eyre::Result<()> {
     let port = addr.port();
     let handler = SubHandler {
@@ -53,10 +60,11 @@ pub async fn handle(
     };
     rpc::server::run(
         port,
-        move |req, name| {
+        access.for_clients(),
+        move |req, name, role| {
             let tx = tx.clone();
             let affectors = affectors.clone();
-            perform_request(req, name.to_owned(), tx, affectors)
+            perform_request(req, name.to_owned(), role, tx, affectors)
         },
         Some(handler),
     )
@@ -66,10 +74,11 @@ pub async fn handle(
 async fn perform_request(
     request: subscriber::Request,
     client_name: String,
+    role: rpc::Role,
     new_event: mpsc::Sender<Event>,
     affectors: Registar,
 ) -> subscriber::Response {
-    match perform_request_inner(request, client_name, new_event, affectors).await {
+    match perform_request_inner(request, client_name, role, new_event, affectors).await {
         Ok(response) => response,
         Err(error) => subscriber::Response::Error(error),
     }
@@ -78,6 +87,7 @@ async fn perform_request(
 async fn perform_request_inner(
     request: subscriber::Request,
     client_name: String,
+    role: rpc::Role,
     new_event: mpsc::Sender<Event>,
     affectors: Registar,
 ) -> Result<subscriber::Response, subscriber::ServerError> {
@@ -85,19 +95,68 @@ async fn perform_request_inner(
         subscriber::Request::Handshake { .. } => {
             unreachable!("handshake only takes place during connection")
         }
-        subscriber::Request::Actuate(affector) => match affectors.activate(affector) {
-            Ok(()) => {
-                new_event
-                    .send(Event::AffectorControlled {
-                        affector,
-                        controlled_by: client_name.to_owned(),
-                    })
-                    .await
-                    .map_err(|_| subscriber::ServerError::FailedToSpread)?;
-                subscriber::Response::Actuate(Ok(()))
-            }
-            Err(err) => subscriber::Response::Actuate(Err(err)),
-        },
+        subscriber::Request::Actuate(affector) => {
+            let delivered = if !role.includes(rpc::Role::Actuate) {
+                Err(affector::AffectorError::NotAllowed)
+            } else {
+                match affectors.activate(affector) {
+                    Ok(delivery) => wait_for_ack(delivery).await,
+                    Err(err) => Err(err),
+                }
+            };
+            new_event
+                .send(Event::AffectorControlled {
+                    affector,
+                    controlled_by: client_name.to_owned(),
+                    result: delivered.clone(),
+                })
+                .await
+                .map_err(|_| subscriber::ServerError::FailedToSpread)?;
+            subscriber::Response::Actuate(delivered)
+        }
         subscriber::Request::ListAffectors => subscriber::Response::ListAffectors(affectors.list()),
+        subscriber::Request::ListNodes => subscriber::Response::ListNodes(affectors.nodes()),
+        subscriber::Request::Snapshot => {
+            let (tx, rx) = oneshot::channel();
+            new_event
+                .send(Event::Snapshot { tx })
+                .await
+                .map_err(|_| subscriber::ServerError::SnapshotUnavailable)?;
+            let mut snapshot = rx
+                .await
+                .map_err(|_| subscriber::ServerError::SnapshotUnavailable)?;
+            snapshot.affectors = affectors.list();
+            subscriber::Response::Snapshot(snapshot)
+        }
+        subscriber::Request::SubscriberStats => {
+            let (tx, rx) = oneshot::channel();
+            new_event
+                .send(Event::SubscriberStats { tx })
+                .await
+                .map_err(|_| subscriber::ServerError::StatsUnavailable)?;
+            let stats = rx
+                .await
+                .map_err(|_| subscriber::ServerError::StatsUnavailable)?;
+            subscriber::Response::SubscriberStats(stats)
+        }
     })
 }
+
+async fn wait_for_ack(
+    delivery: Delivery,
+) -> Result<subscriber::Delivered, subscriber::AffectorError> {
+    let rx = match delivery {
+        Delivery::Send => return Ok(subscriber::Delivered::Send),
+        Delivery::AwaitingAck(rx) => rx,
+    };
+    match tokio::time::timeout(affector::ACK_TIMEOUT, rx).await {
+        Ok(Ok(protocol::OrderOutcome::Executed)) => {
+            Ok(subscriber::Delivered::Confirmed)
+        }
+        Ok(Ok(protocol::OrderOutcome::Rejected)) => {
+            Err(subscriber::AffectorError::Rejected)
+        }
+        // node disconnected before it acknowledged
+        Ok(Err(_)) | Err(_) => Err(subscriber::AffectorError::TimedOut),
+    }
+}
@@ -8,13 +8,15 @@ use futures_concurrency::future::Race;
 use governor::clock::Clock;
 use governor::{Quota, RateLimiter};
 use protocol::Affector;
+use slotmap::DefaultKey;
 use socket2::{Socket, TcpKeepalive};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::net::tcp::OwnedReadHalf;
 use tokio::net::TcpStream;
 use tokio::sync::mpsc::Sender;
 
-use super::Event;
+use super::{Access, Event};
+use crate::api::subscriber::DecodeFailure;
 use tracing::{error, info, instrument, warn};
 
 use super::affector::{control_affectors, Registar};
@@ -26,6 +28,7 @@ pub async fn handle_nodes(
     addr: SocketAddr,
     share: &Sender<Event>,
     registar: Registar,
+    access: Access,
 ) -> Result<()> {
     let socket =
         Socket::new(socket2::Domain::IPV4, socket2::Type::STREAM, None)?;
@@ -60,6 +63,7 @@ pub async fn handle_nodes(
                     source,
                     share.clone(),
                     registar.clone(),
+                    access.clone(),
                 ));
             }
             Err(e) => {
@@ -70,10 +74,10 @@ pub async fn handle_nodes(
     }
 }
 
-pub async fn read_and_decode_packet(
+async fn read_packet<'a>(
     reader: &mut BufReader<OwnedReadHalf>,
-    buf: &mut Vec<u8>,
-) -> color_eyre::Result<protocol::Msg<50>> {
+    buf: &'a mut Vec<u8>,
+) -> color_eyre::Result<&'a mut [u8]> {
     buf.clear();
     let n_read = reader
         .read_until(0, buf)
@@ -84,16 +88,16 @@ pub async fn read_and_decode_packet(
     if bytes.is_empty() {
         return Err(eyre!("End of stream, connection is closed"));
     }
-
-    protocol::Msg::<50>::decode(bytes).wrap_err("decode failed")
+    Ok(bytes)
 }
 
-#[instrument(skip(stream, queue, registar))]
+#[instrument(skip(stream, queue, registar, access))]
 async fn handle_node(
     stream: TcpStream,
     source: SocketAddr,
     queue: Sender<Event>,
     registar: Registar,
+    access: Access,
 ) {
     use tracing_futures::Instrument;
 
@@ -109,19 +113,46 @@ async fn handle_node(
     let (reader, writer) = stream.into_split();
     let mut reader = BufReader::new(reader);
 
-    let affectors = match handshake(&mut reader).await {
-        Ok(affectors) => affectors,
+    if let Err(e) = access.check_node(source.ip(), &mut reader).await {
+        error!("refused node: {e}");
+        return;
+    }
+
+    let list = match handshake(&mut reader).await {
+        Ok(list) => list,
         Err(e) => {
-            error!("failed node handshake: {e}");
+            let HandshakeError::IncompatibleVersion { version, prefix } = &e
+            else {
+                error!("failed node handshake: {e}");
+                return;
+            };
+            error!("refused node: {e}");
+            registar.refused(source, *version);
+            // we can not decode the affectors of another protocol version
+            let failure = DecodeFailure::new(source, Vec::new(), prefix, &e);
+            queue
+                .send(Event::DecodeFailure(Box::new(failure)))
+                .await
+                .expect("fn spread_updates should stay running");
             return;
         }
     };
+    let affectors = list.values.to_vec();
     info!("new node connected with affectors: {affectors:?}");
 
     let (tx, rx) = tokio::sync::mpsc::channel(10);
-    let key = registar.register(tx, affectors);
+    let key = registar.register(
+        tx,
+        source,
+        protocol::PROTOCOL_VERSION,
+        affectors.clone(),
+        list.acks_orders,
+    );
     (
-        receive_and_spread_updates(reader, queue).in_current_span(),
+        receive_and_spread_updates(
+            reader, queue, &registar, key, source, affectors,
+        )
+        .in_current_span(),
         control_affectors(writer, rx).in_current_span(),
     )
         .race()
@@ -131,31 +162,66 @@ async fn handle_node(
     warn!("node removed (lost connection)");
 }
 
+#[derive(Debug, thiserror::Error)]
+enum HandshakeError {
+    #[error("Error while reading and decoding packet: {0}")]
+    Read(color_eyre::Report),
+    #[error("Must get affector list as first message (handshake)")]
+    NotAffectorList,
+    #[error(
+        "node speaks protocol version {version} while this server speaks \
+        version {ours}, the node needs to be reflashed with firmware build \
+        against the same protocol",
+        ours = protocol::PROTOCOL_VERSION
+    )]
+    IncompatibleVersion {
+        version: u8,
+        /// Start of the handshake, for reporting the failure
+        prefix: Vec<u8>,
+    },
+}
+
 async fn handshake(
     reader: &mut BufReader<OwnedReadHalf>,
-) -> Result<Vec<Affector>, String> {
+) -> Result<protocol::affector::ListMessage<50>, HandshakeError> {
     let mut buf = Vec::new();
-    let msg = match read_and_decode_packet(reader, &mut buf).await {
-        Ok(decoded) => decoded,
-        Err(e) => {
-            return Err(format!(
-                "Error while reading and decoding packet: {e}"
-            ));
-        }
-    };
+    let bytes = read_packet(reader, &mut buf)
+        .await
+        .map_err(HandshakeError::Read)?;
+    if bytes[0] != protocol::Msg::<50>::AFFECTOR_LIST {
+        return Err(HandshakeError::NotAffectorList);
+    }
+
+    // check before decoding, a node with another version could send
+    // affectors that decode into the wrong variants
+    let version =
+        protocol::affector::ListMessage::<50>::peek_version(&bytes[1..])
+            .map_err(|e| HandshakeError::Read(e.into()))?;
+    if version != protocol::PROTOCOL_VERSION {
+        let prefix_len = bytes.len().min(DecodeFailure::HEX_PREFIX_LEN);
+        return Err(HandshakeError::IncompatibleVersion {
+            version,
+            prefix: bytes[..prefix_len].to_vec(),
+        });
+    }
+
+    let msg = protocol::Msg::<50>::decode(bytes)
+        .map_err(|e| HandshakeError::Read(e.into()))?;
     let protocol::Msg::AffectorList(list) = msg else {
-        return Err(
-            "Must get affector list as first message (handshake)".to_owned()
-        );
+        unreachable!("header was checked to be an affector list")
     };
 
-    Ok(list.values.to_vec())
+    Ok(list)
 }
 
 #[instrument(skip_all)]
 async fn receive_and_spread_updates(
     mut reader: BufReader<OwnedReadHalf>,
     queue: Sender<Event>,
+    registar: &Registar,
+    key: DefaultKey,
+    source: SocketAddr,
+    affectors: Vec<Affector>,
 ) {
     let quota = Quota::per_second(NonZeroU32::new(40).unwrap())
         .allow_burst(NonZeroU32::new(200u32).unwrap());
@@ -173,10 +239,25 @@ async fn receive_and_spread_updates(
             tokio::time::sleep(allowed_in).await;
         }
 
-        let msg = match read_and_decode_packet(&mut reader, &mut buf).await {
+        let bytes = match read_packet(&mut reader, &mut buf).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("Error while reading packet: {e}");
+                return;
+            }
+        };
+        // decoding happens in place, keep the start for the report
+        let prefix_len = bytes.len().min(DecodeFailure::HEX_PREFIX_LEN);
+        let prefix = bytes[..prefix_len].to_vec();
+        let msg = match protocol::Msg::<50>::decode(bytes) {
             Ok(decoded) => decoded,
             Err(e) => {
-                error!("Error while reading and decoding packet: {e}");
+                let failure = DecodeFailure::new(source, affectors, &prefix, e);
+                error!("{failure}, start of message: {}", failure.hex_prefix);
+                queue
+                    .send(Event::DecodeFailure(Box::new(failure)))
+                    .await
+                    .expect("fn spread_updates should stay running");
                 return;
             }
         };
@@ -201,6 +282,7 @@ async fn receive_and_spread_updates(
                 error!("Affector list should only be send at the start of the connection");
                 return;
             }
+            protocol::Msg::OrderAck(ack) => registar.order_acked(key, ack),
         }
     } // Loop
 }
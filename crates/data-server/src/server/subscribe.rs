@@ -1,22 +1,53 @@
 use protocol::reading::tree::Tree;
 use protocol::reading::ReadingId;
-use protocol::{pir, Reading};
+use protocol::{pir, Device, Reading};
 use std::collections::HashMap;
 use std::mem;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::error::TrySendError;
 use tokio_util::time::FutureExt;
-use tracing::trace;
+use tracing::{trace, warn};
 
 use color_eyre::Result;
 
 use super::Event;
-use crate::api::subscriber::SubMessage;
+use crate::api::subscriber::{
+    Decimation, Filter, Retained, Snapshot, SubMessage, SubscriberStats,
+};
+
+struct Subscriber {
+    tx: mpsc::Sender<SubMessage>,
+    name: String,
+    filter: Filter,
+    decimator: Decimator,
+    /// Dropped since the last [`SubMessage::Lagged`] was send
+    unreported_drops: usize,
+    total_drops: u64,
+}
+
+impl Subscriber {
+    fn stats(&self) -> SubscriberStats {
+        SubscriberStats {
+            name: self.name.clone(),
+            dropped: self.total_drops,
+            queued: self.tx.max_capacity() - self.tx.capacity(),
+        }
+    }
+
+    fn record_drop(&mut self) {
+        if self.unreported_drops == 0 {
+            warn!("Subscriber {} is lagging, dropping messages", self.name);
+        }
+        self.unreported_drops += 1;
+        self.total_drops += 1;
+    }
+}
 
 pub async fn handle_updates(mut events: mpsc::Receiver<Event>) -> Result<()> {
     let mut pir_state_tracker = PirStateTracker::new();
     let mut subscribers = Vec::new();
+    let mut last_known = LastKnown::default();
 
     loop {
         let update = events
@@ -31,27 +62,44 @@ pub async fn handle_updates(mut events: mpsc::Receiver<Event>) -> Result<()> {
         match update {
             Err(_timeout) => {
                 for pir_went_dark in pir_state_tracker.dark_pirs() {
-                    broadcast_reading(
-                        &mut subscribers,
-                        SubMessage::Reading(pir_went_dark),
-                    );
+                    let msg = SubMessage::Reading(pir_went_dark);
+                    last_known.update(&msg);
+                    broadcast_reading(&mut subscribers, msg);
                 }
             }
-            Ok(event) => {
-                spread_updates(&mut pir_state_tracker, &mut subscribers, event)
+            Ok(Event::Snapshot { tx }) => {
+                let _ = tx.send(last_known.snapshot());
             }
+            Ok(Event::SubscriberStats { tx }) => {
+                let _ = tx
+                    .send(subscribers.iter().map(Subscriber::stats).collect());
+            }
+            Ok(event) => spread_updates(
+                &mut pir_state_tracker,
+                &mut subscribers,
+                &mut last_known,
+                event,
+            ),
         }
     }
 }
 
 fn spread_updates(
     pir_state_tracker: &mut PirStateTracker,
-    subscribers: &mut Vec<mpsc::Sender<SubMessage>>,
+    subscribers: &mut Vec<Subscriber>,
+    last_known: &mut LastKnown,
     event: Event,
 ) {
     let to_forward = match event {
-        Event::NewSub { tx } => {
-            subscribers.push(tx);
+        Event::NewSub { tx, name, options } => {
+            subscribers.push(Subscriber {
+                tx,
+                name,
+                filter: options.filter,
+                decimator: Decimator::new(options.decimation),
+                unreported_drops: 0,
+                total_drops: 0,
+            });
             return;
         }
         Event::NewReading(Ok(reading)) => {
@@ -64,27 +112,99 @@ fn spread_updates(
             }
         }
         Event::NewReading(Err(err)) => SubMessage::ErrorReport(err),
+        Event::DecodeFailure(failure) => SubMessage::DecodeFailure(failure),
         Event::AffectorControlled {
             affector,
             controlled_by,
+            result,
         } => SubMessage::AffectorControlled {
             affector,
             controlled_by,
+            result,
         },
+        Event::NodeReset(decision) => SubMessage::NodeReset(decision),
+        Event::Snapshot { .. } | Event::SubscriberStats { .. } => {
+            unreachable!("requests are answered by handle_updates")
+        }
     };
+    last_known.update(&to_forward);
     broadcast_reading(subscribers, to_forward);
 }
 
+/// The most recent reading for every [`ReadingId`] and the outstanding
+/// error for every device. Lets new clients show something before the
+/// next update arrives.
+#[derive(Default)]
+struct LastKnown {
+    readings: HashMap<ReadingId, (Instant, Reading)>,
+    errors: HashMap<Device, (Instant, protocol::Error)>,
+}
+
+impl LastKnown {
+    fn update(&mut self, msg: &SubMessage) {
+        let now = Instant::now();
+        match msg {
+            SubMessage::Reading(reading) => {
+                // a reading means the device recovered from any error
+                self.errors.remove(&reading.device());
+                self.readings.insert(reading.id(), (now, reading.clone()));
+            }
+            SubMessage::ErrorReport(error) => {
+                self.errors
+                    .insert(error.device(), (now, error.as_ref().clone()));
+            }
+            SubMessage::AffectorControlled { .. }
+            | SubMessage::DecodeFailure(_)
+            | SubMessage::NodeReset(_)
+            | SubMessage::Lagged { .. } => (),
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        fn retained<T: Clone>((at, value): &(Instant, T)) -> Retained<T> {
+            Retained {
+                value: value.clone(),
+                age: at.elapsed(),
+            }
+        }
+
+        Snapshot {
+            readings: self.readings.values().map(retained).collect(),
+            errors: self.errors.values().map(retained).collect(),
+            affectors: Vec::new(),
+        }
+    }
+}
+
 fn broadcast_reading(
-    subscribers: &mut Vec<mpsc::Sender<SubMessage>>,
+    subscribers: &mut Vec<Subscriber>,
     to_forward: SubMessage,
 ) {
     let subs = mem::take(subscribers);
-    for sub in subs {
-        let res = sub.try_send(to_forward.clone());
-        if let Ok(()) | Err(TrySendError::Full(_)) = res {
-            subscribers.push(sub)
+    for mut sub in subs {
+        if sub.tx.is_closed() {
+            continue;
+        }
+        if sub.unreported_drops > 0 {
+            let lagged = SubMessage::Lagged {
+                dropped: sub.unreported_drops,
+            };
+            match sub.tx.try_send(lagged) {
+                Ok(()) => sub.unreported_drops = 0,
+                Err(TrySendError::Full(_)) => (),
+                Err(TrySendError::Closed(_)) => continue,
+            }
+        }
+        if sub.filter.matches(&to_forward)
+            && sub.decimator.should_forward(&to_forward)
+        {
+            match sub.tx.try_send(to_forward.clone()) {
+                Ok(()) => sub.decimator.forwarded(&to_forward),
+                Err(TrySendError::Full(_)) => sub.record_drop(),
+                Err(TrySendError::Closed(_)) => continue,
+            }
         }
+        subscribers.push(sub)
     }
 }
 
@@ -151,3 +271,141 @@ impl PirStateTracker {
         }
     }
 }
+
+/// Tracks per reading when it was last forwarded to a subscriber and with
+/// what value. Used to downsample high frequency readings such as the weight
+/// sensors in the bed.
+struct Decimator {
+    config: Decimation,
+    last_forwarded: HashMap<ReadingId, (Instant, f32)>,
+}
+
+impl Decimator {
+    fn new(config: Decimation) -> Self {
+        Self {
+            config,
+            last_forwarded: HashMap::new(),
+        }
+    }
+
+    fn should_forward(&self, msg: &SubMessage) -> bool {
+        let SubMessage::Reading(reading) = msg else {
+            return true;
+        };
+        let info = reading.info();
+        if info.unit == protocol::Unit::None {
+            return true;
+        }
+        let Some((at, val)) = self.last_forwarded.get(&reading.id()) else {
+            return true;
+        };
+
+        let too_soon = self
+            .config
+            .min_interval
+            .is_some_and(|interval| at.elapsed() < interval);
+        let unchanged =
+            self.config.on_change && (info.val - val).abs() <= info.resolution;
+        !too_soon && !unchanged
+    }
+
+    fn forwarded(&mut self, msg: &SubMessage) {
+        if self.config.min_interval.is_none() && !self.config.on_change {
+            return;
+        }
+        if let SubMessage::Reading(reading) = msg {
+            self.last_forwarded
+                .insert(reading.id(), (Instant::now(), reading.info().val));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use protocol::large_bedroom::{self, bed, desk_right};
+
+    use super::*;
+
+    fn temperature(val: f32) -> SubMessage {
+        SubMessage::Reading(Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Temperature(val),
+        )))
+    }
+
+    fn humidity(val: f32) -> SubMessage {
+        SubMessage::Reading(Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Humidity(val),
+        )))
+    }
+
+    fn button_press() -> SubMessage {
+        SubMessage::Reading(Reading::LargeBedroom(
+            large_bedroom::Reading::DeskRight(desk_right::Reading::Button(
+                desk_right::Button::LeftLeft(protocol::button::Press(0)),
+            )),
+        ))
+    }
+
+    /// Forwards like `broadcast_reading` does, returns if it was forwarded
+    fn offer(decimator: &mut Decimator, msg: &SubMessage) -> bool {
+        let forward = decimator.should_forward(msg);
+        if forward {
+            decimator.forwarded(msg);
+        }
+        forward
+    }
+
+    #[test]
+    fn rate_limits_each_reading() {
+        let interval = Duration::from_millis(50);
+        let mut decimator = Decimator::new(Decimation {
+            min_interval: Some(interval),
+            on_change: false,
+        });
+
+        assert!(offer(&mut decimator, &temperature(20.0)));
+        assert!(!offer(&mut decimator, &temperature(21.0)));
+        // another reading has its own interval
+        assert!(offer(&mut decimator, &humidity(50.0)));
+
+        std::thread::sleep(interval);
+        assert!(offer(&mut decimator, &temperature(22.0)));
+        assert!(!offer(&mut decimator, &temperature(23.0)));
+    }
+
+    #[test]
+    fn forwards_changed_values() {
+        let mut decimator = Decimator::new(Decimation {
+            min_interval: None,
+            on_change: true,
+        });
+
+        assert!(offer(&mut decimator, &temperature(20.0)));
+        assert!(!offer(&mut decimator, &temperature(20.0)));
+        // within the sensors resolution
+        assert!(!offer(&mut decimator, &temperature(20.005)));
+        assert!(offer(&mut decimator, &temperature(20.5)));
+        // compared to the last forwarded value not the last offered one
+        assert!(!offer(&mut decimator, &temperature(20.5)));
+        assert!(offer(&mut decimator, &temperature(20.0)));
+    }
+
+    #[test]
+    fn passes_through_what_it_should_not_decimate() {
+        let mut decimator = Decimator::new(Decimation {
+            min_interval: Some(Duration::from_secs(60)),
+            on_change: true,
+        });
+
+        for _ in 0..3 {
+            assert!(offer(&mut decimator, &button_press()));
+            assert!(offer(&mut decimator, &SubMessage::Lagged { dropped: 1 }));
+        }
+
+        let mut no_decimation = Decimator::new(Decimation::default());
+        for _ in 0..3 {
+            assert!(offer(&mut no_decimation, &temperature(20.0)));
+        }
+        assert!(no_decimation.last_forwarded.is_empty());
+    }
+}
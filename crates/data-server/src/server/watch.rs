@@ -1,20 +1,219 @@
-use crate::api::subscriber::SubMessage;
+use crate::api::subscriber::{
+    Filter, Kind, Selector, SubMessage, SubscribeOptions,
+};
+use std::collections::VecDeque;
+use std::path::Path;
 use std::time::Duration;
 
+use super::affector::AffectorError;
 use super::{AffectorRegistar, Event};
+use color_eyre::eyre::Context;
+use color_eyre::{Result, Section};
 use protocol::reading::tree::Tree;
 use protocol::{large_bedroom, small_bedroom, Affector, IsSameAs, Reading};
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 use tokio::time::{timeout_at, Instant};
 use tracing::warn;
 
-const CHECK_INTERVAL: Duration = Duration::from_secs(5);
-const MIN_RESET_INTERVAL: Duration = Duration::from_secs(600);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Settings for the node watchdog. Can be loaded from a RON file, durations
+/// are written as `(secs: 600, nanos: 0)` there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// How often to look for nodes that went silent
+    pub check_interval: Duration,
+    /// The first policy selecting a reading applies to it. Readings no policy
+    /// selects are not watched.
+    pub policies: Vec<Policy>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Policy {
+    /// Readings this applies to, usually all the readings of a single node
+    pub selectors: Vec<Selector>,
+    /// A reading is stale once it has not been seen for its devices
+    /// `max_sample_interval` times this.
+    pub staleness_multiplier: u32,
+    /// Affector that resets the node providing the readings
+    pub reset_affector: Affector,
+    pub backoff: Backoff,
+    /// Stop resetting a node that has been reset this often in the last
+    /// 24 hours.
+    pub max_resets_per_day: u32,
+    /// Only report stale readings, never reset the node.
+    pub alert_only: bool,
+}
+
+/// Time to wait before trying to reset a node again. Doubles with every
+/// attempt that did not bring the node back up to `max`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Backoff {
+    fn after(&self, consecutive_attempts: u32) -> Duration {
+        let factor =
+            2u32.saturating_pow(consecutive_attempts.saturating_sub(1));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        use large_bedroom::{airbox, bed as large_bed};
+        use small_bedroom::bed as small_bed;
+
+        let node = |reading: Reading, reset_affector| Policy {
+            // all readings from the node, for example the large bedroom bed
+            selectors: vec![Selector::subtree_of(&reading, 2)],
+            staleness_multiplier: 10,
+            reset_affector,
+            backoff: Backoff {
+                initial: Duration::from_secs(600),
+                max: Duration::from_secs(4 * 60 * 60),
+            },
+            max_resets_per_day: 24,
+            alert_only: false,
+        };
+
+        Self {
+            check_interval: Duration::from_secs(5),
+            policies: vec![
+                node(
+                    Reading::LargeBedroom(large_bedroom::Reading::Bed(
+                        large_bed::Reading::Temperature(0.0),
+                    )),
+                    Affector::LargeBedroom(large_bedroom::Affector::Bed(
+                        large_bed::Affector::ResetNode,
+                    )),
+                ),
+                node(
+                    Reading::SmallBedroom(small_bedroom::Reading::Bed(
+                        small_bed::Reading::Temperature(0.0),
+                    )),
+                    Affector::SmallBedroom(small_bedroom::Affector::Bed(
+                        small_bed::Affector::ResetNode,
+                    )),
+                ),
+                node(
+                    Reading::LargeBedroom(large_bedroom::Reading::Airbox(
+                        airbox::Reading::Temperature(0.0),
+                    )),
+                    Affector::LargeBedroom(large_bedroom::Affector::Airbox(
+                        airbox::Affector::ResetNode,
+                    )),
+                ),
+            ],
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let config = std::fs::read_to_string(path)
+            .wrap_err("Could not read watchdog config")
+            .with_note(|| format!("path: {}", path.display()))?;
+        ron::from_str(&config)
+            .wrap_err("Could not deserialize watchdog config")
+            .with_note(|| format!("path: {}", path.display()))
+    }
+
+    fn policy_for(&self, reading: &Reading) -> Option<&Policy> {
+        self.policies.iter().find(|policy| {
+            policy.selectors.iter().any(|s| s.matches_reading(reading))
+        })
+    }
+}
+
+/// Send to subscribers whenever the watchdog resets a node or decides not to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResetDecision {
+    /// One of the readings the node stopped sending
+    pub stale_reading: Reading,
+    /// Time since that reading was last seen
+    pub silent_for: Duration,
+    pub reset_affector: Affector,
+    pub action: ResetAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ResetAction {
+    /// A reset was ordered, send again for every following attempt
+    Reset(Result<(), AffectorError>),
+    /// The policy does not allow resets. Send once until the node recovers.
+    AlertOnly,
+    /// The node has been reset `max_resets_per_day` times in the last 24
+    /// hours. Send once until the node recovers.
+    DailyLimitReached,
+}
+
+#[derive(Default)]
+struct NodeState {
+    /// Successful resets in the last 24 hours
+    resets: VecDeque<Instant>,
+    last_attempt: Option<Instant>,
+    /// Attempts since the node last sent all its readings in time
+    consecutive_attempts: u32,
+    /// Whether a decision not to reset has been send since the node went
+    /// silent
+    reported: bool,
+}
+
+impl NodeState {
+    fn recovered(&mut self) {
+        self.consecutive_attempts = 0;
+        self.reported = false;
+    }
+
+    fn report_once(&mut self, action: ResetAction) -> Option<ResetAction> {
+        if self.reported {
+            None
+        } else {
+            self.reported = true;
+            Some(action)
+        }
+    }
+
+    fn decide(
+        &mut self,
+        policy: &Policy,
+        registar: &AffectorRegistar,
+    ) -> Option<ResetAction> {
+        if policy.alert_only {
+            return self.report_once(ResetAction::AlertOnly);
+        }
+
+        while self.resets.front().is_some_and(|at| at.elapsed() > DAY) {
+            self.resets.pop_front();
+        }
+        if self.resets.len() >= policy.max_resets_per_day as usize {
+            return self.report_once(ResetAction::DailyLimitReached);
+        }
+
+        let backoff = policy.backoff.after(self.consecutive_attempts);
+        if self.last_attempt.is_some_and(|at| at.elapsed() < backoff) {
+            return None;
+        }
+
+        let now = Instant::now();
+        self.last_attempt = Some(now);
+        self.consecutive_attempts += 1;
+        let result = registar.activate(policy.reset_affector).map(|_| ());
+        if result.is_ok() {
+            self.resets.push_back(now);
+        }
+        Some(ResetAction::Reset(result))
+    }
+}
 
 #[derive(Default)]
 struct LastSeen {
     map: Vec<(Reading, Instant)>,
-    last_reset: Vec<(Affector, Instant)>,
+    nodes: Vec<(Affector, NodeState)>,
 }
 
 impl LastSeen {
@@ -28,103 +227,89 @@ impl LastSeen {
         }
     }
 
-    fn mark_reset(&mut self, affector: Affector) {
-        if let Some((_, last)) = self
-            .last_reset
-            .iter_mut()
-            .find(|(a, _)| a.is_same_as(&affector))
+    fn node_state(&mut self, affector: Affector) -> &mut NodeState {
+        let idx = if let Some(idx) =
+            self.nodes.iter().position(|(a, _)| a.is_same_as(&affector))
         {
-            *last = Instant::now();
+            idx
         } else {
-            self.last_reset.push((affector, Instant::now()));
-        }
+            self.nodes.push((affector, NodeState::default()));
+            self.nodes.len() - 1
+        };
+        &mut self.nodes[idx].1
     }
 
-    fn check_and_bite(&mut self, registar: &AffectorRegistar) {
-        let to_reset = self.map.iter().filter(|(reading, last_seen)| {
+    fn check_and_bite(
+        &mut self,
+        config: &Config,
+        registar: &AffectorRegistar,
+    ) -> Vec<ResetDecision> {
+        let mut stale: Vec<(&Policy, Reading, Duration)> = Vec::new();
+        for (reading, last_seen) in &self.map {
+            let Some(policy) = config.policy_for(reading) else {
+                continue;
+            };
             let max_interval = reading.info().device.info().max_sample_interval;
-            last_seen.elapsed() > max_interval.saturating_mul(10)
-        });
-
-        let mut reset_commands: Vec<_> = to_reset
-            .filter_map(|(reading, _)| {
-                match reading {
-                    Reading::LargeBedroom(large_bedroom::Reading::Bed(_)) => {
-                        Some(Affector::LargeBedroom(
-                            large_bedroom::Affector::Bed(
-                                large_bedroom::bed::Affector::ResetNode,
-                            ),
-                        ))
-                    }
-                    Reading::SmallBedroom(small_bedroom::Reading::Bed(_)) => {
-                        Some(Affector::SmallBedroom(
-                            small_bedroom::Affector::Bed(
-                                small_bedroom::bed::Affector::ResetNode,
-                            ),
-                        ))
-                    }
-                    Reading::LargeBedroom(large_bedroom::Reading::Airbox(
-                        _,
-                    )) => Some(Affector::LargeBedroom(
-                        large_bedroom::Affector::Airbox(
-                            large_bedroom::airbox::Affector::ResetNode,
-                        ),
-                    )),
-                    Reading::LargeBedroom(large_bedroom::Reading::Desk(_))
-                    | Reading::LargeBedroom(
-                        large_bedroom::Reading::DeskRight(_),
-                    )
-                    | Reading::LargeBedroom(
-                        large_bedroom::Reading::Radiator(_),
-                    )
-                    | Reading::SmallBedroom(small_bedroom::Reading::Desk(_))
-                    | Reading::SmallBedroom(
-                        small_bedroom::Reading::Radiator(_),
-                    )
-                    | Reading::SmallBedroom(
-                        small_bedroom::Reading::ButtonPanel(_),
-                    )
-                    | Reading::SmallBedroom(
-                        small_bedroom::Reading::PortableButtonPanel(_),
-                    ) => None,
-                }
-                .map(|reset_cmd| (reading, reset_cmd))
-            })
-            .filter(|(_, reset_cmd)| {
-                let last_reset = self
-                    .last_reset
-                    .iter()
-                    .find(|(a, _)| a.is_same_as(reset_cmd))
-                    .map(|(_, at)| at)
-                    .copied();
-                !last_reset.is_some_and(|last_reset| {
-                    last_reset.elapsed() > MIN_RESET_INTERVAL
-                })
-            })
-            .map(|(reading, reset_cmd)| (reading.clone(), reset_cmd))
-            .collect();
-        reset_commands.dedup_by(|(_, a), (_, b)| a.is_same_as(b));
-
-        for (reading, cmd) in reset_commands {
-            warn!("resetting node with problematic reading: {reading:?}, using: {cmd:?}");
-            if registar.activate(cmd).is_ok() {
-                self.mark_reset(cmd);
+            let silent_for = last_seen.elapsed();
+            let is_stale = silent_for
+                > max_interval.saturating_mul(policy.staleness_multiplier);
+            let node_listed = stale.iter().any(|(listed, ..)| {
+                listed.reset_affector.is_same_as(&policy.reset_affector)
+            });
+            if is_stale && !node_listed {
+                stale.push((policy, reading.clone(), silent_for));
+            }
+        }
+
+        for (affector, state) in &mut self.nodes {
+            if !stale
+                .iter()
+                .any(|(policy, ..)| policy.reset_affector.is_same_as(affector))
+            {
+                state.recovered();
             }
         }
+
+        let mut decisions = Vec::new();
+        for (policy, reading, silent_for) in stale {
+            let state = self.node_state(policy.reset_affector);
+            let Some(action) = state.decide(policy, registar) else {
+                continue;
+            };
+            warn!(
+                "node with problematic reading: {reading:?} silent for \
+                {silent_for:?}, decided: {action:?}"
+            );
+            decisions.push(ResetDecision {
+                stale_reading: reading,
+                silent_for,
+                reset_affector: policy.reset_affector,
+                action,
+            });
+        }
+        decisions
     }
 }
 
 pub async fn node_watchdog(
     registar: AffectorRegistar,
     sub_tx: &mpsc::Sender<Event>,
+    config: Config,
 ) -> ! {
     let (tx, mut rx) = mpsc::channel(128);
     sub_tx
-        .send(Event::NewSub { tx })
+        .send(Event::NewSub {
+            tx,
+            name: "node-watchdog".to_owned(),
+            options: SubscribeOptions {
+                filter: Filter::default().of_kinds([Kind::Reading]),
+                ..SubscribeOptions::default()
+            },
+        })
         .await
         .expect("handle_sub_should_still_run");
 
-    let mut next_check = tokio::time::Instant::now() + CHECK_INTERVAL;
+    let mut next_check = tokio::time::Instant::now() + config.check_interval;
     let mut last_seen = LastSeen::default();
     loop {
         match timeout_at(next_check, rx.recv()).await {
@@ -132,8 +317,13 @@ pub async fn node_watchdog(
             Ok(Some(SubMessage::Reading(r))) => last_seen.update(r),
             Ok(Some(_)) => (),
             Err(_timeout) => {
-                last_seen.check_and_bite(&registar);
-                next_check = Instant::now() + CHECK_INTERVAL;
+                for decision in last_seen.check_and_bite(&config, &registar) {
+                    sub_tx
+                        .send(Event::NodeReset(decision))
+                        .await
+                        .expect("handle_sub_should_still_run");
+                }
+                next_check = Instant::now() + config.check_interval;
             }
         }
     }
@@ -36,8 +36,8 @@ async fn run_server(
     let (tx, rx) = mpsc::channel(2000);
     let affectors = AffectorRegistar::default();
     select! {
-        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone()) => e.unwrap(),
-        e = server::handle_nodes(data_port.into(), &tx, affectors) => e.unwrap(),
+        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone(), server::Access::default()) => e.unwrap(),
+        e = server::handle_nodes(data_port.into(), &tx, affectors, server::Access::default()) => e.unwrap(),
         e = server::handle_updates(rx) => e?,
     };
 
@@ -4,11 +4,16 @@ use std::time::Duration;
 
 use color_eyre::Result;
 use data_server::api::data_source;
-use data_server::api::subscriber::{Client, SubMessage};
-use data_server::server::{self, AffectorRegistar};
+use data_server::api::subscriber::client::Error;
+use data_server::api::subscriber::{
+    AffectorError, Client, Delivered, Filter, Kind, NodeStatus, ResetAction,
+    Selector, SubMessage, SubscribeOptions,
+};
+use data_server::server::watch::{Backoff, Policy};
+use data_server::server::{self, AffectorRegistar, WatchdogConfig};
 use protocol::large_bedroom::bed;
 use protocol::Reading;
-use protocol::{large_bedroom, Affector};
+use protocol::{large_bedroom, Affector, OrderOutcome};
 use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
 use tokio::select;
@@ -36,8 +41,8 @@ async fn run_server(
     let (tx, rx) = mpsc::channel(2000);
     let affectors = AffectorRegistar::default();
     select! {
-        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone()) => e.unwrap(),
-        e = server::handle_nodes(data_port.into(), &tx, affectors) => e.unwrap(),
+        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone(), server::Access::default()) => e.unwrap(),
+        e = server::handle_nodes(data_port.into(), &tx, affectors, server::Access::default()) => e.unwrap(),
         e = server::handle_updates(rx) => e?,
     };
 
@@ -154,6 +159,7 @@ async fn recv_affector_order(data_port: u16) -> Result<Done> {
         (Ipv4Addr::LOCALHOST, data_port),
         vec![TEST_AFFECTOR],
         Some(tx),
+        None,
     )
     .await
     .unwrap();
@@ -182,3 +188,394 @@ async fn recv_affector() {
     };
     assert_eq!(res.unwrap(), Done::Test);
 }
+
+/// Acks the first order and rejects the second
+async fn ack_affector_orders(data_port: u16) -> Result<Done> {
+    let data_source::Client {
+        mut sender,
+        mut receiver,
+    } = data_source::Client::connect_acking_orders(
+        (Ipv4Addr::LOCALHOST, data_port),
+        vec![TEST_AFFECTOR],
+        None,
+    )
+    .await
+    .unwrap();
+
+    let mut ids = Vec::new();
+    for outcome in [OrderOutcome::Executed, OrderOutcome::Rejected] {
+        let order = receiver.receive().await.unwrap();
+        assert_eq!(order.affector, TEST_AFFECTOR);
+        sender.send_order_ack(order.id, outcome).await.unwrap();
+        ids.push(order.id);
+    }
+    assert_ne!(ids[0], ids[1]);
+
+    Ok(pending::<Done>().await)
+}
+
+async fn trigger_and_check_acks(sub_port: u16) -> Result<Done> {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let mut client = Client::connect(
+        (Ipv4Addr::LOCALHOST, sub_port),
+        "api_integration_tests".to_owned(),
+    )
+    .await
+    .unwrap();
+
+    let delivered = client.actuate_affector(TEST_AFFECTOR).await.unwrap();
+    assert_eq!(delivered, Delivered::Confirmed);
+
+    let err = client.actuate_affector(TEST_AFFECTOR).await.unwrap_err();
+    assert!(
+        matches!(err, Error::Request(AffectorError::Rejected)),
+        "got: {err:?}"
+    );
+
+    Ok(Done::Test)
+}
+
+#[tokio::test]
+async fn affector_order_acks() {
+    logger::tracing::setup_for_tests();
+
+    let sub_port = reserve_port::ReservedPort::random().unwrap();
+    let data_port = reserve_port::ReservedPort::random().unwrap();
+    let res = select! {
+        e = run_server(([127,0,0,1], sub_port.port()), ([127,0,0,1], data_port.port())) => e,
+        e = ack_affector_orders(data_port.port()) => e,
+        e = trigger_and_check_acks(sub_port.port()) => e,
+    };
+    assert_eq!(res.unwrap(), Done::Test);
+}
+
+async fn run_server_with_watchdog(
+    client_addr: impl Into<SocketAddr>,
+    data_port: impl Into<SocketAddr>,
+) -> Result<Done> {
+    let config = WatchdogConfig {
+        check_interval: Duration::from_millis(100),
+        policies: vec![Policy {
+            selectors: vec![Selector::Device(TEST_READING.device())],
+            // any silence is too long
+            staleness_multiplier: 0,
+            reset_affector: TEST_AFFECTOR,
+            backoff: Backoff {
+                initial: Duration::from_secs(600),
+                max: Duration::from_secs(600),
+            },
+            max_resets_per_day: 1,
+            alert_only: false,
+        }],
+    };
+
+    let (tx, rx) = mpsc::channel(2000);
+    let affectors = AffectorRegistar::default();
+    select! {
+        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone(), server::Access::default()) => e.unwrap(),
+        e = server::handle_nodes(data_port.into(), &tx, affectors.clone(), server::Access::default()) => e.unwrap(),
+        e = server::handle_updates(rx) => e?,
+        _ = server::node_watchdog(affectors, &tx, config) => (),
+    };
+
+    Ok(Done::RunServer)
+}
+
+async fn silent_node(data_port: u16) -> Result<Done> {
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let data_source::Client {
+        mut sender,
+        mut receiver,
+    } = data_source::Client::connect(
+        (Ipv4Addr::LOCALHOST, data_port),
+        vec![TEST_AFFECTOR],
+    )
+    .await
+    .unwrap();
+    sender.send_reading(TEST_READING).await.unwrap();
+
+    let order =
+        tokio::time::timeout(Duration::from_secs(1), receiver.receive())
+            .await
+            .unwrap()
+            .unwrap();
+    assert_eq!(order.affector, TEST_AFFECTOR);
+
+    Ok(pending::<Done>().await)
+}
+
+async fn receive_reset_decision(sub_port: u16) -> Result<Done> {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let options = SubscribeOptions {
+        filter: Filter::default().of_kinds([Kind::NodeReset]),
+        ..SubscribeOptions::default()
+    };
+    let mut sub = Client::connect(
+        (Ipv4Addr::LOCALHOST, sub_port),
+        "api_integration_tests".to_owned(),
+    )
+    .await
+    .unwrap()
+    .subscribe_with(options)
+    .await
+    .unwrap();
+
+    let received = sub.next().await.unwrap();
+    let SubMessage::NodeReset(decision) = received else {
+        panic!("expected reset decision, got: {received:?}");
+    };
+    assert_eq!(decision.reset_affector, TEST_AFFECTOR);
+    assert!(
+        matches!(decision.action, ResetAction::Reset(Ok(()))),
+        "got: {decision:?}"
+    );
+    // give the node time to receive the order
+    sleep(Duration::from_millis(100)).await;
+
+    Ok(Done::Test)
+}
+
+#[tokio::test]
+async fn watchdog_resets_silent_node() {
+    logger::tracing::setup_for_tests();
+
+    let sub_port = reserve_port::ReservedPort::random().unwrap();
+    let data_port = reserve_port::ReservedPort::random().unwrap();
+    let res = select! {
+        e = run_server_with_watchdog(([127,0,0,1], sub_port.port()), ([127,0,0,1], data_port.port())) => e,
+        e = silent_node(data_port.port()) => e,
+        e = receive_reset_decision(sub_port.port()) => e,
+    };
+    assert_eq!(res.unwrap(), Done::Test);
+}
+
+async fn run_server_with_access(
+    client_addr: impl Into<SocketAddr>,
+    data_port: impl Into<SocketAddr>,
+) -> Result<Done> {
+    let access = server::Access {
+        tokens: [
+            ("reader", rpc::Role::ReadOnly),
+            ("actuator", rpc::Role::Actuate),
+            ("bridge", rpc::Role::DataSource),
+        ]
+        .into_iter()
+        .map(|(token, role)| (token.to_owned(), role))
+        .collect(),
+        node_allow_list: Vec::new(),
+    };
+
+    let (tx, rx) = mpsc::channel(2000);
+    let affectors = AffectorRegistar::default();
+    select! {
+        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone(), access.clone()) => e.unwrap(),
+        e = server::handle_nodes(data_port.into(), &tx, affectors, access) => e.unwrap(),
+        e = server::handle_updates(rx) => e?,
+    };
+
+    Ok(Done::RunServer)
+}
+
+async fn node_with_token(data_port: u16) -> Result<Done> {
+    let data_source::Client { mut receiver, .. } =
+        data_source::Client::connect_with_token(
+            (Ipv4Addr::LOCALHOST, data_port),
+            vec![TEST_AFFECTOR],
+            "bridge",
+        )
+        .await
+        .unwrap();
+
+    let order = receiver.receive().await.unwrap();
+    assert_eq!(order.affector, TEST_AFFECTOR);
+
+    Ok(pending::<Done>().await)
+}
+
+async fn actuate_with_roles(sub_port: u16) -> Result<Done> {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let addr = (Ipv4Addr::LOCALHOST, sub_port);
+
+    let err = Client::connect(addr, "api_integration_tests".to_owned())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, rpc::client::ConnectError::Unauthorized),
+        "got: {err:?}"
+    );
+
+    let mut reader = Client::connect_with_token(
+        addr,
+        "api_integration_tests".to_owned(),
+        "reader".to_owned(),
+    )
+    .await
+    .unwrap();
+    let err = reader.actuate_affector(TEST_AFFECTOR).await.unwrap_err();
+    assert!(
+        matches!(err, Error::Request(AffectorError::NotAllowed)),
+        "got: {err:?}"
+    );
+
+    let mut actuator = Client::connect_with_token(
+        addr,
+        "api_integration_tests".to_owned(),
+        "actuator".to_owned(),
+    )
+    .await
+    .unwrap();
+    actuator.actuate_affector(TEST_AFFECTOR).await.unwrap();
+    // give the node time to receive the order
+    sleep(Duration::from_millis(100)).await;
+
+    Ok(Done::Test)
+}
+
+#[tokio::test]
+async fn access_roles() {
+    logger::tracing::setup_for_tests();
+
+    let sub_port = reserve_port::ReservedPort::random().unwrap();
+    let data_port = reserve_port::ReservedPort::random().unwrap();
+    let res = select! {
+        e = run_server_with_access(([127,0,0,1], sub_port.port()), ([127,0,0,1], data_port.port())) => e,
+        e = node_with_token(data_port.port()) => e,
+        e = actuate_with_roles(sub_port.port()) => e,
+    };
+    assert_eq!(res.unwrap(), Done::Test);
+}
+
+const OTHER_PROTOCOL_VERSION: u8 = protocol::PROTOCOL_VERSION.wrapping_add(1);
+
+async fn outdated_node(data_port: u16) -> Result<Done> {
+    use tokio::io::AsyncReadExt;
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let mut list = protocol::affector::ListMessage::<50>::empty();
+    list.values.push(TEST_AFFECTOR).unwrap();
+    list.version = OTHER_PROTOCOL_VERSION;
+    let handshake = protocol::Msg::AffectorList(list).encode();
+
+    let mut conn = TcpStream::connect(("127.0.0.1", data_port)).await.unwrap();
+    conn.write_all(&handshake).await.unwrap();
+
+    let mut buf = [0u8; 8];
+    let n_read = conn.read(&mut buf).await.unwrap();
+    assert_eq!(n_read, 0, "server should close the connection");
+
+    Ok(pending::<Done>().await)
+}
+
+async fn list_refused_node(sub_port: u16) -> Result<Done> {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let mut sub = Client::connect(
+        (Ipv4Addr::LOCALHOST, sub_port),
+        "api_integration_tests".to_owned(),
+    )
+    .await
+    .unwrap()
+    .subscribe_with(SubscribeOptions {
+        filter: Filter::default().of_kinds([Kind::ErrorReport]),
+        ..SubscribeOptions::default()
+    })
+    .await
+    .unwrap();
+
+    let received = sub.next().await.unwrap();
+    let SubMessage::DecodeFailure(failure) = received else {
+        panic!("expected decode failure, got: {received:?}");
+    };
+    assert!(failure.affectors.is_empty());
+    assert_eq!(failure.header, protocol::Msg::<50>::AFFECTOR_LIST);
+    assert!(
+        failure.error.contains("protocol version"),
+        "got: {failure:?}"
+    );
+
+    let nodes = Client::connect(
+        (Ipv4Addr::LOCALHOST, sub_port),
+        "api_integration_tests".to_owned(),
+    )
+    .await
+    .unwrap()
+    .list_nodes()
+    .await
+    .unwrap();
+
+    assert_eq!(nodes.len(), 1, "got: {nodes:?}");
+    assert_eq!(nodes[0].protocol_version, OTHER_PROTOCOL_VERSION);
+    assert!(
+        matches!(nodes[0].status, NodeStatus::Refused { .. }),
+        "got: {nodes:?}"
+    );
+
+    Ok(Done::Test)
+}
+
+#[tokio::test]
+async fn refuse_other_protocol_version() {
+    logger::tracing::setup_for_tests();
+
+    let sub_port = reserve_port::ReservedPort::random().unwrap();
+    let data_port = reserve_port::ReservedPort::random().unwrap();
+    let res = select! {
+        e = run_server(([127,0,0,1], sub_port.port()), ([127,0,0,1], data_port.port())) => e,
+        e = outdated_node(data_port.port()) => e,
+        e = list_refused_node(sub_port.port()) => e,
+    };
+    assert_eq!(res.unwrap(), Done::Test);
+}
+
+async fn node_sending_garbage(data_port: u16) -> Result<Done> {
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let mut list = protocol::affector::ListMessage::<50>::empty();
+    list.values.push(TEST_AFFECTOR).unwrap();
+    let handshake = protocol::Msg::AffectorList(list).encode();
+
+    let mut conn = TcpStream::connect(("127.0.0.1", data_port)).await.unwrap();
+    conn.write_all(&handshake).await.unwrap();
+    // 42 is not a known message type
+    conn.write_all(&[42, 1, 2, 3, 0]).await.unwrap();
+
+    Ok(pending::<Done>().await)
+}
+
+async fn receive_decode_failure(sub_port: u16) -> Result<Done> {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let mut sub = Client::connect(
+        (Ipv4Addr::LOCALHOST, sub_port),
+        "api_integration_tests".to_owned(),
+    )
+    .await
+    .unwrap()
+    .subscribe_with(SubscribeOptions {
+        filter: Filter::default().of_kinds([Kind::ErrorReport]),
+        ..SubscribeOptions::default()
+    })
+    .await
+    .unwrap();
+
+    let received = sub.next().await.unwrap();
+    let SubMessage::DecodeFailure(failure) = received else {
+        panic!("expected decode failure, got: {received:?}");
+    };
+    assert_eq!(failure.affectors, [TEST_AFFECTOR]);
+    assert_eq!(failure.header, 42);
+    assert_eq!(failure.hex_prefix, "2a 01 02 03 00");
+
+    Ok(Done::Test)
+}
+
+#[tokio::test]
+async fn decode_failures_reach_subscribers() {
+    logger::tracing::setup_for_tests();
+
+    let sub_port = reserve_port::ReservedPort::random().unwrap();
+    let data_port = reserve_port::ReservedPort::random().unwrap();
+    let res = select! {
+        e = run_server(([127,0,0,1], sub_port.port()), ([127,0,0,1], data_port.port())) => e,
+        e = node_sending_garbage(data_port.port()) => e,
+        e = receive_decode_failure(sub_port.port()) => e,
+    };
+    assert_eq!(res.unwrap(), Done::Test);
+}
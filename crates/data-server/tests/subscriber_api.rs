@@ -2,7 +2,9 @@ use std::net::{Ipv4Addr, SocketAddr};
 use std::time::Duration;
 
 use color_eyre::Result;
-use data_server::api::subscriber::{Client, SubMessage};
+use data_server::api::subscriber::{
+    Client, Filter, Selector, SubMessage, SubscribeOptions,
+};
 use data_server::server::{self, AffectorRegistar};
 use protocol::large_bedroom;
 use protocol::large_bedroom::bed;
@@ -27,8 +29,8 @@ async fn run_server(
     let (tx, rx) = mpsc::channel(2000);
     let affectors = AffectorRegistar::default();
     select! {
-        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone()) => e.unwrap(),
-        e = server::handle_nodes(data_port.into(), &tx, affectors) => e.unwrap(),
+        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone(), server::Access::default()) => e.unwrap(),
+        e = server::handle_nodes(data_port.into(), &tx, affectors, server::Access::default()) => e.unwrap(),
         e = server::handle_updates(rx) => e?,
     };
 
@@ -37,6 +39,8 @@ async fn run_server(
 
 const TEST_READING: Reading =
     Reading::LargeBedroom(large_bedroom::Reading::Bed(bed::Reading::NumberPm2_5(0.0)));
+const OTHER_TEST_READING: Reading =
+    Reading::LargeBedroom(large_bedroom::Reading::Bed(bed::Reading::Temperature(20.0)));
 
 async fn send_sensor_value(data_port: u16) -> Result<Done> {
     tokio::time::sleep(Duration::from_millis(500)).await;
@@ -54,6 +58,7 @@ async fn send_sensor_value(data_port: u16) -> Result<Done> {
 
     let mut sensor_msg = protocol::SensorMessage::<50>::default();
     sensor_msg.values.push(TEST_READING).unwrap();
+    sensor_msg.values.push(OTHER_TEST_READING).unwrap();
     let sensor_msg = protocol::Msg::Readings(sensor_msg).encode();
     conn.write_all(&sensor_msg).await.unwrap();
 
@@ -79,6 +84,31 @@ async fn subscribe_and_receive_inner(sub_port: u16) -> Result<Done> {
     Ok(Done::Test)
 }
 
+async fn subscribe_filtered_inner(sub_port: u16) -> Result<Done> {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let filter = Filter::selecting([Selector::Reading(OTHER_TEST_READING.id())]);
+    let mut sub = Client::connect(
+        (Ipv4Addr::LOCALHOST, sub_port),
+        "api_integration_tests".to_owned(),
+    )
+    .await
+    .unwrap()
+    .subscribe_with(SubscribeOptions {
+        filter,
+        ..SubscribeOptions::default()
+    })
+    .await
+    .unwrap();
+
+    let received = sub.next().await.unwrap();
+    assert!(
+        matches!(received, SubMessage::Reading(OTHER_TEST_READING)),
+        "got: {received:?}"
+    );
+
+    Ok(Done::Test)
+}
+
 async fn list_affectors_inner(sub_port: u16) -> Result<Done> {
     tokio::time::sleep(Duration::from_millis(1000)).await;
     let list = Client::connect(
@@ -101,6 +131,108 @@ async fn list_affectors_inner(sub_port: u16) -> Result<Done> {
     Ok(Done::Test)
 }
 
+async fn snapshot_inner(sub_port: u16) -> Result<Done> {
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    let snapshot = Client::connect(
+        (Ipv4Addr::LOCALHOST, sub_port),
+        "api_integration_tests".to_owned(),
+    )
+    .await
+    .unwrap()
+    .snapshot()
+    .await
+    .unwrap();
+
+    assert_eq!(snapshot.readings.len(), 2, "got: {snapshot:?}");
+    for expected in [TEST_READING, OTHER_TEST_READING] {
+        assert!(
+            snapshot
+                .readings
+                .iter()
+                .any(|retained| retained.value.id() == expected.id()),
+            "missing {expected:?} in: {snapshot:?}"
+        );
+    }
+    assert!(snapshot.errors.is_empty());
+    assert_eq!(snapshot.affectors.len(), 1);
+
+    Ok(Done::Test)
+}
+
+/// Sends more readings then a subscriber that does not read can buffer. The
+/// data-server rate limits each node so the flood comes from multiple nodes.
+/// Keeps sending afterwards as `Lagged` is only send with the next message.
+async fn flood_sensor_values(data_port: u16) -> Result<Done> {
+    const NODES: usize = 10;
+    const PACKETS_PER_NODE: usize = 200; // burst allowed by the rate limiter
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut sensor_msg = protocol::SensorMessage::<50>::default();
+    while sensor_msg.values.push(TEST_READING).is_ok() {}
+    let sensor_msg = protocol::Msg::Readings(sensor_msg).encode();
+    let handshake =
+        protocol::Msg::AffectorList(protocol::affector::ListMessage::<50>::empty()).encode();
+
+    let mut conns = Vec::new();
+    for _ in 0..NODES {
+        let mut conn = TcpStream::connect(("127.0.0.1", data_port)).await.unwrap();
+        conn.write_all(&handshake).await.unwrap();
+        for _ in 0..PACKETS_PER_NODE {
+            conn.write_all(&sensor_msg).await.unwrap();
+        }
+        conns.push(conn);
+    }
+
+    let mut single = protocol::SensorMessage::<50>::default();
+    single.values.push(OTHER_TEST_READING).unwrap();
+    let single = protocol::Msg::Readings(single).encode();
+    loop {
+        sleep(Duration::from_millis(100)).await;
+        conns[0].write_all(&single).await.unwrap();
+    }
+}
+
+async fn subscriber_stats_inner(sub_port: u16) -> Result<Done> {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let mut sub = Client::connect((Ipv4Addr::LOCALHOST, sub_port), "lagging".to_owned())
+        .await
+        .unwrap()
+        .subscribe()
+        .await
+        .unwrap();
+
+    // do not read while the flood arrives
+    sleep(Duration::from_secs(2)).await;
+    let dropped = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let SubMessage::Lagged { dropped } = sub.next().await.unwrap() {
+                return dropped;
+            }
+        }
+    })
+    .await
+    .expect("subscriber should be told it lagged");
+    assert!(dropped > 0);
+
+    let stats = Client::connect(
+        (Ipv4Addr::LOCALHOST, sub_port),
+        "api_integration_tests".to_owned(),
+    )
+    .await
+    .unwrap()
+    .subscriber_stats()
+    .await
+    .unwrap();
+
+    let lagging = stats
+        .iter()
+        .find(|s| s.name == "lagging")
+        .expect("subscriber should be listed");
+    assert!(lagging.dropped >= dropped as u64);
+
+    Ok(Done::Test)
+}
+
 #[tokio::test]
 async fn subscribe_and_receive() {
     setup_tracing();
@@ -115,6 +247,20 @@ async fn subscribe_and_receive() {
     assert_eq!(res.unwrap(), Done::Test);
 }
 
+#[tokio::test]
+async fn subscribe_filtered() {
+    setup_tracing();
+
+    let sub_port = reserve_port::ReservedPort::random().unwrap();
+    let data_port = reserve_port::ReservedPort::random().unwrap();
+    let res = select! {
+        e = run_server(([127,0,0,1], sub_port.port()), ([127,0,0,1], data_port.port())) => e,
+        e = send_sensor_value(data_port.port()) => e,
+        e = subscribe_filtered_inner(sub_port.port()) => e,
+    };
+    assert_eq!(res.unwrap(), Done::Test);
+}
+
 #[tokio::test]
 async fn list_affectors() {
     setup_tracing();
@@ -129,6 +275,34 @@ async fn list_affectors() {
     assert_eq!(res.unwrap(), Done::Test);
 }
 
+#[tokio::test]
+async fn snapshot() {
+    setup_tracing();
+
+    let sub_port = reserve_port::ReservedPort::random().unwrap();
+    let data_port = reserve_port::ReservedPort::random().unwrap();
+    let res = select! {
+        e = run_server(([127,0,0,1], sub_port.port()), ([127,0,0,1], data_port.port())) => e,
+        e = send_sensor_value(data_port.port()) => e,
+        e = snapshot_inner(sub_port.port()) => e,
+    };
+    assert_eq!(res.unwrap(), Done::Test);
+}
+
+#[tokio::test]
+async fn subscriber_stats() {
+    setup_tracing();
+
+    let sub_port = reserve_port::ReservedPort::random().unwrap();
+    let data_port = reserve_port::ReservedPort::random().unwrap();
+    let res = select! {
+        e = run_server(([127,0,0,1], sub_port.port()), ([127,0,0,1], data_port.port())) => e,
+        e = flood_sensor_values(data_port.port()) => e,
+        e = subscriber_stats_inner(sub_port.port()) => e,
+    };
+    assert_eq!(res.unwrap(), Done::Test);
+}
+
 fn setup_tracing() {
     use std::sync::Once;
     use tracing_error::ErrorLayer;
@@ -1,6 +1,9 @@
+use std::path::PathBuf;
 use std::time::Duration;
 
 use protocol::Reading;
+pub use protocol::reading::Aggregation;
+pub use snapshot::Summary as SnapshotSummary;
 
 use serde::{Deserialize, Serialize};
 
@@ -15,6 +18,46 @@ pub(crate) enum Request {
         start: jiff::Timestamp,
         end: jiff::Timestamp,
         n: usize,
+        /// How to combine values that end up in the same point. If `None`
+        /// the default for the reading is used, see
+        /// [`LabelFormatter::aggregation`].
+        ///
+        /// [`LabelFormatter::aggregation`]: protocol::reading::LabelFormatter::aggregation
+        aggregation: Option<Aggregation>,
+    },
+    /// Readings from the same device are read together and share their
+    /// timestamps. Each device gets its own result.
+    GetDataMulti {
+        readings: Vec<Reading>,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        n: usize,
+    },
+    /// Min, max, mean and count of the values in each bucket
+    GetSummary {
+        reading: Reading,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        bucket: Bucket,
+    },
+    /// How the values are distributed over `bins` equally wide bins
+    GetHistogram {
+        reading: Reading,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        bins: usize,
+    },
+    /// Copy all data to a dir on the machine running the data-store
+    /// together with a manifest of checksums. Needs the `Actuate` role.
+    Snapshot { to: PathBuf },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum SubscribeRequest {
+    /// First the stored data after `since` then new data as it is stored
+    Follow {
+        reading: Reading,
+        since: jiff::Timestamp,
     },
 }
 
@@ -26,6 +69,8 @@ pub enum ServerError {
     ReadingFromStore(String),
     #[error("Connect request should only be send once")]
     AlreadyConnected,
+    #[error("The role of this client does not allow this request")]
+    NotAllowed,
     #[error("Too many requests, rate limited, next requested allowed in: {0:?}")]
     TooManyRequests(Duration),
 }
@@ -34,12 +79,113 @@ pub enum ServerError {
 pub struct Data {
     pub time: Vec<jiff::Timestamp>,
     pub values: Vec<f32>,
+    /// Only set when [`Aggregation::Envelope`] was used, `values` then
+    /// holds the minimum of each point and this the maximum.
+    pub max: Option<Vec<f32>>,
+    /// The aggregation used. Long ranges get [`Aggregation::Mean`] even if
+    /// another aggregation was requested.
+    pub aggregation: Aggregation,
+}
+
+/// Data for readings from the same device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlignedData {
+    pub time: Vec<jiff::Timestamp>,
+    /// A column for each reading, every column is as long as `time`
+    pub values: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeriesData {
+    /// Requested readings from a single device, in the order they were
+    /// requested
+    pub readings: Vec<Reading>,
+    /// The columns in the data are in the same order as `readings`
+    pub data: Result<AlignedData, GetDataError>,
+}
+
+/// Width of the buckets in a summary. Buckets start at a multiple of their
+/// width since the unix epoch, a [`Bucket::Day`] therefore starts at
+/// midnight UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Bucket {
+    Day,
+    Hour,
+    Custom(Duration),
+}
+
+impl Bucket {
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        match self {
+            Bucket::Day => Duration::from_secs(24 * 60 * 60),
+            Bucket::Hour => Duration::from_secs(60 * 60),
+            Bucket::Custom(duration) => *duration,
+        }
+    }
+}
+
+/// Statistics of the values stored in one bucket, missing values are not
+/// counted. Buckets without values are left out. If a range holds more
+/// values than are used (see [`SUMMARY_POINTS_PER_BUCKET`]) the statistics
+/// are of the means in the downsampled caches: min and max then lie a bit
+/// closer to the mean and count is the number of means.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BucketStats {
+    pub start: jiff::Timestamp,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub count: usize,
+}
+
+/// Up to this many points are used for each bucket of a summary
+pub const SUMMARY_POINTS_PER_BUCKET: usize = 100;
+/// At most this many points are used to compute a summary
+pub const SUMMARY_POINTS: usize = 100_000;
+
+/// At most this many points are used to compute a histogram. For ranges
+/// with more data the points are means of the downsampled caches, that
+/// narrows the distribution a bit.
+pub const HISTOGRAM_POINTS: usize = 10_000;
+
+/// Bins span the smallest to the largest value, the largest value is
+/// counted in the last bin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    /// Lower edge of the first bin
+    pub start: f32,
+    pub bin_width: f32,
+    /// Number of points in each bin, all zero if there was no data
+    pub counts: Vec<usize>,
+}
+
+/// Points are never repeated and always later then the previous update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FollowUpdate {
+    /// Data that was already stored. Send first and again when the server
+    /// had to catch up because we fell behind. Long histories arrive as
+    /// several of these in chronological order before the first
+    /// [`FollowUpdate::New`].
+    Stored {
+        time: Vec<jiff::Timestamp>,
+        values: Vec<f32>,
+    },
+    /// A point that was just stored
+    New { time: jiff::Timestamp, value: f32 },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) enum Response {
     ListData(Vec<Reading>),
     GetData(Result<Data, GetDataError>),
+    /// One entry per device, ordered by the first requested reading of each
+    GetDataMulti(Vec<SeriesData>),
+    GetSummary(Result<Vec<BucketStats>, GetDataError>),
+    GetHistogram(Result<Histogram, GetDataError>),
+    /// After an error no more updates follow
+    Follow(Result<FollowUpdate, GetDataError>),
+    Snapshot(Result<SnapshotSummary, SnapshotError>),
     Error(ServerError),
     Handshake,
 }
@@ -59,3 +205,7 @@ pub enum GetDataError {
     #[error("Internal error while reading data, error: {0}")]
     ReadingFromStore(String),
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
+#[error("Could not create snapshot: {0}")]
+pub struct SnapshotError(pub String);
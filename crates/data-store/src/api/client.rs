@@ -13,7 +13,13 @@ pub enum Error<T: std::error::Error> {
     Comms(#[from] RpcError),
 }
 
-pub struct Client(rpc::client::RpcClient<super::Request, super::Response>);
+pub struct Client(
+    rpc::client::RpcClient<
+        super::Request,
+        super::Response,
+        super::SubscribeRequest,
+    >,
+);
 
 impl Client {
     pub async fn connect(
@@ -35,18 +41,52 @@ impl Client {
         }
     }
 
+    /// Get data aggregated in the way that suits the reading best, see
+    /// [`LabelFormatter::aggregation`].
+    ///
+    /// [`LabelFormatter::aggregation`]: protocol::reading::LabelFormatter::aggregation
     pub async fn get_data(
         &mut self,
         start: jiff::Timestamp,
         end: jiff::Timestamp,
         reading: protocol::Reading,
         n: usize,
+    ) -> Result<api::Data, Error<api::GetDataError>> {
+        self.get_data_inner(start, end, reading, n, None).await
+    }
+
+    /// Get data where the values that end up in the same point are
+    /// combined using `aggregation`. Anything other then
+    /// [`Aggregation::Mean`](api::Aggregation::Mean) is computed from the
+    /// full resolution data and therefore slower for long ranges. Ranges
+    /// with too many values get the mean instead, see
+    /// [`Data::aggregation`](api::Data::aggregation).
+    pub async fn get_data_aggregated(
+        &mut self,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        reading: protocol::Reading,
+        n: usize,
+        aggregation: api::Aggregation,
+    ) -> Result<api::Data, Error<api::GetDataError>> {
+        self.get_data_inner(start, end, reading, n, Some(aggregation))
+            .await
+    }
+
+    async fn get_data_inner(
+        &mut self,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        reading: protocol::Reading,
+        n: usize,
+        aggregation: Option<api::Aggregation>,
     ) -> Result<api::Data, Error<api::GetDataError>> {
         let request = super::Request::GetData {
             reading,
             start,
             end,
             n,
+            aggregation,
         };
         match self.0.send_receive(request.clone()).await? {
             Response::GetData(Ok(data)) => Ok(data),
@@ -57,6 +97,136 @@ impl Client {
             })),
         }
     }
+
+    /// Get data for multiple readings in one go. The readings are grouped
+    /// by device, readings from the same device share their timestamps.
+    /// Failing to read one device does not fail the others.
+    pub async fn get_data_multi(
+        &mut self,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        readings: Vec<protocol::Reading>,
+        n: usize,
+    ) -> Result<Vec<api::SeriesData>, RpcError> {
+        let request = super::Request::GetDataMulti {
+            readings,
+            start,
+            end,
+            n,
+        };
+        match self.0.send_receive(request.clone()).await? {
+            Response::GetDataMulti(list) => Ok(list),
+            response => Err(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            }),
+        }
+    }
+
+    /// Min, max, mean and count of the values in each bucket between start
+    /// and end. Computed from the full resolution data, buckets without
+    /// data are left out.
+    pub async fn get_summary(
+        &mut self,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        reading: protocol::Reading,
+        bucket: api::Bucket,
+    ) -> Result<Vec<api::BucketStats>, Error<api::GetDataError>> {
+        let request = super::Request::GetSummary {
+            reading,
+            start,
+            end,
+            bucket,
+        };
+        match self.0.send_receive(request.clone()).await? {
+            Response::GetSummary(Ok(stats)) => Ok(stats),
+            Response::GetSummary(Err(err)) => Err(Error::Request(err)),
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
+
+    /// How the values between start and end are distributed over `bins`
+    /// equally wide bins. See [`api::HISTOGRAM_POINTS`] for how many points
+    /// are used.
+    pub async fn get_histogram(
+        &mut self,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        reading: protocol::Reading,
+        bins: usize,
+    ) -> Result<api::Histogram, Error<api::GetDataError>> {
+        let request = super::Request::GetHistogram {
+            reading,
+            start,
+            end,
+            bins,
+        };
+        match self.0.send_receive(request.clone()).await? {
+            Response::GetHistogram(Ok(histogram)) => Ok(histogram),
+            Response::GetHistogram(Err(err)) => Err(Error::Request(err)),
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
+
+    /// Stream the data stored after `since` followed by new data as soon
+    /// as it is stored. Use this instead of combining [`Self::get_data`]
+    /// with a data-server subscription, there will be no gaps or overlap.
+    pub async fn follow(
+        mut self,
+        reading: protocol::Reading,
+        since: jiff::Timestamp,
+    ) -> Result<Following, RpcError> {
+        let request = super::SubscribeRequest::Follow { reading, since };
+        self.0.subscribe(request).await?;
+        Ok(Following(self))
+    }
+
+    /// Have the data-store copy all its data to `to`, a dir on the machine
+    /// the data-store runs on that must be empty or not exist yet. Appends
+    /// are held off while a dir of series is copied. The client needs the
+    /// `Actuate` role.
+    pub async fn snapshot(
+        &mut self,
+        to: std::path::PathBuf,
+    ) -> Result<api::SnapshotSummary, Error<api::SnapshotError>> {
+        let request = super::Request::Snapshot { to };
+        match self.0.send_receive(request.clone()).await? {
+            Response::Snapshot(Ok(summary)) => Ok(summary),
+            Response::Snapshot(Err(err)) => Err(Error::Request(err)),
+            Response::Error(err) => {
+                Err(Error::Request(api::SnapshotError(err.to_string())))
+            }
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
+}
+
+pub struct Following(Client);
+
+impl Following {
+    /// After this returns an [`Error::Request`] no more data will follow
+    pub async fn next(
+        &mut self,
+    ) -> Result<api::FollowUpdate, Error<api::GetDataError>> {
+        match self.0 .0.next().await? {
+            Response::Follow(Ok(update)) => Ok(update),
+            Response::Follow(Err(err)) => Err(Error::Request(err)),
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: "none, we are following".to_string(),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -0,0 +1,479 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use byteseries::ByteSeries;
+use color_eyre::eyre::{bail, eyre, Context, OptionExt, Result};
+use color_eyre::Section;
+use indicatif::{MultiProgress, ProgressBar};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::data::series::{self, bitspec};
+use crate::rewrite::{self, Rewrite};
+
+/// Directory in the data dir where series are rewritten before they replace
+/// the originals.
+const WORK_DIR: &str = ".compacting";
+
+/// How long to keep data and at what resolution. Loaded from a RON file
+/// such as:
+/// ```ron
+/// (rules: [(
+///     path: "largebedroom/bed",
+///     tiers: [
+///         // raw for 30 days then 1-minute means
+///         (older_than: (secs: 2592000, nanos: 0), keep: Mean((secs: 60, nanos: 0))),
+///         // hourly means after two years, forever
+///         (older_than: (secs: 63072000, nanos: 0), keep: Mean((secs: 3600, nanos: 0))),
+///     ],
+/// )])
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Retention {
+    /// The first rule whose path matches a series is used, series without
+    /// a matching rule are left alone.
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    /// Applies to series at or below this path, for example
+    /// `largebedroom/bed` or `largebedroom/bed/nau7802right`
+    pub path: PathBuf,
+    /// Data younger then the first tier is kept as is
+    pub tiers: Vec<Tier>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tier {
+    pub older_than: Duration,
+    pub keep: Keep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Keep {
+    /// One point per interval, the mean of all the points in it
+    Mean(Duration),
+    Nothing,
+}
+
+impl Retention {
+    pub fn load(path: &Path) -> Result<Self> {
+        let retention = fs::read_to_string(path)
+            .wrap_err("Could not read retention config")
+            .with_note(|| format!("path: {}", path.display()))?;
+        let mut retention: Self = ron::from_str(&retention)
+            .wrap_err("Could not deserialize retention config")
+            .with_note(|| format!("path: {}", path.display()))?;
+        for rule in &mut retention.rules {
+            rule.tiers.sort_by_key(|tier| tier.older_than);
+        }
+        Ok(retention)
+    }
+
+    fn rule_for(&self, series: &Path) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .find(|rule| series.starts_with(&rule.path))
+    }
+}
+
+/// Rewrites the data older then the retention allows. The originals are
+/// kept in `.backups` in the data dir. The data-store must not be running
+/// while this happens. Do not interrupt this, a series is replaced by moving
+/// multiple files.
+pub fn perform(
+    data_dir: &Path,
+    retention: &Retention,
+    only: Option<PathBuf>,
+) -> Result<()> {
+    let list = crate::export::files_to_export(data_dir)?;
+    if list.is_empty() {
+        bail!("No files to compact")
+    }
+
+    let selected = crate::select_only(&list, only.as_deref())?;
+
+    let to_handle: Vec<_> = selected
+        .into_iter()
+        .filter_map(|path| {
+            retention
+                .rule_for(&rewrite::relative(data_dir, path))
+                .map(|rule| (path, rule))
+        })
+        .collect();
+
+    if to_handle.is_empty() {
+        bail!("None of the files have a matching retention rule")
+    }
+
+    let bars = MultiProgress::new();
+    let files_bar =
+        ProgressBar::new(to_handle.len() as u64).with_style(crate::bar_style());
+    let files_bar = bars.insert(0, files_bar);
+    files_bar.inc(0); // make the bar appear
+
+    let mut lines_before = 0;
+    let mut lines_after = 0;
+    for (path, rule) in &to_handle {
+        let (before, after) = handle_file(data_dir, path, rule, bars.clone())
+            .wrap_err("Failed to compact data")
+            .with_note(|| format!("Input file: {}", path.display()))?;
+        lines_before += before;
+        lines_after += after;
+        files_bar.inc(1);
+    }
+
+    let work_dir = data_dir.join(WORK_DIR);
+    fs::remove_dir_all(&work_dir)
+        .wrap_err("Could not remove work dir")
+        .with_note(|| format!("dir: {}", work_dir.display()))?;
+
+    drop(bars);
+    info!(
+        "Done, compacted {} files in {} from {lines_before} to {lines_after} lines",
+        to_handle.len(),
+        data_dir.display()
+    );
+    Ok(())
+}
+
+/// Returns the number of lines before and after
+fn handle_file(
+    data_dir: &Path,
+    path: &Path,
+    rule: &Rule,
+    bars: MultiProgress,
+) -> Result<(u64, u64)> {
+    let metadata = crate::export::read_metadata(path)
+        .wrap_err("Could not extract metadata")?;
+    let series::Header {
+        readings, encoding, ..
+    } = series::Header::parse(&metadata)?;
+    let (_, payload_size) =
+        series::meta_list_and_payload_size(&readings, &encoding);
+    let device_info = readings
+        .first()
+        .ok_or_eyre("Series header lists no readings")?
+        .device()
+        .info();
+    let scale_factor = series::millis_to_minimal_representation(device_info);
+
+    let (mut input, _) = ByteSeries::builder()
+        .payload_size(payload_size)
+        .with_header(metadata.as_bytes().to_vec())
+        .open(path)
+        .wrap_err("Could not open byteseries")?;
+
+    let rewrite = Rewrite::start(data_dir, path, WORK_DIR, Some("compact"))?;
+    let mut output =
+        rewrite.create(metadata.into_bytes(), &encoding, payload_size)?;
+
+    let now = jiff::Timestamp::now().as_millisecond() as u64 / scale_factor;
+    let mut compactor =
+        Compactor::new(&rule.tiers, &encoding, payload_size, now, scale_factor);
+
+    let copy_bar = ProgressBar::new(input.len())
+        .with_style(crate::bar_style())
+        .with_message(format!(
+            "{:?}",
+            path.file_name().expect("we only handle files with names")
+        ));
+    let copy_bar = bars.insert(1, copy_bar);
+
+    let mut read = Extent::default();
+    let mut written = Extent::default();
+    let mut compacted = Vec::new();
+    rewrite::for_each_chunk(&mut input, &mut RawDecoder, |timestamps, data| {
+        read.add(&timestamps);
+        copy_bar.inc(timestamps.len() as u64);
+        for (ts, line) in timestamps.into_iter().zip(data) {
+            compactor.push(ts, &line, &mut compacted);
+        }
+        write(&mut output, &mut compacted, &mut written)
+    })
+    .wrap_err("Could not copy series to compact")?;
+    compactor.finish(&mut compacted);
+    write(&mut output, &mut compacted, &mut written)?;
+    copy_bar.finish();
+    bars.remove(&copy_bar);
+
+    verify(&mut output, &read, &written)
+        .wrap_err("Not replacing the original")?;
+
+    drop(input);
+    drop(output);
+    let relative = rewrite.relative().to_path_buf();
+    let backup_dir = rewrite.finish()?.expect("compact keeps a backup");
+    info!(
+        "Compacted {}, original moved to {}",
+        relative.display(),
+        backup_dir.display()
+    );
+    Ok((read.lines, written.lines))
+}
+
+/// First and last timestamp and the number of lines of a series
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Extent {
+    first: Option<u64>,
+    last: Option<u64>,
+    lines: u64,
+}
+
+impl Extent {
+    /// The timestamps must follow those added before
+    fn add(&mut self, timestamps: &[u64]) {
+        let (Some(first), Some(last)) = (timestamps.first(), timestamps.last())
+        else {
+            return;
+        };
+        self.first.get_or_insert(*first);
+        self.last = Some(*last);
+        self.lines += timestamps.len() as u64;
+    }
+}
+
+fn write(
+    output: &mut ByteSeries,
+    lines: &mut Vec<(u64, Vec<u8>)>,
+    written: &mut Extent,
+) -> Result<()> {
+    let timestamps: Vec<_> = lines.iter().map(|(ts, _)| *ts).collect();
+    for (ts, line) in lines.drain(..) {
+        output
+            .push_line(ts, &line)
+            .wrap_err("Could not write compacted line")?;
+    }
+    written.add(&timestamps);
+    Ok(())
+}
+
+/// Reads the compacted series back. It must hold exactly the lines written
+/// to it, no more than the input and nothing outside the input's range.
+fn verify(
+    output: &mut ByteSeries,
+    input: &Extent,
+    written: &Extent,
+) -> Result<()> {
+    output
+        .flush_to_disk()
+        .wrap_err("Could not flush compacted series")?;
+    let mut read_back = Extent::default();
+    rewrite::for_each_chunk(output, &mut RawDecoder, |timestamps, _| {
+        read_back.add(&timestamps);
+        Ok(())
+    })
+    .wrap_err("Could not read back compacted series")?;
+
+    let within_input = read_back.first.is_none_or(|first| {
+        input.first.is_some_and(|input_first| first >= input_first)
+    }) && read_back.last.is_none_or(|last| {
+        input.last.is_some_and(|input_last| last <= input_last)
+    });
+    let problem = if read_back != *written {
+        "it does not hold the lines written to it"
+    } else if read_back.lines > input.lines {
+        "it has more lines than the input"
+    } else if !within_input {
+        "it has lines outside the range of the input"
+    } else {
+        return Ok(());
+    };
+    Err(eyre!("Compacted series is wrong, {problem}"))
+        .with_note(|| format!("input: {input:?}"))
+        .with_note(|| format!("written: {written:?}"))
+        .with_note(|| format!("read back: {read_back:?}"))
+}
+
+/// Passes on the lines untouched so data that is kept as is does not get
+/// decoded and encoded again
+#[derive(Debug)]
+struct RawDecoder;
+impl byteseries::Decoder for RawDecoder {
+    type Item = Vec<u8>;
+
+    fn decode_payload(&mut self, payload: &[u8]) -> Self::Item {
+        payload.to_vec()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Copy,
+    /// Average over buckets this wide (scaled time)
+    Mean(u64),
+    Drop,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    width: u64,
+    index: u64,
+    /// Timestamp of the first line in the bucket
+    first: u64,
+    sums: Vec<f64>,
+    count: usize,
+}
+
+/// Decides per line, based on its age, whether to keep it, drop it or merge
+/// it into a mean.
+#[derive(Debug)]
+struct Compactor<'a> {
+    /// (minimal age, action), in scaled time sorted by age
+    tiers: Vec<(u64, Action)>,
+    fields: &'a [bitspec::Field<f32>],
+    payload_size: usize,
+    now: u64,
+    current: Option<Bucket>,
+}
+
+impl<'a> Compactor<'a> {
+    /// `now` must be in scaled time
+    fn new(
+        tiers: &[Tier],
+        fields: &'a [bitspec::Field<f32>],
+        payload_size: usize,
+        now: u64,
+        scale_factor: u64,
+    ) -> Self {
+        let scaled =
+            |duration: Duration| duration.as_millis() as u64 / scale_factor;
+        let tiers = tiers
+            .iter()
+            .map(|tier| {
+                let action = match tier.keep {
+                    Keep::Mean(interval) => {
+                        Action::Mean(scaled(interval).max(1))
+                    }
+                    Keep::Nothing => Action::Drop,
+                };
+                (scaled(tier.older_than), action)
+            })
+            .collect();
+        Self {
+            tiers,
+            fields,
+            payload_size,
+            now,
+            current: None,
+        }
+    }
+
+    fn action(&self, ts: u64) -> Action {
+        let age = self.now.saturating_sub(ts);
+        self.tiers
+            .iter()
+            .rev()
+            .find(|(min_age, _)| age >= *min_age)
+            .map_or(Action::Copy, |(_, action)| *action)
+    }
+
+    /// Lines must be pushed in chronological order, finished lines are
+    /// appended to `out`
+    fn push(&mut self, ts: u64, line: &[u8], out: &mut Vec<(u64, Vec<u8>)>) {
+        let width = match self.action(ts) {
+            Action::Copy => {
+                self.finish(out);
+                out.push((ts, line.to_vec()));
+                return;
+            }
+            Action::Drop => {
+                self.finish(out);
+                return;
+            }
+            Action::Mean(width) => width,
+        };
+
+        let index = ts / width;
+        let same_bucket = self
+            .current
+            .as_ref()
+            .is_some_and(|b| b.width == width && b.index == index);
+        if !same_bucket {
+            self.finish(out);
+        }
+        let bucket = self.current.get_or_insert_with(|| Bucket {
+            width,
+            index,
+            first: ts,
+            sums: vec![0.0; self.fields.len()],
+            count: 0,
+        });
+        for (sum, field) in bucket.sums.iter_mut().zip(self.fields) {
+            *sum += f64::from(field.decode::<f32>(line));
+        }
+        bucket.count += 1;
+    }
+
+    fn finish(&mut self, out: &mut Vec<(u64, Vec<u8>)>) {
+        let Some(bucket) = self.current.take() else {
+            return;
+        };
+        let mut line = vec![0; self.payload_size];
+        for (sum, field) in bucket.sums.into_iter().zip(self.fields) {
+            field
+                .encode((sum / bucket.count as f64) as f32, &mut line)
+                .expect("the mean is only missing if a value was");
+        }
+        out.push((bucket.first, line));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn field() -> bitspec::Field<f32> {
+        bitspec::Field {
+            offset: 0,
+            length: 8,
+            decode_scale: 1.0,
+            decode_add: 0.0,
+            stores_missing: true,
+        }
+    }
+
+    /// Timestamps are in seconds and now is at 100 seconds
+    fn compact(tiers: &[Tier], lines: &[(u64, f32)]) -> Vec<(u64, f32)> {
+        let fields = [field()];
+        let mut compactor = Compactor::new(tiers, &fields, 1, 100, 1000);
+        let mut out = Vec::new();
+        for (ts, value) in lines {
+            let mut line = vec![0];
+            fields[0].encode(*value, &mut line).unwrap();
+            compactor.push(*ts, &line, &mut out);
+        }
+        compactor.finish(&mut out);
+        out.into_iter()
+            .map(|(ts, line)| (ts, fields[0].decode(&line)))
+            .collect()
+    }
+
+    #[test]
+    fn young_data_is_untouched() {
+        let tiers = [Tier {
+            older_than: Duration::from_secs(50),
+            keep: Keep::Nothing,
+        }];
+        let lines = [(60, 1.0), (70, 2.0)];
+        assert_eq!(compact(&tiers, &lines), lines);
+    }
+
+    #[test]
+    fn old_data_becomes_means_then_is_dropped() {
+        let tiers = [
+            Tier {
+                older_than: Duration::from_secs(50),
+                keep: Keep::Mean(Duration::from_secs(10)),
+            },
+            Tier {
+                older_than: Duration::from_secs(90),
+                keep: Keep::Nothing,
+            },
+        ];
+        let lines = [(5, 9.0), (20, 2.0), (24, 4.0), (31, 6.0), (80, 7.0)];
+        assert_eq!(compact(&tiers, &lines), [(20, 3.0), (31, 6.0), (80, 7.0)]);
+    }
+}
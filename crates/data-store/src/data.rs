@@ -1,6 +1,9 @@
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 
+use color_eyre::eyre::Context;
+
 use tokio::sync::Mutex;
 
 pub mod series;
@@ -8,12 +11,28 @@ use series::Series;
 
 use crate::api;
 
+/// Every series has its own lock. The map is only locked to look up or add
+/// a series, reading one series does not hold up the others.
 #[derive(Debug, Clone)]
 pub(crate) struct Data(
-    pub(crate) Arc<Mutex<HashMap<protocol::Device, Series>>>,
+    pub(crate) Arc<Mutex<HashMap<protocol::Device, Arc<Mutex<Series>>>>>,
 );
 
 impl Data {
+    async fn series(
+        &self,
+        reading: &protocol::Reading,
+    ) -> Result<Arc<Mutex<Series>>, api::GetDataError> {
+        self.0
+            .lock()
+            .await
+            .get(&reading.device())
+            .cloned()
+            .ok_or_else(|| api::GetDataError::NotInStore {
+                reading: reading.clone(),
+            })
+    }
+
     pub(crate) async fn list_readings(&self) -> Vec<protocol::Reading> {
         self.0
             .lock()
@@ -29,40 +48,137 @@ impl Data {
         start: jiff::Timestamp,
         end: jiff::Timestamp,
         n: usize,
+        aggregation: Option<api::Aggregation>,
     ) -> Result<api::Data, api::GetDataError> {
-        use byteseries::seek::Error as Se;
-        use byteseries::series::Error as Be;
+        use protocol::reading::LabelFormatter;
+        let series = self.series(&reading).await?;
+        let mut series = series.lock().await;
 
-        let key = reading.device();
-        let mut all_series = self.0.lock().await;
-        let series = all_series.get_mut(&key).ok_or_else(|| {
-            api::GetDataError::NotInStore {
-                reading: reading.clone(),
+        let aggregation = aggregation
+            .unwrap_or_else(|| reading.info().label_formatter.aggregation());
+        series
+            .read_aggregated(&reading, start, end, n, aggregation)
+            .map_err(read_error)
+    }
+
+    pub(crate) async fn get_summary(
+        &self,
+        reading: protocol::Reading,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        bucket: api::Bucket,
+    ) -> Result<Vec<api::BucketStats>, api::GetDataError> {
+        let series = self.series(&reading).await?;
+        let mut series = series.lock().await;
+        series
+            .read_summary(&reading, start, end, bucket.duration())
+            .map_err(read_error)
+    }
+
+    pub(crate) async fn get_histogram(
+        &self,
+        reading: protocol::Reading,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        bins: usize,
+    ) -> Result<api::Histogram, api::GetDataError> {
+        let series = self.series(&reading).await?;
+        let mut series = series.lock().await;
+        series
+            .read_histogram(&reading, start, end, bins)
+            .map_err(read_error)
+    }
+
+    /// Copies every file in the data dir to `to` except those in hidden
+    /// dirs such as backups. The files are staged while nothing can be
+    /// appended, that keeps the files of a series consistent with each
+    /// other. They are copied after appending resumes.
+    pub(crate) async fn snapshot(
+        &self,
+        data_dir: &Path,
+        to: &Path,
+    ) -> color_eyre::Result<snapshot::Summary> {
+        let snapshot = snapshot::Snapshot::create(to)?;
+        let staged = {
+            // no series can be added while the map is locked
+            let all_series = self.0.lock().await;
+            let mut locked = Vec::with_capacity(all_series.len());
+            for series in all_series.values() {
+                let mut series = series.lock().await;
+                series.flush()?;
+                locked.push(series);
             }
-        })?;
-
-        let (time, mut data) =
-            series
-                .read(&[reading], start, end, n)
-                .map_err(|e| match e {
-                    Be::InvalidRange(Se::NotFound) => {
-                        api::GetDataError::NotFound
-                    }
-                    Be::InvalidRange(Se::EmptyFile) => {
-                        api::GetDataError::EmptyFile
-                    }
-                    Be::InvalidRange(Se::StartAfterData { .. }) => {
-                        api::GetDataError::StartAfterData
-                    }
-                    Be::InvalidRange(Se::StopBeforeData) => {
-                        api::GetDataError::StopBeforeData
-                    }
-                    _ => api::GetDataError::ReadingFromStore(e.to_string()),
-                })?;
-        Ok(api::Data {
-            time,
-            values: data.pop().expect("one reading is put in so one comes out"),
-        })
+            snapshot::Staged::create(data_dir)?
+        };
+        tokio::task::spawn_blocking(move || staged.copy_to(snapshot))
+            .await
+            .wrap_err("Copying snapshot panicked")?
     }
 
+    /// The first `n` stored values between start and end, not resampled
+    pub(crate) async fn get_first(
+        &self,
+        reading: &protocol::Reading,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        n: usize,
+    ) -> Result<(Vec<jiff::Timestamp>, Vec<f32>), api::GetDataError> {
+        let series = self.series(reading).await?;
+        let mut series = series.lock().await;
+        series.read_first(reading, start, end, n).map_err(read_error)
+    }
+
+    /// Reads the readings of each device in one go
+    pub(crate) async fn get_multi(
+        &self,
+        readings: Vec<protocol::Reading>,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        n: usize,
+    ) -> Vec<api::SeriesData> {
+        let mut per_device: Vec<(protocol::Device, Vec<protocol::Reading>)> =
+            Vec::new();
+        for reading in readings {
+            let device = reading.device();
+            if let Some((_, list)) =
+                per_device.iter_mut().find(|(d, _)| *d == device)
+            {
+                list.push(reading);
+            } else {
+                per_device.push((device, vec![reading]));
+            }
+        }
+
+        let mut res = Vec::with_capacity(per_device.len());
+        for (_, readings) in per_device {
+            let data = match self.series(&readings[0]).await {
+                Err(e) => Err(e),
+                Ok(series) => series
+                    .lock()
+                    .await
+                    .read(&readings, start, end, n)
+                    .map(|(time, values)| api::AlignedData { time, values })
+                    .map_err(read_error),
+            };
+            res.push(api::SeriesData { readings, data });
+        }
+        res
+    }
+}
+
+fn read_error(e: byteseries::series::Error) -> api::GetDataError {
+    use byteseries::seek::Error as Se;
+    use byteseries::series::Error as Be;
+
+    match e {
+        Be::InvalidRange(Se::NotFound) => api::GetDataError::NotFound,
+        Be::InvalidRange(Se::EmptyFile) => api::GetDataError::EmptyFile,
+        Be::InvalidRange(Se::StartAfterData { .. }) => {
+            api::GetDataError::StartAfterData
+        }
+        Be::InvalidRange(Se::StopBeforeData) => {
+            api::GetDataError::StopBeforeData
+        }
+        _ => api::GetDataError::ReadingFromStore(e.to_string()),
+    }
 }
@@ -1,6 +1,9 @@
+use std::collections::hash_map::Entry;
 use std::fs::create_dir_all;
 use std::io;
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use byteseries::{downsample, series, ByteSeries};
@@ -9,18 +12,22 @@ use color_eyre::{Result, Section};
 use protocol::reading::tree::{Item, Tree};
 use protocol::{reading, IsSameAs};
 use serde::{Deserialize, Serialize};
-use tracing::{instrument, trace};
+use tokio::sync::Mutex;
+use tracing::{debug, instrument, trace};
 
 use byteseries::file::OpenError as FileOpenError;
 use series::data::OpenError as DataOpenError;
 use series::Error::Open;
 
+mod aggregate;
 pub mod bitspec;
 mod resampler;
 
-use self::resampler::Resampler;
+use self::aggregate::{Aggregator, Summarizer};
+pub(crate) use self::resampler::Resampler;
 
 use super::Data;
+use crate::api;
 
 #[derive(Debug)]
 pub(crate) struct Meta {
@@ -37,13 +44,68 @@ pub(crate) struct Series {
     byteseries: ByteSeries,
 }
 
+/// A line that was just written to a series
+#[derive(Debug, Clone)]
+pub(crate) struct Appended {
+    /// As stored, rounded down to the resolution of the series
+    pub(crate) time: jiff::Timestamp,
+    /// Every reading in the line with its value as stored
+    pub(crate) values: Vec<(protocol::Reading, f32)>,
+}
+
+/// Since version 1 the highest value of every field marks a missing value
+pub(crate) const HEADER_VERSION: u8 = 1;
+
+/// Most stored values [`Series::read_aggregated`] goes through for an
+/// aggregation the downsampled caches do not hold. Longer ranges get the
+/// mean instead.
+const MAX_AGGREGATION_SCAN: usize = 1_000_000;
+
+/// Number of lines read at once when going through full resolution data
+pub(crate) const READ_CHUNK: usize = 10_000;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub(crate) struct Header {
+    /// Headers from before there was a version are version 0
+    #[serde(default)]
+    pub(crate) version: u8,
     pub(crate) readings: Vec<protocol::Reading>,
     pub(crate) encoding: Vec<bitspec::Field<f32>>,
 }
 
 impl Header {
+    /// The header a series for these readings should have, the readings
+    /// should be all the readings affected by a device.
+    pub(crate) fn for_readings(readings: &[protocol::Reading]) -> Self {
+        Self::for_readings_at(readings, HEADER_VERSION)
+    }
+
+    /// The header a series for these readings had when it was created
+    /// with header `version`
+    pub(crate) fn for_readings_at(
+        readings: &[protocol::Reading],
+        version: u8,
+    ) -> Self {
+        let stores_missing = version >= 1;
+        let specs = to_speclist(readings, stores_missing);
+        Self {
+            version,
+            readings: readings.to_vec(),
+            encoding: bitspec::speclist_to_fields(specs, stores_missing),
+        }
+    }
+
+    /// Parses the metadata stored in a series, its fields are decoded as
+    /// the version of the header requires.
+    pub(crate) fn parse(metadata: &str) -> Result<Self> {
+        let mut header: Self = ron::from_str(metadata)
+            .wrap_err("Could not deserialize metadata")?;
+        for field in &mut header.encoding {
+            field.stores_missing = header.version >= 1;
+        }
+        Ok(header)
+    }
+
     pub(crate) fn serialized(&self) -> Result<Vec<u8>> {
         let config = ron::ser::PrettyConfig::new();
         ron::ser::to_string_pretty(self, config)
@@ -56,25 +118,28 @@ impl Series {
     #[instrument]
     fn open_or_create(reading: &protocol::Reading, dir: &Path) -> Result<Self> {
         let readings = reading.device().info().affects_readings;
-        let specs = to_speclist(readings);
-        let fields = bitspec::speclist_to_fields(specs);
+        let path = base_path(reading);
+        let path = dir.join(path);
+
+        let expected = Header::for_readings(readings);
+        let (header, metadata) =
+            match crate::migrate::existing_header(dir, &path, &expected)? {
+                Some((stored, metadata)) => (stored, metadata.into_bytes()),
+                None => {
+                    let serialized = expected.serialized()?;
+                    (expected, serialized)
+                }
+            };
+        let fields = header.encoding;
         let (meta_list, payload_size) =
             meta_list_and_payload_size(readings, &fields);
 
-        let expected_header = Header {
-            readings: readings.to_vec(),
-            encoding: fields.clone(),
-        }
-        .serialized()?;
-
         let (resampler, configs) = resample_setup(&fields, payload_size);
 
-        let path = base_path(reading);
-        let path = dir.join(path);
         let res = ByteSeries::builder()
             .payload_size(payload_size)
             .with_downsampled_cache(resampler.clone(), configs.clone())
-            .with_header(expected_header.clone())
+            .with_header(metadata.clone())
             .open(&path);
 
         let byteseries = match res {
@@ -92,7 +157,7 @@ impl Series {
                 ByteSeries::builder()
                     .payload_size(payload_size)
                     .with_downsampled_cache(resampler, configs)
-                    .with_header(expected_header)
+                    .with_header(metadata)
                     .create_new(true)
                     .open(&path)
                     .wrap_err("Could not create new byteseries")
@@ -114,8 +179,12 @@ impl Series {
         })
     }
 
+    /// Returns what was written if this completed a line
     #[instrument(skip(self))]
-    fn append(&mut self, reading: &protocol::Reading) -> Result<()> {
+    fn append(
+        &mut self,
+        reading: &protocol::Reading,
+    ) -> Result<Option<Appended>> {
         let res = reading
             .device()
             .info()
@@ -134,7 +203,9 @@ impl Series {
             ));
         }
 
-        meta.field.encode(reading.info().val, &mut self.line);
+        meta.field
+            .encode(reading.info().val, &mut self.line)
+            .wrap_err("Could not encode reading")?;
         meta.set_at = Some(Instant::now());
 
         let max_interval = reading.device().info().max_sample_interval;
@@ -154,7 +225,7 @@ impl Series {
 
             if self.last_timestamp_pushed.is_some_and(|ts| ts == new_ts) {
                 tracing::trace!("Skipping datapoint with same timestamp");
-                return Ok(());
+                return Ok(None);
             } else {
                 self.last_timestamp_pushed = Some(new_ts);
             }
@@ -162,14 +233,35 @@ impl Series {
             self.byteseries
                 .push_line(new_ts, &self.line)
                 .wrap_err("Could not write to timeseries on disk")?;
+            let appended = Appended {
+                time: jiff::Timestamp::from_millisecond(
+                    (new_ts * scale_factor) as i64,
+                )
+                .expect("was a jiff::Timestamp before it became a u64"),
+                values: self
+                    .meta_list
+                    .iter()
+                    .map(|meta| {
+                        (meta.reading.clone(), meta.field.decode(&self.line))
+                    })
+                    .collect(),
+            };
             self.line.fill(0);
 
             for meta in &mut self.meta_list {
                 meta.set_at = None;
             }
+            return Ok(Some(appended));
         }
 
-        Ok(())
+        Ok(None)
+    }
+
+    /// Makes sure everything appended so far is on disk
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        self.byteseries
+            .flush_to_disk()
+            .wrap_err("Could not flush series to disk")
     }
 
     /// # Panics
@@ -195,20 +287,7 @@ impl Series {
         let range = start..=end;
         let fields = readings
             .iter()
-            .map(|requested| {
-                self.meta_list
-                    .iter()
-                    .find(|meta| requested.is_same_as(&meta.reading))
-                    .inspect(|meta| trace!("meta used for decoding: {meta:?}"))
-                    .map(|meta| meta.field.clone())
-                    .unwrap_or_else(|| {
-                        panic!(
-                            "caller of read makes sure all readings are part of this \
-                        series.\n\tseries: {:?},\n\trequested: {:?}",
-                            self.meta_list, readings
-                        )
-                    })
-            })
+            .map(|requested| self.field_for(requested))
             .collect();
         let mut resampler = Resampler::from_fields(fields, self.line.len());
 
@@ -247,6 +326,267 @@ impl Series {
         }
         Ok((time, data))
     }
+
+    /// Like [`Self::read`] but for a single reading and with a choice of
+    /// aggregation. The downsampled caches only hold means, any other
+    /// aggregation is computed from the full resolution data. If that holds
+    /// more then [`MAX_AGGREGATION_SCAN`] values between start and end the
+    /// mean is returned instead. The aggregation used is part of the
+    /// returned data.
+    ///
+    /// # Panics
+    /// If the reading is not part of this series.
+    #[instrument(skip(self))]
+    pub fn read_aggregated(
+        &mut self,
+        reading: &protocol::Reading,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        n: usize,
+        aggregation: reading::Aggregation,
+    ) -> Result<api::Data, byteseries::series::Error> {
+        if aggregation == reading::Aggregation::Mean {
+            return self.read_mean(reading, start, end, n);
+        }
+
+        let device_info = reading.info().device.info();
+        let scale_factor = millis_to_minimal_representation(device_info);
+        let start_ts = start.as_millisecond() as u64 / scale_factor;
+        let end_ts = end.as_millisecond() as u64 / scale_factor;
+        let mut aggregator = Aggregator::new(aggregation, start_ts, end_ts, n);
+        let mut scanned = 0;
+        let scan = self.read_full(reading, start_ts, end_ts, |ts, value| {
+            scanned += 1;
+            if scanned > MAX_AGGREGATION_SCAN {
+                return ControlFlow::Break(());
+            }
+            aggregator.push(ts, value);
+            ControlFlow::Continue(())
+        })?;
+        if scan.is_break() {
+            debug!(
+                "More then {MAX_AGGREGATION_SCAN} values in range, \
+                returning the mean instead of {aggregation:?}"
+            );
+            return self.read_mean(reading, start, end, n);
+        }
+        aggregator.finish();
+
+        let to_time = |ts: u64| {
+            jiff::Timestamp::from_millisecond((ts * scale_factor) as i64)
+                .expect("timestamps are between MIN and MAX times of Timestamp type")
+        };
+        let time = aggregator.timestamps.iter().copied().map(to_time).collect();
+        let max = (aggregation == reading::Aggregation::Envelope)
+            .then_some(aggregator.max);
+        Ok(api::Data {
+            time,
+            values: aggregator.values,
+            max,
+            aggregation,
+        })
+    }
+
+    fn read_mean(
+        &mut self,
+        reading: &protocol::Reading,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        n: usize,
+    ) -> Result<api::Data, byteseries::series::Error> {
+        let (time, mut values) = self.read(&[reading.clone()], start, end, n)?;
+        let values =
+            values.pop().expect("one reading is put in so one comes out");
+        Ok(api::Data {
+            time,
+            values,
+            max: None,
+            aggregation: reading::Aggregation::Mean,
+        })
+    }
+
+    /// Min, max, mean and count of a single reading for each bucket between
+    /// start and end. Buckets start at a multiple of their width since the
+    /// unix epoch. Uses the same points as [`Self::read`] with `n` set to
+    /// [`api::SUMMARY_POINTS_PER_BUCKET`] per bucket, for long ranges those
+    /// come from the downsampled caches.
+    ///
+    /// # Panics
+    /// If the reading is not part of this series.
+    #[instrument(skip(self))]
+    pub(crate) fn read_summary(
+        &mut self,
+        reading: &protocol::Reading,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        bucket: Duration,
+    ) -> Result<Vec<api::BucketStats>, byteseries::series::Error> {
+        let bucket_width = (bucket.as_millis() as u64).max(1);
+        let span = (end.as_millisecond() - start.as_millisecond()).max(0);
+        let buckets = span as u64 / bucket_width + 1;
+        let n = usize::try_from(buckets)
+            .unwrap_or(usize::MAX)
+            .saturating_mul(api::SUMMARY_POINTS_PER_BUCKET)
+            .min(api::SUMMARY_POINTS);
+        let (time, mut values) = self.read(&[reading.clone()], start, end, n)?;
+        let values =
+            values.pop().expect("one reading is put in so one comes out");
+
+        let mut summarizer = Summarizer::new(bucket_width);
+        for (time, value) in time.into_iter().zip(values) {
+            summarizer.push(time.as_millisecond() as u64, value);
+        }
+        summarizer.finish();
+
+        Ok(summarizer
+            .stats
+            .into_iter()
+            .map(|stats| api::BucketStats {
+                start: jiff::Timestamp::from_millisecond(stats.start as i64)
+                    .expect("timestamps are between MIN and MAX times of Timestamp type"),
+                min: stats.min,
+                max: stats.max,
+                mean: stats.mean,
+                count: stats.count,
+            })
+            .collect())
+    }
+
+    /// Distribution of the values of a single reading between start and
+    /// end. Uses the same points as [`Self::read`] with `n` set to
+    /// [`api::HISTOGRAM_POINTS`], for long ranges those come from the
+    /// downsampled caches.
+    ///
+    /// # Panics
+    /// If the reading is not part of this series.
+    #[instrument(skip(self))]
+    pub(crate) fn read_histogram(
+        &mut self,
+        reading: &protocol::Reading,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        bins: usize,
+    ) -> Result<api::Histogram, byteseries::series::Error> {
+        let (_, mut values) = self.read(
+            &[reading.clone()],
+            start,
+            end,
+            api::HISTOGRAM_POINTS,
+        )?;
+        let values =
+            values.pop().expect("one reading is put in so one comes out");
+        let (start, bin_width, counts) = aggregate::histogram(&values, bins);
+        Ok(api::Histogram {
+            start,
+            bin_width,
+            counts,
+        })
+    }
+
+    /// The first `n` stored values of a single reading between start and
+    /// end, not resampled.
+    ///
+    /// # Panics
+    /// If the reading is not part of this series.
+    #[instrument(skip(self))]
+    pub fn read_first(
+        &mut self,
+        reading: &protocol::Reading,
+        start: jiff::Timestamp,
+        end: jiff::Timestamp,
+        n: usize,
+    ) -> Result<(Vec<jiff::Timestamp>, Vec<f32>), byteseries::series::Error>
+    {
+        let device_info = reading.info().device.info();
+        let scale_factor = millis_to_minimal_representation(device_info);
+        let start = start.as_millisecond() as u64 / scale_factor;
+        let end = end.as_millisecond() as u64 / scale_factor;
+
+        let mut time = Vec::new();
+        let mut values = Vec::new();
+        self.read_full(reading, start, end, |ts, value| {
+            if values.len() == n {
+                return ControlFlow::Break(());
+            }
+            time.push(
+                jiff::Timestamp::from_millisecond((ts * scale_factor) as i64)
+                    .expect("timestamps are between MIN and MAX times of Timestamp type"),
+            );
+            values.push(value);
+            ControlFlow::Continue(())
+        })?;
+        Ok((time, values))
+    }
+
+    /// Calls `on_value` for each stored value of the reading between the
+    /// scaled timestamps start and end, in chronological order, until it
+    /// breaks. Reads in chunks so memory use stays bounded for long ranges.
+    fn read_full(
+        &mut self,
+        reading: &protocol::Reading,
+        start: u64,
+        end: u64,
+        mut on_value: impl FnMut(u64, f32) -> ControlFlow<()>,
+    ) -> Result<ControlFlow<()>, byteseries::series::Error> {
+        use byteseries::seek::Error::StartAfterData;
+        use byteseries::series::Error::InvalidRange;
+
+        let mut decoder =
+            Resampler::from_fields(vec![self.field_for(reading)], self.line.len());
+        let mut chunk_start = start;
+        let mut timestamps = Vec::with_capacity(READ_CHUNK);
+        let mut data = Vec::with_capacity(READ_CHUNK);
+        loop {
+            timestamps.clear();
+            data.clear();
+            match self.byteseries.read_first_n(
+                READ_CHUNK,
+                &mut decoder,
+                chunk_start..=end,
+                &mut timestamps,
+                &mut data,
+            ) {
+                Ok(()) => (),
+                // the previous chunk ended exactly at the end of the data
+                Err(InvalidRange(StartAfterData { .. }))
+                    if chunk_start != start =>
+                {
+                    break
+                }
+                Err(e) => return Err(e),
+            }
+
+            for (ts, values) in timestamps.iter().zip(&data) {
+                if on_value(*ts, values[0]).is_break() {
+                    return Ok(ControlFlow::Break(()));
+                }
+            }
+            match timestamps.last() {
+                Some(last)
+                    if timestamps.len() == READ_CHUNK && *last < end =>
+                {
+                    chunk_start = last + 1;
+                }
+                _ => break,
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn field_for(&self, requested: &protocol::Reading) -> bitspec::Field<f32> {
+        self.meta_list
+            .iter()
+            .find(|meta| requested.is_same_as(&meta.reading))
+            .inspect(|meta| trace!("meta used for decoding: {meta:?}"))
+            .map(|meta| meta.field.clone())
+            .unwrap_or_else(|| {
+                panic!(
+                    "caller of read makes sure all readings are part of this \
+                series.\n\tseries: {:?},\n\trequested: {:?}",
+                    self.meta_list, requested
+                )
+            })
+    }
 }
 
 fn wrap_inder_err(
@@ -328,37 +668,40 @@ pub(crate) async fn store(
     data: &Data,
     reading: &protocol::Reading,
     data_dir: &Path,
-) -> Result<()> {
-    let mut data = data.0.lock().await;
-
-    let key = reading.device();
-    if let Some(series) = data.get_mut(&key) {
-        series
-            .append(reading)
-            .wrap_err("failed to append to existing timeseries")?;
-    } else {
-        let mut series = Series::open_or_create(reading, data_dir)
-            .wrap_err("Could not open new series")
-            .with_note(|| format!("reading was: {reading:?}"))?;
-        series
-            .append(reading)
-            .wrap_err("failed to newly created timeseries")?;
-        let existing = data.insert(key, series);
-        assert!(existing.is_none(), "should not race we still hold the lock");
-    }
+) -> Result<Option<Appended>> {
+    let series = {
+        let mut data = data.0.lock().await;
+        match data.entry(reading.device()) {
+            Entry::Occupied(entry) => entry.get().clone(),
+            Entry::Vacant(entry) => {
+                let series = Series::open_or_create(reading, data_dir)
+                    .wrap_err("Could not open new series")
+                    .with_note(|| format!("reading was: {reading:?}"))?;
+                entry.insert(Arc::new(Mutex::new(series))).clone()
+            }
+        }
+    };
+    let appended = series
+        .lock()
+        .await
+        .append(reading)
+        .wrap_err("failed to append to timeseries")?;
     trace!("stored new reading");
 
-    Ok(())
+    Ok(appended)
 }
 
-pub(crate) fn to_speclist(readings: &[protocol::Reading]) -> Vec<bitspec::LengthWithOps> {
+pub(crate) fn to_speclist(
+    readings: &[protocol::Reading],
+    stores_missing: bool,
+) -> Vec<bitspec::LengthWithOps> {
     readings
         .iter()
         .map(|r| bitspec::RangeWithRes {
             range: r.range(),
             resolution: r.resolution(),
         })
-        .map(bitspec::LengthWithOps::from)
+        .map(|range| bitspec::LengthWithOps::new(range, stores_missing))
         .collect()
 }
 
@@ -382,7 +725,7 @@ fn base_path(reading: &protocol::Reading) -> PathBuf {
     parts.into_iter().collect()
 }
 
-fn resample_setup(
+pub(crate) fn resample_setup(
     fields: &[bitspec::Field<f32>],
     payload_size: usize,
 ) -> (Resampler, Vec<downsample::Config>) {
@@ -0,0 +1,281 @@
+use protocol::reading::Aggregation;
+
+#[derive(Debug)]
+struct Bucket {
+    index: u64,
+    /// Timestamp of the first value in the bucket
+    first: u64,
+    sum: f64,
+    min: f32,
+    max: f32,
+    last: f32,
+    count: usize,
+}
+
+impl Bucket {
+    fn new(index: u64, ts: u64, value: f32) -> Self {
+        Self {
+            index,
+            first: ts,
+            sum: f64::from(value),
+            min: value,
+            max: value,
+            last: value,
+            count: 1,
+        }
+    }
+
+    fn add(&mut self, value: f32) {
+        self.sum += f64::from(value);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.last = value;
+        self.count += 1;
+    }
+}
+
+/// Splits the time between start and end into `n` equally sized buckets and
+/// aggregates the values in each. Missing (NaN) values are skipped, as are
+/// empty buckets. Each resulting point is placed at the first value of its
+/// bucket.
+#[derive(Debug)]
+pub(crate) struct Aggregator {
+    aggregation: Aggregation,
+    start: u64,
+    bucket_width: u64,
+    current: Option<Bucket>,
+    pub(crate) timestamps: Vec<u64>,
+    pub(crate) values: Vec<f32>,
+    /// Only filled for [`Aggregation::Envelope`], `values` then holds the
+    /// minimum
+    pub(crate) max: Vec<f32>,
+}
+
+impl Aggregator {
+    pub(crate) fn new(
+        aggregation: Aggregation,
+        start: u64,
+        end: u64,
+        n: usize,
+    ) -> Self {
+        let bucket_width = (end.saturating_sub(start) / n.max(1) as u64).max(1);
+        Self {
+            aggregation,
+            start,
+            bucket_width,
+            current: None,
+            timestamps: Vec::with_capacity(n),
+            values: Vec::with_capacity(n),
+            max: Vec::new(),
+        }
+    }
+
+    /// Values must be pushed in chronological order
+    pub(crate) fn push(&mut self, ts: u64, value: f32) {
+        if value.is_nan() {
+            return;
+        }
+
+        let index = ts.saturating_sub(self.start) / self.bucket_width;
+        match &mut self.current {
+            Some(bucket) if bucket.index == index => bucket.add(value),
+            _ => {
+                let finished =
+                    self.current.replace(Bucket::new(index, ts, value));
+                if let Some(bucket) = finished {
+                    self.emit(bucket);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn finish(&mut self) {
+        if let Some(bucket) = self.current.take() {
+            self.emit(bucket);
+        }
+    }
+
+    fn emit(&mut self, bucket: Bucket) {
+        self.timestamps.push(bucket.first);
+        let value = match self.aggregation {
+            Aggregation::Mean => (bucket.sum / bucket.count as f64) as f32,
+            Aggregation::Min => bucket.min,
+            Aggregation::Max => bucket.max,
+            Aggregation::Envelope => {
+                self.max.push(bucket.max);
+                bucket.min
+            }
+            Aggregation::Last => bucket.last,
+            Aggregation::Count => bucket.count as f32,
+        };
+        self.values.push(value);
+    }
+}
+
+/// Min, max, mean and count of the values in one bucket of a
+/// [`Summarizer`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Stats {
+    /// Timestamp at which the bucket starts
+    pub(crate) start: u64,
+    pub(crate) min: f32,
+    pub(crate) max: f32,
+    pub(crate) mean: f32,
+    pub(crate) count: usize,
+}
+
+/// Splits time into buckets of a fixed width that start at a multiple of
+/// that width. Unlike [`Aggregator`] this keeps all statistics and skips
+/// missing (NaN) values. Empty buckets are skipped.
+#[derive(Debug)]
+pub(crate) struct Summarizer {
+    bucket_width: u64,
+    current: Option<Bucket>,
+    pub(crate) stats: Vec<Stats>,
+}
+
+impl Summarizer {
+    pub(crate) fn new(bucket_width: u64) -> Self {
+        Self {
+            bucket_width: bucket_width.max(1),
+            current: None,
+            stats: Vec::new(),
+        }
+    }
+
+    /// Values must be pushed in chronological order
+    pub(crate) fn push(&mut self, ts: u64, value: f32) {
+        if value.is_nan() {
+            return;
+        }
+
+        let index = ts / self.bucket_width;
+        match &mut self.current {
+            Some(bucket) if bucket.index == index => bucket.add(value),
+            _ => {
+                let finished =
+                    self.current.replace(Bucket::new(index, ts, value));
+                if let Some(bucket) = finished {
+                    self.emit(bucket);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn finish(&mut self) {
+        if let Some(bucket) = self.current.take() {
+            self.emit(bucket);
+        }
+    }
+
+    fn emit(&mut self, bucket: Bucket) {
+        self.stats.push(Stats {
+            start: bucket.index * self.bucket_width,
+            min: bucket.min,
+            max: bucket.max,
+            mean: (bucket.sum / bucket.count as f64) as f32,
+            count: bucket.count,
+        });
+    }
+}
+
+/// Counts the values in `bins` equally wide bins spanning the smallest to
+/// the largest value. Missing (NaN) values are skipped. Returns the lower
+/// edge of the first bin, the width of the bins and the counts.
+pub(crate) fn histogram(values: &[f32], bins: usize) -> (f32, f32, Vec<usize>) {
+    let bins = bins.max(1);
+    let mut counts = vec![0; bins];
+    let (min, max) = values
+        .iter()
+        .filter(|v| !v.is_nan())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+            (min.min(*v), max.max(*v))
+        });
+    if min > max {
+        return (0.0, 0.0, counts);
+    }
+
+    let width = (max - min) / bins as f32;
+    for value in values.iter().filter(|v| !v.is_nan()) {
+        let bin = if width > 0.0 {
+            ((value - min) / width) as usize
+        } else {
+            0
+        };
+        counts[bin.min(bins - 1)] += 1;
+    }
+    (min, width, counts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn aggregate(aggregation: Aggregation) -> Aggregator {
+        let mut aggregator = Aggregator::new(aggregation, 0, 20, 2);
+        for (ts, value) in [(0, 1.0), (3, 5.0), (9, 2.0), (12, 7.0)] {
+            aggregator.push(ts, value);
+        }
+        aggregator.finish();
+        aggregator
+    }
+
+    #[test]
+    fn buckets_by_time() {
+        let aggregator = aggregate(Aggregation::Max);
+        assert_eq!(aggregator.timestamps, [0, 12]);
+        assert_eq!(aggregator.values, [5.0, 7.0]);
+    }
+
+    #[test]
+    fn envelope_has_min_and_max() {
+        let aggregator = aggregate(Aggregation::Envelope);
+        assert_eq!(aggregator.values, [1.0, 7.0]);
+        assert_eq!(aggregator.max, [5.0, 7.0]);
+    }
+
+    #[test]
+    fn count_and_last() {
+        assert_eq!(aggregate(Aggregation::Count).values, [3.0, 1.0]);
+        assert_eq!(aggregate(Aggregation::Last).values, [2.0, 7.0]);
+    }
+
+    #[test]
+    fn skips_missing_values() {
+        let mut aggregator = Aggregator::new(Aggregation::Envelope, 0, 20, 2);
+        for (ts, value) in
+            [(0, f32::NAN), (3, 5.0), (9, f32::NAN), (12, f32::NAN)]
+        {
+            aggregator.push(ts, value);
+        }
+        aggregator.finish();
+        assert_eq!(aggregator.timestamps, [3]);
+        assert_eq!(aggregator.values, [5.0]);
+        assert_eq!(aggregator.max, [5.0]);
+    }
+
+    #[test]
+    fn summary_buckets_are_aligned() {
+        let mut summarizer = Summarizer::new(10);
+        for (ts, value) in
+            [(3, 1.0), (5, f32::NAN), (9, 3.0), (12, 7.0), (35, 2.0)]
+        {
+            summarizer.push(ts, value);
+        }
+        summarizer.finish();
+
+        let starts: Vec<_> = summarizer.stats.iter().map(|s| s.start).collect();
+        assert_eq!(starts, [0, 10, 30]);
+        let first = summarizer.stats[0];
+        assert_eq!((first.min, first.max, first.mean), (1.0, 3.0, 2.0));
+        assert_eq!(first.count, 2);
+    }
+
+    #[test]
+    fn histogram_includes_largest_value() {
+        let (start, width, counts) =
+            histogram(&[0.0, 1.0, 2.5, f32::NAN, 4.0], 4);
+        assert_eq!((start, width), (0.0, 1.0));
+        assert_eq!(counts, [1, 1, 1, 1]);
+    }
+}
@@ -9,8 +9,20 @@ pub struct Field<T> {
 
     pub decode_scale: T,
     pub decode_add: T,
+
+    /// Whether the highest value marks a missing value, set from the
+    /// version of the header the field is in.
+    #[serde(skip)]
+    pub(crate) stores_missing: bool,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Field can not store a missing value (NaN), it is from a header \
+    older than version 1"
+)]
+pub struct MissingNotSupported;
+
 #[allow(dead_code)]
 impl<T> Field<T>
 where
@@ -23,9 +35,24 @@ where
         + core::fmt::Display
         + core::fmt::Debug,
 {
+    /// The highest value that fits in the field marks a missing value, see
+    /// [`LengthWithOps`](super::LengthWithOps). Fields from older headers
+    /// have no such value.
+    fn nan_repr(&self) -> Option<u32> {
+        if !self.stores_missing {
+            return None;
+        }
+        match self.length {
+            0 => None,
+            32.. => Some(u32::MAX),
+            length => Some((1 << length) - 1),
+        }
+    }
+
+    /// Returns NaN for missing values
     pub fn decode<D>(&self, line: &[u8]) -> D
     where
-        D: num::cast::NumCast
+        D: num::Float
             + core::fmt::Display
             + core::ops::Add
             + core::ops::SubAssign
@@ -33,6 +60,9 @@ where
             + core::ops::AddAssign,
     {
         let int_repr: u32 = compression::decode(line, self.offset, self.length);
+        if Some(int_repr) == self.nan_repr() {
+            return D::nan();
+        }
         let mut decoded: D = num::cast(int_repr).unwrap();
 
         decoded *= num::cast(self.decode_scale).unwrap(); //FIXME flip decode scale / and *
@@ -40,15 +70,25 @@ where
 
         decoded
     }
-    pub fn encode(&self, mut numb: T, line: &mut [u8])
+    /// NaN is stored as a missing value
+    pub fn encode(
+        &self,
+        mut numb: T,
+        line: &mut [u8],
+    ) -> Result<(), MissingNotSupported>
     where
-        T: num::cast::NumCast
+        T: num::Float
             + core::fmt::Display
             + core::ops::Add
             + core::ops::SubAssign
             + core::ops::AddAssign
             + core::ops::DivAssign,
     {
+        if numb.is_nan() {
+            let nan_repr = self.nan_repr().ok_or(MissingNotSupported)?;
+            compression::encode(nan_repr, line, self.offset, self.length);
+            return Ok(());
+        }
         numb -= num::cast(self.decode_add).unwrap();
         numb /= num::cast(self.decode_scale).unwrap();
 
@@ -57,6 +97,7 @@ where
         });
 
         compression::encode(to_encode, line, self.offset, self.length);
+        Ok(())
     }
 }
 
@@ -74,6 +115,7 @@ mod tests {
                 decode_scale: 1.0000000000,
                 length: 14,
                 offset: 0,
+                stores_missing: true,
             },
             Field::<f32> {
                 // Triangle
@@ -81,6 +123,7 @@ mod tests {
                 decode_scale: 0.0500000007,
                 length: 10,
                 offset: 14,
+                stores_missing: true,
             },
         ];
 
@@ -89,8 +132,8 @@ mod tests {
             let triangle = 20.0 - i as f32 * (20.0 + 10.0) / 100.0;
 
             let mut line = [0u8, 0, 0];
-            fields[0].encode(sine, &mut line);
-            fields[1].encode(triangle, &mut line);
+            fields[0].encode(sine, &mut line).unwrap();
+            fields[1].encode(triangle, &mut line).unwrap();
 
             let decoded_sine: f32 = fields[0].decode(&line);
             let decoded_triangle: f32 = fields[1].decode(&line);
@@ -99,4 +142,22 @@ mod tests {
             assert!(triangle - decoded_triangle <= 0.05 + 0.001);
         }
     }
+    #[test]
+    fn missing_values() {
+        let mut field = Field::<f32> {
+            decode_add: 0.0,
+            decode_scale: 1.0,
+            length: 4,
+            offset: 0,
+            stores_missing: true,
+        };
+        let mut line = [0u8];
+        field.encode(f32::NAN, &mut line).unwrap();
+        assert!(field.decode::<f32>(&line).is_nan());
+
+        // fields from older headers use every value
+        field.stores_missing = false;
+        assert_eq!(field.decode::<f32>(&line), 15.0);
+        assert!(field.encode(f32::NAN, &mut line).is_err());
+    }
 }
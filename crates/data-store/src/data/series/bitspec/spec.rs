@@ -17,12 +17,19 @@ pub struct LengthWithOps {
     pub(crate) decode_add: f32,
 }
 
-impl From<RangeWithRes> for LengthWithOps {
+impl LengthWithOps {
+    /// Fields that store missing values need room for the top of the range
+    /// and for the highest value, that marks a missing value. Headers
+    /// before version 1 had neither, their fields can be a bit shorter.
     #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
-    fn from(field: RangeWithRes) -> Self {
+    pub fn new(field: RangeWithRes, stores_missing: bool) -> Self {
         let given_range = field.range.end() - field.range.start();
         let needed_range = given_range / field.resolution;
-        let length = needed_range.log2().ceil() as u32;
+        let length = if stores_missing {
+            (needed_range + 2.0).log2().ceil() as u32
+        } else {
+            needed_range.log2().ceil() as u32
+        };
         let length = length.try_into().expect("max field length is 256 bits");
         let decode_scale = field.resolution;
 
@@ -35,7 +42,10 @@ impl From<RangeWithRes> for LengthWithOps {
     }
 }
 
-pub fn speclist_to_fields(input: Vec<LengthWithOps>) -> Vec<Field<f32>> {
+pub fn speclist_to_fields(
+    input: Vec<LengthWithOps>,
+    stores_missing: bool,
+) -> Vec<Field<f32>> {
     let mut res = Vec::new();
 
     let mut start_bit = 0;
@@ -45,6 +55,7 @@ pub fn speclist_to_fields(input: Vec<LengthWithOps>) -> Vec<Field<f32>> {
             length: field.length,
             decode_scale: field.decode_scale,
             decode_add: field.decode_add,
+            stores_missing,
         });
         start_bit = start_bit
             .checked_add(field.length)
@@ -46,7 +46,9 @@ impl byteseries::Encoder for Resampler {
     fn encode_item(&mut self, item: &Self::Item) -> Vec<u8> {
         let mut encoded = vec![0u8; self.payload_size];
         for (field, item) in self.fields.iter().zip(item) {
-            field.encode(*item, &mut encoded);
+            field
+                .encode(*item, &mut encoded)
+                .expect("items are only missing if a resampled value was");
         }
         encoded
     }
@@ -68,8 +70,8 @@ mod test {
         );
 
         let readings = reading.device().info().affects_readings;
-        let specs = crate::data::series::to_speclist(readings);
-        let fields = bitspec::speclist_to_fields(specs);
+        let specs = crate::data::series::to_speclist(readings, true);
+        let fields = bitspec::speclist_to_fields(specs, true);
 
         let payload_size = fields
             .iter()
@@ -1,7 +1,8 @@
 use std::ffi::OsStr;
 use std::fs;
 use std::fs::DirEntry;
-use std::io::{ErrorKind, Read};
+use std::io::{ErrorKind, Read, Write};
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -16,30 +17,41 @@ use protocol::Reading;
 use tracing::warn;
 
 use crate::data::series;
+use crate::selection::Selection;
 
 mod csv;
 use csv::Csv;
-mod decoder;
+pub(crate) mod decoder;
 use decoder::ExportDecoder;
+#[cfg(feature = "export")]
+pub(crate) mod parquet;
+#[cfg(feature = "export")]
+use parquet::Parquet;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// RFC 4180 csv with the RON encoded readings as column headers and the
+    /// timestamps as stored
+    #[default]
+    Csv,
+    /// Typed columns: the time in nanoseconds since the unix epoch and a
+    /// float32 column per reading
+    #[cfg(feature = "export")]
+    Parquet,
+}
 
-pub fn perform(data_dir: &Path, only: Option<PathBuf>) -> Result<()> {
+pub fn perform(
+    data_dir: &Path,
+    only: Option<PathBuf>,
+    format: Format,
+    selection: &Selection,
+) -> Result<()> {
     let list = files_to_export(data_dir)?;
     if list.is_empty() {
         bail!("No files left to export")
     }
 
-    let to_handle: Vec<_> = list
-        .iter()
-        .filter(|p| {
-            only.as_ref().is_none_or(|allowed| {
-                p.ends_with(allowed) || p.with_extension("").ends_with(allowed)
-            })
-        })
-        .collect();
-
-    if to_handle.is_empty() {
-        return crate::no_filter_match_err(list, only);
-    }
+    let to_handle = crate::select_only(&list, only.as_deref())?;
 
     let bars = MultiProgress::new();
     let files_bar =
@@ -47,30 +59,51 @@ pub fn perform(data_dir: &Path, only: Option<PathBuf>) -> Result<()> {
     let files_bar = bars.insert(0, files_bar);
     files_bar.inc(0); // make the bar appear
 
+    let mut exported = 0;
     for path in &to_handle {
-        handle_file(&path, bars.clone())
+        exported += handle_file(&path, bars.clone(), format, selection)
             .wrap_err("Failed to export data")
-            .with_note(|| format!("Input file: {}", path.display()))?;
+            .with_note(|| format!("Input file: {}", path.display()))?
+            as usize;
         files_bar.inc(1);
     }
 
     drop(bars);
-    tracing::info!(
-        "Done, exported {} files to {}",
-        to_handle.len(),
-        data_dir.display()
-    );
+    if exported == 0 {
+        return Err(color_eyre::Report::msg(
+            "None of the series contain a reading matching --reading",
+        ))
+        .with_note(|| format!("selectors: {:?}", selection.readings));
+    }
+    tracing::info!("Done, exported {exported} files to {}", data_dir.display());
 
     Ok(())
 }
 
-pub fn handle_file(path: &Path, bars: MultiProgress) -> Result<()> {
+/// Returns false if the series has none of the selected readings, then
+/// nothing is exported.
+pub fn handle_file(
+    path: &Path,
+    bars: MultiProgress,
+    format: Format,
+    selection: &Selection,
+) -> Result<bool> {
     let metadata =
         read_metadata(path).wrap_err("Could not extract metadata")?;
-    let series::Header { readings, encoding } =
-        ron::from_str(&metadata).wrap_err("Could not deserialize metadata")?;
+    let series::Header {
+        readings, encoding, ..
+    } = series::Header::parse(&metadata)?;
     let (meta, payload_size) =
         series::meta_list_and_payload_size(&readings, &encoding);
+    let (readings, meta): (Vec<_>, Vec<_>) = readings
+        .into_iter()
+        .zip(meta)
+        .filter(|(reading, _)| selection.matches(reading))
+        .unzip();
+    let Some(device_info) = readings.first().map(|r| r.device().info()) else {
+        return Ok(false);
+    };
+    let range = selection.range(device_info);
 
     let corrupt_sections_skipped = Arc::new(AtomicUsize::new(0));
     let callback = {
@@ -90,9 +123,7 @@ pub fn handle_file(path: &Path, bars: MultiProgress) -> Result<()> {
     let decoder = ExportDecoder::from_fields(
         meta.iter().map(|m| m.field.clone()).collect(),
     );
-    let output = Csv::open(&readings, path.with_extension("csv"))
-        .wrap_err("Failed to open output csv")
-        .suggestion("If the file already exists remove it")?;
+    let output = Output::open(&readings, path, format)?;
 
     let copy_bar = ProgressBar::new(input_series.len())
         .with_style(crate::bar_style())
@@ -101,14 +132,8 @@ pub fn handle_file(path: &Path, bars: MultiProgress) -> Result<()> {
             path.file_name().expect("we only handle files with names")
         ));
     let copy_bar = bars.insert(1, copy_bar);
-    copy_over_content(
-        input_series,
-        decoder,
-        readings,
-        output,
-        copy_bar.clone(),
-    )
-    .wrap_err("Failed to copy over content")?;
+    copy_over_content(input_series, decoder, range, output, copy_bar.clone())
+        .wrap_err("Failed to copy over content")?;
     bars.remove(&copy_bar);
 
     let skipped = corrupt_sections_skipped.load(Ordering::Relaxed);
@@ -120,54 +145,103 @@ pub fn handle_file(path: &Path, bars: MultiProgress) -> Result<()> {
             )
         })
     }
-    Ok(())
+    Ok(true)
+}
+
+enum Output {
+    Csv { csv: Csv, precisions: Vec<usize> },
+    #[cfg(feature = "export")]
+    Parquet(Parquet),
+}
+
+impl Output {
+    fn open(readings: &[Reading], path: &Path, format: Format) -> Result<Self> {
+        Ok(match format {
+            Format::Csv => Self::Csv {
+                csv: Csv::open(readings, path.with_extension("csv"))
+                    .wrap_err("Failed to open output csv")
+                    .suggestion("If the file already exists remove it")?,
+                precisions: readings
+                    .iter()
+                    .map(|r| r.info().precision())
+                    .collect_vec(),
+            },
+            #[cfg(feature = "export")]
+            Format::Parquet => Self::Parquet(
+                Parquet::open(readings, path.with_extension("parquet"))
+                    .wrap_err("Failed to open output parquet file")
+                    .suggestion("If the file already exists remove it")?,
+            ),
+        })
+    }
+
+    fn write_line(&mut self, ts: u64, line: &[f32]) -> Result<()> {
+        match self {
+            Self::Csv { csv, precisions } => csv
+                .write_line(ts, line, precisions)
+                .wrap_err("failed to write line to csv"),
+            #[cfg(feature = "export")]
+            Self::Parquet(parquet) => parquet
+                .write_line(ts, line)
+                .wrap_err("failed to write line to parquet"),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Csv { mut csv, .. } => {
+                csv.file.flush().wrap_err("failed to flush csv")
+            }
+            #[cfg(feature = "export")]
+            Self::Parquet(parquet) => parquet.finish(),
+        }
+    }
 }
 
 fn copy_over_content(
     mut input_series: ByteSeries,
     mut decoder: ExportDecoder,
-    readings: Vec<Reading>,
-    mut output: Csv,
+    range: RangeInclusive<u64>,
+    mut output: Output,
     copy_bar: ProgressBar,
 ) -> Result<()> {
-    let mut read_start = 0;
-    let precisions = readings
-        .into_iter()
-        .map(|r| r.info().precision())
-        .collect_vec();
+    use byteseries::seek::Error::{StartAfterData, StopBeforeData};
+    use byteseries::series::Error::InvalidRange;
+
+    let mut read_start = *range.start();
 
     loop {
         let mut timestamps = Vec::new();
         let mut data = Vec::new();
 
-        if let Err(byteseries::series::Error::InvalidRange(
-            byteseries::seek::Error::StartAfterData { .. },
-        )) = input_series.read_first_n(
+        if let Err(InvalidRange(StartAfterData { .. } | StopBeforeData)) =
+            input_series.read_first_n(
             100_000,
             &mut decoder,
-            read_start..,
+            read_start..=*range.end(),
             &mut timestamps,
             &mut data,
         ) {
             copy_bar.finish();
-            break Ok(());
+            break output.finish();
         }
 
-        let Some(last_ts) = timestamps.last() else {
-            break Ok(()); // all data consumed
+        let Some(&last_ts) = timestamps.last() else {
+            break output.finish(); // all data consumed
         };
 
-        read_start = *last_ts + 1;
         for (ts, line) in timestamps.into_iter().zip(data.into_iter()) {
             copy_bar.inc(1);
-            output
-                .write_line(ts, &line, &precisions)
-                .wrap_err("failed to write line to csv")?;
+            output.write_line(ts, &line)?;
+        }
+        if last_ts >= *range.end() {
+            break output.finish();
         }
+        read_start = last_ts + 1;
     }
 }
 
-fn read_metadata(path: &Path) -> Result<String> {
+pub(crate) fn read_metadata(path: &Path) -> Result<String> {
     const HEADER_END: &'static str = "In the case the creator of this \
     file wanted to store metadata in it that\n    follows now:";
 
@@ -221,7 +295,7 @@ fn take_around_parenthesis(input: &str) -> Result<&str> {
     Ok(&input[..=end])
 }
 
-fn files_to_export(
+pub(crate) fn files_to_export(
     data_dir: &Path,
 ) -> Result<Vec<PathBuf>, color_eyre::eyre::Error> {
     const HEADER_START: &'static str = "This is a byteseries 1 file, an embedded \
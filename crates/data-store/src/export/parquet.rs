@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, Float32Array, RecordBatch, TimestampNanosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use color_eyre::eyre::{Context, OptionExt, Result};
+use color_eyre::Section;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use protocol::reading::tree::{Item, Tree};
+use protocol::Reading;
+
+use crate::data::series;
+
+/// Name of the timestamp column
+pub(crate) const TIME_COLUMN: &str = "ts";
+/// Field metadata key for the RON encoded reading of a column
+pub(crate) const READING_KEY: &str = "reading";
+const ROWS_PER_BATCH: usize = 100_000;
+
+/// A timestamp column in nanoseconds since the unix epoch (UTC) followed by
+/// a float32 column per reading. Each reading column has the device, unit
+/// and description as metadata.
+pub(crate) struct Parquet {
+    writer: ArrowWriter<fs::File>,
+    schema: SchemaRef,
+    /// Converts the timestamps as stored in the series to nanoseconds
+    ts_to_nanos: i64,
+    time: Vec<i64>,
+    columns: Vec<Vec<f32>>,
+}
+
+impl Parquet {
+    pub(crate) fn open(readings: &[Reading], path: PathBuf) -> Result<Self> {
+        let device_info = readings
+            .first()
+            .ok_or_eyre("Need at least one reading to export")?
+            .device()
+            .info();
+        let ts_to_nanos = series::millis_to_minimal_representation(device_info)
+            as i64
+            * 1_000_000;
+
+        let file = fs::OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .open(&path)
+            .wrap_err("Could not open output parquet path")
+            .with_note(|| format!("parquet path: {}", path.display()))?;
+
+        let time = Field::new(
+            TIME_COLUMN,
+            DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+            false,
+        );
+        let fields = std::iter::once(Ok(time))
+            .chain(readings.iter().map(reading_field))
+            .collect::<Result<Vec<_>>>()?;
+        let schema = Arc::new(Schema::new(fields));
+
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let writer =
+            ArrowWriter::try_new(file, schema.clone(), Some(properties))
+                .wrap_err("Could not create parquet writer")?;
+
+        Ok(Self {
+            writer,
+            schema,
+            ts_to_nanos,
+            time: Vec::with_capacity(ROWS_PER_BATCH),
+            columns: vec![Vec::with_capacity(ROWS_PER_BATCH); readings.len()],
+        })
+    }
+
+    pub(crate) fn write_line(&mut self, ts: u64, line: &[f32]) -> Result<()> {
+        self.time.push(ts as i64 * self.ts_to_nanos);
+        for (column, value) in self.columns.iter_mut().zip(line) {
+            column.push(*value);
+        }
+
+        if self.time.len() >= ROWS_PER_BATCH {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let time =
+            TimestampNanosecondArray::from(std::mem::take(&mut self.time))
+                .with_timezone("UTC");
+        let columns = std::iter::once(Arc::new(time) as ArrayRef)
+            .chain(self.columns.iter_mut().map(|column| {
+                Arc::new(Float32Array::from(std::mem::take(column))) as ArrayRef
+            }))
+            .collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)
+            .wrap_err("Could not create record batch")?;
+        self.writer
+            .write(&batch)
+            .wrap_err("Could not write record batch to parquet file")
+    }
+
+    pub(crate) fn finish(mut self) -> Result<()> {
+        if !self.time.is_empty() {
+            self.flush()?;
+        }
+        self.writer
+            .close()
+            .wrap_err("Could not finish parquet file")?;
+        Ok(())
+    }
+}
+
+fn reading_field(reading: &Reading) -> Result<Field> {
+    let info = reading.info();
+    let encoded =
+        ron::to_string(reading).wrap_err("Could not encode reading")?;
+    let metadata = HashMap::from([
+        (READING_KEY.to_string(), encoded),
+        ("device".to_string(), info.device.info().name.to_string()),
+        ("unit".to_string(), info.unit.to_string()),
+        ("description".to_string(), info.description.to_string()),
+    ]);
+    Ok(Field::new(column_name(reading), DataType::Float32, true)
+        .with_metadata(metadata))
+}
+
+/// The name of the reading without the location and device, for example
+/// `Temperature`. Unique within a series.
+fn column_name(reading: &Reading) -> String {
+    let mut current = reading as &dyn Tree;
+    while let Item::Node(inner) = current.inner() {
+        current = inner;
+    }
+    current.name()
+}
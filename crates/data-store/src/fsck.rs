@@ -0,0 +1,550 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use byteseries::ByteSeries;
+use color_eyre::eyre::{bail, ensure, Context, Result};
+use color_eyre::Section;
+use tracing::info;
+
+use crate::data::series::{self, Header};
+use crate::export::decoder::ExportDecoder;
+use crate::rewrite::{self, Rewrite};
+
+/// Directory in the data dir where series are rewritten before they replace
+/// the originals.
+const WORK_DIR: &str = ".fsck";
+
+#[derive(Debug)]
+pub enum Problem {
+    /// The series can not be checked any further
+    UnreadableHeader(String),
+    /// The readings in the header differ from those the protocol needs,
+    /// fixed by running migrate.
+    NeedsMigration,
+    /// The data file ends in a partially written line
+    TornTail { bytes: u64 },
+    /// The series can not be checked any further
+    CanNotOpen(String),
+    /// Sections with a corrupt full timestamp, their data can not be read
+    CorruptSections(usize),
+    /// Lines with a timestamp that is not after that of the previous line
+    NotMonotonic(usize),
+    /// Values outside the range of their reading, the payload does not
+    /// match the encoding in the header
+    OutOfRange(usize),
+    /// Reading through the downsampled caches gave points the data can not
+    /// produce
+    InconsistentCache(String),
+    /// Reading stopped early, the rest of the series was not checked
+    ReadFailed(String),
+}
+
+impl Problem {
+    fn fixed_by_rewrite(&self) -> bool {
+        matches!(
+            self,
+            Problem::CorruptSections(_)
+                | Problem::NotMonotonic(_)
+                | Problem::OutOfRange(_)
+                | Problem::InconsistentCache(_)
+        )
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::UnreadableHeader(e) => {
+                write!(f, "header can not be read: {e}")
+            }
+            Problem::NeedsMigration => write!(
+                f,
+                "header differs from the protocol, run migrate to update it"
+            ),
+            Problem::TornTail { bytes } => {
+                write!(f, "data ends in a partial line of {bytes} bytes")
+            }
+            Problem::CanNotOpen(e) => write!(f, "can not be opened: {e}"),
+            Problem::CorruptSections(n) => {
+                write!(f, "{n} sections have a corrupt timestamp")
+            }
+            Problem::NotMonotonic(n) => {
+                write!(f, "{n} lines are not later than the line before")
+            }
+            Problem::OutOfRange(n) => {
+                write!(f, "{n} values are outside the range of their reading")
+            }
+            Problem::InconsistentCache(e) => {
+                write!(f, "downsampled cache does not match the data: {e}")
+            }
+            Problem::ReadFailed(e) => write!(f, "reading failed: {e}"),
+        }
+    }
+}
+
+/// Checks every series, with `fix` torn tails are cut off and series with
+/// corrupt or inconsistent data are rewritten. The originals of rewritten
+/// series are kept in `.backups` in the data dir. The data-store must not
+/// be running while this happens.
+pub fn perform(
+    data_dir: &Path,
+    only: Option<PathBuf>,
+    fix: bool,
+) -> Result<()> {
+    let list = crate::export::files_to_export(data_dir)?;
+    if list.is_empty() {
+        bail!("No files to check")
+    }
+
+    let to_handle = crate::select_only(&list, only.as_deref())?;
+
+    let work_dir = data_dir.join(WORK_DIR);
+    let header_len = HeaderLen::measure(&work_dir)?;
+    let mut with_problems = 0;
+    let mut unfixed = 0;
+    for path in &to_handle {
+        let series = rewrite::relative(data_dir, path);
+        let problems = check(path, &header_len)
+            .wrap_err("Failed to check series")
+            .with_note(|| format!("series: {}", path.display()))?;
+        if problems.is_empty() {
+            println!("{}: ok", series.display());
+            continue;
+        }
+
+        with_problems += 1;
+        println!("{}:", series.display());
+        for problem in &problems {
+            println!("\t{problem}");
+        }
+
+        if !fix {
+            continue;
+        }
+        let fixed = repair(data_dir, path, &problems)
+            .wrap_err("Failed to repair series")
+            .with_note(|| format!("series: {}", path.display()))?;
+        if !fixed {
+            unfixed += 1;
+        }
+    }
+
+    if work_dir.exists() {
+        fs::remove_dir_all(&work_dir)
+            .wrap_err("Could not remove work dir")
+            .with_note(|| format!("dir: {}", work_dir.display()))?;
+    }
+
+    println!(
+        "{with_problems} of {} series have problems",
+        to_handle.len()
+    );
+    if fix && unfixed > 0 {
+        bail!("{unfixed} series have problems fsck can not fix")
+    } else if !fix && with_problems > 0 {
+        Err(color_eyre::Report::msg("Found problems"))
+            .suggestion("Run fsck with --fix to repair them")
+    } else {
+        Ok(())
+    }
+}
+
+fn check(path: &Path, header_len: &HeaderLen) -> Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    let (header, metadata) = match crate::migrate::read_header(path) {
+        Ok(header) => header,
+        Err(e) => return Ok(vec![Problem::UnreadableHeader(format!("{e:#}"))]),
+    };
+    if let Some(reading) = header.readings.first() {
+        let device = reading.device();
+        let expected = Header::for_readings(device.info().affects_readings);
+        if expected.serialized()?.as_slice() != metadata.as_bytes() {
+            problems.push(Problem::NeedsMigration);
+        }
+    }
+
+    let (_, payload_size) =
+        series::meta_list_and_payload_size(&header.readings, &header.encoding);
+    let bytes = torn_tail(path, header_len.of(&metadata), payload_size)?;
+    if bytes > 0 {
+        problems.push(Problem::TornTail { bytes });
+    }
+
+    let corrupt_sections = Arc::new(AtomicUsize::new(0));
+    let callback = {
+        let corrupt_sections = corrupt_sections.clone();
+        Box::new(move || {
+            corrupt_sections.fetch_add(1, Ordering::Relaxed);
+            true
+        })
+    };
+    let (resampler, configs) =
+        series::resample_setup(&header.encoding, payload_size);
+    let res = ByteSeries::builder()
+        .payload_size(payload_size)
+        .with_downsampled_cache(resampler.clone(), configs)
+        .with_header(metadata.as_bytes().to_vec())
+        .with_callback_on_recoverable_corruption(callback)
+        .open(path);
+    let mut series = match res {
+        Ok((series, _)) => series,
+        Err(e) => {
+            problems.push(Problem::CanNotOpen(e.to_string()));
+            return Ok(problems);
+        }
+    };
+
+    let mut scan = Scan::default();
+    if let Err(e) = scan.run(&mut series, &header) {
+        problems.push(Problem::ReadFailed(format!("{e:#}")));
+    }
+    let corrupt_sections = corrupt_sections.load(Ordering::Relaxed);
+    if corrupt_sections > 0 {
+        problems.push(Problem::CorruptSections(corrupt_sections));
+    }
+    if scan.not_monotonic > 0 {
+        problems.push(Problem::NotMonotonic(scan.not_monotonic));
+    }
+    if scan.out_of_range > 0 {
+        problems.push(Problem::OutOfRange(scan.out_of_range));
+    }
+    if let Some((first, last)) = scan.first.zip(scan.last) {
+        if let Err(e) =
+            check_cache(&mut series, resampler, &header, first, last)
+        {
+            problems.push(Problem::InconsistentCache(e));
+        }
+    }
+    Ok(problems)
+}
+
+/// Byteseries stores the metadata behind a fixed preamble
+struct HeaderLen {
+    preamble: u64,
+}
+
+impl HeaderLen {
+    /// Creates a few empty series in `work_dir` to find the length of the
+    /// preamble and check it does not depend on anything else.
+    fn measure(work_dir: &Path) -> Result<Self> {
+        if work_dir.exists() {
+            fs::remove_dir_all(work_dir)
+                .wrap_err("Could not clean up work dir")
+                .with_note(|| format!("dir: {}", work_dir.display()))?;
+        }
+        fs::create_dir_all(work_dir)
+            .wrap_err("Could not create work dir")
+            .with_note(|| format!("dir: {}", work_dir.display()))?;
+
+        let header_len = |name: &str, metadata: &str, payload_size: usize| {
+            let path = work_dir.join(name);
+            let empty = ByteSeries::builder()
+                .payload_size(payload_size)
+                .with_header(metadata.as_bytes().to_vec())
+                .create_new(true)
+                .open(&path)
+                .wrap_err("Could not create empty series to measure header")?;
+            drop(empty);
+            file_len(&path.with_extension("byteseries"))
+        };
+        let short = header_len("short", "()", 1)?;
+        let long = header_len("long", "((()))", 1)?;
+        let wide = header_len("wide", "()", 8)?;
+        ensure!(
+            long == short + 4 && wide == short,
+            "Byteseries header length is not a preamble plus the metadata"
+        );
+        Ok(Self {
+            preamble: short - 2,
+        })
+    }
+
+    fn of(&self, metadata: &str) -> u64 {
+        self.preamble + metadata.len() as u64
+    }
+}
+
+/// The number of bytes after the last complete line. Lines are the payload
+/// plus a two byte timestamp.
+fn torn_tail(path: &Path, header_len: u64, payload_size: usize) -> Result<u64> {
+    let data_len = file_len(path)?;
+    let line_len = payload_size as u64 + 2;
+    Ok(data_len.saturating_sub(header_len) % line_len)
+}
+
+fn file_len(path: &Path) -> Result<u64> {
+    Ok(fs::metadata(path)
+        .wrap_err("Could not get file metadata")
+        .with_note(|| format!("path: {}", path.display()))?
+        .len())
+}
+
+#[derive(Debug, Default)]
+struct Scan {
+    first: Option<u64>,
+    last: Option<u64>,
+    not_monotonic: usize,
+    out_of_range: usize,
+}
+
+impl Scan {
+    fn run(&mut self, series: &mut ByteSeries, header: &Header) -> Result<()> {
+        let mut decoder = ExportDecoder::from_fields(header.encoding.clone());
+        rewrite::for_each_chunk(series, &mut decoder, |timestamps, data| {
+            for (ts, values) in timestamps.into_iter().zip(data) {
+                if self.last.is_some_and(|last| ts <= last) {
+                    self.not_monotonic += 1;
+                } else {
+                    self.last = Some(ts);
+                }
+                self.first.get_or_insert(ts);
+                self.out_of_range += values
+                    .iter()
+                    .zip(&header.readings)
+                    .filter(|(v, reading)| {
+                        !v.is_nan() && !reading.range().contains(*v)
+                    })
+                    .count();
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Points read through the caches must lie within the data and be a mean
+/// of valid values.
+fn check_cache(
+    series: &mut ByteSeries,
+    mut resampler: series::Resampler,
+    header: &Header,
+    first: u64,
+    last: u64,
+) -> Result<(), String> {
+    let mut timestamps = Vec::new();
+    let mut data = Vec::new();
+    series
+        .read_n(
+            100,
+            first..=last,
+            &mut resampler,
+            &mut timestamps,
+            &mut data,
+            false,
+        )
+        .map_err(|e| format!("could not read: {e}"))?;
+
+    if let Some(ts) = timestamps.iter().find(|ts| !(first..=last).contains(*ts))
+    {
+        return Err(format!(
+            "point at {ts} is outside the data ({first}..={last})"
+        ));
+    }
+    if timestamps.windows(2).any(|w| w[0] >= w[1]) {
+        return Err("points are not in order".to_string());
+    }
+    let out_of_range = data
+        .iter()
+        .flat_map(|values| values.iter().zip(&header.readings))
+        .filter(|(v, reading)| !v.is_nan() && !reading.range().contains(*v))
+        .count();
+    if out_of_range > 0 {
+        return Err(format!("{out_of_range} points are out of range"));
+    }
+    Ok(())
+}
+
+/// Returns whether all problems could be fixed
+fn repair(data_dir: &Path, path: &Path, problems: &[Problem]) -> Result<bool> {
+    for problem in problems {
+        if let Problem::TornTail { bytes } = problem {
+            let file = fs::OpenOptions::new()
+                .write(true)
+                .open(path)
+                .wrap_err("Could not open series to cut off torn tail")?;
+            let len =
+                file.metadata().wrap_err("Could not get file length")?.len();
+            file.set_len(len - bytes)
+                .wrap_err("Could not cut off torn tail")?;
+            info!("Cut off {bytes} byte torn tail of {}", path.display());
+        }
+    }
+
+    if problems.iter().any(Problem::fixed_by_rewrite) {
+        rewrite(data_dir, path)?;
+    }
+
+    Ok(problems.iter().all(|problem| {
+        matches!(problem, Problem::TornTail { .. })
+            || problem.fixed_by_rewrite()
+    }))
+}
+
+/// Copies every readable line to a new series, dropping lines that are not
+/// later than the line before and making values that are out of range
+/// missing (NaN). The caches are built anew.
+fn rewrite(data_dir: &Path, path: &Path) -> Result<()> {
+    let (header, metadata) = crate::migrate::read_header(path)?;
+    let (_, payload_size) =
+        series::meta_list_and_payload_size(&header.readings, &header.encoding);
+    let (mut input, _) = ByteSeries::builder()
+        .payload_size(payload_size)
+        .with_header(metadata.as_bytes().to_vec())
+        .with_callback_on_recoverable_corruption(Box::new(|| true))
+        .open(path)
+        .wrap_err("Could not open byteseries")?;
+
+    let rewrite = Rewrite::start(
+        data_dir,
+        path,
+        &format!("{WORK_DIR}/rewrite"),
+        Some("fsck"),
+    )?;
+    let mut output = rewrite.create(
+        metadata.into_bytes(),
+        &header.encoding,
+        payload_size,
+    )?;
+
+    let mut decoder = ExportDecoder::from_fields(header.encoding.clone());
+    let mut line = vec![0; payload_size];
+    let mut last = None;
+    rewrite::for_each_chunk(&mut input, &mut decoder, |timestamps, data| {
+        for (ts, values) in timestamps.into_iter().zip(data) {
+            if last.is_some_and(|last| ts <= last) {
+                continue;
+            }
+            line.fill(0);
+            for ((value, field), reading) in
+                values.iter().zip(&header.encoding).zip(&header.readings)
+            {
+                let value = if reading.range().contains(value) {
+                    *value
+                } else {
+                    f32::NAN
+                };
+                field
+                    .encode(value, &mut line)
+                    .wrap_err("Could not encode repaired line")
+                    .suggestion("Run migrate on this series first")?;
+            }
+            output
+                .push_line(ts, &line)
+                .wrap_err("Could not write repaired line")?;
+            last = Some(ts);
+        }
+        Ok(())
+    })
+    .wrap_err("Could not copy series to repair")?;
+
+    drop(input);
+    drop(output);
+    let relative = rewrite.relative().to_path_buf();
+    let backup_dir = rewrite.finish()?.expect("fsck keeps a backup");
+    info!(
+        "Rewrote {}, original moved to {}",
+        relative.display(),
+        backup_dir.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use protocol::large_bedroom::{self, bed};
+    use protocol::Reading;
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    /// Returns the path of the data file and the length of a line
+    fn series_with_lines(data_dir: &Path, lines: u64) -> (PathBuf, usize) {
+        let temperature = Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Temperature(0.0),
+        ));
+        let header =
+            Header::for_readings(temperature.device().info().affects_readings);
+        let (_, payload_size) = series::meta_list_and_payload_size(
+            &header.readings,
+            &header.encoding,
+        );
+        let (resampler, configs) =
+            series::resample_setup(&header.encoding, payload_size);
+        let path = data_dir.join("temperature");
+        let (mut series, _) = ByteSeries::builder()
+            .payload_size(payload_size)
+            .with_downsampled_cache(resampler, configs)
+            .with_header(header.serialized().unwrap())
+            .create_new(true)
+            .open(&path)
+            .unwrap();
+
+        let mut line = vec![0; payload_size];
+        for (field, reading) in header.encoding.iter().zip(&header.readings) {
+            field.encode(*reading.range().start(), &mut line).unwrap();
+        }
+        for ts in 1..=lines {
+            series.push_line(ts * 1000, &line).unwrap();
+        }
+        series.flush_to_disk().unwrap();
+        (path.with_extension("byteseries"), payload_size + 2)
+    }
+
+    fn append(path: &Path, bytes: &[u8]) {
+        fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .unwrap()
+            .write_all(bytes)
+            .unwrap();
+    }
+
+    #[test]
+    fn torn_tail_is_found_and_cut_off() {
+        let data_dir = TempDir::new().unwrap();
+        let header_len =
+            HeaderLen::measure(&data_dir.path().join(WORK_DIR)).unwrap();
+        let (path, _) = series_with_lines(data_dir.path(), 10);
+        assert!(check(&path, &header_len).unwrap().is_empty());
+
+        append(&path, &[1, 2, 3]);
+        let problems = check(&path, &header_len).unwrap();
+        assert!(
+            problems
+                .iter()
+                .any(|p| matches!(p, Problem::TornTail { bytes: 3 })),
+            "problems: {problems:?}"
+        );
+
+        repair(data_dir.path(), &path, &problems).unwrap();
+        let problems = check(&path, &header_len).unwrap();
+        assert!(problems.is_empty(), "problems: {problems:?}");
+    }
+
+    #[test]
+    fn out_of_order_line_is_found_and_dropped() {
+        let data_dir = TempDir::new().unwrap();
+        let header_len =
+            HeaderLen::measure(&data_dir.path().join(WORK_DIR)).unwrap();
+        let (path, line_len) = series_with_lines(data_dir.path(), 10);
+
+        // repeat the last line, its timestamp is not after the one before
+        let data = fs::read(&path).unwrap();
+        append(&path, &data[data.len() - line_len..]);
+        let problems = check(&path, &header_len).unwrap();
+        assert!(
+            matches!(problems[..], [Problem::NotMonotonic(1)]),
+            "problems: {problems:?}"
+        );
+
+        assert!(repair(data_dir.path(), &path, &problems).unwrap());
+        let problems = check(&path, &header_len).unwrap();
+        assert!(problems.is_empty(), "problems: {problems:?}");
+        let backups = data_dir.path().join(".backups");
+        assert!(backups.exists(), "the original is kept");
+    }
+}
@@ -5,33 +5,39 @@ use indicatif::{MultiProgress, ProgressBar};
 use std::ffi::OsStr;
 use std::fs::{self, DirEntry};
 use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
 use tracing::warn;
 
 use crate::data::series::Meta;
+use crate::selection::Selection;
+
+mod merge;
+use merge::Merge;
+
+#[cfg(feature = "export")]
+mod parquet;
+#[cfg(feature = "export")]
+use parquet::ParquetInput;
+
+enum Input {
+    Csv(BufReader<fs::File>),
+    #[cfg(feature = "export")]
+    Parquet(ParquetInput),
+}
 
 pub fn perform(
     data_dir: &Path,
     only: Option<PathBuf>,
     allow_corrupt: bool,
+    selection: &Selection,
 ) -> Result<()> {
     let list = files_to_import(data_dir)?;
     if list.is_empty() {
         bail!("No files left to import")
     }
 
-    let to_handle: Vec<_> = list
-        .iter()
-        .filter(|p| {
-            only.as_ref().is_none_or(|allowed| {
-                p.ends_with(allowed) || p.with_extension("").ends_with(allowed)
-            })
-        })
-        .collect();
-
-    if to_handle.is_empty() {
-        return crate::no_filter_match_err(list, only);
-    }
+    let to_handle = crate::select_only(&list, only.as_deref())?;
 
     let bars = MultiProgress::new();
     let files_bar =
@@ -40,7 +46,7 @@ pub fn perform(
     files_bar.inc(0); // make the bar appear
 
     for path in &to_handle {
-        handle_file(&path, bars.clone(), allow_corrupt)
+        handle_file(data_dir, &path, bars.clone(), allow_corrupt, selection)
             .wrap_err("Failed to import data")
             .with_note(|| format!("Input file: {}", path.display()))?;
         files_bar.inc(1);
@@ -63,6 +69,12 @@ fn files_to_import(
     let mut res = Vec::new();
 
     crate::visit_dirs(data_dir, &mut |entry: &DirEntry| {
+        #[cfg(feature = "export")]
+        if entry.path().extension() == Some(OsStr::new("parquet")) {
+            res.push(entry.path());
+            return Ok(());
+        }
+
         if entry.path().extension() == Some(OsStr::new("csv")) {
             let mut file =
                 fs::File::open(entry.path()).wrap_err("Could not open file")?;
@@ -80,7 +92,7 @@ fn files_to_import(
         }
         Ok(())
     })
-    .wrap_err("Could not search for csv files")?;
+    .wrap_err("Could not search for csv or parquet files")?;
     Ok(res)
 }
 
@@ -135,35 +147,37 @@ fn number_of_lines(
 }
 
 fn handle_file(
+    data_dir: &Path,
     path: &Path,
     bars: MultiProgress,
     allow_corrupt: bool,
+    selection: &Selection,
 ) -> Result<()> {
-    let input_csv = fs::File::open(path).wrap_err("Could not open file")?;
-    let mut input_csv = BufReader::new(input_csv);
-    let (readings, header_len) = resolve_readings(&mut input_csv)?;
-    let lines = number_of_lines(&mut input_csv, header_len)?;
-
-    let specs = crate::data::series::to_speclist(&readings);
-    let fields = crate::data::series::bitspec::speclist_to_fields(specs);
-    let (meta_list, payload_size) =
-        crate::data::series::meta_list_and_payload_size(&readings, &fields);
-    for (Meta { reading, .. }, in_csv) in meta_list.iter().zip(&readings) {
-        assert_eq!(reading, in_csv);
-    }
-
-    let header = crate::data::series::Header {
-        readings: readings.to_vec(),
-        encoding: fields.clone(),
-    }
-    .serialized()?;
-
-    let (bs, _) = byteseries::ByteSeries::builder()
-        .payload_size(payload_size)
-        .with_header(header)
-        .create_new(true)
-        .open(path.with_extension("byteseries"))
-        .wrap_err("Could not open output series")?;
+    let (input, readings, lines) = open_input(path)?;
+    let selected: Vec<_> = (0..readings.len())
+        .filter(|i| selection.matches(&readings[*i]))
+        .collect();
+    let Some(device_info) =
+        selected.first().map(|i| readings[*i].device().info())
+    else {
+        bars.suspend(|| warn!("No selected readings in {}", path.display()));
+        return Ok(());
+    };
+    let readings: Vec<_> =
+        selected.iter().map(|i| readings[*i].clone()).collect();
+    let range = selection.range(device_info);
+
+    let series_path = path.with_extension("byteseries");
+    let output = if series_path.exists() && !selection.is_everything() {
+        Output::Merge(Merge::open(
+            data_dir,
+            &series_path,
+            &readings,
+            range.clone(),
+        )?)
+    } else {
+        Output::new(&readings, &series_path)?
+    };
 
     let copy_bar = ProgressBar::new(lines)
         .with_style(crate::bar_style())
@@ -177,9 +191,10 @@ fn handle_file(
     let mut last_correct_before_corrupt = 0;
     if allow_corrupt {
         copy_over_content(
-            input_csv,
-            bs,
-            meta_list,
+            input,
+            output,
+            &selected,
+            range,
             copy_bar.clone(),
             |_, prev| {
                 if prev != last_correct_before_corrupt {
@@ -193,9 +208,10 @@ fn handle_file(
         .wrap_err("Failed to copy over content")?;
     } else {
         copy_over_content(
-            input_csv,
-            bs,
-            meta_list,
+            input,
+            output,
+            &selected,
+            range,
             copy_bar.clone(),
             |_, _| false,
         )
@@ -216,33 +232,120 @@ fn handle_file(
     Ok(())
 }
 
+/// Where the imported lines end up
+enum Output {
+    /// A series that did not exist yet
+    New {
+        series: ByteSeries,
+        meta_list: Vec<Meta>,
+        line: Vec<u8>,
+    },
+    /// An existing series, see [`Merge`]
+    Merge(Merge),
+}
+
+impl Output {
+    fn new(readings: &[protocol::Reading], series_path: &Path) -> Result<Self> {
+        let header = crate::data::series::Header::for_readings(readings);
+        let fields = header.encoding.clone();
+        let (meta_list, payload_size) =
+            crate::data::series::meta_list_and_payload_size(readings, &fields);
+        for (Meta { reading, .. }, in_csv) in meta_list.iter().zip(readings) {
+            assert_eq!(reading, in_csv);
+        }
+
+        let header = header.serialized()?;
+
+        let (series, _) = byteseries::ByteSeries::builder()
+            .payload_size(payload_size)
+            .with_header(header)
+            .create_new(true)
+            .open(series_path)
+            .wrap_err("Could not open output series")
+            .suggestion(
+                "To import into an existing series select part of the \
+                data using --from, --to or --reading",
+            )?;
+        Ok(Self::New {
+            series,
+            meta_list,
+            line: vec![0; payload_size],
+        })
+    }
+
+    fn push_line(
+        &mut self,
+        ts: u64,
+        values: &[f32],
+    ) -> Result<(), byteseries::series::Error> {
+        match self {
+            Self::New {
+                series,
+                meta_list,
+                line,
+            } => {
+                line.fill(0);
+                for (value, Meta { field, .. }) in
+                    values.iter().zip(meta_list.iter())
+                {
+                    field
+                        .encode(*value, line)
+                        .expect("new series have a current header");
+                }
+                series.push_line(ts, line)
+            }
+            Self::Merge(merge) => merge.push_line(ts, values),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::New { .. } => Ok(()),
+            Self::Merge(merge) => merge.finish(),
+        }
+    }
+}
+
+/// Returns the input, the readings in it and (a guess of) the number of
+/// lines
+fn open_input(path: &Path) -> Result<(Input, Vec<protocol::Reading>, u64)> {
+    #[cfg(feature = "export")]
+    if path.extension() == Some(OsStr::new("parquet")) {
+        let (input, readings, rows) = ParquetInput::open(path)?;
+        return Ok((Input::Parquet(input), readings, rows));
+    }
+
+    let input_csv = fs::File::open(path).wrap_err("Could not open file")?;
+    let mut input_csv = BufReader::new(input_csv);
+    let (readings, header_len) = resolve_readings(&mut input_csv)?;
+    let lines = number_of_lines(&mut input_csv, header_len)?;
+    Ok((Input::Csv(input_csv), readings, lines))
+}
+
+/// Only the `selected` values of lines within `range` are passed on to the
+/// output
 fn copy_over_content(
-    input_csv: BufReader<fs::File>,
-    mut bs: ByteSeries,
-    meta_list: Vec<Meta>,
+    input: Input,
+    mut output: Output,
+    selected: &[usize],
+    range: RangeInclusive<u64>,
     copy_bar: ProgressBar,
     mut on_invalid_ts: impl FnMut(u64, u64) -> bool,
 ) -> color_eyre::Result<()> {
     use byteseries::series::Error as BsError;
 
-    let mut encoded_line = vec![0; bs.payload_size()];
-    for (i, line) in input_csv.lines().enumerate() {
-        let line = line.wrap_err("Could not read line from csv")?;
-        let (ts, line) =
-            line.split_once(',').ok_or_eyre("Empty line in csv")?;
-        let ts: u64 = ts
-            .parse()
-            .wrap_err("Could not parse timestamp as integer")
-            .with_note(|| format!("timestamp: {ts}"))?;
-
-        encoded_line.fill(0);
-        for (value, Meta { field, .. }) in line.split(',').zip(&meta_list) {
-            let value: f32 =
-                value.parse().wrap_err("Could not parse field as f32")?;
-            field.encode(value, &mut encoded_line);
+    let mut picked = Vec::with_capacity(selected.len());
+    let mut line_numb = 0;
+    let push_line = |ts: u64, values: &[f32]| -> Result<()> {
+        line_numb += 1;
+        copy_bar.inc(1);
+        if !range.contains(&ts) {
+            return Ok(());
         }
 
-        match bs.push_line(ts, &encoded_line) {
+        picked.clear();
+        picked.extend(selected.iter().map(|i| values[*i]));
+        match output.push_line(ts, &picked) {
             Ok(()) => (),
             Err(BsError::TimeNotAfterLast { new, prev })
                 if on_invalid_ts(new, prev) =>
@@ -251,11 +354,40 @@ fn copy_over_content(
             }
             Err(other) => Err(other)
                 .wrap_err("Could not push line to output")
-                .with_note(|| format!("line {} in file", i + 1))?,
+                .with_note(|| format!("line {line_numb} in file"))?,
         };
+        Ok(())
+    };
 
-        copy_bar.inc(1);
+    match input {
+        Input::Csv(input_csv) => for_each_csv_line(input_csv, push_line)?,
+        #[cfg(feature = "export")]
+        Input::Parquet(input) => input.for_each_line(push_line)?,
     }
+    output.finish()
+}
 
+fn for_each_csv_line(
+    input_csv: BufReader<fs::File>,
+    mut on_line: impl FnMut(u64, &[f32]) -> Result<()>,
+) -> Result<()> {
+    let mut values = Vec::new();
+    for line in input_csv.lines() {
+        let line = line.wrap_err("Could not read line from csv")?;
+        let (ts, line) =
+            line.split_once(',').ok_or_eyre("Empty line in csv")?;
+        let ts: u64 = ts
+            .parse()
+            .wrap_err("Could not parse timestamp as integer")
+            .with_note(|| format!("timestamp: {ts}"))?;
+
+        values.clear();
+        for value in line.split(',') {
+            let value: f32 =
+                value.parse().wrap_err("Could not parse field as f32")?;
+            values.push(value);
+        }
+        on_line(ts, &values)?;
+    }
     Ok(())
 }
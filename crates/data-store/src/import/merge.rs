@@ -0,0 +1,339 @@
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use byteseries::series::Error as BsError;
+use byteseries::ByteSeries;
+use color_eyre::eyre::{eyre, Context, OptionExt, Result};
+use color_eyre::Section;
+use protocol::{IsSameAs, Reading};
+use tracing::info;
+
+use crate::data::series::{self, bitspec, Header};
+use crate::export::decoder::ExportDecoder;
+use crate::rewrite::{self, Rewrite};
+
+/// Directory in the data dir where series are rewritten before they replace
+/// the originals.
+const WORK_DIR: &str = ".importing";
+
+/// Writes an existing series combined with imported lines to a new series.
+/// Within the selected range the imported readings replace what was stored
+/// for them, outside it the stored lines are copied unchanged. Once
+/// finished the new series replaces the existing one.
+pub(crate) struct Merge {
+    existing: Existing,
+    output: ByteSeries,
+    encoding: Vec<bitspec::Field<f32>>,
+    /// For every imported reading its index in the existing series
+    mapping: Vec<usize>,
+    range: RangeInclusive<u64>,
+    values: Vec<f32>,
+    line: Vec<u8>,
+    rewrite: Rewrite,
+}
+
+impl Merge {
+    pub(crate) fn open(
+        data_dir: &Path,
+        series_path: &Path,
+        imported: &[Reading],
+        range: RangeInclusive<u64>,
+    ) -> Result<Self> {
+        let metadata = crate::export::read_metadata(series_path)
+            .wrap_err("Could not extract metadata of existing series")?;
+        let header = Header::parse(&metadata)
+            .wrap_err("Could not parse metadata of existing series")?;
+        if header.version != series::HEADER_VERSION {
+            return Err(eyre!("Existing series has an outdated header"))
+                .with_note(|| format!("version: {}", header.version))
+                .suggestion("Run migrate on the existing series first");
+        }
+        let mapping = imported
+            .iter()
+            .map(|reading| {
+                header
+                    .readings
+                    .iter()
+                    .position(|r| r.is_same_as(reading))
+                    .ok_or_eyre(
+                        "Imported reading is not in the existing series",
+                    )
+                    .with_note(|| format!("reading: {reading:?}"))
+                    .suggestion(
+                        "If the protocol changed run migrate on the existing \
+                        series first",
+                    )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let (_, payload_size) = series::meta_list_and_payload_size(
+            &header.readings,
+            &header.encoding,
+        );
+        let (existing, _) = ByteSeries::builder()
+            .payload_size(payload_size)
+            .with_header(metadata.as_bytes().to_vec())
+            .open(series_path)
+            .wrap_err("Could not open existing series")?;
+
+        let rewrite =
+            Rewrite::start(data_dir, series_path, WORK_DIR, Some("import"))?;
+        let output = rewrite.create(
+            metadata.into_bytes(),
+            &header.encoding,
+            payload_size,
+        )?;
+
+        Ok(Self {
+            existing: Existing::new(existing, header.encoding.clone()),
+            output,
+            encoding: header.encoding,
+            mapping,
+            range,
+            values: Vec::new(),
+            line: vec![0; payload_size],
+            rewrite,
+        })
+    }
+
+    /// The line must be in the selected range. Stored lines before it are
+    /// written first.
+    pub(crate) fn push_line(
+        &mut self,
+        ts: u64,
+        imported: &[f32],
+    ) -> Result<(), BsError> {
+        while let Some(stored_ts) = self.existing.peek()? {
+            if stored_ts >= ts {
+                break;
+            }
+            self.existing.pop_into(&mut self.values);
+            self.write_stored(stored_ts)?;
+        }
+
+        if self.existing.peek()? == Some(ts) {
+            self.existing.pop_into(&mut self.values);
+        } else {
+            self.values.clear();
+            self.values.resize(self.encoding.len(), f32::NAN);
+        }
+        for (value, index) in imported.iter().zip(&self.mapping) {
+            self.values[*index] = *value;
+        }
+        self.write(ts)
+    }
+
+    pub(crate) fn finish(mut self) -> Result<()> {
+        while let Some(stored_ts) = self
+            .existing
+            .peek()
+            .wrap_err("Could not read existing series")?
+        {
+            self.existing.pop_into(&mut self.values);
+            self.write_stored(stored_ts)
+                .wrap_err("Could not write stored line")?;
+        }
+
+        drop(self.existing);
+        drop(self.output);
+        let relative = self.rewrite.relative().to_path_buf();
+        let backup_dir = self.rewrite.finish()?.expect("import keeps a backup");
+        info!(
+            "Merged import into {}, original moved to {}",
+            relative.display(),
+            backup_dir.display()
+        );
+        Ok(())
+    }
+
+    /// Within the range the stored values of the imported readings are
+    /// dropped, lines left without any value are skipped.
+    fn write_stored(&mut self, ts: u64) -> Result<(), BsError> {
+        if self.range.contains(&ts) {
+            for index in &self.mapping {
+                self.values[*index] = f32::NAN;
+            }
+            if self.values.iter().all(|v| v.is_nan()) {
+                return Ok(());
+            }
+        }
+        self.write(ts)
+    }
+
+    fn write(&mut self, ts: u64) -> Result<(), BsError> {
+        self.line.fill(0);
+        for (value, field) in self.values.iter().zip(&self.encoding) {
+            field
+                .encode(*value, &mut self.line)
+                .expect("existing series has a current header");
+        }
+        self.output.push_line(ts, &self.line)
+    }
+}
+
+/// Reads the lines of the existing series in chunks
+struct Existing {
+    series: ByteSeries,
+    decoder: ExportDecoder,
+    timestamps: Vec<u64>,
+    data: Vec<<ExportDecoder as byteseries::Decoder>::Item>,
+    next: usize,
+    read_start: u64,
+    done: bool,
+}
+
+impl Existing {
+    fn new(series: ByteSeries, encoding: Vec<bitspec::Field<f32>>) -> Self {
+        Self {
+            series,
+            decoder: ExportDecoder::from_fields(encoding),
+            timestamps: Vec::new(),
+            data: Vec::new(),
+            next: 0,
+            read_start: 0,
+            done: false,
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u64>, BsError> {
+        if self.next < self.timestamps.len() || self.done {
+            return Ok(self.timestamps.get(self.next).copied());
+        }
+
+        self.next = 0;
+        self.timestamps.clear();
+        self.data.clear();
+        match rewrite::read_chunk(
+            &mut self.series,
+            &mut self.decoder,
+            self.read_start,
+            &mut self.timestamps,
+            &mut self.data,
+        )? {
+            Some(next) => self.read_start = next,
+            None => self.done = true,
+        }
+        Ok(self.timestamps.first().copied())
+    }
+
+    /// Must only be called after peek returned a timestamp
+    fn pop_into(&mut self, values: &mut Vec<f32>) {
+        values.clear();
+        values.extend_from_slice(&self.data[self.next]);
+        self.next += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use protocol::large_bedroom::{self, bed};
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    fn temperature() -> Reading {
+        Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Temperature(0.0),
+        ))
+    }
+
+    /// Series storing every reading of the bed's temperature sensor. Every
+    /// second from 1 till 10 s it has temperature 20 and humidity 50.
+    /// Returns its path and header.
+    fn existing_series(data_dir: &Path) -> (PathBuf, Header) {
+        let header = Header::for_readings(
+            temperature().device().info().affects_readings,
+        );
+        let (_, payload_size) = series::meta_list_and_payload_size(
+            &header.readings,
+            &header.encoding,
+        );
+        let path = data_dir.join("temperature");
+        let (mut series, _) = ByteSeries::builder()
+            .payload_size(payload_size)
+            .with_header(header.serialized().unwrap())
+            .create_new(true)
+            .open(&path)
+            .unwrap();
+
+        let mut line = vec![0; payload_size];
+        for (field, reading) in header.encoding.iter().zip(&header.readings) {
+            let value = if reading.is_same_as(&temperature()) {
+                20.0
+            } else {
+                50.0
+            };
+            field.encode(value, &mut line).unwrap();
+        }
+        for ts in 1..=10 {
+            series.push_line(ts * 1000, &line).unwrap();
+        }
+        (path.with_extension("byteseries"), header)
+    }
+
+    fn read_back(path: &Path, header: &Header) -> Vec<(u64, Vec<f32>)> {
+        let (_, payload_size) = series::meta_list_and_payload_size(
+            &header.readings,
+            &header.encoding,
+        );
+        let (mut series, _) = ByteSeries::builder()
+            .payload_size(payload_size)
+            .with_header(header.serialized().unwrap())
+            .open(path)
+            .unwrap();
+        let mut decoder = ExportDecoder::from_fields(header.encoding.clone());
+        let mut lines = Vec::new();
+        rewrite::for_each_chunk(&mut series, &mut decoder, |ts, data| {
+            lines.extend(
+                ts.into_iter().zip(data.into_iter().map(|d| d.to_vec())),
+            );
+            Ok(())
+        })
+        .unwrap();
+        lines
+    }
+
+    #[test]
+    fn overlapping_import_replaces_only_selected_range() {
+        let data_dir = TempDir::new().unwrap();
+        let (path, header) = existing_series(data_dir.path());
+        let temp = header
+            .readings
+            .iter()
+            .position(|r| r.is_same_as(&temperature()))
+            .unwrap();
+        let hum = 1 - temp;
+
+        // imports only the temperature, the humidity must be kept
+        let mut merge =
+            Merge::open(data_dir.path(), &path, &[temperature()], 4000..=7000)
+                .unwrap();
+        // same timestamp as a stored line and one in between stored lines
+        merge.push_line(5000, &[30.0]).unwrap();
+        merge.push_line(5500, &[31.0]).unwrap();
+        merge.finish().unwrap();
+
+        let lines = read_back(&path, &header);
+        let timestamps: Vec<_> = lines.iter().map(|(ts, _)| *ts).collect();
+        let mut expected: Vec<_> = (1..=10).map(|s| s * 1000).collect();
+        expected.insert(5, 5500);
+        assert_eq!(timestamps, expected);
+
+        let close = |a: f32, b: f32| (a - b).abs() < 0.1;
+        for (ts, values) in &lines {
+            let (temp, hum) = (values[temp], values[hum]);
+            match ts {
+                5000 => assert!(close(temp, 30.0) && close(hum, 50.0)),
+                5500 => assert!(close(temp, 31.0) && hum.is_nan()),
+                // stored temperature in the range is replaced by the import
+                4000..=7000 => assert!(temp.is_nan() && close(hum, 50.0)),
+                _ => assert!(close(temp, 20.0) && close(hum, 50.0)),
+            }
+        }
+
+        let backups = data_dir.path().join(".backups");
+        assert!(backups.exists(), "the original is kept");
+    }
+}
@@ -0,0 +1,235 @@
+use std::fs;
+use std::path::Path;
+
+use arrow_array::{Array, Float32Array, TimestampNanosecondArray};
+use color_eyre::eyre::{bail, Context, OptionExt, Result};
+use color_eyre::Section;
+use parquet::arrow::arrow_reader::{
+    ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder,
+};
+
+use crate::data::series;
+use crate::export::parquet::{READING_KEY, TIME_COLUMN};
+
+/// Reads parquet files written by export
+pub(crate) struct ParquetInput {
+    reader: ParquetRecordBatchReader,
+    /// Converts the nanosecond timestamps to how they are stored in the
+    /// series
+    nanos_per_ts: i64,
+}
+
+impl ParquetInput {
+    /// Returns the input, the readings in the file and the number of rows
+    pub(crate) fn open(
+        path: &Path,
+    ) -> Result<(Self, Vec<protocol::Reading>, u64)> {
+        let file = fs::File::open(path).wrap_err("Could not open file")?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+            .wrap_err("Could not read parquet metadata")?;
+        let rows = builder.metadata().file_metadata().num_rows() as u64;
+
+        let schema = builder.schema();
+        let Some((time, columns)) = schema.fields().split_first() else {
+            bail!("Parquet file has no columns");
+        };
+        if time.name() != TIME_COLUMN {
+            bail!(
+                "First column should be the timestamp column '{TIME_COLUMN}', \
+                it is: '{}'",
+                time.name()
+            )
+        }
+        let readings = columns
+            .iter()
+            .map(|field| {
+                let encoded = field
+                    .metadata()
+                    .get(READING_KEY)
+                    .ok_or_eyre("Column is missing the reading in its metadata")
+                    .with_note(|| format!("column: {}", field.name()))?;
+                ron::from_str::<protocol::Reading>(encoded)
+                    .wrap_err("Could not decode Reading from string")
+                    .with_note(|| format!("string was: {encoded}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let device_info = readings
+            .first()
+            .ok_or_eyre("Parquet file has no reading columns")?
+            .device()
+            .info();
+        let nanos_per_ts = series::millis_to_minimal_representation(device_info)
+            as i64
+            * 1_000_000;
+
+        let reader = builder
+            .build()
+            .wrap_err("Could not create parquet reader")?;
+        Ok((
+            Self {
+                reader,
+                nanos_per_ts,
+            },
+            readings,
+            rows,
+        ))
+    }
+
+    /// Calls `on_line` with the timestamp as stored in a series and the
+    /// values of each row. Missing values are NaN.
+    pub(crate) fn for_each_line(
+        self,
+        mut on_line: impl FnMut(u64, &[f32]) -> Result<()>,
+    ) -> Result<()> {
+        let mut line = Vec::new();
+        for batch in self.reader {
+            let batch = batch.wrap_err("Could not read record batch")?;
+            let time = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<TimestampNanosecondArray>()
+                .ok_or_eyre("Timestamp column has the wrong type")?;
+            let columns = batch.columns()[1..]
+                .iter()
+                .map(|column| {
+                    column
+                        .as_any()
+                        .downcast_ref::<Float32Array>()
+                        .ok_or_eyre("Reading column is not float32")
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            for row in 0..batch.num_rows() {
+                line.clear();
+                line.extend(columns.iter().map(|column| {
+                    if column.is_null(row) {
+                        f32::NAN
+                    } else {
+                        column.value(row)
+                    }
+                }));
+                let ts = u64::try_from(time.value(row) / self.nanos_per_ts)
+                    .wrap_err("Timestamp is before the unix epoch")?;
+                on_line(ts, &line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use byteseries::ByteSeries;
+    use indicatif::MultiProgress;
+    use protocol::large_bedroom::{self, bed};
+    use protocol::Reading;
+    use temp_dir::TempDir;
+
+    use super::*;
+    use crate::data::series::Header;
+    use crate::export::decoder::ExportDecoder;
+    use crate::export::{self, Format};
+    use crate::rewrite;
+    use crate::selection::Selection;
+
+    fn lines_in(path: &Path) -> (Vec<Reading>, Vec<(u64, Vec<f32>)>) {
+        let metadata = export::read_metadata(path).unwrap();
+        let header = Header::parse(&metadata).unwrap();
+        let (_, payload_size) = series::meta_list_and_payload_size(
+            &header.readings,
+            &header.encoding,
+        );
+        let (mut series, _) = ByteSeries::builder()
+            .payload_size(payload_size)
+            .with_header(metadata.into_bytes())
+            .open(path)
+            .unwrap();
+        let mut decoder = ExportDecoder::from_fields(header.encoding);
+        let mut lines = Vec::new();
+        rewrite::for_each_chunk(&mut series, &mut decoder, |ts, data| {
+            lines.extend(
+                ts.into_iter().zip(data.into_iter().map(|d| d.to_vec())),
+            );
+            Ok(())
+        })
+        .unwrap();
+        (header.readings, lines)
+    }
+
+    #[test]
+    fn export_then_import_gives_same_series() {
+        let exported_dir = TempDir::new().unwrap();
+        let imported_dir = TempDir::new().unwrap();
+
+        let temperature = Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Temperature(0.0),
+        ));
+        let header =
+            Header::for_readings(temperature.device().info().affects_readings);
+        let (_, payload_size) = series::meta_list_and_payload_size(
+            &header.readings,
+            &header.encoding,
+        );
+        let path = exported_dir.path().join("sht31");
+        let (mut original, _) = ByteSeries::builder()
+            .payload_size(payload_size)
+            .with_header(header.serialized().unwrap())
+            .create_new(true)
+            .open(&path)
+            .unwrap();
+        let mut line = vec![0; payload_size];
+        for ts in 1..=10u64 {
+            line.fill(0);
+            for (i, field) in header.encoding.iter().enumerate() {
+                // leave one value missing
+                let value = if ts == 5 && i == 0 {
+                    f32::NAN
+                } else {
+                    ts as f32 + i as f32 * 10.0
+                };
+                field.encode(value, &mut line).unwrap();
+            }
+            original.push_line(ts * 1000, &line).unwrap();
+        }
+        original.flush_to_disk().unwrap();
+        drop(original);
+        let path = path.with_extension("byteseries");
+
+        export::handle_file(
+            &path,
+            MultiProgress::new(),
+            Format::Parquet,
+            &Selection::default(),
+        )
+        .unwrap();
+        let parquet = imported_dir.path().join("sht31.parquet");
+        fs::copy(path.with_extension("parquet"), &parquet).unwrap();
+        crate::import::handle_file(
+            imported_dir.path(),
+            &parquet,
+            MultiProgress::new(),
+            false,
+            &Selection::default(),
+        )
+        .unwrap();
+
+        let (readings, lines) = lines_in(&path);
+        let (imported_readings, imported_lines) =
+            lines_in(&parquet.with_extension("byteseries"));
+        assert_eq!(imported_readings, readings);
+        assert_eq!(imported_lines.len(), 10);
+        for ((ts, values), (imported_ts, imported)) in
+            lines.iter().zip(&imported_lines)
+        {
+            assert_eq!(ts, imported_ts);
+            for (value, imported) in values.iter().zip(imported) {
+                assert!(
+                    (value.is_nan() && imported.is_nan())
+                        || (value - imported).abs() < 0.01,
+                    "at {ts}: {values:?} became {imported:?}"
+                );
+            }
+        }
+    }
+}
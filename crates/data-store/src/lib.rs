@@ -11,14 +11,25 @@ use itertools::Itertools;
 #[cfg(feature = "api")]
 pub mod api;
 #[cfg(feature = "server")]
+pub mod compact;
+#[cfg(feature = "server")]
 pub mod data;
 #[cfg(feature = "server")]
 pub mod export;
 #[cfg(feature = "server")]
+pub mod fsck;
+#[cfg(feature = "server")]
 pub mod import;
 #[cfg(feature = "server")]
+pub mod migrate;
+#[cfg(feature = "server")]
+pub(crate) mod rewrite;
+#[cfg(feature = "server")]
+pub mod selection;
+#[cfg(feature = "server")]
 pub mod server;
 
+/// Skips hidden directories, these hold backups and work in progress
 pub(crate) fn visit_dirs(
     dir: &Path,
     mut cb: &mut dyn FnMut(&DirEntry) -> color_eyre::Result<()>,
@@ -28,7 +39,11 @@ pub(crate) fn visit_dirs(
             let entry = entry.wrap_err("Error walking dir")?;
             let path = entry.path();
             if path.is_dir() {
-                visit_dirs(&path, &mut cb)?;
+                let hidden =
+                    entry.file_name().to_string_lossy().starts_with('.');
+                if !hidden {
+                    visit_dirs(&path, &mut cb)?;
+                }
             } else {
                 cb(&entry)
                     .wrap_err("Could not check file header")
@@ -41,25 +56,36 @@ pub(crate) fn visit_dirs(
     Ok(())
 }
 
-pub(crate) fn no_filter_match_err(
-    list: Vec<PathBuf>,
-    only: Option<PathBuf>,
-) -> color_eyre::Result<()> {
-    return Err(color_eyre::Report::msg(
-        "None of the paths ended with required argument",
-    ))
-    .with_note(|| {
-        format!("filter argument (--only) {}", only.unwrap().display())
-    })
-    .with_note(|| {
-        format!(
-            "examples of files: \n\t- {}",
-            list.iter()
-                .map(|p| p.display().to_string())
-                .take(5)
-                .join("\n\t- ")
-        )
-    });
+/// The files in `list` that end with the filter argument (`--only`), with
+/// or without their extension. Without filter that is every file.
+pub(crate) fn select_only<'a>(
+    list: &'a [PathBuf],
+    only: Option<&Path>,
+) -> color_eyre::Result<Vec<&'a PathBuf>> {
+    let Some(only) = only else {
+        return Ok(list.iter().collect());
+    };
+    let selected: Vec<_> = list
+        .iter()
+        .filter(|p| p.ends_with(only) || p.with_extension("").ends_with(only))
+        .collect();
+
+    if selected.is_empty() {
+        return Err(color_eyre::Report::msg(
+            "None of the paths ended with required argument",
+        ))
+        .with_note(|| format!("filter argument (--only) {}", only.display()))
+        .with_note(|| {
+            format!(
+                "examples of files: \n\t- {}",
+                list.iter()
+                    .map(|p| p.display().to_string())
+                    .take(5)
+                    .join("\n\t- ")
+            )
+        });
+    }
+    Ok(selected)
 }
 
 fn bar_style() -> ProgressStyle {
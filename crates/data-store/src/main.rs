@@ -27,13 +27,33 @@ enum Command {
 
         #[arg(short, long)]
         client_port: u16,
+
+        /// RON file with the tokens clients need, see `rpc::Access`.
+        /// Without it anyone may connect.
+        #[arg(long)]
+        access: Option<PathBuf>,
+
+        /// dir clients may write snapshots to using backup. Without it
+        /// snapshot requests are refused.
+        #[arg(long)]
+        backup_root: Option<PathBuf>,
     },
     Export {
         /// export only one dataset at this path
         /// `largebedroom/bed/nau7802right`
         #[arg(short, long)]
         only: Option<PathBuf>,
+        /// file format to export to
+        #[arg(short, long, value_enum, default_value_t)]
+        format: data_store::export::Format,
+        #[command(flatten)]
+        selection: data_store::selection::Selection,
     },
+    /// Import csv or parquet files made by export, the format is picked
+    /// based on the extension. When part of the data is selected an
+    /// existing series is kept and only the selected part is replaced, the
+    /// original is kept in `.backups` in the data dir. Stop the data-store
+    /// before doing that.
     Import {
         /// import a dataset at this path for example:
         /// `largebedroom/bed/nau7802right`
@@ -42,6 +62,65 @@ enum Command {
         /// skip data that is out of order, this is useful as it fixes an
         /// issue caused by a bug in Byteseries.
         skip_corrupt: bool,
+        #[command(flatten)]
+        selection: data_store::selection::Selection,
+    },
+    /// Rewrite old data to lower resolutions as set by the retention
+    /// policy. Stop the data-store before running this.
+    Compact {
+        /// RON file with the retention policy, see
+        /// `data_store::compact::Retention`
+        #[arg(short, long)]
+        retention: PathBuf,
+        /// compact only one dataset at this path for example:
+        /// `largebedroom/bed/nau7802right`
+        #[arg(short, long)]
+        only: Option<PathBuf>,
+    },
+    /// Rewrite series whose readings changed in the protocol, the originals
+    /// are kept in `.backups` in the data dir. Also upgrades series with an
+    /// older header so they can store missing values. Stop the data-store
+    /// before running this, it refuses to open series whose readings
+    /// changed.
+    Migrate {
+        /// only report which series would change
+        #[arg(long)]
+        dry_run: bool,
+        /// migrate only one dataset at this path for example:
+        /// `largebedroom/bed/nau7802right`
+        #[arg(short, long)]
+        only: Option<PathBuf>,
+    },
+    /// Check every series for torn tails, timestamps that go back, values
+    /// that do not fit the encoding and downsampled caches that do not
+    /// match the data. Stop the data-store before running this with
+    /// `--fix`.
+    Fsck {
+        /// cut off torn tails and rewrite damaged series with new caches,
+        /// the originals are kept in `.backups` in the data dir
+        #[arg(long)]
+        fix: bool,
+        /// check only one dataset at this path for example:
+        /// `largebedroom/bed/nau7802right`
+        #[arg(short, long)]
+        only: Option<PathBuf>,
+    },
+    /// Have a running data-store copy its data dir to a snapshot. Set the
+    /// token in the `HA_RPC_TOKEN` environment variable if access is
+    /// restricted, it needs the `Actuate` role.
+    Backup {
+        /// data-store to back up
+        #[arg(short, long)]
+        server: SocketAddr,
+        /// empty or new dir in the backup root of the data-store, relative
+        /// to that root
+        #[arg(long)]
+        to: PathBuf,
+    },
+    /// Check a snapshot made by backup against its manifest
+    Verify {
+        /// dir containing the snapshot
+        snapshot: PathBuf,
     },
 }
 
@@ -56,15 +135,69 @@ async fn main() -> Result<()> {
         Command::Run {
             data_server,
             client_port,
+            access,
+            backup_root,
         } => {
-            data_store::server::run(data_server, client_port, &cli.data_dir)
-                .await
+            let access = match access {
+                Some(path) => rpc::Access::load(&path)?,
+                None => rpc::Access::default(),
+            };
+            data_store::server::run(
+                data_server,
+                client_port,
+                &cli.data_dir,
+                access,
+                backup_root.as_deref(),
+            )
+            .await
+        }
+        Command::Export {
+            only,
+            format,
+            selection,
+        } => {
+            data_store::export::perform(&cli.data_dir, only, format, &selection)
+        }
+        Command::Import {
+            only,
+            skip_corrupt,
+            selection,
+        } => data_store::import::perform(
+            &cli.data_dir,
+            only,
+            skip_corrupt,
+            &selection,
+        ),
+        Command::Compact { retention, only } => {
+            let retention = data_store::compact::Retention::load(&retention)?;
+            data_store::compact::perform(&cli.data_dir, &retention, only)
+        }
+        Command::Migrate { dry_run, only } => {
+            data_store::migrate::perform(&cli.data_dir, only, dry_run)
+        }
+        Command::Fsck { fix, only } => {
+            data_store::fsck::perform(&cli.data_dir, only, fix)
         }
-        Command::Export { only } => {
-            data_store::export::perform(&cli.data_dir, only)
+        Command::Backup { server, to } => {
+            let mut client =
+                data_store::api::Client::connect(server, "backup".to_string())
+                    .await?;
+            let summary = client.snapshot(to.clone()).await?;
+            tracing::info!(
+                "Done, copied {} files ({} bytes) to {}",
+                summary.files,
+                summary.bytes,
+                to.display()
+            );
+            Ok(())
         }
-        Command::Import { only, skip_corrupt } => {
-            data_store::import::perform(&cli.data_dir, only, skip_corrupt)
+        Command::Verify { snapshot } => {
+            let report = snapshot::verify(&snapshot)?;
+            println!("{report}");
+            if !report.is_intact() {
+                color_eyre::eyre::bail!("Snapshot is damaged")
+            }
+            Ok(())
         }
     }
 }
@@ -0,0 +1,351 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use byteseries::ByteSeries;
+use color_eyre::eyre::{bail, eyre, Context, OptionExt, Result};
+use color_eyre::Section;
+use itertools::Itertools;
+use protocol::{IsSameAs, Reading};
+use tracing::{info, warn};
+
+use crate::data::series::{self, bitspec, Header};
+use crate::export::decoder::ExportDecoder;
+use crate::rewrite::{self, relative, Rewrite};
+
+/// Directory in the data dir where series are rewritten before they replace
+/// the originals.
+const WORK_DIR: &str = ".migrating";
+
+/// How the header of a series differs from the one the current protocol
+/// needs.
+#[derive(Debug)]
+pub struct Change {
+    /// Relative to the data dir without extension
+    pub series: PathBuf,
+    pub added: Vec<Reading>,
+    pub removed: Vec<Reading>,
+    /// The range or resolution of one or more readings changed
+    pub encoding_changed: bool,
+}
+
+impl Change {
+    fn new(series: PathBuf, old: &Header, new: &Header) -> Self {
+        let missing_from = |list: &[Reading], reading: &Reading| {
+            !list.iter().any(|r| r.is_same_as(reading))
+        };
+        Self {
+            series,
+            added: new
+                .readings
+                .iter()
+                .filter(|r| missing_from(&old.readings, r))
+                .cloned()
+                .collect(),
+            removed: old
+                .readings
+                .iter()
+                .filter(|r| missing_from(&new.readings, r))
+                .cloned()
+                .collect(),
+            encoding_changed: old.encoding != new.encoding,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.series.display())?;
+        if !self.added.is_empty() {
+            write!(
+                f,
+                "\n\tadded: {}",
+                self.added.iter().map(|r| format!("{r:?}")).join(", ")
+            )?;
+        }
+        if !self.removed.is_empty() {
+            write!(
+                f,
+                "\n\tremoved: {}",
+                self.removed.iter().map(|r| format!("{r:?}")).join(", ")
+            )?;
+        }
+        if self.encoding_changed {
+            write!(f, "\n\tencoding changed")?;
+        }
+        Ok(())
+    }
+}
+
+/// Migrates every series whose header does not match what the current
+/// protocol needs. With `dry_run` only reports what would change. The
+/// data-store must not be running while this happens.
+pub fn perform(
+    data_dir: &Path,
+    only: Option<PathBuf>,
+    dry_run: bool,
+) -> Result<()> {
+    let list = crate::export::files_to_export(data_dir)?;
+    if list.is_empty() {
+        bail!("No files to migrate")
+    }
+
+    let to_handle = crate::select_only(&list, only.as_deref())?;
+
+    let mut changed = 0;
+    for path in &to_handle {
+        let (stored, metadata) = read_header(path)?;
+        let device = stored
+            .readings
+            .first()
+            .ok_or_eyre("Series header lists no readings")
+            .with_note(|| format!("series: {}", path.display()))?
+            .device();
+        let expected = Header::for_readings(device.info().affects_readings);
+        if expected.serialized()?.as_slice() == metadata.as_bytes() {
+            continue;
+        }
+
+        changed += 1;
+        let series = relative(data_dir, path);
+        if dry_run {
+            println!("{}", Change::new(series, &stored, &expected));
+        } else {
+            migrate(data_dir, path, &stored, &metadata, &expected)
+                .wrap_err("Failed to migrate series")
+                .with_note(|| format!("series: {}", path.display()))?;
+        }
+    }
+
+    if dry_run {
+        println!("{changed} of {} series would be migrated", to_handle.len());
+    } else {
+        info!("Done, migrated {changed} of {} series", to_handle.len());
+    }
+    Ok(())
+}
+
+/// Called before opening a series, returns its header and metadata if it
+/// exists. Series created with an older header version keep it, they are
+/// read and appended to as they are. Errors if the readings or their
+/// encoding changed, only [`perform`] migrates those.
+pub(crate) fn existing_header(
+    data_dir: &Path,
+    series: &Path,
+    expected: &Header,
+) -> Result<Option<(Header, String)>> {
+    let path = series.with_extension("byteseries");
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let (stored, metadata) = read_header(&path)?;
+    let same_version =
+        Header::for_readings_at(&expected.readings, stored.version);
+    if stored.version <= series::HEADER_VERSION && stored == same_version {
+        return Ok(Some((stored, metadata)));
+    }
+
+    let change = Change::new(relative(data_dir, &path), &stored, &same_version);
+    Err(eyre!("Series header differs from what the protocol needs"))
+        .with_note(|| change.to_string())
+        .suggestion(
+            "Stop the data-store and run `data-store migrate` to bring the \
+            series up to date",
+        )
+}
+
+pub(crate) fn read_header(path: &Path) -> Result<(Header, String)> {
+    let metadata = crate::export::read_metadata(path)
+        .wrap_err("Could not extract metadata")
+        .with_note(|| format!("series: {}", path.display()))?;
+    let header = Header::parse(&metadata)
+        .with_note(|| format!("series: {}", path.display()))?;
+    Ok((header, metadata))
+}
+
+fn migrate(
+    data_dir: &Path,
+    path: &Path,
+    old: &Header,
+    old_metadata: &str,
+    new: &Header,
+) -> Result<()> {
+    let (_, old_payload_size) =
+        series::meta_list_and_payload_size(&old.readings, &old.encoding);
+    let (mut input, _) = ByteSeries::builder()
+        .payload_size(old_payload_size)
+        .with_header(old_metadata.as_bytes().to_vec())
+        .open(path)
+        .wrap_err("Could not open byteseries")?;
+
+    let rewrite = Rewrite::start(data_dir, path, WORK_DIR, Some("migrate"))?;
+    let (_, new_payload_size) =
+        series::meta_list_and_payload_size(&new.readings, &new.encoding);
+    let mut output =
+        rewrite.create(new.serialized()?, &new.encoding, new_payload_size)?;
+
+    let mapping = mapping(old, new);
+    let mut decoder = ExportDecoder::from_fields(old.encoding.clone());
+    let mut line = vec![0; new_payload_size];
+    let mut out_of_range = 0;
+    rewrite::for_each_chunk(&mut input, &mut decoder, |timestamps, data| {
+        for (ts, values) in timestamps.into_iter().zip(data) {
+            out_of_range += remap(&values, &mapping, new, &mut line)
+                .wrap_err("Could not encode migrated line")?;
+            output
+                .push_line(ts, &line)
+                .wrap_err("Could not write migrated line")?;
+        }
+        Ok(())
+    })
+    .wrap_err("Could not copy series to migrate")?;
+
+    if out_of_range > 0 {
+        warn!(
+            "{out_of_range} values did not fit the new range of their \
+            reading, they are now missing (NaN)"
+        );
+    }
+
+    drop(input);
+    drop(output);
+    let relative = rewrite.relative().to_path_buf();
+    let backup_dir = rewrite.finish()?.expect("migrate keeps a backup");
+    info!(
+        "Migrated {}, original moved to {}",
+        relative.display(),
+        backup_dir.display()
+    );
+    Ok(())
+}
+
+/// For every reading in the new header where it is in the old one
+fn mapping(old: &Header, new: &Header) -> Vec<Option<usize>> {
+    new.readings
+        .iter()
+        .map(|reading| old.readings.iter().position(|r| r.is_same_as(reading)))
+        .collect()
+}
+
+/// Encodes the values of an old line into `line` using the new header.
+/// New readings and values outside their new range become NaN. Returns how
+/// many values were out of range.
+fn remap(
+    old_values: &[f32],
+    mapping: &[Option<usize>],
+    new: &Header,
+    line: &mut [u8],
+) -> Result<usize, bitspec::MissingNotSupported> {
+    line.fill(0);
+    let mut out_of_range = 0;
+    for ((field, reading), old) in
+        new.encoding.iter().zip(&new.readings).zip(mapping)
+    {
+        let value = old.map_or(f32::NAN, |i| old_values[i]);
+        let value = if value.is_nan() || reading.range().contains(&value) {
+            value
+        } else {
+            out_of_range += 1;
+            f32::NAN
+        };
+        field.encode(value, line)?;
+    }
+    Ok(out_of_range)
+}
+
+#[cfg(test)]
+mod test {
+    use protocol::large_bedroom::{self, bed};
+
+    use super::*;
+
+    #[test]
+    fn new_readings_are_missing() {
+        let temperature = Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Temperature(0.0),
+        ));
+        let humidity = Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Humidity(0.0),
+        ));
+        let old = Header::for_readings(&[temperature.clone()]);
+        let new = Header::for_readings(&[humidity, temperature]);
+        let (_, payload_size) =
+            series::meta_list_and_payload_size(&new.readings, &new.encoding);
+
+        let mut line = vec![0; payload_size];
+        let out_of_range =
+            remap(&[21.5], &mapping(&old, &new), &new, &mut line).unwrap();
+
+        assert_eq!(out_of_range, 0);
+        let humidity: f32 = new.encoding[0].decode(&line);
+        let temperature: f32 = new.encoding[1].decode(&line);
+        assert!(humidity.is_nan());
+        assert!((temperature - 21.5).abs() < 0.1);
+    }
+    #[test]
+    fn headers_without_version_have_no_missing_values() {
+        let temperature = Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Temperature(0.0),
+        ));
+        let current = Header::for_readings(&[temperature]);
+        let metadata = ron::to_string(&current).unwrap();
+        let old = metadata.replace("version:1,", "");
+        assert_ne!(old, metadata);
+
+        let old = Header::parse(&old).unwrap();
+        assert_eq!(old.version, 0);
+        assert!(old.encoding.iter().all(|f| !f.stores_missing));
+        assert_eq!(Header::parse(&metadata).unwrap(), current);
+    }
+
+    /// Creates a series at `path` with `header` as metadata
+    fn create_series(path: &Path, header: &Header, metadata: String) {
+        let (_, payload_size) = series::meta_list_and_payload_size(
+            &header.readings,
+            &header.encoding,
+        );
+        ByteSeries::builder()
+            .payload_size(payload_size)
+            .with_header(metadata.into_bytes())
+            .create_new(true)
+            .open(path)
+            .unwrap();
+    }
+
+    #[test]
+    fn series_without_header_version_open_as_they_are() {
+        let data_dir = temp_dir::TempDir::new().unwrap();
+        let temperature = Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Temperature(0.0),
+        ));
+        let readings = temperature.device().info().affects_readings;
+        let old = Header::for_readings_at(readings, 0);
+        let metadata = ron::to_string(&old).unwrap().replace("version:0,", "");
+        let path = data_dir.path().join("sht31");
+        create_series(&path, &old, metadata.clone());
+
+        let expected = Header::for_readings(readings);
+        assert_ne!(old.encoding, expected.encoding);
+        let (stored, stored_metadata) =
+            existing_header(data_dir.path(), &path, &expected)
+                .unwrap()
+                .expect("series exists");
+        assert_eq!(stored, old);
+        assert_eq!(stored_metadata, metadata);
+    }
+
+    #[test]
+    fn series_with_other_readings_are_refused() {
+        let data_dir = temp_dir::TempDir::new().unwrap();
+        let temperature = Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Temperature(0.0),
+        ));
+        let old = Header::for_readings(&[temperature.clone()]);
+        let path = data_dir.path().join("sht31");
+        create_series(&path, &old, ron::to_string(&old).unwrap());
+
+        let readings = temperature.device().info().affects_readings;
+        let expected = Header::for_readings(readings);
+        assert!(existing_header(data_dir.path(), &path, &expected).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::path::{Path, PathBuf};
+
+use byteseries::series::Error as BsError;
+use byteseries::ByteSeries;
+use color_eyre::eyre::{Context, Result};
+use color_eyre::Section;
+use snapshot::Replacement;
+
+use crate::data::series::{self, bitspec};
+
+const CHUNK: usize = 100_000;
+
+pub(crate) use snapshot::relative;
+
+/// A new version of a series. It is written to a work dir in the data dir
+/// and replaces the original once finished.
+pub(crate) struct Rewrite(Replacement);
+
+impl Rewrite {
+    /// The new version is written in `work_dir`, a directory in the data
+    /// dir. With a `backup` name the original is moved to
+    /// `.backups/<backup>-<unix seconds>` in the data dir when finished.
+    pub(crate) fn start(
+        data_dir: &Path,
+        series_path: &Path,
+        work_dir: &str,
+        backup: Option<&str>,
+    ) -> Result<Self> {
+        Replacement::start(data_dir, series_path, work_dir, backup).map(Self)
+    }
+
+    /// Relative to the data dir without extension
+    pub(crate) fn relative(&self) -> &Path {
+        &self.0.relative
+    }
+
+    /// Creates the new, still empty, series including its caches
+    pub(crate) fn create(
+        &self,
+        header: Vec<u8>,
+        encoding: &[bitspec::Field<f32>],
+        payload_size: usize,
+    ) -> Result<ByteSeries> {
+        let (resampler, configs) =
+            series::resample_setup(encoding, payload_size);
+        let work_path = self.0.work_path();
+        let (output, _) = ByteSeries::builder()
+            .payload_size(payload_size)
+            .with_downsampled_cache(resampler, configs)
+            .with_header(header)
+            .create_new(true)
+            .open(work_path)
+            .wrap_err("Could not create new byteseries")
+            .with_note(|| format!("path: {}", work_path.display()))?;
+        Ok(output)
+    }
+
+    /// Replaces the original with the new series. The new series and the
+    /// series it was made from must be closed. Returns the dir the original
+    /// was moved to.
+    pub(crate) fn finish(self) -> Result<Option<PathBuf>> {
+        self.0.finish()
+    }
+}
+
+/// Reads the chunk of lines starting at `start`. Returns where the next
+/// chunk starts or `None` if there are no more lines.
+pub(crate) fn read_chunk<D: byteseries::Decoder>(
+    series: &mut ByteSeries,
+    decoder: &mut D,
+    start: u64,
+    timestamps: &mut Vec<u64>,
+    data: &mut Vec<D::Item>,
+) -> Result<Option<u64>, BsError> {
+    use byteseries::seek::Error::{EmptyFile, StartAfterData};
+    use byteseries::series::Error::InvalidRange;
+
+    match series.read_first_n(CHUNK, decoder, start.., timestamps, data) {
+        Ok(()) => Ok(timestamps.last().map(|last| last + 1)),
+        Err(InvalidRange(StartAfterData { .. } | EmptyFile)) => Ok(None),
+        Err(other) => Err(other),
+    }
+}
+
+/// Calls `on_chunk` for every chunk of lines, oldest first
+pub(crate) fn for_each_chunk<D: byteseries::Decoder>(
+    series: &mut ByteSeries,
+    decoder: &mut D,
+    mut on_chunk: impl FnMut(Vec<u64>, Vec<D::Item>) -> Result<()>,
+) -> Result<()> {
+    let mut start = 0;
+    loop {
+        let mut timestamps = Vec::new();
+        let mut data = Vec::new();
+        let next =
+            read_chunk(series, decoder, start, &mut timestamps, &mut data)
+                .wrap_err("Could not read series")?;
+        if !timestamps.is_empty() {
+            on_chunk(timestamps, data)?;
+        }
+        match next {
+            Some(next) => start = next,
+            None => return Ok(()),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::ops::RangeInclusive;
+
+use itertools::Itertools;
+use protocol::reading::tree::{Item, Tree};
+use protocol::Reading;
+
+use crate::data::series;
+
+/// Limits export and import to a time range and/or some of the readings in
+/// a series.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct Selection {
+    /// only data at or after this time, for example: `2025-01-20T12:00Z`
+    #[arg(long)]
+    pub from: Option<jiff::Timestamp>,
+    /// only data at or before this time, for example: `2025-01-27T12:00Z`
+    #[arg(long)]
+    pub to: Option<jiff::Timestamp>,
+    /// only readings whose path ends with this, for example: `co2` or
+    /// `smallbedroom/bed/co2`. Case insensitive, can be given multiple
+    /// times.
+    #[arg(long = "reading")]
+    pub readings: Vec<String>,
+}
+
+impl Selection {
+    /// Nothing is filtered out
+    pub(crate) fn is_everything(&self) -> bool {
+        self.from.is_none() && self.to.is_none() && self.readings.is_empty()
+    }
+
+    pub(crate) fn matches(&self, reading: &Reading) -> bool {
+        if self.readings.is_empty() {
+            return true;
+        }
+
+        let path = reading_path(reading);
+        self.readings.iter().any(|selector| {
+            let selector = selector.trim_matches('/').to_lowercase();
+            path == selector || path.ends_with(&format!("/{selector}"))
+        })
+    }
+
+    /// The selected time range in the timestamps as stored in a series of
+    /// this device
+    pub(crate) fn range(
+        &self,
+        device_info: protocol::DeviceInfo,
+    ) -> RangeInclusive<u64> {
+        let scale_factor =
+            series::millis_to_minimal_representation(device_info);
+        let scale = |time: jiff::Timestamp| {
+            u64::try_from(time.as_millisecond()).unwrap_or(0) / scale_factor
+        };
+        let start = self.from.map_or(0, scale);
+        let end = self.to.map_or(u64::MAX, scale);
+        start..=end
+    }
+}
+
+/// The names of the nodes from the root of the reading tree to the leaf
+/// separated by '/' in lowercase, for example: `smallbedroom/bed/co2`
+pub(crate) fn reading_path(reading: &Reading) -> String {
+    let mut names = vec![reading.name()];
+    let mut current = reading as &dyn Tree;
+    while let Item::Node(inner) = current.inner() {
+        names.push(inner.name());
+        current = inner;
+    }
+    names.iter().map(|name| name.to_lowercase()).join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use protocol::small_bedroom::{self, bed};
+
+    use super::*;
+
+    fn co2() -> Reading {
+        Reading::SmallBedroom(small_bedroom::Reading::Bed(bed::Reading::Co2(0)))
+    }
+
+    #[test]
+    fn path_of_reading() {
+        assert_eq!(reading_path(&co2()), "smallbedroom/bed/co2");
+    }
+
+    #[test]
+    fn selectors_match_end_of_path() {
+        let selection = |selector: &str| Selection {
+            readings: vec![selector.to_string()],
+            ..Selection::default()
+        };
+        assert!(selection("co2").matches(&co2()));
+        assert!(selection("Bed/Co2").matches(&co2()));
+        assert!(selection("smallbedroom/bed/co2").matches(&co2()));
+        assert!(!selection("o2").matches(&co2()));
+        assert!(!selection("largebedroom/bed/co2").matches(&co2()));
+    }
+}
@@ -4,22 +4,33 @@ use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 
 mod clients;
 mod db;
+mod follow;
 
 // used from main and tests
 pub async fn run(
     data_server: SocketAddr,
     client_port: u16,
     data_dir: &Path,
+    access: rpc::Access,
+    backup_root: Option<&Path>,
 ) -> Result<()> {
     let data = crate::data::Data(Arc::new(Mutex::new(HashMap::new())));
+    let (appended, _) = broadcast::channel(1000);
 
     let error = (
-        db::run(data_server, data.clone(), data_dir),
-        clients::handle(client_port, data),
+        db::run(data_server, data.clone(), appended.clone(), data_dir),
+        clients::handle(
+            client_port,
+            access,
+            data,
+            appended,
+            data_dir.to_path_buf(),
+            backup_root.map(Path::to_path_buf),
+        ),
     )
         .race()
         .await;
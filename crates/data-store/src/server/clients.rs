@@ -1,19 +1,46 @@
+use std::path::PathBuf;
+
+use tokio::sync::broadcast;
+
+use crate::data::series::Appended;
 use crate::data::Data;
 use crate::api::{self, ServerError};
 
-pub(crate) async fn handle(port: u16, data: Data) -> color_eyre::Result<()> {
+use super::follow::FollowHandler;
+
+pub(crate) async fn handle(
+    port: u16,
+    access: rpc::Access,
+    data: Data,
+    appended: broadcast::Sender<Appended>,
+    data_dir: PathBuf,
+    backup_root: Option<PathBuf>,
+) -> color_eyre::Result<()> {
+    let handler = FollowHandler {
+        data: data.clone(),
+        appended,
+    };
     rpc::server::run(
         port,
-        move |req, _| {
+        access,
+        move |req, _, role| {
             let data = data.clone();
-            perform_request(req, data)
+            let data_dir = data_dir.clone();
+            let backup_root = backup_root.clone();
+            perform_request(req, role, data, data_dir, backup_root)
         },
-        Option::<rpc::SubscribersUnsupported<api::Response>>::None,
+        Some(handler),
     )
     .await
 }
-async fn perform_request(request: api::Request, data: Data) -> api::Response {
-    match perform_request_inner(request, data).await {
+async fn perform_request(
+    request: api::Request,
+    role: rpc::Role,
+    data: Data,
+    data_dir: PathBuf,
+    backup_root: Option<PathBuf>,
+) -> api::Response {
+    match perform_request_inner(request, role, data, data_dir, backup_root).await {
         Ok(resp) => resp,
         Err(e) => api::Response::Error(e),
     }
@@ -21,7 +48,10 @@ async fn perform_request(request: api::Request, data: Data) -> api::Response {
 
 async fn perform_request_inner(
     request: api::Request,
+    role: rpc::Role,
     data: Data,
+    data_dir: PathBuf,
+    backup_root: Option<PathBuf>,
 ) -> Result<api::Response, ServerError> {
     Ok(match request {
         api::Request::ListData => api::Response::ListData(data.list_readings().await),
@@ -30,9 +60,39 @@ async fn perform_request_inner(
             start,
             end,
             n,
+            aggregation,
         } => {
-            let res = data.get(reading, start, end, n).await;
+            let res = data.get(reading, start, end, n, aggregation).await;
             api::Response::GetData(res)
         }
+        api::Request::GetDataMulti {
+            readings,
+            start,
+            end,
+            n,
+        } => api::Response::GetDataMulti(data.get_multi(readings, start, end, n).await),
+        api::Request::GetSummary {
+            reading,
+            start,
+            end,
+            bucket,
+        } => api::Response::GetSummary(data.get_summary(reading, start, end, bucket).await),
+        api::Request::GetHistogram {
+            reading,
+            start,
+            end,
+            bins,
+        } => api::Response::GetHistogram(data.get_histogram(reading, start, end, bins).await),
+        api::Request::Snapshot { to } => {
+            if !role.includes(rpc::Role::Actuate) {
+                return Err(ServerError::NotAllowed);
+            }
+            let res = match snapshot::target_in(backup_root.as_deref(), &to) {
+                Ok(to) => data.snapshot(&data_dir, &to).await,
+                Err(report) => Err(report),
+            }
+            .map_err(|report| api::SnapshotError(format!("{report:?}")));
+            api::Response::Snapshot(res)
+        }
     })
 }
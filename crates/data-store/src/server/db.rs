@@ -3,20 +3,28 @@ use std::path::Path;
 use std::time::{Duration, Instant};
 
 use data_server::api::subscriber::ReconnectingClient;
-use data_server::api::subscriber::SubMessage;
+use data_server::api::subscriber::{Filter, Kind, SubMessage, SubscribeOptions};
 
 use color_eyre::{Result, Section};
+use tokio::sync::broadcast;
 
+use crate::data::series::Appended;
 use crate::data::Data;
 
+/// Newly stored lines are send on `appended` for clients following a reading
 pub(crate) async fn run(
     data_server_addr: SocketAddr,
     data: Data,
+    appended: broadcast::Sender<Appended>,
     data_dir: &Path,
 ) -> Result<()> {
+    let options = SubscribeOptions {
+        filter: Filter::default().of_kinds([Kind::Reading]),
+        ..SubscribeOptions::default()
+    };
     let mut sub =
         ReconnectingClient::new(data_server_addr, "ha-data-store".to_string())
-            .subscribe();
+            .subscribe_with(options);
 
     let mut recently_logged = (Instant::now(), String::new());
     loop {
@@ -29,6 +37,11 @@ pub(crate) async fn run(
             .await
             .with_note(|| format!("reading: {reading:?}"));
 
+        if let Ok(Some(line)) = &res {
+            // an error only means no one is following
+            let _ = appended.send(line.clone());
+        }
+
         const FIVE_MIN: Duration = Duration::from_secs(60 * 5);
         if let Err(report) = res {
             let e = format!("{report:?}");
@@ -0,0 +1,139 @@
+use std::future;
+
+use futures::{stream, Stream};
+use protocol::IsSameAs;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+use crate::api::{self, FollowUpdate, GetDataError};
+use crate::data::series::{Appended, READ_CHUNK};
+use crate::data::Data;
+
+#[derive(Debug, Clone)]
+pub(crate) struct FollowHandler {
+    pub(crate) data: Data,
+    pub(crate) appended: broadcast::Sender<Appended>,
+}
+
+impl rpc::SubscriberHandler for FollowHandler {
+    type Request = api::SubscribeRequest;
+    type Update = api::Response;
+
+    fn setup(
+        &mut self,
+        client_name: &str,
+        request: Self::Request,
+    ) -> impl std::future::Future<
+        Output = impl futures::prelude::Stream<Item = Self::Update> + Send + 'static,
+    > + Send
+           + 'static {
+        let api::SubscribeRequest::Follow { reading, since } = request;
+        debug!("client {client_name} follows {reading:?} since {since}");
+        // subscribe before reading what is stored so nothing appended in
+        // between is missed, duplicates are filtered out later
+        let follower = Follower {
+            data: self.data.clone(),
+            appended: self.appended.subscribe(),
+            reading,
+            since,
+            sent_up_to: None,
+            catch_up: true,
+            failed: false,
+        };
+        future::ready(follow(follower))
+    }
+}
+
+fn follow(
+    follower: Follower,
+) -> impl Stream<Item = api::Response> + Send + 'static {
+    stream::unfold(follower, |mut follower| async move {
+        let update = follower.next().await;
+        Some((api::Response::Follow(update), follower))
+    })
+}
+
+struct Follower {
+    data: Data,
+    appended: broadcast::Receiver<Appended>,
+    reading: protocol::Reading,
+    since: jiff::Timestamp,
+    /// Time of the last point we send
+    sent_up_to: Option<jiff::Timestamp>,
+    /// Read what is stored since the last point we send, a chunk at a time
+    catch_up: bool,
+    failed: bool,
+}
+
+impl Follower {
+    /// The subscription stream may not end so this never returns after an
+    /// error has been returned.
+    async fn next(&mut self) -> Result<FollowUpdate, GetDataError> {
+        if self.failed {
+            return future::pending().await;
+        }
+
+        loop {
+            if self.catch_up {
+                let res = self.read_stored().await;
+                self.failed = res.is_err();
+                return res;
+            }
+
+            match self.appended.recv().await {
+                Ok(Appended { time, values }) => {
+                    if self.sent_up_to.is_some_and(|sent| time <= sent) {
+                        continue;
+                    }
+                    let Some((_, value)) = values
+                        .into_iter()
+                        .find(|(reading, _)| reading.is_same_as(&self.reading))
+                    else {
+                        continue;
+                    };
+                    self.sent_up_to = Some(time);
+                    return Ok(FollowUpdate::New { time, value });
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("follower fell behind, missed {missed} lines, catching up");
+                    self.catch_up = true;
+                }
+                Err(RecvError::Closed) => return future::pending().await,
+            }
+        }
+    }
+
+    /// Reads the next chunk of stored data. Catching up ends once a chunk
+    /// is not full.
+    async fn read_stored(&mut self) -> Result<FollowUpdate, GetDataError> {
+        let start = self.sent_up_to.unwrap_or(self.since);
+        let now = jiff::Timestamp::now();
+        let res = self
+            .data
+            .get_first(&self.reading, start, now, READ_CHUNK)
+            .await;
+        let (mut time, mut values) = match res {
+            Ok(stored) => stored,
+            // the reading could still show up
+            Err(
+                GetDataError::NotInStore { .. }
+                | GetDataError::NotFound
+                | GetDataError::EmptyFile
+                | GetDataError::StartAfterData
+                | GetDataError::StopBeforeData,
+            ) => (Vec::new(), Vec::new()),
+            Err(other) => return Err(other),
+        };
+        self.catch_up = time.len() == READ_CHUNK;
+
+        if let Some(sent_up_to) = self.sent_up_to {
+            let already_sent = time.partition_point(|t| *t <= sent_up_to);
+            time.drain(..already_sent);
+            values.drain(..already_sent);
+        }
+        if let Some(last) = time.last() {
+            self.sent_up_to = Some(*last);
+        }
+        Ok(FollowUpdate::Stored { time, values })
+    }
+}
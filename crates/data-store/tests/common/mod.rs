@@ -0,0 +1,166 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Once;
+use std::time::Duration;
+
+use data_server::server::AffectorRegistar;
+use futures::FutureExt;
+use futures_concurrency::future::Race;
+use protocol::large_bedroom::bed;
+use protocol::{large_bedroom, Reading};
+use reserve_port::ReservedPort;
+use temp_dir::TempDir;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Notify};
+use tokio::time::sleep;
+use tracing_error::ErrorLayer;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+const DATA_SERVER_STARTUP: Duration = Duration::from_millis(20);
+const DATA_STORE_STARTUP: Duration = Duration::from_millis(20);
+const FIRST_MSG_PROCESSED: Duration = Duration::from_millis(1000);
+
+pub const fn test_readings(v: f32) -> [Reading; 2] {
+    [
+        Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Temperature(v),
+        )),
+        Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Humidity(v),
+        )),
+    ]
+}
+
+/// A data-server with a data-store subscribed to it, both on random ports
+pub struct Setup {
+    pub test_dir: TempDir,
+    pub data_store_addr: SocketAddr,
+    /// Notified once all sensor values have been send
+    pub data_send: Notify,
+    sub_port: ReservedPort,
+    data_port: ReservedPort,
+    // keeps the data-store port reserved
+    _store_port: ReservedPort,
+}
+
+impl Setup {
+    pub fn new() -> Self {
+        setup_reporting();
+
+        let store_port = ReservedPort::random().unwrap();
+        Self {
+            test_dir: TempDir::new().unwrap(),
+            data_store_addr: SocketAddr::from((
+                [127, 0, 0, 1],
+                store_port.port(),
+            )),
+            data_send: Notify::new(),
+            sub_port: ReservedPort::random().unwrap(),
+            data_port: ReservedPort::random().unwrap(),
+            _store_port: store_port,
+        }
+    }
+
+    /// Starts the servers, sends `sensor_values` one per second and runs
+    /// `test` once the first value should have been stored. Returns when
+    /// `test` does.
+    pub async fn run(
+        &self,
+        sensor_values: &[f32],
+        backup_root: Option<&Path>,
+        test: impl Future<Output = ()>,
+    ) {
+        let data_server_addr =
+            SocketAddr::from(([127, 0, 0, 1], self.sub_port.port()));
+
+        let run_data_server = data_server(
+            data_server_addr,
+            ([127, 0, 0, 1], self.data_port.port()),
+        );
+        let run_data_store = sleep(DATA_SERVER_STARTUP).then(|()| {
+            data_store::server::run(
+                data_server_addr,
+                self.data_store_addr.port(),
+                self.test_dir.path(),
+                rpc::Access::default(),
+                backup_root,
+            )
+        });
+        let send_sensor_value = sleep(DATA_SERVER_STARTUP + DATA_STORE_STARTUP)
+            .then(|()| {
+                send_sensor_values(
+                    self.data_port.port(),
+                    sensor_values,
+                    &self.data_send,
+                )
+            });
+        let run_test = sleep(
+            DATA_SERVER_STARTUP + DATA_STORE_STARTUP + FIRST_MSG_PROCESSED,
+        )
+        .then(|()| test);
+
+        let res = (
+            run_test.map(Result::Ok),
+            send_sensor_value.map(Result::Ok),
+            run_data_store,
+            run_data_server.map(Result::Ok),
+        )
+            .race()
+            .await;
+
+        res.unwrap();
+    }
+}
+
+async fn data_server(
+    client_addr: impl Into<SocketAddr>,
+    data_port: impl Into<SocketAddr>,
+) {
+    use data_server::server;
+
+    let (tx, rx) = mpsc::channel(2000);
+    let affectors = AffectorRegistar::default();
+    tokio::select! {
+        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone(), server::Access::default()) => e.unwrap(),
+        e = server::handle_nodes(data_port.into(), &tx, affectors, server::Access::default()) => e.unwrap(),
+        e = server::handle_updates(rx) => e.unwrap(),
+    };
+}
+
+async fn send_sensor_values(
+    data_port: u16,
+    values: &[f32],
+    data_send: &Notify,
+) {
+    let mut conn = TcpStream::connect(("127.0.0.1", data_port)).await.unwrap();
+    let list = protocol::affector::ListMessage::<50>::empty();
+    let handshake = protocol::Msg::AffectorList(list).encode();
+    conn.write_all(&handshake).await.unwrap();
+
+    for v in values {
+        let mut sensor_msg = protocol::SensorMessage::<50>::default();
+        for val in test_readings(*v) {
+            sensor_msg.values.push(val).unwrap();
+        }
+        let encoded = protocol::Msg::Readings(sensor_msg).encode();
+        conn.write_all(&encoded).await.unwrap();
+        sleep(Duration::from_secs_f32(1.1)).await;
+    }
+    data_send.notify_waiters();
+    sleep(Duration::from_secs(999)).await;
+}
+
+static SETUP_REPORTING: Once = Once::new();
+
+fn setup_reporting() {
+    SETUP_REPORTING.call_once(|| {
+        color_eyre::install().unwrap();
+        tracing_subscriber::registry()
+            .with(ErrorLayer::default())
+            .with(tracing_subscriber::fmt::layer().pretty().with_test_writer())
+            .init();
+    });
+}
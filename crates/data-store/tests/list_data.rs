@@ -1,59 +1,14 @@
 use std::net::SocketAddr;
-use std::sync::Once;
 use std::time::Duration;
 
-use data_server::server::AffectorRegistar;
-use data_store::api::Data;
-use futures::FutureExt;
-use futures_concurrency::future::Race;
-use protocol::large_bedroom::bed;
+use data_store::api::{AlignedData, Bucket, Data, FollowUpdate, GetDataError};
 use protocol::{large_bedroom, Reading};
 use temp_dir::TempDir;
-use tokio::io::AsyncWriteExt;
-use tokio::net::TcpStream;
-use tokio::sync::{mpsc, Notify};
+use tokio::sync::Notify;
 use tokio::time::sleep;
-use tracing_error::ErrorLayer;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
-
-const fn test_readings(v: f32) -> [Reading; 2] {
-    [
-        Reading::LargeBedroom(large_bedroom::Reading::Bed(bed::Reading::Temperature(v))),
-        Reading::LargeBedroom(large_bedroom::Reading::Bed(bed::Reading::Humidity(v))),
-    ]
-}
-
-async fn data_server(client_addr: impl Into<SocketAddr>, data_port: impl Into<SocketAddr>) {
-    use data_server::server;
-
-    let (tx, rx) = mpsc::channel(2000);
-    let affectors = AffectorRegistar::default();
-    tokio::select! {
-        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone()) => e.unwrap(),
-        e = server::handle_nodes(data_port.into(), &tx, affectors) => e.unwrap(),
-        e = server::handle_updates(rx) => e.unwrap(),
-    };
-}
-
-async fn send_sensor_values(data_port: u16, values: &[f32], data_send: &Notify) {
-    let mut conn = TcpStream::connect(("127.0.0.1", data_port)).await.unwrap();
-    let list = protocol::affector::ListMessage::<50>::empty();
-    let handshake = protocol::Msg::AffectorList(list).encode();
-    conn.write_all(&handshake).await.unwrap();
 
-    for v in values {
-        let mut sensor_msg = protocol::SensorMessage::<50>::default();
-        for val in test_readings(*v) {
-            sensor_msg.values.push(val).unwrap();
-        }
-        let encoded = protocol::Msg::Readings(sensor_msg).encode();
-        conn.write_all(&encoded).await.unwrap();
-        sleep(Duration::from_secs_f32(1.1)).await;
-    }
-    data_send.notify_waiters();
-    sleep(Duration::from_secs(999)).await;
-}
+mod common;
+use common::{test_readings, Setup};
 
 async fn check_client_list_data(data_store_addr: SocketAddr, data_send: &Notify) {
     data_send.notified().await;
@@ -96,7 +51,9 @@ async fn check_client_get_data(
         data_store::api::Client::connect(data_store_addr, "data_store_example".to_owned())
             .await
             .unwrap();
-    let Data { time, values: data } = client
+    let Data {
+        time, values: data, ..
+    } = client
         .get_data(
             jiff::Timestamp::now() - jiff::Span::default().seconds(30),
             jiff::Timestamp::now() + jiff::Span::default().seconds(30),
@@ -114,101 +71,190 @@ async fn check_client_get_data(
         .all(|(a, b)| (a - b).abs() < 0.1))
 }
 
-static SETUP_REPORTING: Once = Once::new();
+async fn check_client_get_summary(
+    data_store_addr: SocketAddr,
+    sensor_values: &[f32],
+    data_send: &Notify,
+) {
+    data_send.notified().await;
+    sleep(Duration::from_secs_f32(0.1)).await;
+    let mut client =
+        data_store::api::Client::connect(data_store_addr, "data_store_example".to_owned())
+            .await
+            .unwrap();
+    let start = jiff::Timestamp::now() - jiff::Span::default().seconds(30);
+    let end = jiff::Timestamp::now() + jiff::Span::default().seconds(30);
 
-fn setup_reporting() {
-    SETUP_REPORTING.call_once(|| {
-        color_eyre::install().unwrap();
-        tracing_subscriber::registry()
-            .with(ErrorLayer::default())
-            .with(tracing_subscriber::fmt::layer().pretty().with_test_writer())
-            .init();
-    });
+    let stats = client
+        .get_summary(start, end, test_readings(0.0)[0].clone(), Bucket::Day)
+        .await
+        .unwrap();
+    assert_eq!(stats.iter().map(|s| s.count).sum::<usize>(), sensor_values.len());
+    let min = stats.iter().map(|s| s.min).fold(f32::INFINITY, f32::min);
+    let max = stats.iter().map(|s| s.max).fold(f32::NEG_INFINITY, f32::max);
+    assert!((min - 1.0).abs() < 0.1, "min: {min}");
+    assert!((max - 3.0).abs() < 0.1, "max: {max}");
+
+    let histogram = client
+        .get_histogram(start, end, test_readings(0.0)[0].clone(), 2)
+        .await
+        .unwrap();
+    assert_eq!(histogram.counts.len(), 2);
+    assert_eq!(histogram.counts.iter().sum::<usize>(), sensor_values.len());
 }
 
-#[tokio::test]
-async fn list_data() {
-    const DATA_SERVER_STARTUP: Duration = Duration::from_millis(20);
-    const DATA_STORE_STARTUP: Duration = Duration::from_millis(20);
-    const FIRST_MSG_PROCESSED: Duration = Duration::from_millis(1000);
+async fn check_client_get_data_multi(
+    data_store_addr: SocketAddr,
+    sensor_values: &[f32],
+    data_send: &Notify,
+) {
+    data_send.notified().await;
+    sleep(Duration::from_secs_f32(0.1)).await;
+    let mut client =
+        data_store::api::Client::connect(data_store_addr, "data_store_example".to_owned())
+            .await
+            .unwrap();
+    let not_stored = Reading::LargeBedroom(large_bedroom::Reading::Airbox(
+        large_bedroom::airbox::Reading::Temperature(0.0),
+    ));
+    let mut readings = test_readings(0.0).to_vec();
+    readings.push(not_stored);
+    let series = client
+        .get_data_multi(
+            jiff::Timestamp::now() - jiff::Span::default().seconds(30),
+            jiff::Timestamp::now() + jiff::Span::default().seconds(30),
+            readings,
+            5,
+        )
+        .await
+        .unwrap();
 
-    setup_reporting();
+    assert_eq!(series.len(), 2, "one result per device, got: {series:?}");
+    assert_eq!(series[0].readings.len(), 2);
+    let AlignedData { time, values } = series[0].data.as_ref().unwrap();
+    assert_eq!(values.len(), 2);
+    for column in values {
+        assert_eq!(time.len(), column.len());
+        assert!(column
+            .iter()
+            .zip(sensor_values)
+            .all(|(a, b)| (a - b).abs() < 0.1));
+    }
+    assert!(matches!(
+        series[1].data,
+        Err(GetDataError::NotInStore { .. })
+    ));
+}
 
-    let test_dir = TempDir::new().unwrap();
+async fn check_client_follow(data_store_addr: SocketAddr, sensor_values: &[f32]) {
+    let client =
+        data_store::api::Client::connect(data_store_addr, "data_store_example".to_owned())
+            .await
+            .unwrap();
+    let mut following = client
+        .follow(
+            test_readings(0.0)[0].clone(),
+            jiff::Timestamp::now() - jiff::Span::default().seconds(30),
+        )
+        .await
+        .unwrap();
 
-    let sub_port = reserve_port::ReservedPort::random().unwrap();
-    let data_port = reserve_port::ReservedPort::random().unwrap();
-    let store_port = reserve_port::ReservedPort::random().unwrap();
+    let mut time = Vec::new();
+    let mut values = Vec::new();
+    while values.len() < sensor_values.len() {
+        match following.next().await.unwrap() {
+            FollowUpdate::Stored {
+                time: stored_time,
+                values: stored,
+            } => {
+                time.extend(stored_time);
+                values.extend(stored);
+            }
+            FollowUpdate::New { time: t, value } => {
+                time.push(t);
+                values.push(value);
+            }
+        }
+    }
 
-    let data_server_addr = SocketAddr::from(([127, 0, 0, 1], sub_port.port()));
-    let data_store_addr = SocketAddr::from(([127, 0, 0, 1], store_port.port()));
+    assert!(time.is_sorted(), "points should be in order: {time:?}");
+    assert_eq!(values.len(), sensor_values.len());
+    assert!(values
+        .into_iter()
+        .zip(sensor_values.iter().copied())
+        .inspect(|r| println!("(got, expected): {r:?}"))
+        .all(|(a, b)| (a - b).abs() < 0.1))
+}
 
-    let data_send = Notify::new();
-    let run_data_server = data_server(
-        ([127, 0, 0, 1], sub_port.port()),
-        ([127, 0, 0, 1], data_port.port()),
-    );
-    let run_data_store = sleep(DATA_SERVER_STARTUP).then(|()| {
-        data_store::server::run(data_server_addr, data_store_addr.port(), test_dir.path())
-    });
-    let send_sensor_value = sleep(DATA_SERVER_STARTUP + DATA_STORE_STARTUP)
-        .then(|()| send_sensor_values(data_port.port(), &[0.0], &data_send));
-    let run_test = sleep(DATA_SERVER_STARTUP + DATA_STORE_STARTUP + FIRST_MSG_PROCESSED)
-        .then(|()| check_client_list_data(data_store_addr, &data_send));
-
-    let res = (
-        run_test.map(Result::Ok),
-        send_sensor_value.map(Result::Ok),
-        run_data_store,
-        run_data_server.map(Result::Ok),
-    )
-        .race()
-        .await;
+async fn check_client_snapshot(
+    data_store_addr: SocketAddr,
+    backup_root: &std::path::Path,
+    data_send: &Notify,
+) {
+    data_send.notified().await;
+    sleep(Duration::from_secs_f32(0.1)).await;
+    let mut client =
+        data_store::api::Client::connect(data_store_addr, "data_store_example".to_owned())
+            .await
+            .unwrap();
+    for outside in ["../escape", "/tmp/escape"] {
+        let res = client.snapshot(outside.into()).await;
+        assert!(res.is_err(), "snapshot outside the backup root: {outside}");
+    }
+    let summary = client.snapshot("snapshot".into()).await.unwrap();
+    assert!(summary.files > 0, "snapshot should contain the series");
 
-    res.unwrap();
+    let report = snapshot::verify(&backup_root.join("snapshot")).unwrap();
+    assert!(report.is_intact(), "{report}");
 }
 
 #[tokio::test]
-async fn read_data() {
-    const DATA_SERVER_STARTUP: Duration = Duration::from_millis(20);
-    const DATA_STORE_STARTUP: Duration = Duration::from_millis(20);
-    const FIRST_MSG_PROCESSED: Duration = Duration::from_millis(1000);
+async fn list_data() {
+    let setup = Setup::new();
+    let test = check_client_list_data(setup.data_store_addr, &setup.data_send);
+    setup.run(&[0.0], None, test).await;
+}
 
-    let test_dir = TempDir::new().unwrap();
-    std::env::set_current_dir(test_dir.path()).unwrap();
+#[tokio::test]
+async fn read_data() {
+    let setup = Setup::new();
+    std::env::set_current_dir(setup.test_dir.path()).unwrap();
 
-    setup_reporting();
+    let sensor_values = [0.5];
+    // let sensor_values = [0.0, 0.1, 0.2, 0.3];
+    let test = check_client_get_data(setup.data_store_addr, &sensor_values, &setup.data_send);
+    setup.run(&sensor_values, None, test).await;
+}
 
-    let sub_port = reserve_port::ReservedPort::random().unwrap();
-    let data_port = reserve_port::ReservedPort::random().unwrap();
-    let store_port = reserve_port::ReservedPort::random().unwrap();
+#[tokio::test]
+async fn read_data_multi() {
+    let setup = Setup::new();
+    let sensor_values = [0.5];
+    let test = check_client_get_data_multi(setup.data_store_addr, &sensor_values, &setup.data_send);
+    setup.run(&sensor_values, None, test).await;
+}
 
-    let data_server_addr = SocketAddr::from(([127, 0, 0, 1], sub_port.port()));
-    let data_store_addr = SocketAddr::from(([127, 0, 0, 1], store_port.port()));
+#[tokio::test]
+async fn follow_data() {
+    let setup = Setup::new();
+    // the first is stored before we follow, the others after
+    let sensor_values = [0.5, 0.6, 0.7];
+    let test = check_client_follow(setup.data_store_addr, &sensor_values);
+    setup.run(&sensor_values, None, test).await;
+}
 
-    let data_send = Notify::new();
-    let sensor_values = [0.5];
-    // let sensor_values = [0.0, 0.1, 0.2, 0.3];
-    let run_data_server = data_server(
-        ([127, 0, 0, 1], sub_port.port()),
-        ([127, 0, 0, 1], data_port.port()),
-    );
-    let run_data_store = sleep(DATA_SERVER_STARTUP).then(|()| {
-        data_store::server::run(data_server_addr, data_store_addr.port(), test_dir.path())
-    });
-    let send_sensor_value = sleep(DATA_SERVER_STARTUP + DATA_STORE_STARTUP)
-        .then(|()| send_sensor_values(data_port.port(), &sensor_values, &data_send));
-    let run_test = sleep(DATA_SERVER_STARTUP + DATA_STORE_STARTUP + FIRST_MSG_PROCESSED)
-        .then(|()| check_client_get_data(data_store_addr, &sensor_values, &data_send));
-
-    let res = (
-        run_test.map(Result::Ok),
-        send_sensor_value.map(Result::Ok),
-        run_data_store,
-        run_data_server.map(Result::Ok),
-    )
-        .race()
-        .await;
+#[tokio::test]
+async fn snapshot_data() {
+    let setup = Setup::new();
+    let backup_root = TempDir::new().unwrap();
+    let test = check_client_snapshot(setup.data_store_addr, backup_root.path(), &setup.data_send);
+    setup.run(&[0.5, 0.6], Some(backup_root.path()), test).await;
+}
 
-    res.unwrap();
+#[tokio::test]
+async fn read_summary() {
+    let setup = Setup::new();
+    let sensor_values = [1.0, 2.0, 3.0];
+    let test = check_client_get_summary(setup.data_store_addr, &sensor_values, &setup.data_send);
+    setup.run(&sensor_values, None, test).await;
 }
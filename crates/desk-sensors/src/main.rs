@@ -22,6 +22,10 @@ struct Cli {
     /// Is this the pi in the large bedroom or small bedroom?
     #[arg(short, long)]
     bedroom: bedroom::Bedroom,
+    /// Token with the `DataSource` role, needed if this pi is not on the
+    /// node allow list of the data-server
+    #[arg(long)]
+    token: Option<String>,
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -35,7 +39,7 @@ async fn main() {
     sensors::start_monitoring(tx.clone(), cli.bedroom);
 
     info!("connecting to dataserver on: {}", cli.data_server);
-    let mut client = Client::new(cli.data_server, Vec::new(), None)
+    let mut client = Client::new(cli.data_server, Vec::new(), None, cli.token)
         .await
         .unwrap();
 
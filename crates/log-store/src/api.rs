@@ -1,9 +1,12 @@
 use std::ops::RangeInclusive;
+use std::path::PathBuf;
 use std::time::Duration;
 
+use data_server::api::subscriber::{AffectorError, DecodeFailure, Delivered};
 use protocol::{Device, Reading};
 
 use serde::{Deserialize, Serialize};
+pub use snapshot::Summary as SnapshotSummary;
 
 pub mod client;
 pub use client::Client;
@@ -13,18 +16,65 @@ pub(crate) enum Request {
     Handshake {
         name: String,
     },
+    /// Error events that started within the range. An error ends when the
+    /// device reports another one or sends a reading, the event then has
+    /// its end set.
     GetLog {
         device: protocol::Device,
         range: RangeInclusive<jiff::Timestamp>,
     },
-    GetStats(protocol::Device),
+    /// Error events of any device, or only of `devices`, that match the
+    /// query and started within the range
+    SearchLogs {
+        query: LogQuery,
+        devices: Option<Vec<protocol::Device>>,
+        range: RangeInclusive<jiff::Timestamp>,
+    },
+    /// Error events of the device in the range merged into episodes
+    GetEpisodes {
+        device: protocol::Device,
+        range: RangeInclusive<jiff::Timestamp>,
+    },
+    /// Devices that have an error right now or are flapping
+    GetUnhealthy {
+        flapping: FlapThreshold,
+    },
+    GetStats {
+        subject: Subject,
+        window: Window,
+    },
+    /// Samples received compared to those expected for each reading
+    GetArrival {
+        subject: Subject,
+        window: Window,
+    },
     ListDevices,
+    GetAffectorHistory {
+        affector: protocol::Affector,
+        range: RangeInclusive<jiff::Timestamp>,
+    },
+    GetDecodeFailures {
+        range: RangeInclusive<jiff::Timestamp>,
+    },
+    /// Alerts that fired or resolved within the range
+    GetAlerts {
+        range: RangeInclusive<jiff::Timestamp>,
+    },
+    /// Alerts that fired and have not yet resolved
+    GetFiring,
+    /// Copy the log dir to a dir on the machine running the log-store
+    /// together with a manifest of checksums. Needs the `Actuate` role.
+    Snapshot {
+        to: PathBuf,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
 pub enum ServerError {
     #[error("Connect request should only be send once")]
     AlreadyConnected,
+    #[error("The role of this client does not allow this request")]
+    NotAllowed,
     #[error("Too many requests, rate limited, next requested allowed in: {0:?}")]
     TooManyRequests(Duration),
 }
@@ -37,6 +87,61 @@ pub enum GetStatsError {
     InternalError(String),
 }
 
+/// Which intervals between readings statistics cover. The windows move in
+/// steps, the last hour covers between 45 and 60 minutes, the last day
+/// between 20 and 24 hours and the last week between 6 and 7 days.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Window {
+    /// Everything recorded, the histograms are kept across restarts
+    #[default]
+    All,
+    LastHour,
+    LastDay,
+    LastWeek,
+}
+
+/// What to get statistics for. Intervals are measured between arrivals of
+/// the same reading, for a device those of all its readings are combined.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Subject {
+    Device(Device),
+    Reading(Reading),
+}
+
+impl From<Device> for Subject {
+    fn from(device: Device) -> Self {
+        Self::Device(device)
+    }
+}
+
+impl From<Reading> for Subject {
+    fn from(reading: Reading) -> Self {
+        Self::Reading(reading)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingArrival {
+    pub reading: Reading,
+    /// Samples that arrived in the window
+    pub received: u64,
+    /// Samples the device should have sent in the window given its
+    /// `max_sample_interval`. None if the device has no such limit.
+    pub expected: Option<u64>,
+}
+
+impl ReadingArrival {
+    /// Fraction of the expected samples that did not arrive, between zero
+    /// and one. None if the device has no `max_sample_interval` or nothing
+    /// was expected yet.
+    #[must_use]
+    pub fn missing_rate(&self) -> Option<f32> {
+        let expected = self.expected.filter(|expected| *expected > 0)?;
+        let missing = expected.saturating_sub(self.received);
+        Some(missing as f32 / expected as f32)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Percentile {
     pub bucket_ends: u64,
@@ -52,6 +157,51 @@ pub struct ErrorEvent {
     pub error: protocol::Error,
 }
 
+/// An error event matches if it matches both the text and the variants.
+/// The default query matches everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogQuery {
+    /// Case insensitive, must be part of the error as it is displayed
+    pub text: Option<String>,
+    /// Names of enum variants that must occur in this order in the
+    /// [`variant_path`](protocol::Error::variant_path) of the error. For
+    /// example `["Sps30"]` matches every error of an SPS30 sensor while
+    /// `["Setup", "Sps30"]` only matches those during its setup. Case
+    /// insensitive.
+    pub variants: Vec<String>,
+}
+
+impl LogQuery {
+    #[must_use]
+    pub fn matches(&self, error: &protocol::Error) -> bool {
+        let text_matches = self.text.as_ref().is_none_or(|text| {
+            error.to_string().to_lowercase().contains(&text.to_lowercase())
+        });
+        text_matches && self.variants_match(error)
+    }
+
+    fn variants_match(&self, error: &protocol::Error) -> bool {
+        let path = error.variant_path();
+        let mut path = path.iter();
+        self.variants.iter().all(|wanted| {
+            path.any(|variant| variant.eq_ignore_ascii_case(wanted))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum SearchLogsResponse {
+    Err(String),
+    /// all matching events between requested ranges
+    All(Vec<ErrorEvent>),
+    /// could not send more events due to rate limits user should request
+    /// more starting after `read_up_to`.
+    Partial {
+        events: Vec<ErrorEvent>,
+        read_up_to: jiff::Timestamp,
+    },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) enum GetLogResponse {
     Err(String),
@@ -63,11 +213,134 @@ pub(crate) enum GetLogResponse {
     Partial(Vec<ErrorEvent>),
 }
 
+/// Repeats of the same error, each starting shortly after the previous one
+/// ended
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Episode {
+    pub error: protocol::Error,
+    /// How often the error started
+    pub count: usize,
+    pub first_seen: jiff::Timestamp,
+    /// When the error last ended or now if it is ongoing
+    pub last_seen: jiff::Timestamp,
+    pub ongoing: bool,
+    /// Time the error was present summed over all repeats
+    pub downtime: Duration,
+}
+
+/// A device is flapping if more errors than `errors` started within the
+/// last `within`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FlapThreshold {
+    pub errors: usize,
+    pub within: Duration,
+}
+
+impl Default for FlapThreshold {
+    fn default() -> Self {
+        Self {
+            errors: 5,
+            within: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnhealthyDevice {
+    pub device: Device,
+    /// The error the device has right now
+    pub current: Option<ErrorEvent>,
+    /// Errors that started within the window of the [`FlapThreshold`]
+    pub recent_errors: usize,
+    pub flapping: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AffectorActivation {
+    pub at: jiff::Timestamp,
+    pub affector: protocol::Affector,
+    /// Name of the client that controlled the affector, truncated to 64 bytes
+    pub controlled_by: String,
+    pub result: Result<Delivered, AffectorError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum GetAffectorHistoryResponse {
+    Err(String),
+    /// all activations between requested ranges
+    All(Vec<AffectorActivation>),
+    /// could not send more activations due to rate limits user should
+    /// request more starting after `read_up_to`.
+    Partial {
+        activations: Vec<AffectorActivation>,
+        read_up_to: jiff::Timestamp,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedDecodeFailure {
+    pub at: jiff::Timestamp,
+    /// Long errors are truncated to 128 bytes and only the first 10
+    /// affectors are kept
+    pub failure: DecodeFailure,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum GetDecodeFailuresResponse {
+    Err(String),
+    /// all failures between requested ranges
+    All(Vec<LoggedDecodeFailure>),
+    /// could not send more failures due to rate limits user should request
+    /// more starting after `read_up_to`.
+    Partial {
+        failures: Vec<LoggedDecodeFailure>,
+        read_up_to: jiff::Timestamp,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlertState {
+    Fired,
+    Resolved,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertEvent {
+    pub at: jiff::Timestamp,
+    /// Name of the rule that fired or resolved, truncated to 64 bytes
+    pub rule: String,
+    pub state: AlertState,
+    /// Describes the condition of the rule, truncated to 192 bytes
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum GetAlertsResponse {
+    Err(String),
+    /// all alert events between requested ranges
+    All(Vec<AlertEvent>),
+    /// could not send more events due to rate limits user should request
+    /// more starting after `read_up_to`.
+    Partial {
+        alerts: Vec<AlertEvent>,
+        read_up_to: jiff::Timestamp,
+    },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) enum Response {
     GetLog(GetLogResponse),
+    SearchLogs(SearchLogsResponse),
+    GetEpisodes(Result<Vec<Episode>, String>),
+    GetUnhealthy(Result<Vec<UnhealthyDevice>, String>),
     ListDevices(Vec<Device>),
     GetStats(Result<Vec<Percentile>, GetStatsError>),
+    GetArrival(Result<Vec<ReadingArrival>, GetStatsError>),
+    GetAffectorHistory(GetAffectorHistoryResponse),
+    GetDecodeFailures(GetDecodeFailuresResponse),
+    GetAlerts(GetAlertsResponse),
+    GetFiring(Vec<AlertEvent>),
+    Snapshot(Result<SnapshotSummary, String>),
     Error(ServerError),
     Handshake,
 }
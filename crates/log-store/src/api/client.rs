@@ -8,12 +8,22 @@ use tokio::net::ToSocketAddrs;
 use tokio::time::sleep;
 use tracing::instrument;
 
-use crate::api::Percentile;
+use crate::api::{
+    Episode, FlapThreshold, Percentile, ReadingArrival, Subject, UnhealthyDevice, Window,
+};
 
+use super::AffectorActivation;
+use super::AlertEvent;
 use super::ErrorEvent;
+use super::GetAffectorHistoryResponse;
+use super::GetAlertsResponse;
+use super::GetDecodeFailuresResponse;
 use super::GetLogResponse;
 use super::GetStatsError;
+use super::LogQuery;
+use super::LoggedDecodeFailure;
 use super::Response;
+use super::SearchLogsResponse;
 
 pub struct Client(rpc::client::RpcClient<super::Request, super::Response>);
 pub use rpc::client::ConnectError;
@@ -35,11 +45,26 @@ impl Client {
         Ok(Self(rpc_client))
     }
 
+    /// Percentiles of the time between arrivals of each reading of the
+    /// device over everything recorded
     pub async fn get_percentiles(
         &mut self,
         device: protocol::Device,
     ) -> Result<Vec<Percentile>, Error<GetStatsError>> {
-        let request = super::Request::GetStats(device);
+        self.get_percentiles_in(device, Window::All).await
+    }
+
+    /// Like [`Self::get_percentiles`] but only for the intervals in
+    /// `window`. The subject can be a single reading or a device.
+    pub async fn get_percentiles_in(
+        &mut self,
+        subject: impl Into<Subject>,
+        window: Window,
+    ) -> Result<Vec<Percentile>, Error<GetStatsError>> {
+        let request = super::Request::GetStats {
+            subject: subject.into(),
+            window,
+        };
         match self.0.send_receive(request.clone()).await? {
             Response::GetStats(Ok(percentiles)) => Ok(percentiles),
             Response::GetStats(Err(e)) => Err(Error::Request(e)),
@@ -50,6 +75,28 @@ impl Client {
         }
     }
 
+    /// For each reading of the subject how many samples arrived in
+    /// `window` compared to how many were expected, see
+    /// [`ReadingArrival::missing_rate`].
+    pub async fn get_arrival(
+        &mut self,
+        subject: impl Into<Subject>,
+        window: Window,
+    ) -> Result<Vec<ReadingArrival>, Error<GetStatsError>> {
+        let request = super::Request::GetArrival {
+            subject: subject.into(),
+            window,
+        };
+        match self.0.send_receive(request.clone()).await? {
+            Response::GetArrival(Ok(arrivals)) => Ok(arrivals),
+            Response::GetArrival(Err(e)) => Err(Error::Request(e)),
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
+
     #[instrument(skip(self))]
     pub async fn get_logs(
         &mut self,
@@ -83,7 +130,7 @@ impl Client {
                 .last()
                 .expect("if log.len() == 0 then response is GetLogResponse::All")
                 .start;
-            range = RangeInclusive::new(partial_ends + jiff::Span::new().seconds(1), *range.end());
+            range = RangeInclusive::new(partial_ends + jiff::Span::new().milliseconds(1), *range.end());
             tracing::debug!("Got logs up till {partial_ends}, next requesting: {range:?}");
             all.extend_from_slice(&partial);
             // do not overburden the server
@@ -92,6 +139,226 @@ impl Client {
         Ok(all)
     }
 
+    /// Error events that started within the range and match the query.
+    /// Searches every device unless `devices` is given.
+    #[instrument(skip(self))]
+    pub async fn search_logs(
+        &mut self,
+        query: LogQuery,
+        devices: Option<Vec<protocol::Device>>,
+        mut range: RangeInclusive<jiff::Timestamp>,
+    ) -> Result<Vec<ErrorEvent>, Error<String>> {
+        let mut all = Vec::new();
+
+        while !range.is_empty() {
+            let request = super::Request::SearchLogs {
+                query: query.clone(),
+                devices: devices.clone(),
+                range: range.clone(),
+            };
+            let (partial, read_up_to) = match self.0.send_receive(request.clone()).await? {
+                Response::SearchLogs(SearchLogsResponse::All(events)) => {
+                    all.extend(events);
+                    return Ok(all);
+                }
+                Response::SearchLogs(SearchLogsResponse::Partial { events, read_up_to }) => {
+                    (events, read_up_to)
+                }
+                Response::SearchLogs(SearchLogsResponse::Err(e)) => return Err(Error::Request(e)),
+                response => {
+                    return Err(Error::Comms(RpcError::IncorrectResponse {
+                        request: format!("{request:?}"),
+                        response: format!("{response:?}"),
+                    }))
+                }
+            };
+
+            range = RangeInclusive::new(read_up_to + jiff::Span::new().milliseconds(1), *range.end());
+            all.extend(partial);
+            // do not overburden the server
+            sleep(Duration::from_millis(100)).await;
+        }
+        Ok(all)
+    }
+
+    /// The errors of the device in the range with repeats of the same
+    /// error merged into episodes, oldest first
+    pub async fn get_episodes(
+        &mut self,
+        device: protocol::Device,
+        range: RangeInclusive<jiff::Timestamp>,
+    ) -> Result<Vec<Episode>, Error<String>> {
+        let request = super::Request::GetEpisodes { device, range };
+        match self.0.send_receive(request.clone()).await? {
+            Response::GetEpisodes(Ok(episodes)) => Ok(episodes),
+            Response::GetEpisodes(Err(e)) => Err(Error::Request(e)),
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
+
+    /// Devices that have an error right now or had more errors than the
+    /// threshold allows recently
+    pub async fn unhealthy_devices(
+        &mut self,
+        flapping: FlapThreshold,
+    ) -> Result<Vec<UnhealthyDevice>, Error<String>> {
+        let request = super::Request::GetUnhealthy { flapping };
+        match self.0.send_receive(request.clone()).await? {
+            Response::GetUnhealthy(Ok(devices)) => Ok(devices),
+            Response::GetUnhealthy(Err(e)) => Err(Error::Request(e)),
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
+
+    /// Every attempt to control this affector within the range, successful
+    /// or not.
+    #[instrument(skip(self))]
+    pub async fn get_affector_history(
+        &mut self,
+        affector: protocol::Affector,
+        mut range: RangeInclusive<jiff::Timestamp>,
+    ) -> Result<Vec<AffectorActivation>, Error<String>> {
+        let mut all = Vec::new();
+
+        while !range.is_empty() {
+            let request = super::Request::GetAffectorHistory {
+                affector,
+                range: range.clone(),
+            };
+            let (partial, read_up_to) = match self.0.send_receive(request.clone()).await? {
+                Response::GetAffectorHistory(GetAffectorHistoryResponse::All(list)) => {
+                    all.extend_from_slice(&list);
+                    return Ok(all);
+                }
+                Response::GetAffectorHistory(GetAffectorHistoryResponse::Partial {
+                    activations,
+                    read_up_to,
+                }) => (activations, read_up_to),
+                Response::GetAffectorHistory(GetAffectorHistoryResponse::Err(e)) => {
+                    return Err(Error::Request(e))
+                }
+                response => {
+                    return Err(Error::Comms(RpcError::IncorrectResponse {
+                        request: format!("{request:?}"),
+                        response: format!("{response:?}"),
+                    }))
+                }
+            };
+
+            range = RangeInclusive::new(
+                read_up_to + jiff::Span::new().milliseconds(1),
+                *range.end(),
+            );
+            all.extend_from_slice(&partial);
+            // do not overburden the server
+            sleep(Duration::from_millis(100)).await;
+        }
+        Ok(all)
+    }
+
+    /// Messages nodes send within the range that the data-server could not
+    /// decode.
+    #[instrument(skip(self))]
+    pub async fn get_decode_failures(
+        &mut self,
+        mut range: RangeInclusive<jiff::Timestamp>,
+    ) -> Result<Vec<LoggedDecodeFailure>, Error<String>> {
+        let mut all = Vec::new();
+
+        while !range.is_empty() {
+            let request = super::Request::GetDecodeFailures {
+                range: range.clone(),
+            };
+            let (partial, read_up_to) = match self.0.send_receive(request.clone()).await? {
+                Response::GetDecodeFailures(GetDecodeFailuresResponse::All(list)) => {
+                    all.extend_from_slice(&list);
+                    return Ok(all);
+                }
+                Response::GetDecodeFailures(GetDecodeFailuresResponse::Partial {
+                    failures,
+                    read_up_to,
+                }) => (failures, read_up_to),
+                Response::GetDecodeFailures(GetDecodeFailuresResponse::Err(e)) => {
+                    return Err(Error::Request(e))
+                }
+                response => {
+                    return Err(Error::Comms(RpcError::IncorrectResponse {
+                        request: format!("{request:?}"),
+                        response: format!("{response:?}"),
+                    }))
+                }
+            };
+
+            range = RangeInclusive::new(
+                read_up_to + jiff::Span::new().milliseconds(1),
+                *range.end(),
+            );
+            all.extend_from_slice(&partial);
+            // do not overburden the server
+            sleep(Duration::from_millis(100)).await;
+        }
+        Ok(all)
+    }
+
+    /// Alerts that fired or resolved within the range, oldest first
+    #[instrument(skip(self))]
+    pub async fn get_alerts(
+        &mut self,
+        mut range: RangeInclusive<jiff::Timestamp>,
+    ) -> Result<Vec<AlertEvent>, Error<String>> {
+        let mut all = Vec::new();
+
+        while !range.is_empty() {
+            let request = super::Request::GetAlerts {
+                range: range.clone(),
+            };
+            let (partial, read_up_to) = match self.0.send_receive(request.clone()).await? {
+                Response::GetAlerts(GetAlertsResponse::All(list)) => {
+                    all.extend_from_slice(&list);
+                    return Ok(all);
+                }
+                Response::GetAlerts(GetAlertsResponse::Partial { alerts, read_up_to }) => {
+                    (alerts, read_up_to)
+                }
+                Response::GetAlerts(GetAlertsResponse::Err(e)) => return Err(Error::Request(e)),
+                response => {
+                    return Err(Error::Comms(RpcError::IncorrectResponse {
+                        request: format!("{request:?}"),
+                        response: format!("{response:?}"),
+                    }))
+                }
+            };
+
+            range = RangeInclusive::new(
+                read_up_to + jiff::Span::new().milliseconds(1),
+                *range.end(),
+            );
+            all.extend_from_slice(&partial);
+            // do not overburden the server
+            sleep(Duration::from_millis(100)).await;
+        }
+        Ok(all)
+    }
+
+    /// For every alert that has fired and not yet resolved the event of it
+    /// firing
+    pub async fn firing_alerts(&mut self) -> Result<Vec<AlertEvent>, Error<String>> {
+        let request = super::Request::GetFiring;
+        match self.0.send_receive(request.clone()).await? {
+            Response::GetFiring(alerts) => Ok(alerts),
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
+
     pub async fn list_devices(&mut self) -> Result<Vec<Device>, Error<String>> {
         let request = super::Request::ListDevices;
         match self.0.send_receive(request.clone()).await? {
@@ -102,4 +369,23 @@ impl Client {
             })),
         }
     }
+
+    /// Have the log-store copy its log dir to `to`, a dir on the machine
+    /// the log-store runs on that must be empty or not exist yet. Nothing
+    /// is logged while copying. The client needs the `Actuate` role.
+    pub async fn snapshot(
+        &mut self,
+        to: std::path::PathBuf,
+    ) -> Result<super::SnapshotSummary, Error<String>> {
+        let request = super::Request::Snapshot { to };
+        match self.0.send_receive(request.clone()).await? {
+            Response::Snapshot(Ok(summary)) => Ok(summary),
+            Response::Snapshot(Err(e)) => Err(Error::Request(e)),
+            Response::Error(e) => Err(Error::Request(e.to_string())),
+            response => Err(Error::Comms(RpcError::IncorrectResponse {
+                request: format!("{request:?}"),
+                response: format!("{response:?}"),
+            })),
+        }
+    }
 }
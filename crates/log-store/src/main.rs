@@ -2,22 +2,63 @@ use std::net::SocketAddr;
 use std::path::PathBuf;
 
 use clap::Parser;
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{bail, Result};
 
 #[derive(Parser, Debug)]
 #[command(name = "data server")]
 #[command(version = "1.0")]
 #[command(about = "Receives sensor events then logs errors and tracks timing")]
+#[command(subcommand_negates_reqs = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// data server
-    #[arg(short, long)]
-    data_server: SocketAddr,
+    #[arg(short, long, required = true)]
+    data_server: Option<SocketAddr>,
 
-    #[arg(short, long)]
-    client_port: u16,
+    #[arg(short, long, required = true)]
+    client_port: Option<u16>,
 
     #[arg(long, default_value = ".")]
     log_dir: PathBuf,
+
+    /// RON file with the tokens clients need, see `rpc::Access`. Without it
+    /// anyone may connect.
+    #[arg(long)]
+    access: Option<PathBuf>,
+
+    /// RON file with alert rules and notifiers, see
+    /// `log_store::server::alert::Rules`. Without it no alerts are raised.
+    #[arg(long)]
+    rules: Option<PathBuf>,
+
+    /// dir clients may write snapshots to using backup. Without it snapshot
+    /// requests are refused.
+    #[arg(long)]
+    backup_root: Option<PathBuf>,
+}
+
+/// Without a command the log-store runs
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Have a running log-store copy its log dir to a snapshot. Set the
+    /// token in the `HA_RPC_TOKEN` environment variable if access is
+    /// restricted, it needs the `Actuate` role.
+    Backup {
+        /// log-store to back up
+        #[arg(short, long)]
+        server: SocketAddr,
+        /// empty or new dir in the backup root of the log-store, relative to
+        /// that root
+        #[arg(long)]
+        to: PathBuf,
+    },
+    /// Check a snapshot made by backup against its manifest
+    Verify {
+        /// dir containing the snapshot
+        snapshot: PathBuf,
+    },
 }
 
 #[tokio::main]
@@ -26,5 +67,54 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
     tracing::info!("started log-store, args: {cli:?}");
 
-    log_store::server::run(cli.data_server, cli.client_port, &cli.log_dir).await
+    match cli.command {
+        Some(Command::Backup { server, to }) => {
+            let mut client =
+                log_store::api::Client::connect(server, "backup".to_string())
+                    .await?;
+            let summary = client.snapshot(to.clone()).await?;
+            tracing::info!(
+                "Done, copied {} files ({} bytes) to {}",
+                summary.files,
+                summary.bytes,
+                to.display()
+            );
+            return Ok(());
+        }
+        Some(Command::Verify { snapshot }) => {
+            let report = snapshot::verify(&snapshot)?;
+            println!("{report}");
+            if !report.is_intact() {
+                bail!("Snapshot is damaged")
+            }
+            return Ok(());
+        }
+        None => (),
+    }
+
+    let (Some(data_server), Some(client_port)) =
+        (cli.data_server, cli.client_port)
+    else {
+        unreachable!("clap requires these without a subcommand")
+    };
+
+    let access = match &cli.access {
+        Some(path) => rpc::Access::load(path)?,
+        None => rpc::Access::default(),
+    };
+
+    let rules = match &cli.rules {
+        Some(path) => log_store::server::alert::Rules::load(path)?,
+        None => log_store::server::alert::Rules::default(),
+    };
+
+    log_store::server::run(
+        data_server,
+        client_port,
+        &cli.log_dir,
+        access,
+        rules,
+        cli.backup_root.as_deref(),
+    )
+    .await
 }
@@ -6,28 +6,55 @@ use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+pub mod alert;
 mod clients;
 mod db;
 
 // used from main and tests
-pub async fn run(data_server: SocketAddr, client_port: u16, log_dir: &Path) -> Result<()> {
-    let stats = db::Stats(Arc::new(Mutex::new(HashMap::new())));
+pub async fn run(
+    data_server: SocketAddr,
+    client_port: u16,
+    log_dir: &Path,
+    access: rpc::Access,
+    rules: alert::Rules,
+    backup_root: Option<&Path>,
+) -> Result<()> {
     let logs = db::Logs(Arc::new(Mutex::new(HashMap::new())));
+    let affectors = db::AffectorHistory::open_or_create(log_dir)?;
+    let decode_failures = db::DecodeFailures::open_or_create(log_dir)?;
+    let stats = db::Stats::load(log_dir)?;
+    let alerts = db::AlertHistory::open_or_create(log_dir)?;
+    let alerting = alert::Alerting::new(rules, alerts);
 
     let error = (
         db::run(
             data_server,
             stats.clone(),
             logs.clone(),
+            affectors.clone(),
+            decode_failures.clone(),
+            alerting.clone(),
             log_dir,
         ),
-        clients::handle(client_port, stats, logs),
+        stats.save_periodically(log_dir),
+        alerting.check_periodically(),
+        clients::handle(
+            client_port,
+            access,
+            stats.clone(),
+            logs,
+            affectors,
+            decode_failures,
+            alerting,
+            log_dir.to_path_buf(),
+            backup_root.map(Path::to_path_buf),
+        ),
     )
         .race()
         .await;
     assert!(
         error.is_err(),
-        "db::run and client::handle never return unless an error happens"
+        "db::run, save_periodically, check_periodically and client::handle never return unless an error happens"
     );
     error
 }
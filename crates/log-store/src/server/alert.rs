@@ -0,0 +1,624 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{bail, Context, Result};
+use color_eyre::Section;
+use protocol::reading::tree::Tree;
+use protocol::{Device, Reading};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use super::db::AlertHistory;
+use crate::api::{AlertEvent, AlertState};
+
+mod notify;
+
+/// How often rules that depend on time passing are checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Rules that raise alerts and where to send them. Loaded from a RON file
+/// such as:
+/// ```ron
+/// (
+///     notifiers: {
+///         "phone": Http(url: "https://ntfy.sh/our-home"),
+///         "journal": Command(program: "logger", args: ["-t", "home-alert"]),
+///     },
+///     rules: [(
+///         name: "bed sensors down",
+///         condition: Error(device: LargeBedroom(Bed(Sht31))),
+///         fire_after: (secs: 600, nanos: 0),
+///         notify: ["phone"],
+///     ), (
+///         name: "bedroom stuffy",
+///         // the value in the reading is ignored
+///         condition: Above(reading: LargeBedroom(Bed(Co2(0))), threshold: 1500, clear: 1200),
+///         fire_after: (secs: 900, nanos: 0),
+///         resolve_after: (secs: 300, nanos: 0),
+///         notify: ["phone", "journal"],
+///     )],
+/// )
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Rules {
+    /// Rules refer to these by name
+    #[serde(default)]
+    pub notifiers: HashMap<String, Notifier>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    /// Identifies the rule in the alert history, must be unique
+    pub name: String,
+    pub condition: Condition,
+    /// The condition must be met this long before the alert fires
+    #[serde(default)]
+    pub fire_after: Duration,
+    /// Once fired the condition must be cleared this long before the alert
+    /// resolves
+    #[serde(default)]
+    pub resolve_after: Duration,
+    /// Names of the notifiers to tell when the alert fires or resolves
+    #[serde(default)]
+    pub notify: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Condition {
+    /// Met while the device has an error, any reading from the device
+    /// clears it.
+    Error { device: Device },
+    /// Met when the reading has not arrived for `factor` times the
+    /// `max_sample_interval` of its device. Never met for devices without
+    /// such a limit.
+    NoReading { reading: Reading, factor: u32 },
+    /// Met once the reading rises above `threshold`, cleared once it drops
+    /// below `clear`. In between nothing changes.
+    Above {
+        reading: Reading,
+        threshold: f32,
+        clear: f32,
+    },
+    /// Met once the reading drops below `threshold`, cleared once it rises
+    /// above `clear`. In between nothing changes.
+    Below {
+        reading: Reading,
+        threshold: f32,
+        clear: f32,
+    },
+}
+
+/// Where to send alerts, the text names the rule, whether it fired or
+/// resolved and describes its condition.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Notifier {
+    /// POST the text as body to the url, works with ntfy
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: Vec<(String, String)>,
+    },
+    /// Run the program with the text as last argument
+    Command {
+        program: PathBuf,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+impl Rules {
+    pub fn load(path: &Path) -> Result<Self> {
+        let rules = fs::read_to_string(path)
+            .wrap_err("Could not read alert rules")
+            .with_note(|| format!("path: {}", path.display()))?;
+        let rules: Self = ron::from_str(&rules)
+            .wrap_err("Could not deserialize alert rules")
+            .with_note(|| format!("path: {}", path.display()))?;
+        rules
+            .check()
+            .wrap_err("Invalid alert rules")
+            .with_note(|| format!("path: {}", path.display()))?;
+        Ok(rules)
+    }
+
+    fn check(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        for rule in &self.rules {
+            if !names.insert(&rule.name) {
+                bail!("There are multiple rules named: {}", rule.name)
+            }
+            if let Some(missing) = rule
+                .notify
+                .iter()
+                .find(|name| !self.notifiers.contains_key(*name))
+            {
+                bail!("Rule {} uses unknown notifier: {missing}", rule.name)
+            }
+            match rule.condition {
+                Condition::Above {
+                    threshold, clear, ..
+                } if clear > threshold => {
+                    bail!("Rule {} clears above its threshold", rule.name)
+                }
+                Condition::Below {
+                    threshold, clear, ..
+                } if clear < threshold => {
+                    bail!("Rule {} clears below its threshold", rule.name)
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Condition {
+    fn describe(&self) -> String {
+        match self {
+            Condition::Error { device } => format!("{device:?} has an error"),
+            Condition::NoReading { reading, factor } => format!(
+                "no {} for {factor} times its max sample interval",
+                describe(reading)
+            ),
+            Condition::Above {
+                reading, threshold, ..
+            } => format!("{} above {threshold}", describe(reading)),
+            Condition::Below {
+                reading, threshold, ..
+            } => format!("{} below {threshold}", describe(reading)),
+        }
+    }
+}
+
+fn describe(reading: &Reading) -> String {
+    let info = reading.info();
+    format!("{:?} {}", info.device, info.description)
+}
+
+#[derive(Debug)]
+struct Tracked {
+    rule: Rule,
+    /// Whether the condition is met, None until that is known
+    met: Option<bool>,
+    /// Since when `met` has its current value
+    since: Instant,
+    /// Last time the reading of a [`Condition::NoReading`] arrived
+    last_seen: Instant,
+    /// The alert fired and has not yet resolved
+    firing: Option<AlertEvent>,
+}
+
+impl Tracked {
+    fn new(rule: Rule, now: Instant) -> Self {
+        Self {
+            rule,
+            met: None,
+            since: now,
+            last_seen: now,
+            firing: None,
+        }
+    }
+
+    fn update(&mut self, met: bool, now: Instant) {
+        if self.met != Some(met) {
+            self.met = Some(met);
+            self.since = now;
+        }
+    }
+
+    fn on_reading(&mut self, reading: &Reading, now: Instant) {
+        match &self.rule.condition {
+            Condition::Error { device } if reading.device() == *device => {
+                self.update(false, now);
+            }
+            Condition::NoReading { reading: r, .. }
+                if r.id() == reading.id() =>
+            {
+                self.last_seen = now;
+                self.update(false, now);
+            }
+            Condition::Above {
+                reading: r,
+                threshold,
+                clear,
+            } if r.id() == reading.id() => {
+                let value = reading.info().val;
+                if value > *threshold {
+                    self.update(true, now);
+                } else if value < *clear {
+                    self.update(false, now);
+                }
+            }
+            Condition::Below {
+                reading: r,
+                threshold,
+                clear,
+            } if r.id() == reading.id() => {
+                let value = reading.info().val;
+                if value < *threshold {
+                    self.update(true, now);
+                } else if value > *clear {
+                    self.update(false, now);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn on_error(&mut self, error: &protocol::Error, now: Instant) {
+        if let Condition::Error { device } = &self.rule.condition {
+            if error.device() == *device {
+                self.update(true, now);
+            }
+        }
+    }
+
+    fn on_tick(&mut self, now: Instant) {
+        if let Condition::NoReading { reading, factor } = &self.rule.condition {
+            let max_interval = reading.device().info().max_sample_interval;
+            if let Some(limit) = max_interval.checked_mul(*factor) {
+                let silent = now.duration_since(self.last_seen) > limit;
+                self.update(silent, now);
+            }
+        }
+    }
+
+    /// The state the alert should move to, if any
+    fn transition(&self, now: Instant) -> Option<AlertState> {
+        let held = now.duration_since(self.since);
+        match (self.met, &self.firing) {
+            (Some(true), None) if held >= self.rule.fire_after => {
+                Some(AlertState::Fired)
+            }
+            (Some(false), Some(_)) if held >= self.rule.resolve_after => {
+                Some(AlertState::Resolved)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Evaluates the rules, records alerts that fire or resolve and tells the
+/// notifiers. Alerts do not survive a restart, one that is still firing
+/// fires again once its condition has been met long enough.
+#[derive(Debug, Clone)]
+pub(crate) struct Alerting {
+    rules: Arc<Mutex<Vec<Tracked>>>,
+    notifiers: Arc<HashMap<String, Notifier>>,
+    history: AlertHistory,
+}
+
+impl Alerting {
+    pub(crate) fn new(rules: Rules, history: AlertHistory) -> Self {
+        let now = Instant::now();
+        let tracked = rules
+            .rules
+            .into_iter()
+            .map(|rule| Tracked::new(rule, now))
+            .collect();
+        Self {
+            rules: Arc::new(Mutex::new(tracked)),
+            notifiers: Arc::new(rules.notifiers),
+            history,
+        }
+    }
+
+    pub(crate) async fn on_reading(&self, reading: &Reading) {
+        let now = Instant::now();
+        let mut rules = self.rules.lock().await;
+        for tracked in rules.iter_mut() {
+            tracked.on_reading(reading, now);
+        }
+        self.apply_transitions(&mut rules, now).await;
+    }
+
+    pub(crate) async fn on_error(&self, error: &protocol::Error) {
+        let now = Instant::now();
+        let mut rules = self.rules.lock().await;
+        for tracked in rules.iter_mut() {
+            tracked.on_error(error, now);
+        }
+        self.apply_transitions(&mut rules, now).await;
+    }
+
+    /// Alerts that fire or resolve because time passed are only noticed
+    /// while this runs
+    pub(crate) async fn check_periodically(&self) -> Result<()> {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let now = Instant::now();
+            let mut rules = self.rules.lock().await;
+            for tracked in rules.iter_mut() {
+                tracked.on_tick(now);
+            }
+            self.apply_transitions(&mut rules, now).await;
+        }
+    }
+
+    pub(crate) async fn firing(&self) -> Vec<AlertEvent> {
+        self.rules
+            .lock()
+            .await
+            .iter()
+            .filter_map(|tracked| tracked.firing.clone())
+            .collect()
+    }
+
+    pub(crate) fn history(&self) -> &AlertHistory {
+        &self.history
+    }
+
+    /// A transition that could not be recorded is logged and not applied,
+    /// it is tried again on the next reading, error or check
+    async fn apply_transitions(&self, rules: &mut [Tracked], now: Instant) {
+        for tracked in rules {
+            let Some(state) = tracked.transition(now) else {
+                continue;
+            };
+
+            let message = tracked.rule.condition.describe();
+            let res = self
+                .history
+                .record(tracked.rule.name.clone(), state, message)
+                .await;
+            let event = match res {
+                Ok(event) => event,
+                Err(err) => {
+                    warn!(
+                        "Could not record alert {}: {err:?}",
+                        tracked.rule.name
+                    );
+                    continue;
+                }
+            };
+            tracked.firing = match state {
+                AlertState::Fired => Some(event.clone()),
+                AlertState::Resolved => None,
+            };
+            self.notify(&tracked.rule, &event);
+        }
+    }
+
+    /// Notifiers run in the background, a slow or unreachable one does not
+    /// hold up the log-store
+    fn notify(&self, rule: &Rule, event: &AlertEvent) {
+        let state = match event.state {
+            AlertState::Fired => "fired",
+            AlertState::Resolved => "resolved",
+        };
+        let text = format!("{} {state}: {}", rule.name, event.message);
+        for name in &rule.notify {
+            let Some(notifier) = self.notifiers.get(name).cloned() else {
+                continue; // checked when the rules are loaded
+            };
+            let text = text.clone();
+            let name = name.clone();
+            tokio::spawn(async move {
+                if let Err(err) = notify::send(notifier, text).await {
+                    warn!("Notifier {name} failed: {err:?}");
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use protocol::large_bedroom::{self, bed};
+
+    use super::*;
+
+    const MIN: Duration = Duration::from_secs(60);
+
+    fn temperature(v: f32) -> Reading {
+        Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Temperature(v),
+        ))
+    }
+
+    fn sht31_error() -> protocol::Error {
+        protocol::Error::LargeBedroom(large_bedroom::Error::Bed(
+            bed::Error::Setup(bed::SensorError::Sht31(
+                heapless::String::from_str("test").unwrap(),
+            )),
+        ))
+    }
+
+    fn tracked(condition: Condition, start: Instant) -> Tracked {
+        let rule = Rule {
+            name: "test".to_string(),
+            condition,
+            fire_after: 10 * MIN,
+            resolve_after: 5 * MIN,
+            notify: Vec::new(),
+        };
+        Tracked::new(rule, start)
+    }
+
+    /// Applies the transition like [`Alerting`] does
+    fn step(tracked: &mut Tracked, now: Instant) -> Option<AlertState> {
+        let state = tracked.transition(now)?;
+        tracked.firing = match state {
+            AlertState::Fired => Some(AlertEvent {
+                at: jiff::Timestamp::UNIX_EPOCH,
+                rule: tracked.rule.name.clone(),
+                state,
+                message: String::new(),
+            }),
+            AlertState::Resolved => None,
+        };
+        Some(state)
+    }
+
+    #[test]
+    fn above_fires_after_delay_and_resolves_below_clear() {
+        let start = Instant::now();
+        let condition = Condition::Above {
+            reading: temperature(0.0),
+            threshold: 30.0,
+            clear: 25.0,
+        };
+        let mut tracked = tracked(condition, start);
+
+        tracked.on_reading(&temperature(31.0), start);
+        assert_eq!(step(&mut tracked, start + 9 * MIN), None);
+        assert_eq!(
+            step(&mut tracked, start + 10 * MIN),
+            Some(AlertState::Fired)
+        );
+
+        // within the hysteresis band nothing changes
+        tracked.on_reading(&temperature(27.0), start + 11 * MIN);
+        assert_eq!(step(&mut tracked, start + 30 * MIN), None);
+
+        tracked.on_reading(&temperature(24.0), start + 31 * MIN);
+        assert_eq!(step(&mut tracked, start + 35 * MIN), None);
+        assert_eq!(
+            step(&mut tracked, start + 36 * MIN),
+            Some(AlertState::Resolved)
+        );
+    }
+
+    #[test]
+    fn below_restarts_delay_when_cleared() {
+        let start = Instant::now();
+        let condition = Condition::Below {
+            reading: temperature(0.0),
+            threshold: 10.0,
+            clear: 12.0,
+        };
+        let mut tracked = tracked(condition, start);
+
+        tracked.on_reading(&temperature(9.0), start);
+        tracked.on_reading(&temperature(13.0), start + 5 * MIN);
+        tracked.on_reading(&temperature(9.0), start + 6 * MIN);
+        assert_eq!(step(&mut tracked, start + 15 * MIN), None);
+        assert_eq!(
+            step(&mut tracked, start + 16 * MIN),
+            Some(AlertState::Fired)
+        );
+    }
+
+    #[test]
+    fn resolving_needs_condition_cleared_for_delay() {
+        let start = Instant::now();
+        let condition = Condition::Above {
+            reading: temperature(0.0),
+            threshold: 30.0,
+            clear: 25.0,
+        };
+        let mut tracked = tracked(condition, start);
+        tracked.on_reading(&temperature(31.0), start);
+        assert_eq!(
+            step(&mut tracked, start + 10 * MIN),
+            Some(AlertState::Fired)
+        );
+
+        tracked.on_reading(&temperature(20.0), start + 11 * MIN);
+        tracked.on_reading(&temperature(31.0), start + 13 * MIN);
+        assert_eq!(step(&mut tracked, start + 20 * MIN), None);
+        // still firing, the condition is met again
+        assert!(tracked.firing.is_some());
+    }
+
+    #[test]
+    fn error_fires_and_reading_of_device_clears_it() {
+        let start = Instant::now();
+        let error = sht31_error();
+        let condition = Condition::Error {
+            device: error.device(),
+        };
+        let mut tracked = tracked(condition, start);
+
+        tracked.on_error(&error, start);
+        assert_eq!(
+            step(&mut tracked, start + 10 * MIN),
+            Some(AlertState::Fired)
+        );
+
+        let reading = error.device().info().affects_readings[0].clone();
+        tracked.on_reading(&reading, start + 11 * MIN);
+        assert_eq!(
+            step(&mut tracked, start + 16 * MIN),
+            Some(AlertState::Resolved)
+        );
+    }
+
+    #[test]
+    fn no_reading_fires_on_tick_and_resolves_on_arrival() {
+        let start = Instant::now();
+        let reading = temperature(0.0);
+        let max_interval = reading.device().info().max_sample_interval;
+        let condition = Condition::NoReading {
+            reading: reading.clone(),
+            factor: 3,
+        };
+        let mut tracked = tracked(condition, start);
+
+        tracked.on_tick(start + 3 * max_interval);
+        assert_eq!(step(&mut tracked, start + 3 * max_interval), None);
+
+        let silent = start + 3 * max_interval + Duration::from_secs(1);
+        tracked.on_tick(silent);
+        assert_eq!(step(&mut tracked, silent + 9 * MIN), None);
+        tracked.on_tick(silent + 10 * MIN);
+        assert_eq!(
+            step(&mut tracked, silent + 10 * MIN),
+            Some(AlertState::Fired)
+        );
+
+        let arrived = silent + 11 * MIN;
+        tracked.on_reading(&reading, arrived);
+        tracked.on_tick(arrived + MIN);
+        assert_eq!(step(&mut tracked, arrived + 4 * MIN), None);
+        assert_eq!(
+            step(&mut tracked, arrived + 5 * MIN),
+            Some(AlertState::Resolved)
+        );
+    }
+
+    #[test]
+    fn check_rejects_unknown_notifier_and_inverted_clear() {
+        let rule = |condition| Rule {
+            name: "test".to_string(),
+            condition,
+            fire_after: Duration::ZERO,
+            resolve_after: Duration::ZERO,
+            notify: vec!["phone".to_string()],
+        };
+        let above = Condition::Above {
+            reading: temperature(0.0),
+            threshold: 30.0,
+            clear: 25.0,
+        };
+        let mut rules = Rules {
+            notifiers: HashMap::new(),
+            rules: vec![rule(above)],
+        };
+        assert!(rules.check().is_err());
+
+        rules.notifiers.insert(
+            "phone".to_string(),
+            Notifier::Http {
+                url: "http://localhost".to_string(),
+                headers: Vec::new(),
+            },
+        );
+        assert!(rules.check().is_ok());
+
+        rules.rules[0].condition = Condition::Above {
+            reading: temperature(0.0),
+            threshold: 25.0,
+            clear: 30.0,
+        };
+        assert!(rules.check().is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use color_eyre::eyre::{bail, Context, Result};
+use color_eyre::Section;
+
+use super::Notifier;
+
+/// Give up on a notifier that takes longer than this
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub(super) async fn send(notifier: Notifier, text: String) -> Result<()> {
+    match notifier {
+        Notifier::Http { url, headers } => {
+            let mut request = reqwest::Client::new()
+                .post(&url)
+                .timeout(TIMEOUT)
+                .body(text);
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+            request
+                .send()
+                .await
+                .wrap_err("Could not send alert")
+                .with_note(|| format!("url: {url}"))?
+                .error_for_status()
+                .wrap_err("Server did not accept alert")
+                .with_note(|| format!("url: {url}"))?;
+        }
+        Notifier::Command { program, args } => {
+            let status = tokio::process::Command::new(&program)
+                .args(args)
+                .arg(text)
+                .kill_on_drop(true)
+                .status();
+            let status = tokio::time::timeout(TIMEOUT, status)
+                .await
+                .wrap_err("Command took too long")
+                .with_note(|| format!("program: {}", program.display()))?
+                .wrap_err("Could not run command")
+                .with_note(|| format!("program: {}", program.display()))?;
+            if !status.success() {
+                bail!("Command {} failed: {status}", program.display())
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn command_gets_text_as_last_argument() {
+        let notifier = Notifier::Command {
+            program: "sh".into(),
+            // the argument after the script becomes $0
+            args: vec![
+                "-c".to_string(),
+                r#"test "$0" = "co2 fired""#.to_string(),
+            ],
+        };
+        send(notifier.clone(), "co2 fired".to_string())
+            .await
+            .unwrap();
+        assert!(send(notifier, "other".to_string()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn http_posts_text_as_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+        let server = async {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"co2 fired") {
+                let n = conn.read(&mut buf).await.unwrap();
+                assert!(n > 0, "connection closed before body arrived");
+                request.extend_from_slice(&buf[..n]);
+            }
+            conn.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        };
+
+        let notifier = Notifier::Http {
+            url,
+            headers: vec![("Title".to_string(), "home".to_string())],
+        };
+        let (request, res) =
+            tokio::join!(server, send(notifier, "co2 fired".to_string()));
+        res.unwrap();
+        assert!(request.starts_with("POST /alerts"));
+        assert!(request.to_lowercase().contains("title: home"));
+    }
+}
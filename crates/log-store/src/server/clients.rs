@@ -1,34 +1,122 @@
-use super::db::{Logs, Stats};
+use std::path::PathBuf;
+
+use super::alert::Alerting;
+use super::db::{self, AffectorHistory, DecodeFailures, Logs, Stats};
 use crate::api::{self, ServerError};
 
-pub(crate) async fn handle(port: u16, stats: Stats, logs: Logs) -> color_eyre::Result<()> {
+pub(crate) async fn handle(
+    port: u16,
+    access: rpc::Access,
+    stats: Stats,
+    logs: Logs,
+    affectors: AffectorHistory,
+    decode_failures: DecodeFailures,
+    alerting: Alerting,
+    log_dir: PathBuf,
+    backup_root: Option<PathBuf>,
+) -> color_eyre::Result<()> {
     rpc::server::run(
         port,
-        move |req, _| {
-            let stats = stats.clone();
-            let logs = logs.clone();
-            perform_request(req, stats, logs)
+        access,
+        move |req, _, role| {
+            let stores = Stores {
+                stats: stats.clone(),
+                logs: logs.clone(),
+                affectors: affectors.clone(),
+                decode_failures: decode_failures.clone(),
+                alerting: alerting.clone(),
+                log_dir: log_dir.clone(),
+                backup_root: backup_root.clone(),
+            };
+            perform_request(req, role, stores)
         },
         Option::<rpc::SubscribersUnsupported<api::Response>>::None,
     )
     .await
 }
 
-async fn perform_request(request: api::Request, stats: Stats, logs: Logs) -> api::Response {
-    match perform_request_inner(request, stats, logs).await {
+struct Stores {
+    stats: Stats,
+    logs: Logs,
+    affectors: AffectorHistory,
+    decode_failures: DecodeFailures,
+    alerting: Alerting,
+    log_dir: PathBuf,
+    backup_root: Option<PathBuf>,
+}
+
+async fn perform_request(request: api::Request, role: rpc::Role, stores: Stores) -> api::Response {
+    match perform_request_inner(request, role, stores).await {
         Ok(resp) => resp,
         Err(e) => api::Response::Error(e),
     }
 }
 async fn perform_request_inner(
     request: api::Request,
-    stats: Stats,
-    logs: Logs,
+    role: rpc::Role,
+    stores: Stores,
 ) -> Result<api::Response, ServerError> {
+    let Stores {
+        stats,
+        logs,
+        affectors,
+        decode_failures,
+        alerting,
+        log_dir,
+        backup_root,
+    } = stores;
     Ok(match request {
         api::Request::Handshake { .. } => return Err(ServerError::AlreadyConnected),
         api::Request::GetLog { device, range } => api::Response::GetLog(logs.get(&device, range).await),
-        api::Request::GetStats(device) => api::Response::GetStats(stats.get(&device).await),
+        api::Request::GetStats { subject, window } => {
+            api::Response::GetStats(stats.get(&subject, window).await)
+        }
+        api::Request::GetArrival { subject, window } => {
+            api::Response::GetArrival(stats.arrival(&subject, window).await)
+        }
+        api::Request::SearchLogs {
+            query,
+            devices,
+            range,
+        } => api::Response::SearchLogs(logs.search(&query, devices.as_deref(), range).await),
+        api::Request::GetEpisodes { device, range } => {
+            api::Response::GetEpisodes(logs.episodes(&device, range).await)
+        }
+        api::Request::GetUnhealthy { flapping } => {
+            api::Response::GetUnhealthy(logs.unhealthy(flapping).await)
+        }
         api::Request::ListDevices => api::Response::ListDevices(logs.list_devices().await),
+        api::Request::GetAffectorHistory { affector, range } => {
+            api::Response::GetAffectorHistory(affectors.get(&affector, range).await)
+        }
+        api::Request::GetDecodeFailures { range } => {
+            api::Response::GetDecodeFailures(decode_failures.get(range).await)
+        }
+        api::Request::GetAlerts { range } => {
+            api::Response::GetAlerts(alerting.history().get(range).await)
+        }
+        api::Request::GetFiring => api::Response::GetFiring(alerting.firing().await),
+        api::Request::Snapshot { to } => {
+            if !role.includes(rpc::Role::Actuate) {
+                return Err(ServerError::NotAllowed);
+            }
+            let res = match snapshot::target_in(backup_root.as_deref(), &to) {
+                Ok(to) => {
+                    db::snapshot(
+                        &stats,
+                        &logs,
+                        &affectors,
+                        &decode_failures,
+                        alerting.history(),
+                        &log_dir,
+                        &to,
+                    )
+                    .await
+                }
+                Err(report) => Err(report),
+            }
+            .map_err(|report| format!("{report:?}"));
+            api::Response::Snapshot(res)
+        }
     })
 }
@@ -3,10 +3,27 @@ use std::path::Path;
 use std::time::{Duration, Instant};
 
 use data_server::api::subscriber::ReconnectingClient;
-use data_server::api::subscriber::SubMessage;
+use data_server::api::subscriber::{
+    Delivered, Filter, Kind, ResetAction, ResetDecision, SubMessage, SubscribeOptions,
+};
 
 use color_eyre::Result;
 
+use super::alert::Alerting;
+
+mod affectors;
+pub(crate) use affectors::AffectorHistory;
+
+mod alerts;
+pub(crate) use alerts::AlertHistory;
+
+mod decode_failures;
+pub(crate) use decode_failures::DecodeFailures;
+
+mod fixed_log;
+
+mod health;
+
 mod log;
 pub(crate) use log::Logs;
 
@@ -18,37 +35,134 @@ pub(crate) async fn run(
     data_server_addr: SocketAddr,
     stats: Stats,
     logs: Logs,
+    affectors: AffectorHistory,
+    decode_failures: DecodeFailures,
+    alerting: Alerting,
     log_dir: &Path,
 ) -> Result<()> {
-    let mut sub =
-        ReconnectingClient::new(data_server_addr, "ha-log-store".to_string()).subscribe();
+    let options = SubscribeOptions {
+        filter: Filter::default().of_kinds([
+            Kind::Reading,
+            Kind::ErrorReport,
+            Kind::AffectorControlled,
+            Kind::NodeReset,
+        ]),
+        ..SubscribeOptions::default()
+    };
+    let mut sub = ReconnectingClient::new(data_server_addr, "ha-log-store".to_string())
+        .subscribe_with(options);
 
-    let mut recently_logged = (Instant::now(), String::new());
+    let mut errors = ErrorLog::default();
     loop {
         let msg = sub.next().await;
         debug!("Got msg from data-server: {msg:?}");
-        let res = match msg {
+        match msg {
             SubMessage::Reading(reading) => {
-                if let Err(e) = stats.increment(reading.device()).await {
-                    Err(e)
-                } else {
-                    logs.clear_err(reading.device()).await
-                }
+                // independent, one failing should not stop the others
+                errors.report(stats.increment(&reading).await);
+                alerting.on_reading(&reading).await;
+                errors.report(logs.clear_err(reading.device()).await);
             }
-            SubMessage::ErrorReport(report) => logs.set_err(*report, log_dir).await,
-            SubMessage::AffectorControlled { .. } => continue,
-        };
+            SubMessage::ErrorReport(report) => {
+                alerting.on_error(&report).await;
+                errors.report(logs.set_err(*report, log_dir).await);
+            }
+            SubMessage::DecodeFailure(failure) => {
+                errors.report(decode_failures.record(*failure).await);
+            }
+            SubMessage::AffectorControlled {
+                affector,
+                controlled_by,
+                result,
+            } => errors.report(affectors.record(affector, controlled_by, result).await),
+            SubMessage::NodeReset(ResetDecision {
+                silent_for,
+                reset_affector,
+                action: ResetAction::Reset(result),
+                ..
+            }) => {
+                let controlled_by =
+                    format!("node-watchdog, silent for {}s", silent_for.as_secs());
+                let result = result.map(|()| Delivered::Send);
+                errors.report(affectors.record(reset_affector, controlled_by, result).await);
+            }
+            SubMessage::NodeReset(decision) => {
+                tracing::warn!("Node watchdog did not reset a silent node: {decision:?}");
+            }
+            SubMessage::Lagged { dropped } => {
+                tracing::warn!("Data-server dropped {dropped} messages, ignoring the gap in the stats");
+                stats.skip_next_intervals().await;
+            }
+        }
+    }
+}
+
+/// Logs errors but not the same one again within five minutes
+struct ErrorLog {
+    recently_logged: (Instant, String),
+}
+
+impl Default for ErrorLog {
+    fn default() -> Self {
+        Self {
+            recently_logged: (Instant::now(), String::new()),
+        }
+    }
+}
 
+impl ErrorLog {
+    fn report(&mut self, res: Result<()>) {
         const FIVE_MIN: Duration = Duration::from_secs(60 * 5);
-        if let Err(report) = res {
-            let e = format!("got error with report: {report:?}");
-            tracing::warn!("test: {e}");
-            if recently_logged.1 == e && recently_logged.0.elapsed() <= FIVE_MIN {
-                continue;
-            } else {
-                tracing::error!("Error processing new reading: {e}");
-                recently_logged = (Instant::now(), e);
-            }
+        let Err(report) = res else {
+            return;
+        };
+
+        let e = format!("got error with report: {report:?}");
+        tracing::warn!("test: {e}");
+        if self.recently_logged.1 == e && self.recently_logged.0.elapsed() <= FIVE_MIN {
+            return;
+        }
+        tracing::error!("Error processing new reading: {e}");
+        self.recently_logged = (Instant::now(), e);
+    }
+}
+
+/// Copies every file in the log dir to `to` except those in hidden dirs.
+/// The histograms are saved first. Nothing is logged while the files are
+/// staged, they are copied after logging resumes.
+pub(crate) async fn snapshot(
+    stats: &Stats,
+    logs: &Logs,
+    affectors: &AffectorHistory,
+    decode_failures: &DecodeFailures,
+    alerts: &AlertHistory,
+    log_dir: &Path,
+    to: &Path,
+) -> Result<snapshot::Summary> {
+    use color_eyre::eyre::Context;
+
+    let snapshot = snapshot::Snapshot::create(to)?;
+    let staged = {
+        let _stats = stats.pause(log_dir).await?;
+        let _logs = logs.pause().await?;
+        let _affectors = affectors.pause().await?;
+        let _decode_failures = decode_failures.pause().await?;
+        let _alerts = alerts.pause().await?;
+        snapshot::Staged::create(log_dir)?
+    };
+    tokio::task::spawn_blocking(move || staged.copy_to(snapshot))
+        .await
+        .wrap_err("Copying snapshot panicked")?
+}
+
+/// Cuts off the end of `text` such that it is at most `max_len` bytes
+fn truncate(mut text: String, max_len: usize) -> String {
+    if text.len() > max_len {
+        let mut end = max_len;
+        while !text.is_char_boundary(end) {
+            end -= 1;
         }
+        text.truncate(end);
     }
+    text
 }
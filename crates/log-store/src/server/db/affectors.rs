@@ -0,0 +1,99 @@
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use color_eyre::Result;
+use data_server::api::subscriber::{AffectorError, Delivered};
+use protocol::{Affector, IsSameAs};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::fixed_log::{FixedLog, Read};
+use crate::api::{AffectorActivation, GetAffectorHistoryResponse};
+
+/// Lines in the byteseries have a fixed size, longer names are truncated
+const MAX_NAME_LEN: usize = 64;
+/// The affector, the name including its length and the result
+const PAYLOAD_SIZE: usize = Affector::ENCODED_SIZE + MAX_NAME_LEN + 1 + 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct StoredActivation {
+    affector: Affector,
+    controlled_by: String,
+    result: Result<Delivered, AffectorError>,
+}
+
+pub(crate) type Log = FixedLog<StoredActivation>;
+
+/// Every attempt to control an affector, successful or not
+#[derive(Debug, Clone)]
+pub(crate) struct AffectorHistory(Arc<Mutex<Log>>);
+
+impl AffectorHistory {
+    /// No new activations are recorded until the returned guard is dropped,
+    /// everything recorded so far is on disk.
+    pub(crate) async fn pause(&self) -> Result<MutexGuard<'_, Log>> {
+        let mut log = self.0.lock().await;
+        log.flush()?;
+        Ok(log)
+    }
+
+    pub(crate) fn open_or_create(dir: &Path) -> Result<Self> {
+        let log = Log::open_or_create(
+            dir,
+            "affector_history",
+            "affector activations",
+            PAYLOAD_SIZE,
+        )
+        .wrap_err("Failed to open or create affector history")?;
+        Ok(Self(Arc::new(Mutex::new(log))))
+    }
+
+    pub(crate) async fn record(
+        &self,
+        affector: Affector,
+        controlled_by: String,
+        result: Result<Delivered, AffectorError>,
+    ) -> Result<()> {
+        let activation = StoredActivation {
+            affector,
+            controlled_by: super::truncate(controlled_by, MAX_NAME_LEN),
+            result,
+        };
+        self.0.lock().await.record(&activation).map(|_| ())
+    }
+
+    pub(crate) async fn get(
+        &self,
+        affector: &Affector,
+        range: RangeInclusive<jiff::Timestamp>,
+    ) -> GetAffectorHistoryResponse {
+        let matching =
+            |items: Vec<(jiff::Timestamp, StoredActivation)>| -> Vec<_> {
+                items
+                    .into_iter()
+                    .filter(|(_, stored)| stored.affector.is_same_as(affector))
+                    .map(|(at, stored)| AffectorActivation {
+                        at,
+                        affector: stored.affector,
+                        controlled_by: stored.controlled_by,
+                        result: stored.result,
+                    })
+                    .collect()
+            };
+
+        match self.0.lock().await.get(range) {
+            Ok(Read::All(items)) => {
+                GetAffectorHistoryResponse::All(matching(items))
+            }
+            Ok(Read::Partial { items, read_up_to }) => {
+                GetAffectorHistoryResponse::Partial {
+                    activations: matching(items),
+                    read_up_to,
+                }
+            }
+            Err(report) => GetAffectorHistoryResponse::Err(report),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::fixed_log::{FixedLog, Read};
+use crate::api::{AlertEvent, AlertState, GetAlertsResponse};
+
+/// Lines in the byteseries have a fixed size, longer rule names are
+/// truncated
+const MAX_RULE_LEN: usize = 64;
+/// Longer messages are truncated
+const MAX_MESSAGE_LEN: usize = 192;
+/// The rule, the state and the message. Strings need at most 3 bytes for
+/// their length.
+const PAYLOAD_SIZE: usize = 3 + MAX_RULE_LEN + 1 + 3 + MAX_MESSAGE_LEN;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct StoredAlert {
+    rule: String,
+    state: AlertState,
+    message: String,
+}
+
+pub(crate) type Log = FixedLog<StoredAlert>;
+
+/// Every time an alert rule fired or resolved
+#[derive(Debug, Clone)]
+pub(crate) struct AlertHistory(Arc<Mutex<Log>>);
+
+impl AlertHistory {
+    /// No new alerts are recorded until the returned guard is dropped,
+    /// everything recorded so far is on disk.
+    pub(crate) async fn pause(&self) -> Result<MutexGuard<'_, Log>> {
+        let mut log = self.0.lock().await;
+        log.flush()?;
+        Ok(log)
+    }
+
+    pub(crate) fn open_or_create(dir: &Path) -> Result<Self> {
+        let log = Log::open_or_create(
+            dir,
+            "alerts",
+            "alerts that fired or resolved",
+            PAYLOAD_SIZE,
+        )
+        .wrap_err("Failed to open or create alert history")?;
+        Ok(Self(Arc::new(Mutex::new(log))))
+    }
+
+    pub(crate) async fn record(
+        &self,
+        rule: String,
+        state: AlertState,
+        message: String,
+    ) -> Result<AlertEvent> {
+        let alert = StoredAlert {
+            rule: super::truncate(rule, MAX_RULE_LEN),
+            state,
+            message: super::truncate(message, MAX_MESSAGE_LEN),
+        };
+        let at = self.0.lock().await.record(&alert)?;
+        Ok(AlertEvent {
+            at,
+            rule: alert.rule,
+            state: alert.state,
+            message: alert.message,
+        })
+    }
+
+    pub(crate) async fn get(
+        &self,
+        range: RangeInclusive<jiff::Timestamp>,
+    ) -> GetAlertsResponse {
+        let events = |items: Vec<(jiff::Timestamp, StoredAlert)>| -> Vec<_> {
+            items
+                .into_iter()
+                .map(|(at, stored)| AlertEvent {
+                    at,
+                    rule: stored.rule,
+                    state: stored.state,
+                    message: stored.message,
+                })
+                .collect()
+        };
+
+        match self.0.lock().await.get(range) {
+            Ok(Read::All(items)) => GetAlertsResponse::All(events(items)),
+            Ok(Read::Partial { items, read_up_to }) => {
+                GetAlertsResponse::Partial {
+                    alerts: events(items),
+                    read_up_to,
+                }
+            }
+            Err(report) => GetAlertsResponse::Err(report),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use color_eyre::Result;
+use data_server::api::subscriber::DecodeFailure;
+use protocol::Affector;
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::fixed_log::{FixedLog, Read};
+use crate::api::{GetDecodeFailuresResponse, LoggedDecodeFailure};
+
+/// Lines in the byteseries have a fixed size, longer errors are truncated
+const MAX_ERROR_LEN: usize = 128;
+/// Nodes registering more affectors have the rest cut off
+const MAX_AFFECTORS: usize = 10;
+/// Space for the peer address, the affectors, the header, the hex prefix
+/// and the error. Strings and lists need at most 3 bytes for their length.
+const PAYLOAD_SIZE: usize = 32
+    + 3
+    + MAX_AFFECTORS * Affector::ENCODED_SIZE
+    + 1
+    + 3
+    + DecodeFailure::HEX_PREFIX_LEN * 3
+    + 3
+    + MAX_ERROR_LEN;
+
+pub(crate) type Log = FixedLog<DecodeFailure>;
+
+/// Messages from nodes that the data-server could not decode
+#[derive(Debug, Clone)]
+pub(crate) struct DecodeFailures(Arc<Mutex<Log>>);
+
+impl DecodeFailures {
+    /// No new failures are recorded until the returned guard is dropped,
+    /// everything recorded so far is on disk.
+    pub(crate) async fn pause(&self) -> Result<MutexGuard<'_, Log>> {
+        let mut log = self.0.lock().await;
+        log.flush()?;
+        Ok(log)
+    }
+
+    pub(crate) fn open_or_create(dir: &Path) -> Result<Self> {
+        let log = Log::open_or_create(
+            dir,
+            "decode_failures",
+            "messages nodes send that could not be decoded",
+            PAYLOAD_SIZE,
+        )
+        .wrap_err("Failed to open or create decode failure log")?;
+        Ok(Self(Arc::new(Mutex::new(log))))
+    }
+
+    pub(crate) async fn record(
+        &self,
+        mut failure: DecodeFailure,
+    ) -> Result<()> {
+        failure.affectors.truncate(MAX_AFFECTORS);
+        failure.error = super::truncate(failure.error, MAX_ERROR_LEN);
+        self.0.lock().await.record(&failure).map(|_| ())
+    }
+
+    pub(crate) async fn get(
+        &self,
+        range: RangeInclusive<jiff::Timestamp>,
+    ) -> GetDecodeFailuresResponse {
+        let logged = |items: Vec<(jiff::Timestamp, DecodeFailure)>| -> Vec<_> {
+            items
+                .into_iter()
+                .map(|(at, failure)| LoggedDecodeFailure { at, failure })
+                .collect()
+        };
+
+        match self.0.lock().await.get(range) {
+            Ok(Read::All(items)) => {
+                GetDecodeFailuresResponse::All(logged(items))
+            }
+            Ok(Read::Partial { items, read_up_to }) => {
+                GetDecodeFailuresResponse::Partial {
+                    failures: logged(items),
+                    read_up_to,
+                }
+            }
+            Err(report) => GetDecodeFailuresResponse::Err(report),
+        }
+    }
+}
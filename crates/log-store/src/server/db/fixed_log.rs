@@ -0,0 +1,215 @@
+use std::io;
+use std::iter;
+use std::marker::PhantomData;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use byteseries::file::OpenError as FileOpenError;
+use byteseries::{series, ByteSeries};
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::{Result, Section};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use series::data::OpenError as DataOpenError;
+use series::Error::Open;
+use tracing::{info, instrument};
+
+/// At most this many items are returned by one [`FixedLog::get`]
+const MAX_IN_ONE_READ: usize = 200;
+
+/// History of bincode encoded items, each padded to a line of the same
+/// size and stored under the millisecond it was recorded at.
+#[derive(derivative::Derivative)]
+#[derivative(Debug)]
+pub(crate) struct FixedLog<T> {
+    #[derivative(Debug = "ignore")]
+    history: ByteSeries,
+    last_timestamp_pushed: Option<u64>,
+    payload_size: usize,
+    name: &'static str,
+    #[derivative(Debug = "ignore")]
+    item: PhantomData<T>,
+}
+
+/// The items from one [`FixedLog::get`], oldest first
+pub(crate) enum Read<T> {
+    /// everything in the requested range
+    All(Vec<(jiff::Timestamp, T)>),
+    /// there is more, continue after `read_up_to`
+    Partial {
+        items: Vec<(jiff::Timestamp, T)>,
+        read_up_to: jiff::Timestamp,
+    },
+}
+
+impl<T> FixedLog<T>
+where
+    T: Serialize + DeserializeOwned + std::fmt::Debug,
+{
+    /// The file is named `name` and describes its content as: "Bincode
+    /// encoded `description`".
+    #[instrument]
+    pub(crate) fn open_or_create(
+        dir: &Path,
+        name: &'static str,
+        description: &str,
+        payload_size: usize,
+    ) -> Result<Self> {
+        let path = dir.join(name);
+        let header = format!(
+            "Bincode encoded {description}. \
+            Each line has size: {payload_size} + 2"
+        );
+
+        let res = ByteSeries::builder()
+            .payload_size(payload_size)
+            .with_header(header.as_bytes().to_vec())
+            .open(&path);
+
+        let history = match res {
+            Ok((byteseries, _)) => byteseries,
+            Err(Open(DataOpenError::File {
+                source: FileOpenError::Io(e),
+                ..
+            })) if e.kind() == io::ErrorKind::NotFound => {
+                std::fs::create_dir_all(dir)
+                    .wrap_err("Could not create log dir")
+                    .with_note(|| format!("dir: {}", dir.display()))?;
+                info!("creating new byteseries for {name}");
+                ByteSeries::builder()
+                    .payload_size(payload_size)
+                    .with_header(header.into_bytes())
+                    .create_new(true)
+                    .open(&path)
+                    .wrap_err("Could not create new byteseries")
+                    .with_note(|| format!("path: {}", path.display()))?
+                    .0
+            }
+            Err(e) => {
+                return Err(e)
+                    .wrap_err("Could not open existing byteseries")
+                    .with_note(|| format!("path: {}", path.display()))
+            }
+        };
+
+        Ok(Self {
+            history,
+            last_timestamp_pushed: None,
+            payload_size,
+            name,
+            item: PhantomData,
+        })
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        self.history
+            .flush_to_disk()
+            .wrap_err("Could not flush history to disk")
+            .with_note(|| format!("log: {}", self.name))
+    }
+
+    /// Returns the timestamp the item was recorded under
+    pub(crate) fn record(&mut self, item: &T) -> Result<jiff::Timestamp> {
+        let line =
+            bincode::serde::encode_to_vec(item, bincode::config::standard())
+                .wrap_err("Could not serialize item")
+                .with_note(|| format!("log: {}", self.name))?;
+        if line.len() > self.payload_size {
+            return Err(eyre!(
+                "Serialized item is {} bytes, more then fits in a line ({})",
+                line.len(),
+                self.payload_size,
+            ))
+            .with_note(|| format!("log: {}", self.name))
+            .with_note(|| format!("item: {item:?}"));
+        }
+        let line: Vec<_> = line
+            .into_iter()
+            .chain(iter::repeat(0))
+            .take(self.payload_size)
+            .collect();
+
+        let mut ts = jiff::Timestamp::now().as_millisecond() as u64;
+        // timestamps must increase, items can be recorded within the same
+        // millisecond
+        if let Some(last) = self.last_timestamp_pushed {
+            ts = ts.max(last + 1);
+        }
+        self.history
+            .push_line(ts, line)
+            .wrap_err("Could not push item into history")
+            .with_note(|| format!("log: {}", self.name))?;
+        self.last_timestamp_pushed = Some(ts);
+        Ok(to_timestamp(ts))
+    }
+
+    /// The error is a formatted report
+    pub(crate) fn get(
+        &mut self,
+        range: RangeInclusive<jiff::Timestamp>,
+    ) -> Result<Read<T>, String> {
+        use byteseries::seek::Error::{
+            EmptyFile, StartAfterData, StopBeforeData,
+        };
+        use byteseries::series::Error::InvalidRange;
+
+        let ts_range = RangeInclusive::new(
+            range.start().as_millisecond() as u64,
+            range.end().as_millisecond() as u64,
+        );
+
+        let mut timestamps = Vec::new();
+        let mut data = Vec::new();
+        match self.history.read_first_n(
+            MAX_IN_ONE_READ,
+            &mut Decoder(PhantomData),
+            ts_range,
+            &mut timestamps,
+            &mut data,
+        ) {
+            Ok(()) => (),
+            Err(InvalidRange(
+                StopBeforeData | StartAfterData { .. } | EmptyFile,
+            )) => return Ok(Read::All(Vec::new())),
+            Err(other) => {
+                let report = color_eyre::eyre::Report::new(other)
+                    .wrap_err("Could not read history from disk")
+                    .with_note(|| format!("log: {}", self.name));
+                return Err(format!("{report:?}"));
+            }
+        }
+
+        let read_up_to = timestamps.last().copied();
+        let read_all = timestamps.len() < MAX_IN_ONE_READ;
+        let items = timestamps
+            .into_iter()
+            .zip(data)
+            .map(|(at, item)| Ok((to_timestamp(at), item?)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(match read_up_to {
+            Some(read_up_to) if !read_all => Read::Partial {
+                items,
+                read_up_to: to_timestamp(read_up_to),
+            },
+            _ => Read::All(items),
+        })
+    }
+}
+
+fn to_timestamp(ts: u64) -> jiff::Timestamp {
+    jiff::Timestamp::from_millisecond(ts as i64)
+        .expect("was a jiff::Timestamp before it became a u64")
+}
+
+#[derive(Debug)]
+struct Decoder<T>(PhantomData<T>);
+impl<T: DeserializeOwned + std::fmt::Debug> byteseries::Decoder for Decoder<T> {
+    type Item = Result<T, String>;
+
+    fn decode_payload(&mut self, payload: &[u8]) -> Self::Item {
+        bincode::serde::decode_from_slice(payload, bincode::config::standard())
+            .map(|(item, _)| item)
+            .map_err(|e| format!("Could not decode line from disk: {e}"))
+    }
+}
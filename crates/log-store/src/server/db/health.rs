@@ -0,0 +1,216 @@
+use std::ops::{ControlFlow, RangeInclusive};
+use std::time::Duration;
+
+use jiff::SignedDuration;
+use protocol::Device;
+
+use super::Logs;
+use crate::api::{Episode, ErrorEvent, FlapThreshold, UnhealthyDevice};
+
+/// An identical error that starts within this time after the previous one
+/// ended continues its episode
+const EPISODE_GAP: SignedDuration = SignedDuration::from_secs(10 * 60);
+
+impl Logs {
+    pub(crate) async fn episodes(
+        &self,
+        device: &Device,
+        range: RangeInclusive<jiff::Timestamp>,
+    ) -> Result<Vec<Episode>, String> {
+        let mut map = self.0.lock().await;
+        let Some(log) = map.get_mut(device) else {
+            return Ok(Vec::new());
+        };
+
+        let now = jiff::Timestamp::now();
+        let mut open: Vec<Episode> = Vec::new();
+        let mut closed = Vec::new();
+        log.read_events(&range, |event| {
+            add_to_episodes(event, now, &mut open, &mut closed);
+            ControlFlow::Continue(())
+        })
+        .map_err(read_error)?;
+
+        closed.extend(open);
+        closed.sort_by_key(|episode| episode.first_seen);
+        Ok(closed)
+    }
+
+    /// Only reads the errors within the window of the threshold and stops
+    /// once a device is known to be flapping
+    pub(crate) async fn unhealthy(
+        &self,
+        threshold: FlapThreshold,
+    ) -> Result<Vec<UnhealthyDevice>, String> {
+        let now = jiff::Timestamp::now();
+        let window_start = jiff::SignedDuration::try_from(threshold.within)
+            .ok()
+            .and_then(|within| now.checked_sub(within).ok())
+            .unwrap_or(jiff::Timestamp::UNIX_EPOCH);
+
+        let mut map = self.0.lock().await;
+        let mut unhealthy = Vec::new();
+        for (device, log) in map.iter_mut() {
+            let mut recent_errors = 0;
+            log.read_events(&(window_start..=now), |_| {
+                recent_errors += 1;
+                if recent_errors > threshold.errors {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .map_err(read_error)?;
+
+            let current = log.current();
+            let flapping = recent_errors > threshold.errors;
+            if current.is_some() || flapping {
+                unhealthy.push(UnhealthyDevice {
+                    device: device.clone(),
+                    current,
+                    recent_errors,
+                    flapping,
+                });
+            }
+        }
+        Ok(unhealthy)
+    }
+}
+
+/// Events should be added oldest first. One that starts before the
+/// previous one ended, for example because it arrived late, continues the
+/// episode. Only the part of an event outside the episode adds downtime.
+fn add_to_episodes(
+    event: ErrorEvent,
+    now: jiff::Timestamp,
+    open: &mut Vec<Episode>,
+    closed: &mut Vec<Episode>,
+) {
+    let ErrorEvent { start, end, error } = event;
+    let ongoing = end.is_none();
+    let end = end.unwrap_or(now).max(start);
+    let duration = start.duration_until(end).unsigned_abs();
+
+    // episodes that have been quiet for too long are done, a negative gap
+    // means the event overlaps the episode
+    let (still_open, done): (Vec<_>, Vec<_>) =
+        open.drain(..).partition(|episode| {
+            episode.last_seen.duration_until(start) <= EPISODE_GAP
+        });
+    *open = still_open;
+    closed.extend(done);
+
+    if let Some(episode) =
+        open.iter_mut().find(|episode| episode.error == error)
+    {
+        let before = start.duration_until(episode.first_seen.min(end));
+        let after = episode.last_seen.max(start).duration_until(end);
+        for extends in [before, after] {
+            episode.downtime += extends.try_into().unwrap_or(Duration::ZERO);
+        }
+        // the event that ends last tells if the error is still there
+        if end >= episode.last_seen {
+            episode.ongoing = ongoing;
+        }
+        episode.count += 1;
+        episode.first_seen = episode.first_seen.min(start);
+        episode.last_seen = episode.last_seen.max(end);
+    } else {
+        open.push(Episode {
+            error,
+            count: 1,
+            first_seen: start,
+            last_seen: end,
+            ongoing,
+            downtime: duration,
+        });
+    }
+}
+
+fn read_error(err: byteseries::series::Error) -> String {
+    let report = color_eyre::eyre::Report::new(err)
+        .wrap_err("Could not read log events from disk");
+    format!("{report:?}")
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use protocol::large_bedroom::{self, bed};
+
+    use super::*;
+
+    fn error(text: &str) -> protocol::Error {
+        protocol::Error::LargeBedroom(large_bedroom::Error::Bed(
+            bed::Error::Setup(bed::SensorError::Sht31(
+                heapless::String::from_str(text).unwrap(),
+            )),
+        ))
+    }
+
+    fn at(minute: i64) -> jiff::Timestamp {
+        jiff::Timestamp::from_second(minute * 60).unwrap()
+    }
+
+    fn episodes(events: &[(i64, Option<i64>, &str)]) -> Vec<Episode> {
+        let now = at(1000);
+        let mut open = Vec::new();
+        let mut closed = Vec::new();
+        for (start, end, text) in events {
+            let event = ErrorEvent {
+                start: at(*start),
+                end: end.map(at),
+                error: error(text),
+            };
+            add_to_episodes(event, now, &mut open, &mut closed);
+        }
+        closed.extend(open);
+        closed.sort_by_key(|episode| episode.first_seen);
+        closed
+    }
+
+    #[test]
+    fn repeats_within_gap_merge() {
+        let list =
+            episodes(&[(0, Some(1), "a"), (5, Some(6), "a"), (8, None, "a")]);
+        assert_eq!(list.len(), 1);
+        let episode = &list[0];
+        assert_eq!(episode.count, 3);
+        assert_eq!(episode.first_seen, at(0));
+        assert_eq!(episode.last_seen, at(1000));
+        assert!(episode.ongoing);
+    }
+
+    #[test]
+    fn long_gap_or_other_error_splits() {
+        let list = episodes(&[
+            (0, Some(1), "a"),
+            (3, Some(4), "b"),
+            (20, Some(21), "a"),
+        ]);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.iter().map(|e| e.count).sum::<usize>(), 3);
+        assert_eq!(list[0].downtime, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn overlapping_event_continues_episode() {
+        // the second event started before the first ended
+        let list = episodes(&[(10, Some(30), "a"), (5, Some(12), "a")]);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].count, 2);
+        assert_eq!(list[0].first_seen, at(5));
+        assert_eq!(list[0].last_seen, at(30));
+        // 5 to 30 minutes, the overlap is only counted once
+        assert_eq!(list[0].downtime, Duration::from_secs(25 * 60));
+    }
+
+    #[test]
+    fn ended_late_event_does_not_end_ongoing_episode() {
+        let list = episodes(&[(10, None, "a"), (12, Some(14), "a")]);
+        assert_eq!(list.len(), 1);
+        assert!(list[0].ongoing);
+        assert_eq!(list[0].downtime, Duration::from_secs(990 * 60));
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::io::{self, Read, Write};
 use std::iter;
+use std::ops::ControlFlow;
 use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -13,19 +14,27 @@ use protocol::Device;
 use serde::{Deserialize, Serialize};
 use series::data::OpenError as DataOpenError;
 use series::Error::Open;
-use tokio::sync::Mutex;
+use snapshot::Replacement;
+use tokio::sync::{Mutex, MutexGuard};
 use tracing::{debug, info, instrument, warn};
 
-use crate::api::{self, ErrorEvent, GetLogResponse};
+use crate::api::{
+    self, ErrorEvent, GetLogResponse, LogQuery, SearchLogsResponse,
+};
 
 #[derive(derivative::Derivative)]
 #[derivative(Debug)]
 pub(crate) struct Log {
     #[derivative(Debug = "ignore")]
     history: ByteSeries,
+    last_timestamp_pushed: Option<u64>,
     current: CurrentError,
 }
 
+/// Part of the header of histories keyed by the millisecond the error
+/// started, older ones are keyed by the second.
+const MILLISECOND_KEYS: &str = "Keyed by start in unix milliseconds.";
+
 #[derive(Debug)]
 struct CurrentError {
     file: std::fs::File,
@@ -111,10 +120,17 @@ impl Log {
 
         let payload_size = protocol::Error::max_size();
         let header = format!(
-            "Bincode encoded error logs for {device:?}. \
+            "Bincode encoded error logs for {device:?}. {MILLISECOND_KEYS} \
             Each line has size: {payload_size} + 2"
         );
 
+        let data_path = path.with_extension("byteseries");
+        if data_path.exists() && !keyed_by_millisecond(&data_path)? {
+            migrate_to_millisecond_keys(dir, &path, device, &header)
+                .wrap_err("Could not migrate history to millisecond keys")
+                .with_note(|| format!("path: {}", path.display()))?;
+        }
+
         let res = ByteSeries::builder()
             .payload_size(payload_size)
             .with_header(header.as_bytes().to_vec())
@@ -150,6 +166,7 @@ impl Log {
 
         Ok(Self {
             history,
+            last_timestamp_pushed: None,
             current: CurrentError::open_or_create(&path)
                 .wrap_err("Could not setup current error store")
                 .with_note(|| format!("device: {device:?}"))
@@ -157,31 +174,19 @@ impl Log {
         })
     }
 
+    fn flush(&mut self) -> Result<()> {
+        self.history
+            .flush_to_disk()
+            .wrap_err("Could not flush history to disk")
+    }
+
     #[instrument]
     pub fn set_err(&mut self, new_report: protocol::Error) -> Result<()> {
         if let Some((started, report)) = self.current.get() {
             if report == &new_report {
                 return Ok(());
             }
-            let line = StoredErrorEvent {
-                end: jiff::Timestamp::now(),
-                error: report.clone(),
-            };
-            let line = bincode::serde::encode_to_vec(
-                &line,
-                bincode::config::standard(),
-            )
-            .wrap_err("Could not serialize ErrorEvent")?;
-            let payload_size = protocol::Error::max_size();
-            let line: Vec<_> = line
-                .into_iter()
-                .chain(iter::repeat(0))
-                .take(payload_size)
-                .collect();
-
-            self.history
-                .push_line(started.as_second() as u64, line)
-                .wrap_err("Could not push new ErrorEvent into history")?;
+            self.push_ended(*started, report.clone())?;
         }
 
         debug!("Registered new error: {new_report}");
@@ -190,12 +195,50 @@ impl Log {
             .wrap_err("Failed to set new error in current error store")
     }
 
+    /// Moves the current error, if any, to the history. From there it is
+    /// returned by `get` with its end set to when it was cleared.
     #[instrument]
     fn clear(&mut self) -> Result<()> {
-        self.current
+        let ended = self
+            .current
             .take()
             .wrap_err("failed to set value of current error to None")?;
-        debug!("Cleared any error");
+        if let Some((started, report)) = ended {
+            self.push_ended(started, report)?;
+            debug!("Cleared error");
+        }
+        Ok(())
+    }
+
+    fn push_ended(
+        &mut self,
+        started: jiff::Timestamp,
+        error: protocol::Error,
+    ) -> Result<()> {
+        let line = StoredErrorEvent {
+            end: jiff::Timestamp::now(),
+            error,
+        };
+        let line =
+            bincode::serde::encode_to_vec(&line, bincode::config::standard())
+                .wrap_err("Could not serialize ErrorEvent")?;
+        let payload_size = protocol::Error::max_size();
+        let line: Vec<_> = line
+            .into_iter()
+            .chain(iter::repeat(0))
+            .take(payload_size)
+            .collect();
+
+        let mut ts = started.as_millisecond() as u64;
+        // timestamps must increase, a flapping device can start errors
+        // within the same millisecond
+        if let Some(last) = self.last_timestamp_pushed {
+            ts = ts.max(last + 1);
+        }
+        self.history
+            .push_line(ts, line)
+            .wrap_err("Could not push new ErrorEvent into history")?;
+        self.last_timestamp_pushed = Some(ts);
         Ok(())
     }
 
@@ -210,8 +253,8 @@ impl Log {
         const MAX_IN_ONE_READ: usize = 200;
 
         let ts_range = RangeInclusive::new(
-            range.start().as_second() as u64,
-            range.end().as_second() as u64,
+            range.start().as_millisecond() as u64,
+            range.end().as_millisecond() as u64,
         );
 
         let current = self
@@ -251,7 +294,7 @@ impl Log {
             .into_iter()
             .zip(data)
             .map(|(start, StoredErrorEvent { end, error })| api::ErrorEvent {
-                start: jiff::Timestamp::from_second(start as i64)
+                start: jiff::Timestamp::from_millisecond(start as i64)
                     .expect("was a jiff::Timestamp before it became a u64"),
                 end: Some(end),
                 error,
@@ -268,6 +311,131 @@ impl Log {
             GetLogResponse::Partial(res)
         }
     }
+
+    /// Calls `on_event` for every event that started within the range
+    /// oldest first, ending with the current error. Reads in chunks so
+    /// memory use stays bounded for long ranges.
+    pub(super) fn read_events(
+        &mut self,
+        range: &RangeInclusive<jiff::Timestamp>,
+        mut on_event: impl FnMut(ErrorEvent) -> ControlFlow<()>,
+    ) -> Result<(), byteseries::series::Error> {
+        use byteseries::seek::Error::{
+            EmptyFile, StartAfterData, StopBeforeData,
+        };
+        use byteseries::series::Error::InvalidRange;
+        const CHUNK: usize = 200;
+
+        let end = range.end().as_millisecond() as u64;
+        let mut chunk_start = range.start().as_millisecond() as u64;
+        loop {
+            let mut timestamps = Vec::new();
+            let mut data = Vec::new();
+            match self.history.read_first_n(
+                CHUNK,
+                &mut Decoder,
+                chunk_start..=end,
+                &mut timestamps,
+                &mut data,
+            ) {
+                Ok(()) => (),
+                Err(InvalidRange(
+                    StopBeforeData | StartAfterData { .. } | EmptyFile,
+                )) => break,
+                Err(other) => return Err(other),
+            }
+
+            let read = timestamps.len();
+            let last_read = timestamps.last().copied();
+            for (start, StoredErrorEvent { end, error }) in
+                timestamps.into_iter().zip(data)
+            {
+                let event = ErrorEvent {
+                    start: jiff::Timestamp::from_millisecond(start as i64)
+                        .expect("was a jiff::Timestamp before it became a u64"),
+                    end: Some(end),
+                    error,
+                };
+                if on_event(event).is_break() {
+                    return Ok(());
+                }
+            }
+
+            match last_read {
+                Some(last) if read == CHUNK && last < end => {
+                    chunk_start = last + 1;
+                }
+                _ => break,
+            }
+        }
+
+        if let Some((start, error)) = &self.current.value {
+            if range.contains(start) {
+                let _ = on_event(ErrorEvent {
+                    start: *start,
+                    end: None,
+                    error: error.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The first `max` events in the range that match, more if events
+    /// after those started in the same millisecond. Stops early once
+    /// `max_scanned` events have been looked at, then everything up to
+    /// and including the returned millisecond has been searched.
+    fn search(
+        &mut self,
+        query: &LogQuery,
+        range: &RangeInclusive<jiff::Timestamp>,
+        max: usize,
+        max_scanned: usize,
+    ) -> Result<Searched, byteseries::series::Error> {
+        let mut found: Vec<ErrorEvent> = Vec::new();
+        let mut scanned = 0;
+        let mut last_scanned = None;
+        let mut scanned_up_to = None;
+        self.read_events(range, |event| {
+            let new_millisecond = last_scanned != Some(event.start);
+            if found.len() >= max
+                && found.last().is_some_and(|last| last.start != event.start)
+            {
+                return ControlFlow::Break(());
+            }
+            if scanned >= max_scanned && new_millisecond {
+                scanned_up_to = last_scanned;
+                return ControlFlow::Break(());
+            }
+            scanned += 1;
+            last_scanned = Some(event.start);
+            if query.matches(&event.error) {
+                found.push(event);
+            }
+            ControlFlow::Continue(())
+        })?;
+        Ok(Searched {
+            found,
+            scanned_up_to,
+        })
+    }
+
+    pub(super) fn current(&self) -> Option<ErrorEvent> {
+        self.current
+            .value
+            .as_ref()
+            .map(|(start, error)| ErrorEvent {
+                start: *start,
+                end: None,
+                error: error.clone(),
+            })
+    }
+}
+
+struct Searched {
+    found: Vec<ErrorEvent>,
+    /// set if the search stopped before the end of the range
+    scanned_up_to: Option<jiff::Timestamp>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -292,6 +460,18 @@ impl byteseries::Decoder for Decoder {
 pub(crate) struct Logs(pub(crate) Arc<Mutex<HashMap<protocol::Device, Log>>>);
 
 impl Logs {
+    /// No errors are logged until the returned guard is dropped, everything
+    /// logged so far is on disk.
+    pub(crate) async fn pause(
+        &self,
+    ) -> Result<MutexGuard<'_, HashMap<protocol::Device, Log>>> {
+        let mut map = self.0.lock().await;
+        for log in map.values_mut() {
+            log.flush()?;
+        }
+        Ok(map)
+    }
+
     pub async fn set_err(
         &self,
         report: protocol::Error,
@@ -323,6 +503,75 @@ impl Logs {
         }
     }
 
+    /// Searches the logs of all devices or only those in `devices`. Results
+    /// are sorted by start, if there are too many the first are returned
+    /// together with where to continue. The logs are searched one device
+    /// at a time and each search scans a limited number of events, so
+    /// logging is never blocked for long.
+    pub(crate) async fn search(
+        &self,
+        query: &LogQuery,
+        devices: Option<&[Device]>,
+        range: RangeInclusive<jiff::Timestamp>,
+    ) -> SearchLogsResponse {
+        const MAX_IN_ONE_RESPONSE: usize = 200;
+        const MAX_SCANNED_PER_DEVICE: usize = 5_000;
+
+        let to_search: Vec<_> = self
+            .0
+            .lock()
+            .await
+            .keys()
+            .filter(|device| devices.is_none_or(|list| list.contains(*device)))
+            .cloned()
+            .collect();
+
+        let mut found = Vec::new();
+        // events after this have not been searched for every device
+        let mut searched_up_to: Option<jiff::Timestamp> = None;
+        for device in to_search {
+            let mut map = self.0.lock().await;
+            let Some(log) = map.get_mut(&device) else {
+                continue; // removed in the meantime
+            };
+            match log.search(
+                query,
+                &range,
+                MAX_IN_ONE_RESPONSE,
+                MAX_SCANNED_PER_DEVICE,
+            ) {
+                Ok(searched) => {
+                    found.extend(searched.found);
+                    if let Some(up_to) = searched.scanned_up_to {
+                        searched_up_to = Some(
+                            searched_up_to.map_or(up_to, |t| t.min(up_to)),
+                        );
+                    }
+                }
+                Err(err) => {
+                    let report = color_eyre::eyre::Report::new(err)
+                        .wrap_err("Could not read log events from disk");
+                    return SearchLogsResponse::Err(format!("{report:?}"));
+                }
+            }
+        }
+
+        found.sort_by_key(|event| event.start);
+        // keep events that started in the same millisecond together, the
+        // next request starts a millisecond after the last one returned
+        let too_many = (found.len() > MAX_IN_ONE_RESPONSE)
+            .then(|| found[MAX_IN_ONE_RESPONSE - 1].start);
+        let read_up_to = too_many.into_iter().chain(searched_up_to).min();
+        let Some(read_up_to) = read_up_to else {
+            return SearchLogsResponse::All(found);
+        };
+        found.retain(|event| event.start <= read_up_to);
+        SearchLogsResponse::Partial {
+            events: found,
+            read_up_to,
+        }
+    }
+
     pub(crate) async fn clear_err(
         &self,
         device: protocol::Device,
@@ -340,8 +589,115 @@ impl Logs {
     }
 }
 
+/// Needs the path to the byteseries data file
+fn keyed_by_millisecond(path: &Path) -> Result<bool> {
+    let mut start = Vec::with_capacity(4096);
+    std::fs::File::open(path)
+        .wrap_err("Could not open history")?
+        .take(4096)
+        .read_to_end(&mut start)
+        .wrap_err("Could not read start of history")?;
+    Ok(String::from_utf8_lossy(&start).contains(MILLISECOND_KEYS))
+}
+
+/// Histories used to be keyed by the second the error started, copies every
+/// event into a history keyed by millisecond. The original files are moved
+/// to `.backups/second-keys-<unix seconds>` in the log dir.
+fn migrate_to_millisecond_keys(
+    dir: &Path,
+    path: &Path,
+    device: &Device,
+    header: &str,
+) -> Result<()> {
+    use byteseries::seek::Error::{EmptyFile, StartAfterData, StopBeforeData};
+    use byteseries::series::Error::InvalidRange;
+    const CHUNK: usize = 1000;
+
+    let replacement =
+        Replacement::start(dir, path, ".migrating", Some("second-keys"))?;
+
+    let payload_size = protocol::Error::max_size();
+    let old_header = format!(
+        "Bincode encoded error logs for {device:?}. \
+        Each line has size: {payload_size} + 2"
+    );
+    let (mut input, _) = ByteSeries::builder()
+        .payload_size(payload_size)
+        .with_header(old_header.into_bytes())
+        .open(path)
+        .wrap_err("Could not open history keyed by second")?;
+    let (mut output, _) = ByteSeries::builder()
+        .payload_size(payload_size)
+        .with_header(header.as_bytes().to_vec())
+        .create_new(true)
+        .open(replacement.work_path())
+        .wrap_err("Could not create history keyed by millisecond")?;
+
+    let mut lines = 0;
+    let mut read_start = 0;
+    let mut last_pushed: Option<u64> = None;
+    loop {
+        let mut timestamps = Vec::new();
+        let mut data = Vec::new();
+        match input.read_first_n(
+            CHUNK,
+            &mut Decoder,
+            read_start..=u64::MAX,
+            &mut timestamps,
+            &mut data,
+        ) {
+            Ok(()) => (),
+            Err(InvalidRange(
+                StopBeforeData | StartAfterData { .. } | EmptyFile,
+            )) => break,
+            Err(other) => {
+                return Err(other)
+                    .wrap_err("Could not read history keyed by second")
+            }
+        }
+
+        let read = timestamps.len();
+        for (second, event) in timestamps.iter().zip(data) {
+            let mut ts = second * 1000;
+            if let Some(last) = last_pushed {
+                ts = ts.max(last + 1);
+            }
+            let line = bincode::serde::encode_to_vec(
+                &event,
+                bincode::config::standard(),
+            )
+            .wrap_err("Could not serialize ErrorEvent")?;
+            let line: Vec<_> = line
+                .into_iter()
+                .chain(iter::repeat(0))
+                .take(payload_size)
+                .collect();
+            output
+                .push_line(ts, line)
+                .wrap_err("Could not push ErrorEvent into new history")?;
+            last_pushed = Some(ts);
+            lines += 1;
+        }
+
+        match timestamps.last() {
+            Some(last) if read == CHUNK => read_start = last + 1,
+            _ => break,
+        }
+    }
+    output
+        .flush_to_disk()
+        .wrap_err("Could not flush new history to disk")?;
+    drop((input, output));
+
+    replacement
+        .finish()
+        .wrap_err("Could not replace history keyed by second")?;
+    info!("Migrated {lines} error events of {device:?} to millisecond keys");
+    Ok(())
+}
+
 /// Relative path without extension
-fn base_path(device: &protocol::Device) -> PathBuf {
+pub(super) fn base_path(device: &protocol::Device) -> PathBuf {
     use protocol::reading::tree::{Item, Tree};
     use protocol::reading::Info;
 
@@ -1,68 +1,528 @@
-use color_eyre::eyre::WrapErr;
+use color_eyre::eyre::{bail, WrapErr};
 use color_eyre::{Result, Section};
-use std::collections::HashMap;
+use hdrhistogram::serialization::{Deserializer, Serializer, V2Serializer};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::warn;
 
-use crate::api::{self, Percentile};
+use crate::api::{self, Percentile, ReadingArrival, Subject, Window};
 
+/// How often the histograms are written to disk
+const SAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const EXTENSION: &str = "histograms";
+
+/// Histograms covering a window that moves with time. Split into slices so
+/// old intervals can be dropped. The current slice is still filling up
+/// therefore the window covers between one slice less than its length and
+/// its full length.
+#[derive(Debug)]
+struct Windowed {
+    slice: Duration,
+    slices: u64,
+    /// Index of the slice (seconds since the unix epoch divided by the
+    /// slice length) and the histogram for it, oldest first
+    recent: VecDeque<(u64, hdrhistogram::Histogram<u64>)>,
+}
+
+impl Windowed {
+    fn new(slice: Duration, slices: u64) -> Self {
+        Self {
+            slice,
+            slices,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Last hour, day and week
+    fn standard() -> [Self; 3] {
+        const HOUR: u64 = 60 * 60;
+        [
+            Self::new(Duration::from_secs(HOUR / 4), 4),
+            Self::new(Duration::from_secs(HOUR * 4), 6),
+            Self::new(Duration::from_secs(HOUR * 24), 7),
+        ]
+    }
+
+    fn slice_index(&self, now: jiff::Timestamp) -> u64 {
+        now.as_second().max(0) as u64 / self.slice.as_secs()
+    }
+
+    fn record(&mut self, now: jiff::Timestamp, value: u64) -> Result<()> {
+        let index = self.slice_index(now);
+        if self.recent.back().is_none_or(|(last, _)| *last != index) {
+            self.recent.push_back((index, empty_histogram()?));
+        }
+        while self
+            .recent
+            .front()
+            .is_some_and(|(first, _)| first + self.slices <= index)
+        {
+            self.recent.pop_front();
+        }
+
+        let (_, histogram) =
+            self.recent.back_mut().expect("just made sure there is one");
+        histogram
+            .record(value)
+            .wrap_err("Could not record event in window")
+    }
+
+    /// Start of the oldest slice that is part of the window
+    fn start(&self, now: jiff::Timestamp) -> jiff::Timestamp {
+        let oldest = (self.slice_index(now) + 1).saturating_sub(self.slices);
+        let secs = oldest * self.slice.as_secs();
+        jiff::Timestamp::from_second(secs as i64)
+            .expect("slices start between now and the unix epoch")
+    }
+
+    fn merged(
+        &self,
+        now: jiff::Timestamp,
+    ) -> Result<hdrhistogram::Histogram<u64>> {
+        let index = self.slice_index(now);
+        let mut merged = empty_histogram()?;
+        for (_, histogram) in self
+            .recent
+            .iter()
+            .filter(|(slice, _)| slice + self.slices > index)
+        {
+            merged
+                .add(histogram)
+                .wrap_err("Could not merge slices of window")?;
+        }
+        Ok(merged)
+    }
+}
+
+fn empty_histogram() -> Result<hdrhistogram::Histogram<u64>> {
+    hdrhistogram::Histogram::new_with_bounds(1, 24 * 60 * 60 * 1000, 2)
+        .wrap_err("Could not create empty histogram")
+}
+
+/// Intervals between arrivals of a single reading
 #[derive(Debug)]
 pub(crate) struct Histogram {
+    reading: protocol::Reading,
+    /// When the first interval was recorded
+    since: jiff::Timestamp,
     last_reading: Instant,
+    /// Readings could have been missed, the next interval is not reliable
+    skip_next: bool,
+    /// Every interval since the histograms were first created
     histogram: hdrhistogram::Histogram<u64>,
+    /// Last hour, day and week
+    windows: [Windowed; 3],
 }
 
 impl Histogram {
-    fn new() -> Result<Self> {
+    fn new(reading: protocol::Reading) -> Result<Self> {
         Ok(Self {
+            reading,
+            since: jiff::Timestamp::now(),
             last_reading: Instant::now(),
-            histogram: hdrhistogram::Histogram::new_with_bounds(1, 24 * 60 * 60 * 1000, 2)
-                .wrap_err("Could not create empty histogram")?,
+            skip_next: false,
+            histogram: empty_histogram()?,
+            windows: Windowed::standard(),
         })
     }
     fn increment(&mut self) -> Result<()> {
         let val = self.last_reading.elapsed().as_millis();
         self.last_reading = Instant::now();
+        if self.skip_next {
+            self.skip_next = false;
+            return Ok(());
+        }
         self.histogram
             .record(val as u64)
             .wrap_err("Could not record event")
-            .with_note(|| format!("duration was: {val}ms"))
+            .with_note(|| format!("duration was: {val}ms"))?;
+        let now = jiff::Timestamp::now();
+        for window in &mut self.windows {
+            window.record(now, val as u64)?;
+        }
+        Ok(())
     }
+
+    fn in_window(
+        &self,
+        window: Window,
+    ) -> Result<hdrhistogram::Histogram<u64>> {
+        let now = jiff::Timestamp::now();
+        match window {
+            Window::All => Ok(self.histogram.clone()),
+            Window::LastHour => self.windows[0].merged(now),
+            Window::LastDay => self.windows[1].merged(now),
+            Window::LastWeek => self.windows[2].merged(now),
+        }
+    }
+
+    /// The part of `window` covered by recorded intervals
+    fn covered(&self, window: Window) -> Duration {
+        let now = jiff::Timestamp::now();
+        let start = match window {
+            Window::All => self.since,
+            Window::LastHour => self.windows[0].start(now),
+            Window::LastDay => self.windows[1].start(now),
+            Window::LastWeek => self.windows[2].start(now),
+        };
+        let start = start.max(self.since);
+        now.duration_since(start).try_into().unwrap_or(Duration::ZERO)
+    }
+
+    fn arrival(&self, window: Window) -> Result<ReadingArrival> {
+        let intervals = self.in_window(window)?.len();
+        // the first sample in the window starts the first interval
+        let received = if intervals > 0 { intervals + 1 } else { 0 };
+        let max_interval = self.reading.device().info().max_sample_interval;
+        let expected = (max_interval != Duration::MAX && !max_interval.is_zero())
+            .then(|| self.covered(window).as_secs() / max_interval.as_secs().max(1));
+        Ok(ReadingArrival {
+            reading: self.reading.clone(),
+            received,
+            expected,
+        })
+    }
+}
+
+/// Written at the start of every file
+const FORMAT_VERSION: u8 = 1;
+
+/// On disk format of the histograms of one device
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredDevice {
+    /// Always [`FORMAT_VERSION`]
+    version: u8,
+    readings: Vec<Stored>,
+}
+
+/// The histograms are V2 serialized
+#[derive(Debug, Serialize, Deserialize)]
+struct Stored {
+    reading: protocol::Reading,
+    since: jiff::Timestamp,
+    histogram: Vec<u8>,
+    /// For each window its slices
+    windows: Vec<Vec<(u64, Vec<u8>)>>,
+}
+
+impl Stored {
+    fn new(histogram: &Histogram) -> Result<Self> {
+        let windows = histogram
+            .windows
+            .iter()
+            .map(|window| {
+                window
+                    .recent
+                    .iter()
+                    .map(|(index, histogram)| Ok((*index, serialize(histogram)?)))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            reading: histogram.reading.clone(),
+            since: histogram.since,
+            histogram: serialize(&histogram.histogram)?,
+            windows,
+        })
+    }
+
+    fn into_histogram(self) -> Result<Histogram> {
+        let mut windows = Windowed::standard();
+        for (window, stored) in windows.iter_mut().zip(self.windows) {
+            for (index, bytes) in stored {
+                window.recent.push_back((index, deserialize(&bytes)?));
+            }
+        }
+        Ok(Histogram {
+            reading: self.reading,
+            since: self.since,
+            last_reading: Instant::now(),
+            // the time between the last reading before the restart and the
+            // first one after is not an interval the device caused
+            skip_next: true,
+            histogram: deserialize(&self.histogram)?,
+            windows,
+        })
+    }
+}
+
+fn serialize(histogram: &hdrhistogram::Histogram<u64>) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    V2Serializer::new()
+        .serialize(histogram, &mut buf)
+        .wrap_err("Could not serialize histogram")?;
+    Ok(buf)
+}
+
+fn deserialize(mut bytes: &[u8]) -> Result<hdrhistogram::Histogram<u64>> {
+    Deserializer::new()
+        .deserialize(&mut bytes)
+        .wrap_err("Could not deserialize histogram")
+}
+
+fn path(log_dir: &Path, device: &protocol::Device) -> PathBuf {
+    log_dir
+        .join(super::log::base_path(device))
+        .with_extension(EXTENSION)
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Map {
+    readings: HashMap<protocol::reading::ReadingId, Histogram>,
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct Stats(pub(crate) Arc<Mutex<HashMap<protocol::Device, Histogram>>>);
+pub(crate) struct Stats(pub(crate) Arc<Mutex<Map>>);
 
 impl Stats {
-    pub async fn increment(&self, device: protocol::Device) -> Result<()> {
+    /// Loads the histograms saved in the log dir. Files that can not be
+    /// read are skipped with a warning.
+    pub(crate) fn load(log_dir: &Path) -> Result<Self> {
+        let mut map = Map::default();
+        let files = snapshot::files_in(log_dir)
+            .wrap_err("Could not list files in log dir")?;
+        for relative in files {
+            if relative.extension().is_none_or(|ext| ext != EXTENSION) {
+                continue;
+            }
+            let path = log_dir.join(relative);
+            let bytes = std::fs::read(&path)
+                .wrap_err("Could not read histograms")
+                .with_note(|| format!("path: {}", path.display()))?;
+            match decode(&bytes) {
+                Ok(readings) => map.readings.extend(
+                    readings
+                        .into_iter()
+                        .map(|histogram| (histogram.reading.id(), histogram)),
+                ),
+                Err(err) => warn!(
+                    "Skipping histograms at {}, error was: {err:?}",
+                    path.display()
+                ),
+            }
+        }
+        Ok(Self(Arc::new(Mutex::new(map))))
+    }
+
+    pub async fn increment(&self, reading: &protocol::Reading) -> Result<()> {
         let mut map = self.0.lock().await;
-        if let Some(hist) = map.get_mut(&device) {
+        if let Some(hist) = map.readings.get_mut(&reading.id()) {
             hist.increment()?;
         } else {
-            map.insert(device, Histogram::new()?);
+            map.readings
+                .insert(reading.id(), Histogram::new(reading.clone())?);
         }
         Ok(())
     }
 
+    /// Call when messages from the data-server have been dropped. Otherwise
+    /// the interval spanning the gap would be recorded.
+    pub async fn skip_next_intervals(&self) {
+        for hist in self.0.lock().await.readings.values_mut() {
+            hist.skip_next = true;
+        }
+    }
+
+    /// For a device the intervals of all its readings are combined
     pub(crate) async fn get(
         &self,
-        device: &protocol::Device,
+        subject: &Subject,
+        window: Window,
     ) -> Result<Vec<crate::api::Percentile>, api::GetStatsError> {
-        let mut map = self.0.lock().await;
-        Ok(if let Some(timings) = map.get_mut(device) {
-            timings
-                .histogram
-                .iter_quantiles(1)
-                .map(|it| Percentile {
-                    bucket_ends: it.value_iterated_to(),
-                    percentile: it.percentile(),
-                    count_in_bucket: it.count_at_value(),
-                })
-                .collect()
-        } else {
-            Vec::new()
+        let map = self.0.lock().await;
+        let mut histogram = empty_histogram().map_err(internal_error)?;
+        for timings in of_subject(&map, subject) {
+            let in_window = timings.in_window(window).map_err(internal_error)?;
+            histogram
+                .add(in_window)
+                .wrap_err("Could not combine histograms")
+                .map_err(internal_error)?;
+        }
+        if histogram.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(histogram
+            .iter_quantiles(1)
+            .map(|it| Percentile {
+                bucket_ends: it.value_iterated_to(),
+                percentile: it.percentile(),
+                count_in_bucket: it.count_at_value(),
+            })
+            .collect())
+    }
+
+    /// For each reading of the subject how many samples arrived in the
+    /// window and how many the device promises to send
+    pub(crate) async fn arrival(
+        &self,
+        subject: &Subject,
+        window: Window,
+    ) -> Result<Vec<ReadingArrival>, api::GetStatsError> {
+        let map = self.0.lock().await;
+        of_subject(&map, subject)
+            .map(|timings| timings.arrival(window))
+            .collect::<Result<_>>()
+            .map_err(internal_error)
+    }
+
+    /// Writes the histograms of every device next to its log. The files are
+    /// written without holding the lock.
+    pub(crate) async fn save(&self, log_dir: &Path) -> Result<()> {
+        let files = encode(&*self.0.lock().await, log_dir)?;
+        write_in_background(files).await
+    }
+
+    /// Saves the histograms then holds off recording and saving until the
+    /// returned guard is dropped
+    pub(crate) async fn pause(
+        &self,
+        log_dir: &Path,
+    ) -> Result<MutexGuard<'_, Map>> {
+        let map = self.0.lock().await;
+        let files = encode(&map, log_dir)?;
+        write_in_background(files).await?;
+        Ok(map)
+    }
+
+    pub(crate) async fn save_periodically(&self, log_dir: &Path) -> Result<()> {
+        loop {
+            tokio::time::sleep(SAVE_INTERVAL).await;
+            if let Err(err) = self.save(log_dir).await {
+                warn!("Could not save histograms: {err:?}");
+            }
+        }
+    }
+}
+
+fn of_subject<'a>(map: &'a Map, subject: &'a Subject) -> impl Iterator<Item = &'a Histogram> {
+    map.readings.values().filter(move |timings| match subject {
+        Subject::Device(device) => timings.reading.device() == *device,
+        Subject::Reading(reading) => timings.reading.id() == reading.id(),
+    })
+}
+
+fn internal_error(report: color_eyre::Report) -> api::GetStatsError {
+    api::GetStatsError::InternalError(format!("{report:?}"))
+}
+
+/// Returns the histograms of each reading of the device
+fn decode(bytes: &[u8]) -> Result<Vec<Histogram>> {
+    let (stored, _) =
+        bincode::serde::decode_from_slice::<StoredDevice, _>(bytes, bincode::config::standard())
+            .wrap_err("Could not decode stored histograms")?;
+    if stored.version != FORMAT_VERSION {
+        bail!(
+            "Unknown histogram format version {}, expected {FORMAT_VERSION}",
+            stored.version
+        );
+    }
+
+    stored
+        .readings
+        .into_iter()
+        .map(Stored::into_histogram)
+        .collect()
+}
+
+/// One file per device containing the histograms of its readings, returns
+/// the path and content for each
+fn encode(map: &Map, log_dir: &Path) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut per_device: HashMap<protocol::Device, StoredDevice> = HashMap::new();
+    let new = || StoredDevice {
+        version: FORMAT_VERSION,
+        readings: Vec::new(),
+    };
+    for histogram in map.readings.values() {
+        per_device
+            .entry(histogram.reading.device())
+            .or_insert_with(new)
+            .readings
+            .push(Stored::new(histogram)?);
+    }
+
+    per_device
+        .into_iter()
+        .map(|(device, stored)| {
+            let bytes = bincode::serde::encode_to_vec(&stored, bincode::config::standard())
+                .wrap_err("Could not encode histograms")?;
+            Ok((path(log_dir, &device), bytes))
         })
+        .collect()
+}
+
+async fn write_in_background(files: Vec<(PathBuf, Vec<u8>)>) -> Result<()> {
+    tokio::task::spawn_blocking(move || write(files))
+        .await
+        .wrap_err("Writing histograms panicked")?
+}
+
+fn write(files: Vec<(PathBuf, Vec<u8>)>) -> Result<()> {
+    for (path, bytes) in files {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .wrap_err("Could not create dir for histograms")
+                .with_note(|| format!("dir: {}", dir.display()))?;
+        }
+        // written to a temporary file first so a crash while writing does
+        // not lose the previous save
+        let temp = path.with_extension("histograms_tmp");
+        std::fs::write(&temp, bytes)
+            .wrap_err("Could not write histograms")
+            .with_note(|| format!("path: {}", temp.display()))?;
+        std::fs::rename(&temp, &path)
+            .wrap_err("Could not move histograms into place")
+            .with_note(|| format!("path: {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use protocol::large_bedroom::{self, bed};
+
+    use super::*;
+
+    fn temperature() -> protocol::Reading {
+        protocol::Reading::LargeBedroom(large_bedroom::Reading::Bed(
+            bed::Reading::Temperature(0.0),
+        ))
+    }
+
+    fn recorded(values: &[u64]) -> Histogram {
+        let mut histogram = Histogram::new(temperature()).unwrap();
+        let now = jiff::Timestamp::now();
+        for value in values {
+            histogram.histogram.record(*value).unwrap();
+            for window in &mut histogram.windows {
+                window.record(now, *value).unwrap();
+            }
+        }
+        histogram
+    }
+
+    #[test]
+    fn save_then_load() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let mut map = Map::default();
+        map.readings.insert(temperature().id(), recorded(&[100, 200, 300]));
+        write(encode(&map, dir.path()).unwrap()).unwrap();
+
+        let stats = Stats::load(dir.path()).unwrap();
+        let map = stats.0.try_lock().unwrap();
+        let loaded = &map.readings[&temperature().id()];
+        assert_eq!(loaded.histogram.len(), 3);
+        assert_eq!(loaded.in_window(Window::LastHour).unwrap().len(), 3);
+        assert_eq!(loaded.histogram.max(), map_max(300));
+        assert!(loaded.skip_next, "restart gap must not become an interval");
+    }
+
+    /// hdrhistogram only keeps two significant digits
+    fn map_max(value: u64) -> u64 {
+        recorded(&[value]).histogram.max()
     }
 }
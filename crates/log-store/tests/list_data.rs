@@ -36,8 +36,8 @@ async fn data_server(client_addr: impl Into<SocketAddr>, data_port: impl Into<So
     let affectors = AffectorRegistar::default();
     let (tx, rx) = mpsc::channel(2000);
     tokio::select! {
-        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone()) => e.unwrap(),
-        e = server::handle_nodes(data_port.into(), &tx, affectors) => e.unwrap(),
+        e = server::client::handle(client_addr.into(), tx.clone(), affectors.clone(), server::Access::default()) => e.unwrap(),
+        e = server::handle_nodes(data_port.into(), &tx, affectors, server::Access::default()) => e.unwrap(),
         e = server::handle_updates(rx) => e.unwrap(),
     };
 }
@@ -79,6 +79,50 @@ async fn send_sensor_errors(data_port: u16, data_send: &Notify) {
     sleep(Duration::from_secs(999)).await;
 }
 
+const TEST_AFFECTOR: protocol::Affector =
+    protocol::Affector::LargeBedroom(large_bedroom::Affector::Bed(bed::Affector::ResetNode));
+
+async fn node_with_affector(data_port: u16, node_connected: &Notify) {
+    let mut conn = TcpStream::connect(("127.0.0.1", data_port)).await.unwrap();
+    let mut list = protocol::affector::ListMessage::<50>::empty();
+    list.values.push(TEST_AFFECTOR).unwrap();
+    let handshake = protocol::Msg::AffectorList(list).encode();
+    conn.write_all(&handshake).await.unwrap();
+
+    // give the log-store time to subscribe
+    sleep(Duration::from_millis(500)).await;
+    node_connected.notify_waiters();
+    sleep(Duration::from_secs(999)).await;
+}
+
+async fn check_client_get_affector_history(
+    data_server_addr: SocketAddr,
+    log_store_addr: SocketAddr,
+    node_connected: &Notify,
+) {
+    node_connected.notified().await;
+    let mut data_server =
+        data_server::api::subscriber::Client::connect(data_server_addr, "actuator".to_owned())
+            .await
+            .unwrap();
+    data_server.actuate_affector(TEST_AFFECTOR).await.unwrap();
+    data_server.actuate_affector(TEST_AFFECTOR).await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let mut client = log_store::api::Client::connect(log_store_addr, "log_store_test".to_owned())
+        .await
+        .unwrap();
+    let range = jiff::Timestamp::new(0, 0).unwrap()..=jiff::Timestamp::now();
+    let history = client
+        .get_affector_history(TEST_AFFECTOR, range)
+        .await
+        .unwrap();
+
+    assert_eq!(history.len(), 2);
+    assert!(history.iter().all(|a| a.controlled_by == "actuator"));
+    assert!(history.iter().all(|a| a.result.is_ok()));
+}
+
 async fn check_client_get_percentiles(data_store_addr: SocketAddr, data_send: &Notify) {
     data_send.notified().await;
     sleep(Duration::from_secs_f32(0.1)).await;
@@ -88,12 +132,29 @@ async fn check_client_get_percentiles(data_store_addr: SocketAddr, data_send: &N
             .await
             .unwrap();
     let test_device = test_readings(0.0).first().unwrap().device();
-    let list = client.get_percentiles(test_device).await.unwrap();
+    let list = client.get_percentiles(test_device.clone()).await.unwrap();
 
     assert!(
         !list.is_empty(),
         "list is empty, should contain one reading"
     );
+
+    let list = client
+        .get_percentiles_in(test_device, log_store::api::Window::LastHour)
+        .await
+        .unwrap();
+    assert!(
+        !list.is_empty(),
+        "list for last hour is empty, should contain one reading"
+    );
+
+    let test_reading = test_readings(0.0).first().unwrap().clone();
+    let arrival = client
+        .get_arrival(test_reading, log_store::api::Window::All)
+        .await
+        .unwrap();
+    assert_eq!(arrival.len(), 1, "arrival should cover one reading");
+    assert!(arrival[0].received > 0);
 }
 
 async fn check_client_get_logs(data_store_addr: SocketAddr, data_send: &Notify) {
@@ -105,9 +166,58 @@ async fn check_client_get_logs(data_store_addr: SocketAddr, data_send: &Notify)
 
     let test_device = test_readings(0.0).first().unwrap().device();
     let range = jiff::Timestamp::new(0, 0).unwrap()..=jiff::Timestamp::now();
-    let logs = client.get_logs(test_device, range).await.unwrap();
+    let logs = client.get_logs(test_device.clone(), range.clone()).await.unwrap();
 
     assert_eq!(logs.len(), 3);
+
+    let query = |text: Option<&str>, variants: &[&str]| log_store::api::LogQuery {
+        text: text.map(str::to_owned),
+        variants: variants.iter().map(|v| v.to_string()).collect(),
+    };
+    let found = client
+        .search_logs(query(Some("ERROR: 1"), &[]), None, range.clone())
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 2);
+    let found = client
+        .search_logs(query(None, &["Setup", "sht31"]), None, range.clone())
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 3);
+    let found = client
+        .search_logs(query(None, &["Running"]), None, range.clone())
+        .await
+        .unwrap();
+    assert!(found.is_empty());
+
+    let episodes = client.get_episodes(test_device.clone(), range).await.unwrap();
+    assert_eq!(episodes.len(), 2, "one episode per distinct error");
+    assert_eq!(episodes.iter().map(|e| e.count).sum::<usize>(), 3);
+
+    let unhealthy = client
+        .unhealthy_devices(log_store::api::FlapThreshold::default())
+        .await
+        .unwrap();
+    assert!(unhealthy
+        .iter()
+        .any(|u| u.device == test_device && u.current.is_some()));
+}
+
+async fn check_client_get_alerts(data_store_addr: SocketAddr, data_send: &Notify) {
+    data_send.notified().await;
+    sleep(Duration::from_secs_f32(0.1)).await;
+    let mut client = log_store::api::Client::connect(data_store_addr, "log_store_test".to_owned())
+        .await
+        .unwrap();
+
+    let firing = client.firing_alerts().await.unwrap();
+    assert_eq!(firing.len(), 1);
+    assert_eq!(firing[0].rule, "too warm");
+
+    let range = jiff::Timestamp::new(0, 0).unwrap()..=jiff::Timestamp::now();
+    let alerts = client.get_alerts(range).await.unwrap();
+    assert_eq!(alerts.len(), 1, "the alert fired once and did not resolve");
+    assert_eq!(alerts[0].state, log_store::api::AlertState::Fired);
 }
 
 static SETUP_REPORTING: Once = Once::new();
@@ -145,7 +255,14 @@ async fn get_logs() {
         ([127, 0, 0, 1], data_port.port()),
     );
     let run_data_store = sleep(DATA_SERVER_STARTUP).then(|()| {
-        log_store::server::run(data_server_addr, data_store_addr.port(), test_dir.path())
+        log_store::server::run(
+            data_server_addr,
+            data_store_addr.port(),
+            test_dir.path(),
+            rpc::Access::default(),
+            log_store::server::alert::Rules::default(),
+            None,
+        )
     });
     let send_sensor_value = sleep(DATA_SERVER_STARTUP + DATA_STORE_STARTUP)
         .then(|()| send_sensor_errors(data_port.port(), &errors_send));
@@ -188,7 +305,14 @@ async fn get_percentiles() {
         ([127, 0, 0, 1], data_port.port()),
     );
     let run_data_store = sleep(DATA_SERVER_STARTUP).then(|()| {
-        log_store::server::run(data_server_addr, data_store_addr.port(), test_dir.path())
+        log_store::server::run(
+            data_server_addr,
+            data_store_addr.port(),
+            test_dir.path(),
+            rpc::Access::default(),
+            log_store::server::alert::Rules::default(),
+            None,
+        )
     });
     let send_sensor_value = sleep(DATA_SERVER_STARTUP + DATA_STORE_STARTUP)
         .then(|()| send_sensor_values(data_port.port(), &data_send));
@@ -206,3 +330,120 @@ async fn get_percentiles() {
 
     res.unwrap();
 }
+
+#[tokio::test]
+async fn get_affector_history() {
+    const DATA_SERVER_STARTUP: Duration = Duration::from_millis(20);
+    const DATA_STORE_STARTUP: Duration = Duration::from_millis(20);
+
+    setup_reporting();
+
+    let test_dir = TempDir::new().unwrap();
+
+    let sub_port = reserve_port::ReservedPort::random().unwrap();
+    let data_port = reserve_port::ReservedPort::random().unwrap();
+    let store_port = reserve_port::ReservedPort::random().unwrap();
+
+    let data_server_addr = SocketAddr::from(([127, 0, 0, 1], sub_port.port()));
+    let data_store_addr = SocketAddr::from(([127, 0, 0, 1], store_port.port()));
+
+    let node_connected = Notify::new();
+    let run_data_server = data_server(
+        ([127, 0, 0, 1], sub_port.port()),
+        ([127, 0, 0, 1], data_port.port()),
+    );
+    let run_data_store = sleep(DATA_SERVER_STARTUP).then(|()| {
+        log_store::server::run(
+            data_server_addr,
+            data_store_addr.port(),
+            test_dir.path(),
+            rpc::Access::default(),
+            log_store::server::alert::Rules::default(),
+            None,
+        )
+    });
+    let run_node = sleep(DATA_SERVER_STARTUP + DATA_STORE_STARTUP)
+        .then(|()| node_with_affector(data_port.port(), &node_connected));
+    let run_test = sleep(DATA_SERVER_STARTUP + DATA_STORE_STARTUP).then(|()| {
+        check_client_get_affector_history(data_server_addr, data_store_addr, &node_connected)
+    });
+
+    let res = (
+        run_test.map(Result::Ok),
+        run_node.map(Result::Ok),
+        run_data_store,
+        run_data_server.map(Result::Ok),
+    )
+        .race()
+        .await;
+
+    res.unwrap();
+}
+
+#[tokio::test]
+async fn get_alerts() {
+    use log_store::server::alert::{Condition, Rule, Rules};
+
+    const DATA_SERVER_STARTUP: Duration = Duration::from_millis(20);
+    const DATA_STORE_STARTUP: Duration = Duration::from_millis(20);
+    const FIRST_MSG_PROCESSED: Duration = Duration::from_millis(1000);
+
+    setup_reporting();
+
+    let test_dir = TempDir::new().unwrap();
+
+    let sub_port = reserve_port::ReservedPort::random().unwrap();
+    let data_port = reserve_port::ReservedPort::random().unwrap();
+    let store_port = reserve_port::ReservedPort::random().unwrap();
+
+    let data_server_addr = SocketAddr::from(([127, 0, 0, 1], sub_port.port()));
+    let data_store_addr = SocketAddr::from(([127, 0, 0, 1], store_port.port()));
+
+    // the values sent are 0.1, 0.2 and 0.3, with the clear level below all
+    // of them the alert fires once and never resolves
+    let rules = Rules {
+        notifiers: Default::default(),
+        rules: vec![Rule {
+            name: "too warm".to_string(),
+            condition: Condition::Above {
+                reading: test_readings(0.0)[0].clone(),
+                threshold: 0.15,
+                clear: 0.0,
+            },
+            fire_after: Duration::ZERO,
+            resolve_after: Duration::ZERO,
+            notify: Vec::new(),
+        }],
+    };
+
+    let data_send = Notify::new();
+    let run_data_server = data_server(
+        ([127, 0, 0, 1], sub_port.port()),
+        ([127, 0, 0, 1], data_port.port()),
+    );
+    let run_data_store = sleep(DATA_SERVER_STARTUP).then(|()| {
+        log_store::server::run(
+            data_server_addr,
+            data_store_addr.port(),
+            test_dir.path(),
+            rpc::Access::default(),
+            rules,
+            None,
+        )
+    });
+    let send_sensor_value = sleep(DATA_SERVER_STARTUP + DATA_STORE_STARTUP)
+        .then(|()| send_sensor_values(data_port.port(), &data_send));
+    let run_test = sleep(DATA_SERVER_STARTUP + DATA_STORE_STARTUP + FIRST_MSG_PROCESSED)
+        .then(|()| check_client_get_alerts(data_store_addr, &data_send));
+
+    let res = (
+        run_test.map(Result::Ok),
+        send_sensor_value.map(Result::Ok),
+        run_data_store,
+        run_data_server.map(Result::Ok),
+    )
+        .race()
+        .await;
+
+    res.unwrap();
+}
@@ -83,8 +83,35 @@ impl Affector {
     }
 }
 
+/// An affector order as send to a node
+#[derive(
+    Clone, Copy, Debug, defmt::Format, Serialize, Deserialize, MaxSize, PartialEq, Eq,
+)]
+pub struct Order {
+    /// Assigned by the data-server, a node that acknowledges orders must
+    /// put this in its [`OrderAck`](crate::OrderAck)
+    pub id: u32,
+    pub affector: Affector,
+}
+
+impl Order {
+    pub const ENCODED_SIZE: usize =
+        Order::POSTCARD_MAX_SIZE + cobs_overhead(Order::POSTCARD_MAX_SIZE);
+
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        postcard::to_allocvec_cobs(self).expect("Encoding should not fail")
+    }
+
+    pub fn decode(mut bytes: impl AsMut<[u8]>) -> Result<Self, DecodeMsgError> {
+        postcard::from_bytes_cobs(bytes.as_mut())
+            .map_err(DecodeMsgError::CorruptEncoding)
+    }
+}
+
 pub struct Decoder {
-    cobs_buf: CobsAccumulator<{ 2 * Affector::ENCODED_SIZE }>,
+    cobs_buf: CobsAccumulator<{ 2 * Order::ENCODED_SIZE }>,
 }
 
 impl Default for Decoder {
@@ -103,10 +130,10 @@ impl Decoder {
     pub fn feed<'a>(
         &mut self,
         read_bytes: &'a [u8],
-    ) -> Result<Option<(Affector, &'a [u8])>, DeserializeError> {
+    ) -> Result<Option<(Order, &'a [u8])>, DeserializeError> {
         let mut window = read_bytes;
         while !window.is_empty() {
-            window = match self.cobs_buf.feed::<Affector>(read_bytes) {
+            window = match self.cobs_buf.feed::<Order>(read_bytes) {
                 FeedResult::Consumed => break,
                 FeedResult::OverFull(new_window) => new_window,
                 FeedResult::DeserError(_) => return Err(DeserializeError),
@@ -122,14 +149,18 @@ impl Decoder {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListMessage<const MAX_ITEMS: usize> {
     pub values: heapless::Vec<Affector, MAX_ITEMS>,
+    /// Whether the node sends an [`OrderAck`](crate::OrderAck) for every
+    /// order it handles
+    pub acks_orders: bool,
     pub version: u8,
 }
 
 impl<const MAX_ITEMS: usize> ListMessage<MAX_ITEMS> {
     /// +2 is for the version
+    /// +1 is for `acks_orders`
     /// +4 covers the length of the heapless list
     pub const HALF_ENCODED_SIZE: usize =
-        (MAX_ITEMS * Affector::POSTCARD_MAX_SIZE + 2 + 4);
+        (MAX_ITEMS * Affector::POSTCARD_MAX_SIZE + 2 + 1 + 4);
     /// cobs and postcard encoded
     pub const ENCODED_SIZE: usize =
         Self::HALF_ENCODED_SIZE + cobs_overhead(Self::HALF_ENCODED_SIZE);
@@ -151,7 +182,8 @@ impl<const MAX_ITEMS: usize> ListMessage<MAX_ITEMS> {
     pub fn empty() -> Self {
         Self {
             values: heapless::Vec::new(),
-            version: 1,
+            acks_orders: false,
+            version: crate::PROTOCOL_VERSION,
         }
     }
 
@@ -159,6 +191,25 @@ impl<const MAX_ITEMS: usize> ListMessage<MAX_ITEMS> {
         postcard::from_bytes_cobs(bytes.as_mut())
             .map_err(DecodeMsgError::CorruptEncoding)
     }
+
+    /// Reads only the version from an encoded list. This works even if the
+    /// affectors in it can not be decoded because the sender uses another
+    /// protocol version. The version is the last field and postcard encodes
+    /// an u8 as a single byte, so its position never changes.
+    #[cfg(feature = "alloc")]
+    pub fn peek_version(bytes: &[u8]) -> Result<u8, DecodeMsgError> {
+        let mut decoded = bytes.to_vec();
+        let len = cobs::decode_in_place(&mut decoded).map_err(|()| {
+            DecodeMsgError::CorruptEncoding(
+                postcard::Error::DeserializeBadEncoding,
+            )
+        })?;
+        decoded[..len].last().copied().ok_or(
+            DecodeMsgError::CorruptEncoding(
+                postcard::Error::DeserializeUnexpectedEnd,
+            ),
+        )
+    }
 }
 
 #[cfg(all(test, feature = "alloc"))]
@@ -176,11 +227,16 @@ mod test {
             }),
         );
 
-        let encoded = test_affector.encode();
+        let test_order = Order {
+            id: 7,
+            affector: test_affector,
+        };
+
+        let encoded = test_order.encode();
         let mut encoded_copy = encoded.clone();
-        let decoded: Affector =
+        let decoded: Order =
             postcard::from_bytes_cobs(encoded_copy.as_mut_slice()).unwrap();
-        assert_eq!(decoded, test_affector);
+        assert_eq!(decoded, test_order);
 
         let mut decoder = Decoder::default();
 
@@ -188,6 +244,20 @@ mod test {
         let res = decoder.feed(&encoded).unwrap();
 
         let empty_slice = [].as_slice();
-        assert_eq!(res, Some((test_affector, empty_slice)));
+        assert_eq!(res, Some((test_order, empty_slice)));
+    }
+
+    #[test]
+    fn version_can_be_peeked() {
+        let mut list = ListMessage::<5>::empty();
+        list.values
+            .push(Affector::LargeBedroom(large_bedroom::Affector::Bed(
+                bed::Affector::Sps30FanClean,
+            )))
+            .unwrap();
+        list.version = 42;
+
+        let encoded = list.encode();
+        assert_eq!(ListMessage::<5>::peek_version(&encoded).unwrap(), 42);
     }
 }
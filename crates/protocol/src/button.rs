@@ -137,4 +137,8 @@ impl LabelFormatter for ButtonLabelFormatter {
     fn box_clone(&self) -> Box<dyn LabelFormatter> {
         Box::new(Self)
     }
+    /// A long press beats a short press beats no press
+    fn aggregation(&self) -> crate::reading::Aggregation {
+        crate::reading::Aggregation::Max
+    }
 }
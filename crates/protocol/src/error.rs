@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use crate::{large_bedroom, small_bedroom, Device};
 
 #[derive(
+    strum::IntoStaticStr,
     strum::EnumDiscriminants,
     strum::VariantNames,
     Clone,
@@ -33,6 +34,29 @@ impl Error {
     pub const fn max_size() -> usize {
         Self::POSTCARD_MAX_SIZE
     }
+
+    /// Names of the variants from this error to the innermost error or
+    /// device. For example the path of
+    /// `SmallBedroom(Bed(Running(Sps30(..))))` is
+    /// `["SmallBedroom", "Bed", "Running", "Sps30"]`.
+    #[must_use]
+    pub fn variant_path(&self) -> VariantPath {
+        let mut path = VariantPath::new();
+        push_variant(&mut path, self.into());
+        match self {
+            Error::LargeBedroom(error) => error.extend_variant_path(&mut path),
+            Error::SmallBedroom(error) => error.extend_variant_path(&mut path),
+        }
+        path
+    }
+}
+
+/// See [`Error::variant_path`]
+pub type VariantPath = heapless::Vec<&'static str, 4>;
+
+pub(crate) fn push_variant(path: &mut VariantPath, variant: &'static str) {
+    path.push(variant)
+        .expect("errors are nested no deeper then a VariantPath fits");
 }
 
 impl core::fmt::Display for Error {
@@ -43,3 +67,30 @@ impl core::fmt::Display for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::large_bedroom::bed;
+
+    #[test]
+    fn variant_path_ends_at_innermost_error_or_device() {
+        let running = Error::LargeBedroom(large_bedroom::Error::Bed(
+            bed::Error::Running(bed::SensorError::Sps30(
+                heapless::String::try_from("(Setup, Sht31)").unwrap(),
+            )),
+        ));
+        assert_eq!(
+            running.variant_path().as_slice(),
+            ["LargeBedroom", "Bed", "Running", "Sps30"]
+        );
+
+        let timed_out = Error::LargeBedroom(large_bedroom::Error::Bed(
+            bed::Error::SetupTimedOut(bed::Device::Nau7802Left),
+        ));
+        assert_eq!(
+            timed_out.variant_path().as_slice(),
+            ["LargeBedroom", "Bed", "SetupTimedOut", "Nau7802Left"]
+        );
+    }
+}
@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "alloc")]
 use crate::{affector, reading};
+use crate::error::{push_variant, VariantPath};
 
 pub mod airbox;
 pub mod bed;
@@ -35,6 +36,7 @@ pub enum Reading {
 reading::tree::all_nodes! {Reading; ReadingDiscriminants; Bed, Desk, Radiator, Airbox, DeskRight}
 
 #[derive(
+    strum::IntoStaticStr,
     strum::EnumDiscriminants,
     Clone,
     Debug,
@@ -52,6 +54,15 @@ pub enum Error {
 }
 
 impl Error {
+    pub(crate) fn extend_variant_path(&self, path: &mut VariantPath) {
+        push_variant(path, self.into());
+        match self {
+            Error::Bed(error) => error.extend_variant_path(path),
+            Error::Desk(error) => error.extend_variant_path(path),
+            Error::Airbox(error) => error.extend_variant_path(path),
+        }
+    }
+
     #[must_use]
     pub fn device(&self) -> Device {
         match self {
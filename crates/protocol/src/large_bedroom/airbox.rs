@@ -6,6 +6,7 @@ use crate::button::Press;
 use postcard::experimental::max_size::MaxSize;
 use serde::{Deserialize, Serialize};
 
+use crate::error::{push_variant, VariantPath};
 #[cfg(feature = "alloc")]
 use crate::reading::tree::{Item, ItemMut, Tree};
 #[cfg(feature = "alloc")]
@@ -104,7 +105,15 @@ impl Tree for Reading {
 }
 
 #[derive(
-    Clone, Debug, defmt::Format, Serialize, Deserialize, MaxSize, Eq, PartialEq,
+    strum::IntoStaticStr,
+    Clone,
+    Debug,
+    defmt::Format,
+    Serialize,
+    Deserialize,
+    MaxSize,
+    Eq,
+    PartialEq,
 )]
 pub enum Error {
     Running(DeviceError),
@@ -114,6 +123,18 @@ pub enum Error {
 }
 
 impl Error {
+    pub(crate) fn extend_variant_path(&self, path: &mut VariantPath) {
+        push_variant(path, self.into());
+        match self {
+            Self::Running(error) | Self::Setup(error) => {
+                push_variant(path, error.into());
+            }
+            Self::SetupTimedOut(device) | Self::Timeout(device) => {
+                push_variant(path, device.into());
+            }
+        }
+    }
+
     pub fn device(&self) -> Device {
         match self {
             Self::Running(sensor_err) | Self::Setup(sensor_err) => {
@@ -142,7 +163,14 @@ impl core::fmt::Display for Error {
 }
 
 #[derive(
-    Clone, Debug, defmt::Format, Serialize, Deserialize, Eq, PartialEq,
+    strum::IntoStaticStr,
+    Clone,
+    Debug,
+    defmt::Format,
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
 )]
 pub enum DeviceError {
     BmeError(heapless::String<200>),
@@ -169,6 +197,7 @@ impl MaxSize for DeviceError {
 }
 
 #[derive(
+    strum::IntoStaticStr,
     Clone,
     Debug,
     defmt::Format,
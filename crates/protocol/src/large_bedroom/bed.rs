@@ -4,6 +4,7 @@ use core::time::Duration;
 use crate::affector::{Control, ControlValue};
 use crate::button::Press;
 use crate::button_enum;
+use crate::error::{push_variant, VariantPath};
 #[cfg(feature = "alloc")]
 use crate::reading::tree::{Id, Item, ItemMut, Tree};
 #[cfg(feature = "alloc")]
@@ -301,7 +302,15 @@ impl_is_same_as! {Reading; Brightness, Temperature, Humidity, GassResistance, Pr
 }
 
 #[derive(
-    Clone, Debug, defmt::Format, Serialize, Deserialize, MaxSize, Eq, PartialEq,
+    strum::IntoStaticStr,
+    Clone,
+    Debug,
+    defmt::Format,
+    Serialize,
+    Deserialize,
+    MaxSize,
+    Eq,
+    PartialEq,
 )]
 pub enum Error {
     Running(SensorError),
@@ -311,6 +320,18 @@ pub enum Error {
 }
 
 impl Error {
+    pub(crate) fn extend_variant_path(&self, path: &mut VariantPath) {
+        push_variant(path, self.into());
+        match self {
+            Self::Running(error) | Self::Setup(error) => {
+                push_variant(path, error.into());
+            }
+            Self::SetupTimedOut(device) | Self::Timeout(device) => {
+                push_variant(path, device.into());
+            }
+        }
+    }
+
     #[must_use]
     pub(crate) fn device(&self) -> Device {
         match self {
@@ -336,7 +357,14 @@ impl core::fmt::Display for Error {
 }
 
 #[derive(
-    Clone, Debug, defmt::Format, Serialize, Deserialize, Eq, PartialEq,
+    strum::IntoStaticStr,
+    Clone,
+    Debug,
+    defmt::Format,
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
 )]
 pub enum SensorError {
     Sht31(heapless::String<200>),
@@ -385,6 +413,7 @@ impl SensorError {
 }
 
 #[derive(
+    strum::IntoStaticStr,
     Clone,
     Debug,
     defmt::Format,
@@ -4,6 +4,7 @@ use postcard::experimental::max_size::MaxSize;
 use serde::{Deserialize, Serialize};
 
 use crate::button::Press;
+use crate::error::{push_variant, VariantPath};
 #[cfg(feature = "alloc")]
 use crate::reading::tree::{Item, Tree};
 #[cfg(feature = "alloc")]
@@ -107,7 +108,15 @@ impl_is_same_as! {Reading; Temperature, Humidity, Pressure;
 (Reading::Button(a), Self::Button(b)) => a.is_same_as(b)}
 
 #[derive(
-    Clone, Debug, defmt::Format, Serialize, Deserialize, MaxSize, Eq, PartialEq,
+    strum::IntoStaticStr,
+    Clone,
+    Debug,
+    defmt::Format,
+    Serialize,
+    Deserialize,
+    MaxSize,
+    Eq,
+    PartialEq,
 )]
 pub enum Error {
     Running(SensorError),
@@ -117,6 +126,18 @@ pub enum Error {
 }
 
 impl Error {
+    pub(crate) fn extend_variant_path(&self, path: &mut VariantPath) {
+        push_variant(path, self.into());
+        match self {
+            Self::Running(error) | Self::Setup(error) => {
+                push_variant(path, error.into());
+            }
+            Self::SetupTimedOut(device) | Self::Timeout(device) => {
+                push_variant(path, device.into());
+            }
+        }
+    }
+
     #[must_use]
     pub fn device(&self) -> Device {
         match self {
@@ -146,7 +167,14 @@ impl core::fmt::Display for Error {
 }
 
 #[derive(
-    Clone, Debug, defmt::Format, Serialize, Deserialize, Eq, PartialEq,
+    strum::IntoStaticStr,
+    Clone,
+    Debug,
+    defmt::Format,
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
 )]
 pub enum SensorError {
     BmeError(heapless::String<200>),
@@ -176,6 +204,7 @@ impl core::fmt::Display for SensorError {
 }
 
 #[derive(
+    strum::IntoStaticStr,
     Clone,
     Debug,
     defmt::Format,
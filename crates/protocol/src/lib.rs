@@ -21,12 +21,24 @@ mod msg;
 pub use affector::Affector;
 pub use device::Device;
 pub use device::Info as DeviceInfo;
-pub use error::Error;
+pub use error::{Error, VariantPath};
+pub use msg::ack::{OrderAck, OrderOutcome};
 pub use msg::error::{make_error_string, ErrorReport, ErrorString};
 pub use msg::sensor::SensorMessage;
 pub use msg::{DecodeMsgError, Msg};
 pub use reading::Reading;
 
+/// Version of the wire protocol. Bump this whenever the encoding of any
+/// message changes, for example when a `Reading` or `Affector` variant is
+/// added or moved. Nodes send it during their handshake, the data-server
+/// refuses nodes speaking a different version.
+///
+/// Version 2 sends affectors to nodes wrapped in an [`affector::Order`] and
+/// adds `acks_orders` to the affector list. Every node's firmware has to be
+/// rebuilt and reflashed, and the bridges redeployed, together with the
+/// data-server. Until then the data-server refuses them.
+pub const PROTOCOL_VERSION: u8 = 2;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Unit {
     C,
@@ -1,5 +1,6 @@
 use crate::affector;
 
+pub(crate) mod ack;
 pub(crate) mod error;
 pub(crate) mod sensor;
 
@@ -9,12 +10,14 @@ pub enum Msg<const M: usize> {
     Readings(sensor::SensorMessage<M>),
     ErrorReport(error::ErrorReport),
     AffectorList(affector::ListMessage<M>),
+    OrderAck(ack::OrderAck),
 }
 
 impl<const M: usize> Msg<M> {
     pub const READINGS: u8 = 1;
     pub const ERROR_REPORT: u8 = 2;
     pub const AFFECTOR_LIST: u8 = 3;
+    pub const ORDER_ACK: u8 = 4;
 
     #[must_use]
     pub const fn max_size() -> usize {
@@ -27,7 +30,10 @@ impl<const M: usize> Msg<M> {
                 sensor::SensorMessage::<M>::ENCODED_SIZE,
                 error::ErrorReport::ENCODED_SIZE,
             ),
-            affector::ListMessage::<5>::ENCODED_SIZE,
+            max(
+                affector::ListMessage::<5>::ENCODED_SIZE,
+                ack::OrderAck::ENCODED_SIZE,
+            ),
         )
     }
 
@@ -37,6 +43,7 @@ impl<const M: usize> Msg<M> {
             Msg::Readings(_) => Self::READINGS,
             Msg::ErrorReport(_) => Self::ERROR_REPORT,
             Msg::AffectorList(_) => Self::AFFECTOR_LIST,
+            Msg::OrderAck(_) => Self::ORDER_ACK,
         };
         assert_ne!(header, 0, "0 is reserved for cobs encoding");
         header
@@ -57,6 +64,8 @@ impl<const M: usize> Msg<M> {
             Ok(Self::AffectorList(affector::ListMessage::<M>::decode(
                 bytes,
             )?))
+        } else if msg_type == Self::ORDER_ACK {
+            Ok(Self::OrderAck(ack::OrderAck::decode(bytes)?))
         } else {
             Err(DecodeMsgError::IncorrectMsgType(msg_type))
         }
@@ -69,6 +78,7 @@ impl<const M: usize> Msg<M> {
             Msg::Readings(readings) => readings.encode(),
             Msg::ErrorReport(report) => report.encode(),
             Msg::AffectorList(list) => list.encode(),
+            Msg::OrderAck(ack) => ack.encode(),
         };
 
         bytes.insert(0, self.header());
@@ -91,6 +101,10 @@ impl<const M: usize> Msg<M> {
                 let len = list.encode_slice(&mut buf[1..]).len();
                 &mut buf[..1 + len]
             }
+            Msg::OrderAck(ack) => {
+                let len = ack.encode_slice(&mut buf[1..]).len();
+                &mut buf[..1 + len]
+            }
         }
     }
 }
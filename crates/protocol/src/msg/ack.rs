@@ -0,0 +1,64 @@
+#![allow(clippy::module_name_repetitions)]
+
+use postcard::experimental::max_size::MaxSize;
+use serde::{Deserialize, Serialize};
+
+use super::{cobs_overhead, DecodeMsgError};
+
+/// Send by a node after it handled an affector order. Optional, nodes that
+/// never send these are assumed to execute every order they receive. Nodes
+/// that do send them set `acks_orders` in their affector list.
+#[derive(Debug, Clone, Serialize, Deserialize, MaxSize)]
+pub struct OrderAck {
+    /// Correlation id: the `id` of the [`Order`](crate::affector::Order)
+    /// this acknowledges
+    pub order: u32,
+    pub outcome: OrderOutcome,
+    pub version: u8,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize,
+)]
+pub enum OrderOutcome {
+    Executed,
+    /// The node could not or would not execute the order, for example
+    /// because the affector is in a state that does not allow it.
+    Rejected,
+}
+
+impl OrderAck {
+    /// cobs encoding still needed
+    const HALF_ENCODED_SIZE: usize = Self::POSTCARD_MAX_SIZE;
+
+    /// cobs and postcard encoded
+    pub const ENCODED_SIZE: usize =
+        Self::HALF_ENCODED_SIZE + cobs_overhead(Self::HALF_ENCODED_SIZE);
+
+    #[must_use]
+    pub fn new(order: u32, outcome: OrderOutcome) -> Self {
+        Self {
+            order,
+            outcome,
+            version: crate::PROTOCOL_VERSION,
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        postcard::to_allocvec_cobs(self).expect("Encoding should not fail")
+    }
+
+    /// Buffer should be at least `Self::ENCODED_SIZE` long. The returned slice contains
+    /// the serialized data. It can be shorter then the input buffer.
+    #[must_use]
+    pub fn encode_slice<'a>(&self, buf: &'a mut [u8]) -> &'a mut [u8] {
+        postcard::to_slice_cobs(self, buf).expect("Encoding should not fail")
+    }
+
+    pub fn decode(mut bytes: impl AsMut<[u8]>) -> Result<Self, DecodeMsgError> {
+        postcard::from_bytes_cobs(bytes.as_mut())
+            .map_err(DecodeMsgError::CorruptEncoding)
+    }
+}
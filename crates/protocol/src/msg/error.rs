@@ -25,7 +25,10 @@ impl ErrorReport {
 
     #[must_use]
     pub fn new(error: Error) -> Self {
-        Self { error, version: 0 }
+        Self {
+            error,
+            version: crate::PROTOCOL_VERSION,
+        }
     }
 
     #[cfg(feature = "alloc")]
@@ -17,7 +17,7 @@ impl<const MAX_ITEMS: usize> Default for SensorMessage<MAX_ITEMS> {
     fn default() -> Self {
         Self {
             values: heapless::Vec::new(),
-            version: 0,
+            version: crate::PROTOCOL_VERSION,
         }
     }
 }
@@ -39,4 +39,8 @@ impl LabelFormatter for PirLabelFormatter {
     fn box_clone(&self) -> Box<dyn LabelFormatter> {
         Box::new(Self)
     }
+    /// Shows whether any activity started in the period
+    fn aggregation(&self) -> crate::reading::Aggregation {
+        crate::reading::Aggregation::Max
+    }
 }
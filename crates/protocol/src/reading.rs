@@ -31,9 +31,26 @@ pub enum Reading {
 
 #[cfg(feature = "alloc")]
 /// Unique Id for a specific reading (not the value)
-#[derive(Debug, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ReadingId([tree::Id; 8]);
 
+#[cfg(feature = "alloc")]
+impl ReadingId {
+    /// The branch ids from the root of the reading tree to the leaf. Padded
+    /// with zeros to a length of 8.
+    #[must_use]
+    pub fn path(&self) -> &[tree::Id] {
+        &self.0
+    }
+
+    /// Is this reading part of the subtree described by `prefix`? An empty
+    /// prefix contains all readings.
+    #[must_use]
+    pub fn starts_with(&self, prefix: &[tree::Id]) -> bool {
+        self.0.starts_with(prefix)
+    }
+}
+
 #[cfg(feature = "alloc")]
 impl Reading {
     #[must_use]
@@ -85,7 +102,7 @@ impl Reading {
 impl Reading {
     #[must_use]
     pub fn version() -> u8 {
-        0u8
+        crate::PROTOCOL_VERSION
     }
 }
 
@@ -95,6 +112,21 @@ pub enum LabelPositions {
     Fixed(&'static [f64]),
 }
 
+/// How to combine the values in a period into a single value, for example
+/// when downsampling a long history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Aggregation {
+    Mean,
+    Min,
+    Max,
+    /// Both the minimum and the maximum
+    Envelope,
+    /// The most recent value
+    Last,
+    /// The number of values
+    Count,
+}
+
 #[cfg(feature = "alloc")]
 pub trait LabelFormatter: core::fmt::Debug {
     fn format(&self, value: f64, info: &Info) -> String;
@@ -102,6 +134,11 @@ pub trait LabelFormatter: core::fmt::Debug {
     fn positions(&self) -> LabelPositions {
         LabelPositions::Flexible
     }
+    /// The aggregation that makes sense for the values formatted, the mean
+    /// of states like a button press means nothing.
+    fn aggregation(&self) -> Aggregation {
+        Aggregation::Mean
+    }
 }
 
 #[cfg(feature = "alloc")]
@@ -44,5 +44,9 @@ impl LabelFormatter for SetByLabelFormatter {
     fn box_clone(&self) -> Box<dyn LabelFormatter> {
         Box::new(Self)
     }
+
+    fn aggregation(&self) -> reading::Aggregation {
+        reading::Aggregation::Last
+    }
 }
 
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "alloc")]
 use crate::affector;
 use crate::button::Press;
+use crate::error::{push_variant, VariantPath};
 
 pub mod bed;
 pub mod desk;
@@ -53,6 +54,7 @@ pub enum Reading {
 crate::reading::tree::all_nodes! {Reading; ReadingDiscriminants; ButtonPanel, Desk, Bed, Radiator, PortableButtonPanel}
 
 #[derive(
+    strum::IntoStaticStr,
     strum::EnumDiscriminants,
     Clone,
     Debug,
@@ -69,6 +71,14 @@ pub enum Error {
 }
 
 impl Error {
+    pub(crate) fn extend_variant_path(&self, path: &mut VariantPath) {
+        push_variant(path, self.into());
+        match self {
+            Error::Desk(error) => error.extend_variant_path(path),
+            Error::Bed(error) => error.extend_variant_path(path),
+        }
+    }
+
     #[must_use]
     pub fn device(&self) -> Device {
         match self {
@@ -3,6 +3,7 @@ use core::time::Duration;
 #[cfg(feature = "alloc")]
 use crate::affector::{Control, ControlValue};
 use crate::button::Press;
+use crate::error::{push_variant, VariantPath};
 #[cfg(feature = "alloc")]
 use crate::reading::tree::{Id, Item, ItemMut, Tree};
 #[cfg(feature = "alloc")]
@@ -283,7 +284,15 @@ impl_is_same_as! {Reading;
 }
 
 #[derive(
-    Clone, Debug, defmt::Format, Serialize, Deserialize, MaxSize, Eq, PartialEq,
+    strum::IntoStaticStr,
+    Clone,
+    Debug,
+    defmt::Format,
+    Serialize,
+    Deserialize,
+    MaxSize,
+    Eq,
+    PartialEq,
 )]
 pub enum Error {
     Running(SensorError),
@@ -293,6 +302,18 @@ pub enum Error {
 }
 
 impl Error {
+    pub(crate) fn extend_variant_path(&self, path: &mut VariantPath) {
+        push_variant(path, self.into());
+        match self {
+            Self::Running(error) | Self::Setup(error) => {
+                push_variant(path, error.into());
+            }
+            Self::SetupTimedOut(device) | Self::Timeout(device) => {
+                push_variant(path, device.into());
+            }
+        }
+    }
+
     #[must_use]
     pub(crate) fn device(&self) -> Device {
         match self {
@@ -318,7 +339,14 @@ impl core::fmt::Display for Error {
 }
 
 #[derive(
-    Clone, Debug, defmt::Format, Serialize, Deserialize, Eq, PartialEq,
+    strum::IntoStaticStr,
+    Clone,
+    Debug,
+    defmt::Format,
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
 )]
 pub enum SensorError {
     Sht31(heapless::String<200>),
@@ -364,6 +392,7 @@ impl SensorError {
 }
 
 #[derive(
+    strum::IntoStaticStr,
     Clone,
     Debug,
     defmt::Format,
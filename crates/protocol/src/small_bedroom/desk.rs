@@ -3,6 +3,7 @@ use core::time::Duration;
 use postcard::experimental::max_size::MaxSize;
 use serde::{Deserialize, Serialize};
 
+use crate::error::{push_variant, VariantPath};
 #[cfg(feature = "alloc")]
 use crate::reading::tree::{Item, ItemMut, Tree};
 #[cfg(feature = "alloc")]
@@ -86,7 +87,15 @@ impl Tree for Reading {
 impl_is_same_as!(Reading; Temperature, Humidity, Pressure);
 
 #[derive(
-    Clone, Debug, defmt::Format, Serialize, Deserialize, MaxSize, Eq, PartialEq,
+    strum::IntoStaticStr,
+    Clone,
+    Debug,
+    defmt::Format,
+    Serialize,
+    Deserialize,
+    MaxSize,
+    Eq,
+    PartialEq,
 )]
 pub enum Error {
     Running(SensorError),
@@ -96,6 +105,18 @@ pub enum Error {
 }
 
 impl Error {
+    pub(crate) fn extend_variant_path(&self, path: &mut VariantPath) {
+        push_variant(path, self.into());
+        match self {
+            Self::Running(error) | Self::Setup(error) => {
+                push_variant(path, error.into());
+            }
+            Self::SetupTimedOut(device) | Self::Timeout(device) => {
+                push_variant(path, device.into());
+            }
+        }
+    }
+
     #[must_use]
     pub fn device(&self) -> Device {
         match self {
@@ -125,7 +146,14 @@ impl core::fmt::Display for Error {
 }
 
 #[derive(
-    Clone, Debug, defmt::Format, Serialize, Deserialize, Eq, PartialEq,
+    strum::IntoStaticStr,
+    Clone,
+    Debug,
+    defmt::Format,
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
 )]
 pub enum SensorError {
     BmeError(heapless::String<200>),
@@ -153,6 +181,7 @@ impl core::fmt::Display for SensorError {
 }
 
 #[derive(
+    strum::IntoStaticStr,
     Clone,
     Debug,
     defmt::Format,
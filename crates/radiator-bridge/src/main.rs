@@ -24,6 +24,11 @@ struct Opt {
     /// IP address for MQTT broker
     #[clap(long)]
     mqtt_ip: IpAddr,
+
+    /// Token with the `DataSource` role, needed if this machine is not on
+    /// the node allow list of the data-server
+    #[clap(long)]
+    token: Option<String>,
 }
 
 enum RelevantMsg {
@@ -85,12 +90,13 @@ async fn main() {
         args.data_server_subscribe,
         env!("CARGO_PKG_NAME").to_owned(),
     )
-    .subscribe();
+    .subscribe_with(RelevantMsg::subscribe_options());
 
     let mut data_source = data_source::reconnecting::Client::new(
         args.data_server_update,
         Vec::new(),
         None,
+        args.token,
     )
     .await
     .expect("address is correct");
@@ -167,6 +173,31 @@ enum Event {
 }
 
 impl RelevantMsg {
+    fn subscribe_options() -> subscriber::SubscribeOptions {
+        use protocol::large_bedroom;
+        use protocol::large_bedroom::desk as ldesk;
+        use protocol::small_bedroom;
+        use protocol::small_bedroom::desk as sdesk;
+        use protocol::Reading;
+        use subscriber::{Filter, Kind, Selector};
+
+        let large = Reading::LargeBedroom(large_bedroom::Reading::Desk(
+            ldesk::Reading::Temperature(0.0),
+        ));
+        let small = Reading::SmallBedroom(small_bedroom::Reading::Desk(
+            sdesk::Reading::Temperature(0.0),
+        ));
+        let filter = Filter::selecting([
+            Selector::Reading(large.id()),
+            Selector::Reading(small.id()),
+        ])
+        .of_kinds([Kind::Reading]);
+        subscriber::SubscribeOptions {
+            filter,
+            ..subscriber::SubscribeOptions::default()
+        }
+    }
+
     fn from(msg: M) -> Option<Self> {
         use protocol::large_bedroom;
         use protocol::large_bedroom::desk as ldesk;
@@ -182,8 +213,11 @@ impl RelevantMsg {
                 small_bedroom::Reading::Desk(sdesk::Reading::Temperature(temp)),
             )) => Some(RelevantMsg::SmallBedroom(temp as f64)),
             M::ErrorReport(_)
+            | M::DecodeFailure(_)
             | M::Reading(_)
-            | M::AffectorControlled { .. } => None,
+            | M::AffectorControlled { .. }
+            | M::NodeReset(_)
+            | M::Lagged { .. } => None,
         }
     }
 }
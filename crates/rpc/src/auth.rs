@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use color_eyre::eyre::Context;
+use color_eyre::Section;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// What a client may do once connected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Role {
+    /// May request data and subscribe
+    ReadOnly,
+    /// May also control affectors
+    Actuate,
+    /// May only supply data, for example readings to the data-server
+    DataSource,
+}
+
+impl Role {
+    /// Whether a client with this role may do what needs the `needed` role
+    #[must_use]
+    pub fn includes(self, needed: Role) -> bool {
+        match (self, needed) {
+            (Role::Actuate, Role::ReadOnly) => true,
+            (have, needed) => have == needed,
+        }
+    }
+}
+
+/// Reads an access config from a RON file. Also used by services whose
+/// access config extends [`Access`].
+pub fn load<T: DeserializeOwned>(path: &Path) -> color_eyre::Result<T> {
+    let access = std::fs::read_to_string(path)
+        .wrap_err("Could not read access config")
+        .with_note(|| format!("path: {}", path.display()))?;
+    ron::from_str(&access)
+        .wrap_err("Could not deserialize access config")
+        .with_note(|| format!("path: {}", path.display()))
+}
+
+/// Tokens clients must present during the handshake. Can be loaded from a
+/// RON file such as: `(tokens: { "secret": ReadOnly })`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Access {
+    /// The role of the clients presenting each token. If there are no tokens
+    /// every client may do anything.
+    pub tokens: HashMap<String, Role>,
+}
+
+impl Access {
+    pub fn load(path: &Path) -> color_eyre::Result<Self> {
+        load(path)
+    }
+
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// The role of a client presenting `token`, `None` if it may not
+    /// connect at all.
+    #[must_use]
+    pub fn role_for(&self, token: Option<&str>) -> Option<Role> {
+        if self.is_open() {
+            return Some(Role::Actuate);
+        }
+        token.and_then(|token| self.tokens.get(token)).copied()
+    }
+}
@@ -16,21 +16,24 @@ use tokio_util::codec::{Framed, LengthDelimitedCodec};
 use crate::Request;
 use crate::Response;
 
-type Stream<RpcReq, RpcResp> = tokio_serde::Framed<
+/// Environment variable [`RpcClient::connect`] takes the token from
+pub const TOKEN_ENV_VAR: &str = "HA_RPC_TOKEN";
+
+type Stream<RpcReq, RpcResp, SubReq> = tokio_serde::Framed<
     Framed<TcpStream, LengthDelimitedCodec>,
     Response<RpcResp>,
-    Request<RpcReq>,
-    Bincode<Response<RpcResp>, Request<RpcReq>>,
+    Request<RpcReq, SubReq>,
+    Bincode<Response<RpcResp>, Request<RpcReq, SubReq>>,
 >;
 
-pub struct RpcClient<RpcReq, RpcResp>
+pub struct RpcClient<RpcReq, RpcResp, SubReq = ()>
 where
     RpcResp: Serialize,
 {
-    stream: Stream<RpcReq, RpcResp>,
+    stream: Stream<RpcReq, RpcResp, SubReq>,
 }
 
-impl<T, V: Serialize> fmt::Debug for RpcClient<T, V> {
+impl<T, V: Serialize, S> fmt::Debug for RpcClient<T, V, S> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("RpcClient").finish()
     }
@@ -50,6 +53,8 @@ pub enum ConnectError {
     Receiving(#[source] std::io::Error),
     #[error("Client was already connected")]
     AlreadyConnected,
+    #[error("Server refused our token, or we did not send one while it needs one")]
+    Unauthorized,
     #[error("Client tried to connected too many times, allowed again in: {0:?}")]
     RateLimited(Duration),
 }
@@ -66,15 +71,17 @@ pub enum RpcError {
     ConnectionClosed,
 }
 
-impl<RpcReq, RpcResp> RpcClient<RpcReq, RpcResp>
+impl<RpcReq, RpcResp, SubReq> RpcClient<RpcReq, RpcResp, SubReq>
 where
     RpcReq: Unpin + Serialize + fmt::Debug,
     RpcResp: Unpin + Serialize + DeserializeOwned + fmt::Debug,
+    SubReq: Unpin + Serialize + fmt::Debug,
 {
     async fn try_connect(
         addr: impl ToSocketAddrs,
         name: String,
-    ) -> Result<Stream<RpcReq, RpcResp>, ConnectError> {
+        token: Option<String>,
+    ) -> Result<Stream<RpcReq, RpcResp, SubReq>, ConnectError> {
         let stream = TcpStream::connect(addr).await.map_err(ConnectError::Io)?;
         let _ignore_error = stream.set_nodelay(true);
 
@@ -87,17 +94,32 @@ where
 
         let mut stream = tokio_serde::Framed::new(length_delimited, Bincode::default());
         stream
-            .send(Request::Handshake { client_name: name })
+            .send(Request::Handshake {
+                client_name: name,
+                token,
+            })
             .await
             .map_err(ConnectError::Sending)?;
         Ok(stream)
     }
 
+    /// Authenticates using the token in the `HA_RPC_TOKEN` environment
+    /// variable if it is set.
     pub async fn connect(addr: impl ToSocketAddrs, name: String) -> Result<Self, ConnectError> {
-        let mut stream = Self::try_connect(addr, name).await?;
+        let token = std::env::var(TOKEN_ENV_VAR).ok();
+        Self::connect_with_token(addr, name, token).await
+    }
+
+    pub async fn connect_with_token(
+        addr: impl ToSocketAddrs,
+        name: String,
+        token: Option<String>,
+    ) -> Result<Self, ConnectError> {
+        let mut stream = Self::try_connect(addr, name, token).await?;
         match tokio::time::timeout(Duration::from_secs(2), stream.try_next()).await {
             Ok(Ok(Some(Response::HandshakeOk))) => Ok(Self { stream }),
             Ok(Ok(Some(Response::AlreadyConnected))) => Err(ConnectError::AlreadyConnected),
+            Ok(Ok(Some(Response::Unauthorized))) => Err(ConnectError::Unauthorized),
             Ok(Ok(Some(Response::TooManyReq { allowed_in }))) => {
                 Err(ConnectError::RateLimited(allowed_in))
             }
@@ -112,7 +134,9 @@ where
         }
     }
 
-    pub async fn subscribe(&mut self) -> Result<(), RpcError> {
+    /// The request is passed on to the servers subscriber handler, use it to
+    /// tell the server what you want to be subscribed to.
+    pub async fn subscribe(&mut self, request: SubReq) -> Result<(), RpcError> {
         fn send_timeout_err(_: Elapsed) -> RpcError {
             RpcError::Sending(std::io::Error::new(std::io::ErrorKind::TimedOut, ""))
         }
@@ -121,7 +145,7 @@ where
         }
 
         let deadline = Instant::now() + Duration::from_secs(5);
-        let request = Request::Subscribe;
+        let request = Request::Subscribe(request);
         timeout_at(deadline, self.stream.send(request))
             .await
             .map_err(send_timeout_err)?
@@ -134,6 +158,7 @@ where
             .ok_or(RpcError::ConnectionClosed)?;
         match response {
             Response::AlreadyConnected
+            | Response::Unauthorized
             | Response::RpcResponse(_)
             | Response::Update(_)
             | Response::TooManyReq { .. }
@@ -154,6 +179,7 @@ where
 
         match received {
             Response::AlreadyConnected
+            | Response::Unauthorized
             | Response::RpcResponse(_)
             | Response::TooManyReq { .. }
             | Response::HandshakeOk
@@ -185,6 +211,7 @@ where
             .ok_or(RpcError::ConnectionClosed)?
         {
             Response::AlreadyConnected
+            | Response::Unauthorized
             | Response::Update(_)
             | Response::TooManyReq { .. }
             | Response::HandshakeOk
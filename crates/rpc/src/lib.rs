@@ -5,19 +5,28 @@ use std::time::Duration;
 use futures::{stream, Stream};
 use serde::{Deserialize, Serialize};
 
+pub mod auth;
 pub mod client;
 pub mod server;
 
+pub use auth::{Access, Role};
+
 // 8 MB
 pub(crate) const MAX_PACKAGE_SIZE: usize = 8 * 1024 * 1024;
 
 pub trait SubscriberHandler: Send + 'static {
+    /// Send by the client along with the subscribe request. Use this to let
+    /// clients pick what they are subscribed to.
+    type Request;
     type Update;
 
     /// The returned stream should never end before the client disconnects.
+    /// The `client_name` is the name the client gave during the handshake.
     #[allow(async_fn_in_trait)]
     fn setup(
         &mut self,
+        client_name: &str,
+        request: Self::Request,
     ) -> impl std::future::Future<Output = impl Stream<Item = Self::Update> + Send + 'static>
            + Send
            + 'static;
@@ -31,10 +40,13 @@ pub struct SubscribersUnsupported<Update> {
 }
 
 impl<Update: std::marker::Send + 'static> SubscriberHandler for SubscribersUnsupported<Update> {
+    type Request = ();
     type Update = Update;
 
     fn setup(
         &mut self,
+        _: &str,
+        _: Self::Request,
     ) -> impl std::future::Future<
         Output = impl futures::prelude::Stream<Item = Self::Update> + Send + 'static,
     > + Send
@@ -44,9 +56,13 @@ impl<Update: std::marker::Send + 'static> SubscriberHandler for SubscribersUnsup
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Request<R> {
-    Handshake { client_name: String },
-    Subscribe,
+pub enum Request<R, S = ()> {
+    Handshake {
+        client_name: String,
+        /// Needed if the server requires authentication
+        token: Option<String>,
+    },
+    Subscribe(S),
     Rpc(R),
 }
 
@@ -58,6 +74,8 @@ where
     HandshakeOk,
     SubscribeOk,
     AlreadyConnected,
+    /// The token was missing, unknown or its role may not use this server
+    Unauthorized,
     TooManyReq { allowed_in: Duration },
     RpcResponse(V),
     Update(V),
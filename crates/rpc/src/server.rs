@@ -18,23 +18,28 @@ use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
 use tracing::{debug, error, instrument};
 
-type Conn<RpcReq, RpcResp> = tokio_serde::Framed<
+type Conn<RpcReq, RpcResp, SubReq> = tokio_serde::Framed<
     Framed<TcpStream, LengthDelimitedCodec>,
-    crate::Request<RpcReq>,
+    crate::Request<RpcReq, SubReq>,
     crate::Response<RpcResp>,
-    Bincode<crate::Request<RpcReq>, crate::Response<RpcResp>>,
+    Bincode<crate::Request<RpcReq, SubReq>, crate::Response<RpcResp>>,
 >;
 
-pub async fn run<RpcReq, RpcResp, PerfFut>(
+/// Clients that present no (valid) token are refused unless `access` is
+/// open. Every client needs at least [`Role::ReadOnly`], `perform_request` is
+/// passed the role of the client so it can check more.
+pub async fn run<RpcReq, RpcResp, SubReq, PerfFut>(
     port: u16,
-    perform_request: impl Fn(RpcReq, &str) -> PerfFut + Clone + Send + 'static,
+    access: Access,
+    perform_request: impl Fn(RpcReq, &str, Role) -> PerfFut + Clone + Send + 'static,
     sub_handler: Option<
-        impl SubscriberHandler<Update = RpcResp> + Clone + 'static,
+        impl SubscriberHandler<Request = SubReq, Update = RpcResp> + Clone + 'static,
     >,
 ) -> color_eyre::Result<()>
 where
     RpcReq: Unpin + Serialize + DeserializeOwned + fmt::Debug + Send + 'static,
     RpcResp: Unpin + Serialize + DeserializeOwned + fmt::Debug + Send + 'static,
+    SubReq: Unpin + Serialize + DeserializeOwned + fmt::Debug + Send + 'static,
     PerfFut: Future<Output = RpcResp> + Send + 'static,
 {
     let quota = Quota::with_period(Duration::from_secs(1))
@@ -55,12 +60,13 @@ where
             Ok(res) => res,
         };
 
-        let Some((mut conn, name)) =
-            handshake_and_log::<RpcReq, RpcResp>(socket, source).await
+        let Some((mut conn, name, token)) =
+            handshake_and_log::<RpcReq, RpcResp, SubReq>(socket, source).await
         else {
             continue;
         };
 
+        // before the token check so refused attempts are limited too
         if let Err(allowed_again) =
             limiter.check_key(&(source.ip(), name.clone()))
         {
@@ -71,7 +77,18 @@ where
             continue;
         }
 
-        debug!("Client {name} connected from {source}");
+        let role = access
+            .role_for(token.as_deref())
+            .filter(|role| role.includes(Role::ReadOnly));
+        let Some(role) = role else {
+            tracing::warn!(
+                "refused client {name} from {source}, missing or invalid token"
+            );
+            let _ignore_err = conn.send(crate::Response::Unauthorized).await;
+            continue;
+        };
+
+        debug!("Client {name} connected from {source} with role {role:?}");
         let Ok(()) = conn.send(crate::Response::HandshakeOk).await else {
             continue;
         };
@@ -79,19 +96,21 @@ where
         tokio::task::spawn(handle_client(
             conn,
             name,
+            role,
             perform_request.clone(),
             sub_handler.clone(),
         ));
     }
 }
 
-async fn handshake_and_log<RpcReq, RpcResp>(
+async fn handshake_and_log<RpcReq, RpcResp, SubReq>(
     stream: TcpStream,
     source: SocketAddr,
-) -> Option<(Conn<RpcReq, RpcResp>, String)>
+) -> Option<(Conn<RpcReq, RpcResp, SubReq>, String, Option<String>)>
 where
     RpcReq: Unpin + Serialize + DeserializeOwned + fmt::Debug + Send + 'static,
     RpcResp: Unpin + Serialize + DeserializeOwned + fmt::Debug + Send + 'static,
+    SubReq: Unpin + Serialize + DeserializeOwned + fmt::Debug + Send + 'static,
 {
     let length_delimited = Framed::new(
         stream,
@@ -101,14 +120,14 @@ where
     );
     let mut stream: tokio_serde::Framed<
         _,
-        crate::Request<RpcReq>,
+        crate::Request<RpcReq, SubReq>,
         crate::Response<RpcResp>,
         _,
     > = tokio_serde::Framed::new(length_delimited, Bincode::default());
 
     match stream.try_next().await {
-        Ok(Some(crate::Request::Handshake { client_name })) => {
-            return Some((stream, client_name));
+        Ok(Some(crate::Request::Handshake { client_name, token })) => {
+            return Some((stream, client_name, token));
         }
         Ok(Some(other)) => {
             tracing::warn!("client from {source} tried to connected without handshake, it send: {other:?}")
@@ -126,18 +145,22 @@ where
 
 use core::future::Future;
 
-use crate::SubscriberHandler;
+use crate::{Access, Role, SubscriberHandler};
 #[instrument(skip(conn, perform_request, sub_handler))]
 /// When the provided sub_handler is used to create a stream that stream should
 /// never end before the client disconnects
-async fn handle_client<RpcReq, RpcResp, PerfFut>(
-    mut conn: Conn<RpcReq, RpcResp>,
+async fn handle_client<RpcReq, RpcResp, SubReq, PerfFut>(
+    mut conn: Conn<RpcReq, RpcResp, SubReq>,
     client_name: String,
-    perform_request: impl Fn(RpcReq, &str) -> PerfFut + Clone + Send + 'static,
-    mut sub_handler: Option<impl SubscriberHandler<Update = RpcResp> + 'static>,
+    role: Role,
+    perform_request: impl Fn(RpcReq, &str, Role) -> PerfFut + Clone + Send + 'static,
+    mut sub_handler: Option<
+        impl SubscriberHandler<Request = SubReq, Update = RpcResp> + 'static,
+    >,
 ) where
     RpcReq: Unpin + Serialize + DeserializeOwned + fmt::Debug + Send + 'static,
     RpcResp: Unpin + Serialize + DeserializeOwned + fmt::Debug + Send + 'static,
+    SubReq: Unpin + Serialize + DeserializeOwned + fmt::Debug + Send + 'static,
     PerfFut: Future<Output = RpcResp> + Send + 'static,
 {
     loop {
@@ -154,7 +177,8 @@ async fn handle_client<RpcReq, RpcResp, PerfFut>(
         };
         match request {
             crate::Request::Rpc(rpc_request) => {
-                let response = perform_request(rpc_request, &client_name).await;
+                let response =
+                    perform_request(rpc_request, &client_name, role).await;
                 if let Err(e) =
                     conn.send(crate::Response::RpcResponse(response)).await
                 {
@@ -162,9 +186,10 @@ async fn handle_client<RpcReq, RpcResp, PerfFut>(
                     return;
                 }
             }
-            crate::Request::Subscribe => {
+            crate::Request::Subscribe(sub_request) => {
                 if let Some(mut sub_handler) = sub_handler.take() {
-                    let stream = sub_handler.setup().await;
+                    let stream =
+                        sub_handler.setup(&client_name, sub_request).await;
                     pin!(stream);
                     if let Err(e) =
                         conn.send(crate::Response::SubscribeOk).await
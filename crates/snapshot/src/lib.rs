@@ -0,0 +1,410 @@
+//! Copies of the files of a store together with a manifest listing the
+//! length and checksum of each file. Used by the data-store and log-store
+//! to make backups while they keep running.
+
+mod replace;
+
+pub use replace::{relative, Replacement};
+
+use std::fmt;
+use std::fs::{self, DirEntry};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use color_eyre::eyre::{bail, ensure, Context};
+use color_eyre::{Result, Section};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Name of the manifest in the snapshot dir
+pub const MANIFEST: &str = "manifest.ron";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub created: jiff::Timestamp,
+    pub files: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// Relative to the snapshot dir
+    pub path: PathBuf,
+    pub len: u64,
+    /// Hex encoded sha256 of the content
+    pub sha256: String,
+}
+
+/// Where a snapshot requested by a client is written. Snapshots are only
+/// made if the store was given a `backup_root`, `to` must be a relative
+/// path inside it.
+pub fn target_in(backup_root: Option<&Path>, to: &Path) -> Result<PathBuf> {
+    let Some(backup_root) = backup_root else {
+        return Err(color_eyre::Report::msg(
+            "This store does not accept snapshot requests",
+        ))
+        .suggestion("Start it with a backup root (--backup-root)");
+    };
+    let inside = to.components().next().is_some()
+        && to.components().all(|c| matches!(c, Component::Normal(_)));
+    ensure!(
+        inside,
+        "Snapshot dir must be a relative path without `..`, got: {}",
+        to.display()
+    );
+    Ok(backup_root.join(to))
+}
+
+/// Hard links to the files of a store together with their length when they
+/// were staged. Staging is quick, it is done while the store can not write.
+/// The files are copied afterwards while the store keeps running. The stores
+/// only append to their files or replace them: the length cuts off what is
+/// appended and a link keeps a replaced file around.
+#[derive(Debug)]
+pub struct Staged {
+    root: PathBuf,
+    /// Hidden dir in the root that holds the links
+    dir: PathBuf,
+    /// Relative to the root
+    files: Vec<(PathBuf, u64)>,
+}
+
+impl Staged {
+    /// Links every file in `root` except those in hidden dirs
+    pub fn create(root: &Path) -> Result<Self> {
+        let files = files_in(root)?;
+        let dir = root.join(format!(
+            ".snapshot-{}",
+            jiff::Timestamp::now().as_nanosecond()
+        ));
+        // cleans up the links if one of the steps below fails
+        let mut staged = Self {
+            root: root.to_path_buf(),
+            dir,
+            files: Vec::new(),
+        };
+        for relative in files {
+            let source = root.join(&relative);
+            let link = staged.dir.join(&relative);
+            if let Some(parent) = link.parent() {
+                fs::create_dir_all(parent)
+                    .wrap_err("Could not create staging dir")
+                    .with_note(|| format!("dir: {}", parent.display()))?;
+            }
+            fs::hard_link(&source, &link)
+                .wrap_err("Could not link file to stage it")
+                .with_note(|| format!("path: {}", source.display()))?;
+            let len = fs::metadata(&link)
+                .wrap_err("Could not get length of staged file")
+                .with_note(|| format!("path: {}", source.display()))?
+                .len();
+            staged.files.push((relative, len));
+        }
+        Ok(staged)
+    }
+
+    /// Copies the staged files into the snapshot and finishes it. This
+    /// blocks while reading and writing, the links are removed afterwards.
+    pub fn copy_to(self, mut snapshot: Snapshot) -> Result<Summary> {
+        for (relative, len) in &self.files {
+            snapshot
+                .add(&self.dir, relative, *len)
+                .wrap_err("Could not add file to snapshot")
+                .with_note(|| {
+                    format!("file: {}", self.root.join(relative).display())
+                })?;
+        }
+        snapshot.finish()
+    }
+}
+
+impl Drop for Staged {
+    fn drop(&mut self) {
+        if self.dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&self.dir) {
+                tracing::warn!(
+                    "Could not remove staging dir {}: {e}",
+                    self.dir.display()
+                );
+            }
+        }
+    }
+}
+
+/// A snapshot that is being written. Files are copied as they are added,
+/// the manifest is written on [`finish`](Self::finish).
+#[derive(Debug)]
+pub struct Snapshot {
+    dir: PathBuf,
+    manifest: Manifest,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Summary {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl Snapshot {
+    /// The dir may exist but must be empty
+    pub fn create(dir: &Path) -> Result<Self> {
+        match fs::read_dir(dir) {
+            Ok(mut entries) => {
+                if entries.next().is_some() {
+                    bail!("Snapshot dir is not empty: {}", dir.display())
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                fs::create_dir_all(dir)
+                    .wrap_err("Could not create snapshot dir")
+                    .with_note(|| format!("dir: {}", dir.display()))?;
+            }
+            Err(e) => {
+                return Err(e)
+                    .wrap_err("Could not check snapshot dir")
+                    .with_note(|| format!("dir: {}", dir.display()))
+            }
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            manifest: Manifest {
+                created: jiff::Timestamp::now(),
+                files: Vec::new(),
+            },
+        })
+    }
+
+    /// Copies the first `len` bytes of the file at `relative` in `root` to
+    /// the same relative path in the snapshot.
+    pub fn add(
+        &mut self,
+        root: &Path,
+        relative: &Path,
+        len: u64,
+    ) -> Result<()> {
+        let source = root.join(relative);
+        let target = self.dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .wrap_err("Could not create dir in snapshot")
+                .with_note(|| format!("dir: {}", parent.display()))?;
+        }
+
+        let mut input = fs::File::open(&source)
+            .wrap_err("Could not open file to snapshot")
+            .with_note(|| format!("path: {}", source.display()))?
+            .take(len);
+        let mut output = fs::File::create_new(&target)
+            .wrap_err("Could not create file in snapshot")
+            .with_note(|| format!("path: {}", target.display()))?;
+
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; 64 * 1024];
+        let mut copied = 0;
+        loop {
+            let n = input
+                .read(&mut buf)
+                .wrap_err("Could not read file to snapshot")
+                .with_note(|| format!("path: {}", source.display()))?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            output
+                .write_all(&buf[..n])
+                .wrap_err("Could not write file in snapshot")
+                .with_note(|| format!("path: {}", target.display()))?;
+            copied += n as u64;
+        }
+        ensure!(
+            copied == len,
+            "File is shorter then when it was staged: {}",
+            source.display()
+        );
+        output
+            .sync_all()
+            .wrap_err("Could not sync file in snapshot to disk")?;
+
+        self.manifest.files.push(Entry {
+            path: relative.to_path_buf(),
+            len: copied,
+            sha256: hex(&hasher.finalize()),
+        });
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<Summary> {
+        let manifest =
+            ron::ser::to_string_pretty(&self.manifest, Default::default())
+                .wrap_err("Could not serialize manifest")?;
+        let path = self.dir.join(MANIFEST);
+        fs::write(&path, manifest)
+            .wrap_err("Could not write manifest")
+            .with_note(|| format!("path: {}", path.display()))?;
+
+        Ok(Summary {
+            files: self.manifest.files.len(),
+            bytes: self.manifest.files.iter().map(|entry| entry.len).sum(),
+        })
+    }
+}
+
+/// All files in `dir` and its subdirs relative to `dir`. Skips hidden
+/// directories as the stores keep backups and work in progress there.
+pub fn files_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    fn visit(root: &Path, dir: &Path, res: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)
+            .wrap_err("Could not read dir")
+            .with_note(|| format!("dir: {}", dir.display()))?
+        {
+            let entry: DirEntry = entry.wrap_err("Error walking dir")?;
+            let path = entry.path();
+            if path.is_dir() {
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    visit(root, &path, res)?;
+                }
+            } else {
+                let relative = path
+                    .strip_prefix(root)
+                    .expect("we only visit dirs in root");
+                res.push(relative.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    let mut res = Vec::new();
+    visit(dir, dir, &mut res)?;
+    res.sort();
+    Ok(res)
+}
+
+#[derive(Debug, Clone)]
+pub enum Problem {
+    Missing(PathBuf),
+    WrongLength {
+        path: PathBuf,
+        expected: u64,
+        found: u64,
+    },
+    WrongChecksum(PathBuf),
+    NotInManifest(PathBuf),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Missing(path) => {
+                write!(f, "{} is missing", path.display())
+            }
+            Problem::WrongLength {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} should be {expected} bytes long, it is {found} bytes",
+                path.display()
+            ),
+            Problem::WrongChecksum(path) => {
+                write!(f, "{} does not match its checksum", path.display())
+            }
+            Problem::NotInManifest(path) => {
+                write!(f, "{} is not in the manifest", path.display())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub manifest: Manifest,
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    #[must_use]
+    pub fn is_intact(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for problem in &self.problems {
+            writeln!(f, "{problem}")?;
+        }
+        write!(
+            f,
+            "Snapshot from {} with {} files: ",
+            self.manifest.created,
+            self.manifest.files.len()
+        )?;
+        if self.is_intact() {
+            write!(f, "intact")
+        } else {
+            write!(f, "{} problems", self.problems.len())
+        }
+    }
+}
+
+/// Checks every file in the snapshot against the manifest
+pub fn verify(dir: &Path) -> Result<Report> {
+    let path = dir.join(MANIFEST);
+    let manifest = fs::read_to_string(&path)
+        .wrap_err("Could not read manifest")
+        .with_note(|| format!("path: {}", path.display()))?;
+    let manifest: Manifest = ron::from_str(&manifest)
+        .wrap_err("Could not deserialize manifest")
+        .with_note(|| format!("path: {}", path.display()))?;
+
+    let mut problems = Vec::new();
+    for entry in &manifest.files {
+        let path = dir.join(&entry.path);
+        let (len, sha256) = match hash_file(&path) {
+            Ok(found) => found,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                problems.push(Problem::Missing(entry.path.clone()));
+                continue;
+            }
+            Err(e) => {
+                return Err(e)
+                    .wrap_err("Could not read file in snapshot")
+                    .with_note(|| format!("path: {}", path.display()))
+            }
+        };
+        if len != entry.len {
+            problems.push(Problem::WrongLength {
+                path: entry.path.clone(),
+                expected: entry.len,
+                found: len,
+            });
+        } else if sha256 != entry.sha256 {
+            problems.push(Problem::WrongChecksum(entry.path.clone()));
+        }
+    }
+
+    for path in files_in(dir)? {
+        let listed = manifest.files.iter().any(|entry| entry.path == path);
+        if !listed && path != Path::new(MANIFEST) {
+            problems.push(Problem::NotInManifest(path));
+        }
+    }
+
+    Ok(Report { manifest, problems })
+}
+
+fn hash_file(path: &Path) -> std::io::Result<(u64, String)> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let len = std::io::copy(&mut file, &mut hasher)?;
+    Ok((len, hex(&hasher.finalize())))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes.iter().fold(String::new(), |mut s, byte| {
+        let _ = write!(s, "{byte:02x}");
+        s
+    })
+}
@@ -0,0 +1,129 @@
+//! Replacing the files of a series in a store with a new version. The new
+//! version is written to a work dir inside the store dir and moved into
+//! place once finished.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::Context;
+use color_eyre::{Result, Section};
+
+/// Directory in the store dir the original series are moved to
+const BACKUP_DIR: &str = ".backups";
+
+/// Where the new version of a series is written and where the original goes
+/// once it is replaced. All paths keep the path of the series relative to
+/// the store dir so series with the same name in different dirs do not
+/// collide.
+#[derive(Debug)]
+pub struct Replacement {
+    /// Relative to the store dir without extension
+    pub relative: PathBuf,
+    series_dir: PathBuf,
+    work_dir: PathBuf,
+    work_path: PathBuf,
+    backup_dir: Option<PathBuf>,
+}
+
+impl Replacement {
+    /// The new version is written in `work_dir`, a directory in the store
+    /// dir. With a `backup` name the original is moved to
+    /// `.backups/<backup>-<unix seconds>` in the store dir when finished.
+    pub fn start(
+        store_dir: &Path,
+        series_path: &Path,
+        work_dir: &str,
+        backup: Option<&str>,
+    ) -> Result<Self> {
+        let relative = relative(store_dir, series_path);
+        let work_path = store_dir.join(work_dir).join(&relative);
+        let work_dir = work_path
+            .parent()
+            .expect("work path is in the work dir")
+            .to_path_buf();
+        if work_dir.exists() {
+            fs::remove_dir_all(&work_dir)
+                .wrap_err("Could not remove work dir left by interrupted run")
+                .with_note(|| format!("dir: {}", work_dir.display()))?;
+        }
+        fs::create_dir_all(&work_dir)
+            .wrap_err("Could not create work dir")
+            .with_note(|| format!("dir: {}", work_dir.display()))?;
+
+        let backup_dir = backup.map(|name| {
+            store_dir
+                .join(BACKUP_DIR)
+                .join(format!("{name}-{}", jiff::Timestamp::now().as_second()))
+                .join(relative.parent().unwrap_or(Path::new("")))
+        });
+        Ok(Self {
+            series_dir: series_path
+                .parent()
+                .expect("series are in the store dir")
+                .to_path_buf(),
+            relative,
+            work_dir,
+            work_path,
+            backup_dir,
+        })
+    }
+
+    /// Path (without extension) to create the new version of the series at
+    pub fn work_path(&self) -> &Path {
+        &self.work_path
+    }
+
+    /// Replaces the original with the new series. The new series and the
+    /// series it was made from must be closed. Returns the dir the original
+    /// was moved to.
+    pub fn finish(self) -> Result<Option<PathBuf>> {
+        replace_series_files(
+            &self.work_dir,
+            &self.series_dir,
+            self.backup_dir.as_deref(),
+        )
+        .wrap_err("Could not replace series with new version")
+        .with_note(|| format!("series: {}", self.relative.display()))?;
+        Ok(self.backup_dir)
+    }
+}
+
+/// Path of a series relative to the store dir without extension
+#[must_use]
+pub fn relative(store_dir: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(store_dir)
+        .unwrap_or(path)
+        .with_extension("")
+}
+
+/// Moves every file byteseries created in `work_dir` (the data, its index
+/// and the downsampled caches) into `series_dir` replacing the originals.
+/// If a `backup_dir` is given the originals are moved there.
+fn replace_series_files(
+    work_dir: &Path,
+    series_dir: &Path,
+    backup_dir: Option<&Path>,
+) -> Result<()> {
+    if let Some(backup_dir) = backup_dir {
+        fs::create_dir_all(backup_dir)
+            .wrap_err("Could not create backup dir")
+            .with_note(|| format!("dir: {}", backup_dir.display()))?;
+    }
+
+    for entry in fs::read_dir(work_dir).wrap_err("Could not read work dir")? {
+        let entry = entry.wrap_err("Error walking work dir")?;
+        let target = series_dir.join(entry.file_name());
+        if let Some(backup_dir) = backup_dir.filter(|_| target.exists()) {
+            let backup = backup_dir.join(entry.file_name());
+            fs::rename(&target, &backup)
+                .wrap_err("Could not move original file to backup")
+                .with_note(|| format!("from: {}", target.display()))
+                .with_note(|| format!("to: {}", backup.display()))?;
+        }
+        fs::rename(entry.path(), &target)
+            .wrap_err("Could not move new file into place")
+            .with_note(|| format!("from: {}", entry.path().display()))
+            .with_note(|| format!("to: {}", target.display()))?;
+    }
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use color_eyre::eyre::{eyre, Context};
 use color_eyre::Result;
-use data_server::api::subscriber::SubMessage;
 use data_server::api::subscriber::{
-    ReconnectingClient, ReconnectingSubscribedClient,
+    Decimation, Filter, ReconnectingClient, ReconnectingSubscribedClient, Selector,
+    SubMessage, SubscribeOptions,
 };
 use gethostname::gethostname;
 use itertools::Itertools;
@@ -66,11 +66,9 @@ async fn wait_for_update(
     }
 }
 
-async fn setup(
-    cli: &Cli,
-    client: &mut ReconnectingSubscribedClient,
-) -> Result<Vec<protocol::Reading>> {
-    let available = resolve::available_readings(cli.store, client).await;
+async fn setup(cli: &Cli) -> Result<Vec<protocol::Reading>> {
+    let mut client = ReconnectingClient::new(cli.server, name()).subscribe();
+    let available = resolve::available_readings(cli.store, &mut client).await;
     let mut fully_qualified = Vec::new();
     for query in &cli.readings {
         let reading = resolve::query(&available, query)
@@ -88,7 +86,6 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
     logger::tracing::setup();
 
-    let mut client = ReconnectingClient::new(cli.server, name()).subscribe();
     assert!(
         !cli.readings.is_empty(),
         "must provide at least one sensor to display"
@@ -101,7 +98,7 @@ async fn main() -> Result<()> {
     let readings = match cache::load_from_file(&cli.readings).await {
         Ok(readings) if readings.iter().any(Option::is_none) => {
             println!("RunSetup");
-            setup(&cli, &mut client).await?
+            setup(&cli).await?
         }
         Ok(readings) => readings.into_iter().map(Option::unwrap).collect(),
         Err(e) => {
@@ -113,13 +110,24 @@ async fn main() -> Result<()> {
     };
 
     tracing::debug!("Will be showing: {readings:?}");
-    let mut next_timeout_at =
-        tokio::time::Instant::now() + Duration::from_secs(100);
-
+    let filter =
+        Filter::selecting(readings.iter().map(|r| Selector::Reading(r.id())));
+    let mut client = ReconnectingClient::new(cli.server, name())
+        .subscribe_with(SubscribeOptions {
+            filter,
+            decimation: Decimation {
+                min_interval: Some(Duration::from_secs(1)),
+                on_change: false,
+            },
+        });
     let mut entries: Vec<_> = readings
         .iter()
         .map(|reading| Entry::new_for(reading))
         .collect();
+    fill_from_snapshot(&cli, &readings, &mut entries).await;
+    // print the values from the snapshot right away
+    let mut next_timeout_at = tokio::time::Instant::now();
+
     loop {
         let get_update = wait_for_update(&mut client, &readings);
         if let Ok((new, idx)) = timeout_at(next_timeout_at, get_update).await {
@@ -158,6 +166,39 @@ async fn main() -> Result<()> {
     }
 }
 
+/// Without this nothing is shown until the sensors send their next reading
+/// which can take a while.
+async fn fill_from_snapshot(
+    cli: &Cli,
+    readings: &[Reading],
+    entries: &mut [Entry],
+) {
+    let mut client = ReconnectingClient::new(cli.server, name());
+    let Ok(snapshot) =
+        tokio::time::timeout(Duration::from_secs(2), client.snapshot()).await
+    else {
+        tracing::warn!("Could not get last known values from data-server");
+        return;
+    };
+
+    for retained in snapshot.readings {
+        let Some(idx) = readings
+            .iter()
+            .position(|watched| retained.value.is_same_as(watched))
+        else {
+            continue;
+        };
+        let entry = &mut entries[idx];
+        if retained.age > entry.timeout_interval {
+            continue;
+        }
+        if let Some(received_at) = Instant::now().checked_sub(retained.age) {
+            entry.curr_value = Some(retained.value);
+            entry.last_updated_at = received_at;
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Entry {
     curr_value: Option<Reading>,
@@ -2,7 +2,7 @@ use std::net::SocketAddr;
 use std::time::Duration;
 
 use data_server::api::subscriber::client::Error;
-use data_server::api::subscriber::{AffectorError, Client};
+use data_server::api::subscriber::{AffectorError, Client, Delivered};
 use protocol::Affector;
 use std::sync::mpsc as std_mpsc;
 use tokio::sync::mpsc;
@@ -13,9 +13,18 @@ use crate::{client_name, Update};
 
 #[derive(Debug)]
 pub enum AffectorStatus {
+    /// The node does not acknowledge orders
     Send,
+    /// The node acknowledged it executed the order
+    Confirmed,
+    /// The node refused to execute the order
+    Rejected,
+    /// The node did not acknowledge the order in time
+    TimedOut,
     RateLimited,
     NodeOffline,
+    /// Our token does not allow controlling affectors
+    NotAllowed,
     /// connection to data_server is/was down, retrying
     ConnIssues,
 }
@@ -24,12 +33,20 @@ impl std::fmt::Display for AffectorStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AffectorStatus::Send => f.write_str("Send succesfully"),
+            AffectorStatus::Confirmed => f.write_str("Executed by node"),
+            AffectorStatus::Rejected => f.write_str("Refused by node"),
+            AffectorStatus::TimedOut => {
+                f.write_str("Send, but node did not confirm in time")
+            }
             AffectorStatus::RateLimited => f.write_str(
                 "Dropped, affector is rate limited, try again later",
             ),
             AffectorStatus::NodeOffline => {
                 f.write_str("Dropped, node is offline")
             }
+            AffectorStatus::NotAllowed => {
+                f.write_str("Refused, our token may not control affectors")
+            }
             AffectorStatus::ConnIssues => {
                 f.write_str("Queued, trying to resolve connection issues")
             }
@@ -57,10 +74,22 @@ pub async fn watch_and_send(
         tracing::debug!("got affect order: {order:?}");
         if let Some(mut client) = connected_client.take() {
             let status = match client.actuate_affector(order).await {
-                Ok(()) => {
+                Ok(Delivered::Send) => {
                     connected_client = Some(client);
                     AffectorStatus::Send
                 }
+                Ok(Delivered::Confirmed) => {
+                    connected_client = Some(client);
+                    AffectorStatus::Confirmed
+                }
+                Err(Error::Request(AffectorError::Rejected)) => {
+                    connected_client = Some(client);
+                    AffectorStatus::Rejected
+                }
+                Err(Error::Request(AffectorError::TimedOut)) => {
+                    connected_client = Some(client);
+                    AffectorStatus::TimedOut
+                }
                 Err(Error::Request(AffectorError::RateLimited)) => {
                     connected_client = Some(client);
                     AffectorStatus::RateLimited
@@ -69,6 +98,10 @@ pub async fn watch_and_send(
                     connected_client = Some(client);
                     AffectorStatus::NodeOffline
                 }
+                Err(Error::Request(AffectorError::NotAllowed)) => {
+                    connected_client = Some(client);
+                    AffectorStatus::NotAllowed
+                }
                 Err(e) => {
                     queued = Some(order);
                     error!(
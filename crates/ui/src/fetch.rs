@@ -8,7 +8,7 @@ use std::time::Duration;
 use color_eyre::eyre::{self, Context, Report};
 use color_eyre::Result;
 use jiff::Timestamp;
-use log_store::api::{ErrorEvent, Percentile};
+use log_store::api::{AffectorActivation, ErrorEvent, LogQuery, Percentile};
 use protocol::{IsSameAs, Reading};
 use std::sync::Mutex;
 use tokio::time::Instant;
@@ -38,11 +38,26 @@ pub struct Hist {
     range: RangeInclusive<Timestamp>,
 }
 
+#[derive(Debug, Clone)]
+pub struct LogSearch {
+    reading: Reading,
+    query: String,
+    range: RangeInclusive<Timestamp>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AffectorHistory {
+    affector: protocol::Affector,
+    range: RangeInclusive<Timestamp>,
+}
+
 #[derive(Debug, Clone)]
 pub enum Request {
     Data(Data),
     Logs(Logs),
     Hist(Hist),
+    LogSearch(LogSearch),
+    AffectorHistory(AffectorHistory),
 }
 
 impl Request {
@@ -108,6 +123,37 @@ impl Fetch {
         }
     }
 
+    /// Searches the logs of the device behind `reading` unless `searched_for`
+    /// shows the search is done or it was recently requested.
+    pub fn assure_searched(
+        &mut self,
+        plot_range: plot_range::Range,
+        reading: Reading,
+        query: &str,
+        searched_for: Option<&str>,
+    ) {
+        if searched_for == Some(query) {
+            return;
+        }
+        let requested = self.recently_issued.iter().any(|req| {
+            matches!(req, Request::LogSearch(search)
+                if search.query == query && search.reading.is_same_as(&reading))
+        });
+        if !requested {
+            debug!("Searching logs of {reading:?} for: {query}");
+            self.request(Request::LogSearch(LogSearch {
+                reading,
+                query: query.to_owned(),
+                range: plot_range.range_inclusive(),
+            }))
+        }
+    }
+
+    pub fn affector_history(&mut self, affector: protocol::Affector, range: RangeInclusive<Timestamp>) {
+        debug!("Requesting history for {affector:?}");
+        self.request(Request::AffectorHistory(AffectorHistory { affector, range }))
+    }
+
     #[instrument(skip(self))]
     fn current_or_requested_data_suffice(
         &mut self,
@@ -357,6 +403,42 @@ pub(crate) async fn handle_requests(
                     tx,
                 ))
             }
+            Request::LogSearch(LogSearch {
+                reading,
+                query,
+                range,
+            }) => {
+                let reading_clone = reading.clone();
+                let query_clone = query.clone();
+                tokio::spawn(get_retry_then_wrap_send(
+                    move || search_logs(log_store, reading.clone(), query.clone(), range.clone()),
+                    log_store_queue.clone(),
+                    "Could not search logs",
+                    move |res| match res {
+                        Ok(found) => Update::Fetched {
+                            reading: reading_clone,
+                            thing: Fetchable::LogSearch {
+                                query: query_clone,
+                                found,
+                            },
+                        },
+                        Err(err) => Update::FetchError(err),
+                    },
+                    tx,
+                ))
+            }
+            Request::AffectorHistory(AffectorHistory { affector, range }) => {
+                tokio::spawn(get_retry_then_wrap_send(
+                    move || get_affector_history(log_store, affector, range.clone()),
+                    log_store_queue.clone(),
+                    "Could not fetch affector history",
+                    move |res| match res {
+                        Ok(history) => Update::AffectorHistory { affector, history },
+                        Err(err) => Update::FetchError(err),
+                    },
+                    tx,
+                ))
+            }
         };
         inflight_request.push_front(handle);
         if inflight_request.len() > 6 {
@@ -474,7 +556,7 @@ async fn get_data(
         .get_data(*range.start(), *range.end(), reading, 300)
         .await
     {
-        Ok(Data { time, values }) => GetResult::Ok((time, values)),
+        Ok(Data { time, values, .. }) => GetResult::Ok((time, values)),
         Err(Error::Request(GetDataError::NotFound))
         | Err(Error::Request(GetDataError::EmptyFile))
         | Err(Error::Request(GetDataError::StartAfterData))
@@ -509,6 +591,64 @@ async fn get_logs(
         .into()
 }
 
+/// Words starting with `#` are variant names, for example `#Setup`, the
+/// other words form the text to look for.
+fn parse_query(query: &str) -> LogQuery {
+    let (variants, text): (Vec<_>, Vec<_>) = query
+        .split_whitespace()
+        .partition(|word| word.starts_with('#'));
+    LogQuery {
+        text: (!text.is_empty()).then(|| text.join(" ")),
+        variants: variants
+            .into_iter()
+            .map(|variant| variant.trim_start_matches('#').to_owned())
+            .collect(),
+    }
+}
+
+async fn search_logs(
+    log_store: SocketAddr,
+    reading: Reading,
+    query: String,
+    range: RangeInclusive<Timestamp>,
+) -> GetResult<Vec<ErrorEvent>> {
+    use log_store::api::client::{Client, ConnectError};
+
+    let mut api = match Client::connect(log_store, client_name()).await {
+        Ok(api) => api,
+        Err(ConnectError::RateLimited(d)) => return GetResult::RateLimited { allowed_in: d },
+        Err(other) => {
+            return GetResult::Err(Report::new(other).wrap_err("Could not connect to log-store"))
+        }
+    };
+
+    api.search_logs(parse_query(&query), Some(vec![reading.device()]), range)
+        .await
+        .wrap_err("Log store returned an error to our request")
+        .into()
+}
+
+async fn get_affector_history(
+    log_store: SocketAddr,
+    affector: protocol::Affector,
+    range: RangeInclusive<Timestamp>,
+) -> GetResult<Vec<AffectorActivation>> {
+    use log_store::api::client::{Client, ConnectError};
+
+    let mut api = match Client::connect(log_store, client_name()).await {
+        Ok(api) => api,
+        Err(ConnectError::RateLimited(d)) => return GetResult::RateLimited { allowed_in: d },
+        Err(other) => {
+            return GetResult::Err(Report::new(other).wrap_err("Could not connect to log-store"))
+        }
+    };
+
+    api.get_affector_history(affector, range)
+        .await
+        .wrap_err("Log store returned an error to our request")
+        .into()
+}
+
 async fn get_percentiles(log_store: SocketAddr, reading: Reading) -> GetResult<Vec<Percentile>> {
     use log_store::api::client::{Client, ConnectError};
 
@@ -6,7 +6,10 @@ mod time;
 pub mod tui;
 
 pub use fetch::Fetch;
-use log_store::api::{ErrorEvent, Percentile};
+use data_server::api::subscriber::{
+    AffectorError, DecodeFailure, Delivered, ResetDecision,
+};
+use log_store::api::{AffectorActivation, ErrorEvent, Percentile};
 use protocol::Reading;
 use std::ops::RangeInclusive;
 
@@ -25,6 +28,11 @@ pub enum Fetchable {
         logs: Vec<ErrorEvent>,
         start_at: jiff::Timestamp,
     },
+    /// The errors matching a search typed in the log pane
+    LogSearch {
+        query: String,
+        found: Vec<ErrorEvent>,
+    },
     Hist {
         percentiles: Vec<Percentile>,
         range: RangeInclusive<jiff::Timestamp>,
@@ -40,13 +48,21 @@ pub enum Update {
     FetchError(color_eyre::Report),
     SensorReading(protocol::Reading),
     SensorError(Box<protocol::Error>),
+    /// A node send something the data-server could not decode
+    DecodeFailure(Box<DecodeFailure>),
     SubscribeError(color_eyre::Report),
     DeviceList(Vec<protocol::Device>),
     AffectorControlled {
         affector: protocol::Affector,
         controlled_by: String,
+        result: Result<Delivered, AffectorError>,
+    },
+    AffectorHistory {
+        affector: protocol::Affector,
+        history: Vec<AffectorActivation>,
     },
     AffectorList(Vec<protocol::Affector>),
+    NodeReset(ResetDecision),
     AffectorOrderStatus {
         affector: protocol::Affector,
         status: control::AffectorStatus,
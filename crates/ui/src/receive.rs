@@ -1,8 +1,11 @@
 use std::net::SocketAddr;
 use std::sync::mpsc;
+use std::time::Duration;
 
-use color_eyre::eyre::{Report, WrapErr};
-use data_server::api::subscriber::{Client, SubMessage};
+use color_eyre::eyre::{eyre, Report, WrapErr};
+use data_server::api::subscriber::{
+    Client, Decimation, SubMessage, SubscribeOptions,
+};
 
 use crate::{client_name, Update};
 
@@ -17,7 +20,15 @@ pub async fn receive_data(data_server: SocketAddr, tx: mpsc::Sender<Update>) {
         }
     };
 
-    let mut subbed = match client.subscribe().await {
+    // the plots can not show more then a few points per second
+    let options = SubscribeOptions {
+        decimation: Decimation {
+            min_interval: Some(Duration::from_millis(500)),
+            on_change: false,
+        },
+        ..SubscribeOptions::default()
+    };
+    let mut subbed = match client.subscribe_with(options).await {
         Ok(client) => client,
         Err(err) => {
             let _ignore_panicked_ui = tx.send(Update::SubscribeError(
@@ -35,13 +46,24 @@ pub async fn receive_data(data_server: SocketAddr, tx: mpsc::Sender<Update>) {
             .map(|msg| match msg {
                 SubMessage::Reading(reading) => Update::SensorReading(reading),
                 SubMessage::ErrorReport(error) => Update::SensorError(error),
+                SubMessage::DecodeFailure(failure) => {
+                    Update::DecodeFailure(failure)
+                }
                 SubMessage::AffectorControlled {
                     affector,
                     controlled_by,
+                    result,
                 } => Update::AffectorControlled {
                     affector,
                     controlled_by,
+                    result,
                 },
+                SubMessage::NodeReset(decision) => Update::NodeReset(decision),
+                SubMessage::Lagged { dropped } => {
+                    Update::SubscribeError(eyre!(
+                    "Could not keep up, the server dropped {dropped} updates"
+                ))
+                }
             });
 
         match res {
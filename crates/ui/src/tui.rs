@@ -18,6 +18,9 @@ use std::{
     time::Duration,
 };
 
+use color_eyre::eyre::eyre;
+use color_eyre::Section;
+
 use crate::{Fetch, Update, UserIntent};
 
 mod affectors;
@@ -112,8 +115,13 @@ impl App {
                 }
             })?;
 
-            if let ActiveTab::Readings = self.active_tab {
-                self.readings_tab.fetch_if_needed(&mut fetcher);
+            match self.active_tab {
+                ActiveTab::Readings => {
+                    self.readings_tab.fetch_if_needed(&mut fetcher)
+                }
+                ActiveTab::Affectors => {
+                    self.affectors_tab.fetch_if_needed(&mut fetcher)
+                }
             }
 
             if event::poll(Duration::from_millis(16))? {
@@ -175,6 +183,8 @@ impl App {
     fn register_errors(&mut self, update: Update) -> Option<Update> {
         match update {
             Update::AffectorControlled { .. }
+            | Update::AffectorHistory { .. }
+            | Update::NodeReset(_)
             | Update::AffectorList(_)
             | Update::DeviceList(_)
             | Update::Fetched { .. }
@@ -182,6 +192,15 @@ impl App {
             | Update::SensorError(_)
             | Update::AffectorOrderStatus { .. }
             | Update::SensorReading(_) => return Some(update),
+            Update::DecodeFailure(ref failure) => {
+                self.reports.add(
+                    eyre!("{failure}").with_note(|| {
+                        format!("start of message: {}", failure.hex_prefix)
+                    }),
+                );
+                // the affectors tab marks the node's affectors as broken
+                return Some(update);
+            }
             Update::PopulateError(e) => {
                 self.reports.add(e.wrap_err("Error populating lists"))
             }
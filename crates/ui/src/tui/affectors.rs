@@ -2,6 +2,8 @@ use std::collections::HashMap;
 use std::time::Instant;
 
 use crossterm::event::{KeyCode, KeyEvent};
+use data_server::api::subscriber::ResetDecision;
+use log_store::api::AffectorActivation;
 use protocol::{affector, Affector};
 use ratatui::layout::{Constraint, Layout, Rect};
 use ratatui::Frame;
@@ -9,7 +11,7 @@ use tokio::sync::mpsc;
 use tui_tree_widget::{TreeItem, TreeState};
 
 use crate::control;
-use crate::Update;
+use crate::{Fetch, Update};
 
 use super::Theme;
 use protocol::affector::tree::Item;
@@ -27,10 +29,19 @@ struct AffectorState {
     last_input: Option<Instant>,
     last_controlled_by: Option<String>,
     last_order_status: Option<control::AffectorStatus>,
+    /// Most recent decision of the data-server's watchdog concerning the
+    /// node this affector resets
+    last_watchdog_decision: Option<ResetDecision>,
     info: affector::Info,
     device_broken: DeviceBroken,
+    history: Vec<AffectorActivation>,
+    /// Set when the history needs to be (re)fetched from the log-store
+    history_outdated: bool,
 }
 
+/// How far back the history pane looks
+const HISTORY_SPAN: jiff::SignedDuration = jiff::SignedDuration::from_hours(7 * 24);
+
 #[derive(Default)]
 pub struct Tab {
     tree_state: TreeState<TreeKey>,
@@ -56,15 +67,36 @@ impl Tab {
             .and_then(|key| self.data.get_mut(key));
 
         if let Some(ref mut data) = data {
-            let [top, bottom] =
-                Layout::vertical([Constraint::Fill(1), Constraint::Fill(1)])
-                    .areas(right);
+            let [top, middle, bottom] = Layout::vertical([
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ])
+            .areas(right);
             render::details(frame, data, top);
-            render::controls(frame, data, bottom);
+            render::controls(frame, data, middle);
+            render::history(frame, data, bottom);
         };
         render::footer(frame, footer, data, theme)
     }
 
+    pub fn fetch_if_needed(&mut self, fetcher: &mut Fetch) {
+        let Some(state) = self
+            .tree_state
+            .selected()
+            .last() // unique leaf id
+            .and_then(|key| self.data.get_mut(key))
+        else {
+            return;
+        };
+
+        if state.history_outdated {
+            let now = jiff::Timestamp::now();
+            fetcher.affector_history(state.affector, now - HISTORY_SPAN..=now);
+            state.history_outdated = false;
+        }
+    }
+
     pub fn handle_key(
         &mut self,
         key: KeyEvent,
@@ -108,8 +140,24 @@ impl Tab {
             Update::AffectorControlled {
                 affector,
                 controlled_by,
+                result,
             } => {
-                self.mark_controlled(&affector, controlled_by);
+                if result.is_ok() {
+                    self.mark_controlled(&affector, controlled_by);
+                }
+                self.mark_history_outdated(&affector);
+                None
+            }
+            Update::NodeReset(decision) => {
+                let affector = decision.reset_affector;
+                self.mark_history_outdated(&affector);
+                self.update_tree(&affector, move |state| {
+                    state.last_watchdog_decision = Some(decision);
+                });
+                None
+            }
+            Update::AffectorHistory { affector, history } => {
+                self.set_history(&affector, history);
                 None
             }
             Update::SensorError(ref err) => {
@@ -119,6 +167,12 @@ impl Tab {
                 }
                 Some(update)
             }
+            Update::DecodeFailure(failure) => {
+                for affector in &failure.affectors {
+                    self.mark_broken(affector);
+                }
+                None
+            }
             Update::AffectorList(affectors) => {
                 for affector in affectors {
                     self.add(&affector);
@@ -156,6 +210,22 @@ impl Tab {
         })
     }
 
+    fn mark_history_outdated(&mut self, affector: &protocol::Affector) {
+        self.update_tree(affector, move |state| {
+            state.history_outdated = true;
+        })
+    }
+
+    fn set_history(
+        &mut self,
+        affector: &protocol::Affector,
+        history: Vec<AffectorActivation>,
+    ) {
+        self.update_tree(affector, move |state| {
+            state.history = history;
+        })
+    }
+
     fn mark_broken(&mut self, affector: &protocol::Affector) {
         self.update_tree(affector, move |state| {
             state.device_broken = DeviceBroken::Yes;
@@ -204,6 +274,9 @@ impl Tab {
                         device_broken: DeviceBroken::No,
                         last_input: None,
                         last_order_status: None,
+                        last_watchdog_decision: None,
+                        history: Vec::new(),
+                        history_outdated: true,
                     });
                     update(item);
                     return;
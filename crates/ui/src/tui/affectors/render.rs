@@ -1,4 +1,8 @@
 use core::iter;
+use data_server::api::subscriber::ResetAction;
+use jiff::tz::TimeZone;
+use jiff::Zoned;
+use log_store::api::AffectorActivation;
 use protocol::affector;
 use ratatui::layout::{Alignment, Constraint, Layout, Rect};
 use ratatui::style::{Modifier, Style, Stylize};
@@ -55,6 +59,24 @@ pub(super) fn details(frame: &mut Frame, data: &mut AffectorState, top: Rect) {
     if let Some(ref status) = data.last_order_status {
         text.push(format!("\nstatus: {status}"));
     }
+    if let Some(ref decision) = data.last_watchdog_decision {
+        let silent_for =
+            crate::time::format::duration(decision.silent_for.as_secs_f64());
+        let action = match &decision.action {
+            ResetAction::Reset(Ok(())) => "reset the node".to_owned(),
+            ResetAction::Reset(Err(e)) => {
+                format!("failed to reset the node: {e}")
+            }
+            ResetAction::AlertOnly => "did not reset, alert only".to_owned(),
+            ResetAction::DailyLimitReached => {
+                "did not reset, reset too often today".to_owned()
+            }
+        };
+        text.push(format!(
+            "\nwatchdog: {action}, {:?} was silent for {silent_for}",
+            decision.stale_reading
+        ));
+    }
     if let DeviceBroken::Yes = data.device_broken {
         text.push(
             "\nWarning: Device reports error, affector might not work"
@@ -107,6 +129,48 @@ pub(crate) fn controls(
     }
 }
 
+pub(super) fn history(frame: &mut Frame, data: &AffectorState, bottom: Rect) {
+    let now = Zoned::now();
+    let lines: Vec<_> = data
+        .history
+        .iter()
+        .rev()
+        .map(
+            |AffectorActivation {
+                 at,
+                 controlled_by,
+                 result,
+                 ..
+             }| {
+                let at = at.to_zoned(TimeZone::system());
+                let at = if at.day() == now.day() && at.year() == now.year() {
+                    format!("{}", at.strftime("%H:%M:%S"))
+                } else {
+                    format!("{}", at.strftime("%D %H:%M:%S"))
+                };
+                let result = match result {
+                    Ok(delivered) => format!("{delivered:?}"),
+                    Err(err) => format!("{err}"),
+                };
+                format!("{at} {controlled_by}: {result}")
+            },
+        )
+        .collect();
+
+    let text = if lines.is_empty() {
+        "not controlled in the last week".to_owned()
+    } else {
+        lines.join("\n")
+    };
+
+    frame.render_widget(
+        widgets::Paragraph::new(text)
+            .block(Block::bordered().title("History"))
+            .wrap(widgets::Wrap { trim: true }),
+        bottom,
+    )
+}
+
 #[tracing::instrument(skip(frame, layout))]
 fn render_slider(
     frame: &mut Frame,
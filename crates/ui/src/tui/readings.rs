@@ -26,6 +26,15 @@ struct InputMode {
     chart_cursor: bool,
 }
 
+/// The search box of the log pane
+#[derive(Debug, Default)]
+pub(crate) struct LogSearch {
+    /// Text typed so far, set while the search box is open
+    editing: Option<String>,
+    /// The search whose results the log pane shows
+    submitted: Option<String>,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct ChartCursor {
     enabled: bool,
@@ -100,6 +109,7 @@ pub struct UiState {
     input_mode: InputMode,
     tree_state: TreeState<u16>,
     logs_table_state: TableState,
+    log_search: LogSearch,
     reading_selected: bool,
     comparing: HashSet<u16>,
 }
@@ -133,6 +143,7 @@ fn fill_data<'a>(
     readings: &mut Readings,
     plot_bufs: &'a mut Vec<Vec<(f64, f64)>>,
     history_len: &plot_range::Range,
+    log_search: Option<&str>,
 ) -> DataToDisplay<'a> {
     for buf in plot_bufs.iter_mut() {
         buf.clear();
@@ -150,7 +161,7 @@ fn fill_data<'a>(
             // TODO merge to show details from all plots
             res.details = Some(info.details());
             // TODO merge to show logs interleaved
-            res.logs = Some(info.logs());
+            res.logs = Some(info.logs(log_search));
         }
 
         res.chart_parts.push(info.chart(buf, &history_len));
@@ -182,7 +193,13 @@ impl Tab {
                 .collect();
             to_display.sort();
             to_display.dedup();
-            fill_data(to_display, readings, plot_bufs, &ui_state.plot_range.range)
+            fill_data(
+                to_display,
+                readings,
+                plot_bufs,
+                &ui_state.plot_range.range,
+                ui_state.log_search.submitted.as_deref(),
+            )
         };
 
         let [top, bottom, footer] = render::layout(
@@ -211,6 +228,9 @@ impl Tab {
     }
 
     pub fn handle_key(&mut self, key: KeyEvent) -> Option<KeyEvent> {
+        if self.ui_state.log_search.editing.is_some() {
+            return self.ui_state.handle_key_search(key);
+        }
         self.ui_state.handle_key_all(key)?;
         if self.ui_state.input_mode.editing_bounds {
             self.ui_state.handle_key_bounds(key)?;
@@ -277,6 +297,15 @@ impl Tab {
                 continue;
             };
 
+            if let Some(query) = &self.ui_state.log_search.submitted {
+                fetcher.assure_searched(
+                    self.ui_state.plot_range.range,
+                    data.reading.clone(),
+                    query,
+                    data.logs.searched_for(),
+                );
+            }
+
             fetcher.assure_up_to_date(
                 self.ui_state.plot_range.range,
                 || *history_len = plot_range::State::Fetching(Instant::now()),
@@ -30,6 +30,10 @@ impl UiState {
             KeyCode::Char('l') => {
                 self.show_logs = !self.show_logs;
             }
+            KeyCode::Char('/') if self.show_logs => {
+                let text = self.log_search.submitted.clone().unwrap_or_default();
+                self.log_search.editing = Some(text);
+            }
             KeyCode::Char('x') => {
                 self.chart_cursor.toggle();
                 self.input_mode.chart_cursor = self.chart_cursor.is_enabled();
@@ -85,6 +89,30 @@ impl UiState {
         }
     }
 
+    /// Takes every key while the search box is open
+    pub(crate) fn handle_key_search(&mut self, key: KeyEvent) -> Option<KeyEvent> {
+        let text = self
+            .log_search
+            .editing
+            .as_mut()
+            .expect("only called while editing the search");
+        match key.code {
+            KeyCode::Char(c) => text.push(c),
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Enter => {
+                let text = self.log_search.editing.take().expect("checked above");
+                let text = text.trim();
+                self.log_search.submitted = (!text.is_empty()).then(|| text.to_owned());
+                self.logs_table_state.select(None);
+            }
+            KeyCode::Esc => self.log_search.editing = None,
+            _ => (),
+        }
+        None
+    }
+
     pub(crate) fn handle_key_all(&mut self, key: KeyEvent) -> Option<KeyEvent> {
         match key.code {
             KeyCode::Down => {
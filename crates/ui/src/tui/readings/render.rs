@@ -80,8 +80,11 @@ pub(crate) fn footer(frame: &mut Frame, layout: Rect, app: &mut UiState, theme:
             main.push("h: show histogram");
         }
 
-        if app.show_logs {
+        if app.log_search.editing.is_some() {
+            main.push("enter: search, #word matches an error variant  esc: cancel");
+        } else if app.show_logs {
             main.push("l: hide logs");
+            main.push("/: search logs");
         } else {
             main.push("l: show logs");
         }
@@ -176,6 +179,7 @@ pub(crate) fn graph_hist_logs(
             layout.next().unwrap(),
             &mut app.logs_table_state,
             logs,
+            app.log_search.editing.as_deref(),
             theme,
         )
     }
@@ -33,13 +33,26 @@ pub fn render_log_range((start, stop): (Bound<jiff::Timestamp>, Bound<jiff::Time
     format!("{start} and {stop}")
 }
 
+/// `search_box` is the text typed in the search box if it is open
 pub fn render(
     frame: &mut Frame,
     layout: Rect,
     table_state: &mut TableState,
     logs: Option<LogList>,
+    search_box: Option<&str>,
     theme: &super::Theme,
 ) {
+    let layout = if let Some(text) = search_box {
+        let [search_box, layout] =
+            Layout::new(Direction::Vertical, [Constraint::Max(1), Constraint::Fill(1)])
+                .areas(layout);
+        let text = Text::raw(format!("search: {text}█")).style(theme.bars);
+        frame.render_widget(text, search_box);
+        layout
+    } else {
+        layout
+    };
+
     match logs {
         Some(LogList {
             items,
@@ -77,6 +90,29 @@ pub fn render(
                 render_table(frame, layout, table_state, items);
             }
         }
+        Some(LogList {
+            items,
+            source: LogSource::Search { query, done },
+            ..
+        }) => {
+            let [status, layout] = Layout::new(
+                Direction::Vertical,
+                [Constraint::Max(1), Constraint::Fill(1)],
+            )
+            .areas(layout);
+
+            let text = if done {
+                format!("Errors matching: {query}")
+            } else {
+                format!("Searching store for: {query} ..")
+            };
+            centered_text(&text, frame, status, theme);
+            if done && items.is_empty() {
+                centered_text("No errors match", frame, layout, theme)
+            } else {
+                render_table(frame, layout, table_state, items);
+            }
+        }
         None => centered_text("This item can not have logs", frame, layout, theme),
     }
 }
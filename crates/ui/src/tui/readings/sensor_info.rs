@@ -265,8 +265,9 @@ impl SensorInfo {
         }
     }
 
-    pub(crate) fn logs(&self) -> logs::List {
-        self.logs.list()
+    /// Shows the results of `search` if it is set
+    pub(crate) fn logs(&self, search: Option<&str>) -> logs::List {
+        self.logs.list(search)
     }
 
     pub(crate) fn covers(&self) -> Cover {
@@ -438,6 +439,9 @@ impl Readings {
                     since: start_at,
                 })
             }
+            Fetchable::LogSearch { query, found } => {
+                info.logs.search = Some(logs::Search { query, found })
+            }
             Fetchable::Hist { percentiles, range } => {
                 info.percentiles_from_store = percentiles;
                 info.histogram_range = Some(range);
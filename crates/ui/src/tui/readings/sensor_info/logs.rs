@@ -17,11 +17,19 @@ pub(crate) struct Local {
     pub(crate) since: jiff::Timestamp,
 }
 
+#[derive(Debug)]
+pub(crate) struct Search {
+    pub(crate) query: String,
+    pub(crate) found: Vec<api::ErrorEvent>,
+}
+
 #[derive(Debug)]
 pub(crate) struct Logs {
     current: Option<ErrorEvent>,
     pub local: Local,
     pub from_store: Option<FromStore>,
+    /// results of the last search done in the log store
+    pub search: Option<Search>,
 }
 
 impl Logs {
@@ -37,6 +45,7 @@ impl Logs {
                 since: jiff::Timestamp::now(),
             },
             from_store: None,
+            search: None,
         }
     }
 
@@ -48,6 +57,7 @@ impl Logs {
                 since: jiff::Timestamp::now(),
             },
             from_store: None,
+            search: None,
         }
     }
 
@@ -89,7 +99,13 @@ impl Logs {
         buckets.map(|(_, count)| count as f32)
     }
 
-    pub(crate) fn list(&self) -> List {
+    /// Lists the results of `search` if it is set, they are empty until
+    /// the search is done.
+    pub(crate) fn list(&self, search: Option<&str>) -> List {
+        if let Some(query) = search {
+            return self.search_results(query);
+        }
+
         let last = self
             .from_store
             .as_ref()
@@ -133,6 +149,26 @@ impl Logs {
         }
     }
 
+    fn search_results(&self, query: &str) -> List {
+        let (items, done) = match &self.search {
+            Some(search) if search.query == query => (search.found.clone(), true),
+            _ => (Vec::new(), false),
+        };
+        List {
+            items,
+            source: LogSource::Search {
+                query: query.to_owned(),
+                done,
+            },
+            covers: (Bound::Unbounded, Bound::Unbounded),
+        }
+    }
+
+    /// The query of the search whose results are stored
+    pub(crate) fn searched_for(&self) -> Option<&str> {
+        self.search.as_ref().map(|Search { query, .. }| query.as_str())
+    }
+
     pub(crate) fn covers(&self) -> Cover {
         let store = self.from_store.as_ref().map(|FromStore { since, list }| {
             let until = list
@@ -166,4 +202,7 @@ pub(crate) struct List {
 pub enum LogSource {
     Local,
     Store,
+    /// Results of a search in the log store, `done` is false while
+    /// waiting for them
+    Search { query: String, done: bool },
 }
@@ -23,6 +23,10 @@ struct Cli {
     /// Serial number of the device to connect case insensitive
     #[arg(short, long)]
     serial_number: String,
+    /// Token with the `DataSource` role, needed if this machine is not on
+    /// the node allow list of the data-server
+    #[arg(long)]
+    token: Option<String>,
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -39,9 +43,13 @@ async fn main() -> Result<(), color_eyre::Report> {
         .get_affectors()
         .await
         .wrap_err("Could not get affector list")?;
-    let mut server_client =
-        reconnecting::Client::new(args.data_server, affectors, Some(order_tx))
-            .await?;
+    let mut server_client = reconnecting::Client::new(
+        args.data_server,
+        affectors,
+        Some(order_tx),
+        args.token,
+    )
+    .await?;
 
     loop {
         let encoded_msg = usb.handle_usb().await;
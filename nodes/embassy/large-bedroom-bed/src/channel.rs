@@ -1,4 +1,4 @@
-use embassy_futures::select::{self, Either};
+use embassy_futures::select::{self, Either3};
 use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
 use embassy_sync::channel::Channel;
 use embassy_sync::mutex::Mutex;
@@ -6,6 +6,7 @@ use embassy_sync::priority_channel::{self, PriorityChannel};
 use embassy_time::{Duration, Instant};
 use heapless::HistoryBuffer;
 use protocol::large_bedroom::bed::Reading;
+use protocol::OrderAck;
 
 pub struct Queues {
     sensor_queue: PriorityChannel<
@@ -15,6 +16,7 @@ pub struct Queues {
         20,
     >,
     error_queue: Channel<ThreadModeRawMutex, sensors::Error, 20>,
+    ack_queue: Channel<ThreadModeRawMutex, OrderAck, 4>,
     recent_errors: Mutex<ThreadModeRawMutex, HistoryBuffer<sensors::Error, 20>>,
     recent_is_since: Instant,
 }
@@ -22,6 +24,7 @@ pub struct Queues {
 pub enum QueueItem {
     Reading(PriorityValue),
     Error(sensors::Error),
+    Ack(OrderAck),
 }
 
 impl Queues {
@@ -29,6 +32,7 @@ impl Queues {
         Self {
             sensor_queue: PriorityChannel::new(),
             error_queue: Channel::new(),
+            ack_queue: Channel::new(),
             recent_errors: Mutex::new(HistoryBuffer::new()),
             recent_is_since: Instant::MIN, // time since CPU start
         }
@@ -36,21 +40,28 @@ impl Queues {
 
     pub async fn clear(&self) {
         while self.sensor_queue.try_receive().is_ok() {}
+        // acks are for orders of the previous connection
+        while self.ack_queue.try_receive().is_ok() {}
         self.recent_errors.lock().await.clear();
     }
 
     pub async fn receive(&self) -> QueueItem {
+        if let Ok(ack) = self.ack_queue.try_receive() {
+            return QueueItem::Ack(ack);
+        }
         if let Ok(val) = self.sensor_queue.try_receive() {
             return QueueItem::Reading(val);
         }
 
-        let race = select::select(
+        let race = select::select3(
+            self.ack_queue.receive(),
             self.sensor_queue.receive(),
             self.error_queue.receive(),
         );
         match race.await {
-            Either::First(reading) => QueueItem::Reading(reading),
-            Either::Second(error) => QueueItem::Error(error),
+            Either3::First(ack) => QueueItem::Ack(ack),
+            Either3::Second(reading) => QueueItem::Reading(reading),
+            Either3::Third(error) => QueueItem::Error(error),
         }
     }
 
@@ -79,6 +90,14 @@ impl Queues {
         recent_errors.write(error);
     }
 
+    /// Acks are dropped if the queue is full, the data-server then reports
+    /// the order as unconfirmed
+    pub fn queue_ack(&self, ack: OrderAck) {
+        if self.ack_queue.try_send(ack).is_err() {
+            defmt::warn!("ack queue full, dropping ack");
+        }
+    }
+
     pub fn send_p0(&self, value: Reading) {
         use protocol::large_bedroom::Reading::Bed;
         let entry = PriorityValue {
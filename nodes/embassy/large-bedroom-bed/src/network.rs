@@ -7,7 +7,9 @@ use embassy_time::{with_timeout, Duration, Instant, Timer};
 use embedded_io_async::Write;
 use protocol::affector::DeserializeError;
 use protocol::large_bedroom::{self, bed};
-use protocol::{affector, Affector, ErrorReport, SensorMessage};
+use protocol::{
+    affector, Affector, ErrorReport, OrderAck, OrderOutcome, SensorMessage,
+};
 
 use crate::channel::{PriorityValue, QueueItem, Queues};
 use crate::rgb_led::LedHandle;
@@ -66,6 +68,11 @@ async fn get_messages<'a>(publish: &Queues, buf: &'a mut [u8]) -> &'a [u8] {
             buf[0] = protocol::Msg::<0>::ERROR_REPORT;
             &buf[..=encoded_len]
         }
+        QueueItem::Ack(ack) => {
+            let encoded_len = ack.encode_slice(&mut buf[1..]).len();
+            buf[0] = protocol::Msg::<0>::ORDER_ACK;
+            &buf[..=encoded_len]
+        }
     }
 }
 
@@ -118,7 +125,7 @@ pub async fn handle(
         let (reader, writer) = socket.split();
         match select(
             send_messages(writer, publish),
-            receive_orders(reader, &led, driver_orderers),
+            receive_orders(reader, publish, &led, driver_orderers),
         )
         .await
         {
@@ -136,6 +143,7 @@ pub async fn handle(
 
 fn affector_list() -> affector::ListMessage<6> {
     let mut list = affector::ListMessage::<6>::empty();
+    list.acks_orders = true;
     unwrap!(list.values.push(Affector::LargeBedroom(
         protocol::large_bedroom::Affector::Bed(bed::Affector::RgbLed {
             red: 0,
@@ -172,7 +180,10 @@ async fn send_messages(
 ) -> embassy_net::tcp::Error {
     let mut buf = [0; 1 + max(
         max(SensMsg::ENCODED_SIZE, ErrorReport::ENCODED_SIZE),
-        affector::ListMessage::<5>::ENCODED_SIZE,
+        max(
+            affector::ListMessage::<5>::ENCODED_SIZE,
+            OrderAck::ENCODED_SIZE,
+        ),
     )];
     let encoded_len = affector_list().encode_slice(&mut buf[1..]).len();
     buf[0] = protocol::Msg::<5>::AFFECTOR_LIST;
@@ -202,8 +213,10 @@ enum ReadError {
     Deserialize(DeserializeError),
 }
 
+/// Every order is acked, the ack is send by [`send_messages`]
 async fn receive_orders(
     mut tcp: TcpReader<'_>,
+    publish: &Queues,
     led: &LedHandle,
     driver_orderers: &slow::DriverOrderers,
 ) -> ReadError {
@@ -228,10 +241,12 @@ async fn receive_orders(
 
             read = remaining;
 
+            let ack = |outcome| OrderAck::new(item.id, outcome);
             let Affector::LargeBedroom(large_bedroom::Affector::Bed(affector)) =
-                item
+                item.affector
             else {
                 defmt::error!("Got affector for other node");
+                publish.queue_ack(ack(OrderOutcome::Rejected));
                 continue;
             };
 
@@ -239,13 +254,16 @@ async fn receive_orders(
             match affector {
                 bed::Affector::Nau7802LeftCalib
                 | bed::Affector::Nau7802RightCalib => {
-                    defmt::warn!("unimplemented affector: {:?}", affector)
+                    defmt::warn!("unimplemented affector: {:?}", affector);
+                    publish.queue_ack(ack(OrderOutcome::Rejected));
                 }
                 bed::Affector::MhzZeroPointCalib => {
                     driver_orderers.mhz.send(()).await;
+                    publish.queue_ack(ack(OrderOutcome::Executed));
                 }
                 bed::Affector::Sps30FanClean => {
                     driver_orderers.sps.send(()).await;
+                    publish.queue_ack(ack(OrderOutcome::Executed));
                 }
                 bed::Affector::RgbLed { red, green, blue } => {
                     led.set_color(
@@ -253,9 +271,13 @@ async fn receive_orders(
                         green as f32 / u8::MAX as f32,
                         blue as f32 / u8::MAX as f32,
                     )
-                    .await
+                    .await;
+                    publish.queue_ack(ack(OrderOutcome::Executed));
                 }
                 bed::Affector::ResetNode => {
+                    publish.queue_ack(ack(OrderOutcome::Executed));
+                    // give send_messages time to deliver the ack
+                    Timer::after_millis(500).await;
                     defmt::info!("resetting node as orderd via affector");
                     defmt::flush();
                     cortex_m::peripheral::SCB::sys_reset();
@@ -148,7 +148,7 @@ async fn receive_orders(
 
             let protocol::Affector::LargeBedroom(
                 protocol::large_bedroom::Affector::Airbox(affector),
-            ) = item
+            ) = item.affector
             else {
                 defmt::error!("Got affector for other node");
                 continue;